@@ -0,0 +1,9 @@
+-- migrate:up
+
+ALTER TABLE users_verification
+    ADD COLUMN failed_attempts smallint NOT NULL DEFAULT 0;
+
+-- migrate:down
+
+ALTER TABLE users_verification
+    DROP COLUMN failed_attempts;
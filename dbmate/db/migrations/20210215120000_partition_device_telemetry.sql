@@ -0,0 +1,101 @@
+-- migrate:up
+
+ALTER TABLE device_telemetry RENAME TO device_telemetry_unpartitioned;
+ALTER TABLE device_telemetry_unpartitioned
+    RENAME CONSTRAINT device_telemetry_username_fkey TO device_telemetry_unpartitioned_username_fkey;
+DROP INDEX device_telemetry_timestamp_idx;
+
+CREATE TABLE device_telemetry (
+    username varchar(255) NOT NULL,
+    recipient_username varchar(255) NOT NULL,
+    encrypted_location text NOT NULL,
+    device_id varchar(255) NOT NULL,
+    creation_timestamp timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    app_state AppState NOT NULL DEFAULT 'UNKNOWN',
+    charging_state ChargingState NOT NULL DEFAULT 'UNKNOWN',
+    battery_level double precision NOT NULL DEFAULT 0,
+    is_charging boolean NOT NULL DEFAULT false,
+    CONSTRAINT device_telemetry_username_fkey
+        FOREIGN KEY (username)
+        REFERENCES users(username)
+        ON DELETE CASCADE
+) PARTITION BY RANGE (creation_timestamp);
+
+-- Rows without a daily partition, they stay here until they expire.
+CREATE TABLE device_telemetry_default PARTITION OF device_telemetry DEFAULT;
+
+-- Matches get_historical_telemetry.
+CREATE INDEX device_telemetry_history_idx
+    ON device_telemetry (username, recipient_username, creation_timestamp);
+
+-- Creates the empty partition of the given day. Days with rows in the default
+-- partition are skipped, rows are never moved while telemetry is being inserted.
+CREATE FUNCTION create_device_telemetry_partition(day date) RETURNS boolean AS $create_partition$
+    DECLARE
+        partition_name text := 'device_telemetry_p' || to_char(day, 'YYYYMMDD');
+    BEGIN
+        IF to_regclass(partition_name) IS NOT NULL OR EXISTS (
+            SELECT 1 FROM device_telemetry_default
+            WHERE creation_timestamp >= day AND creation_timestamp < day + 1
+        ) THEN
+            RETURN false;
+        END IF;
+        EXECUTE format(
+            'CREATE TABLE %I PARTITION OF device_telemetry FOR VALUES FROM (%L) TO (%L)',
+            partition_name, day, day + 1
+        );
+        RETURN true;
+    END;
+$create_partition$ LANGUAGE plpgsql;
+
+-- The days phones can still backfill, TELEMETRY_MAX_BACKFILL_DAYS. The retention worker
+-- creates the upcoming days and moves the rows of device_telemetry_unpartitioned in batches.
+SELECT create_device_telemetry_partition(day::date)
+FROM generate_series((current_date - 7)::timestamp, current_date::timestamp, interval '1 day') AS day;
+
+-- Every emergency of a user, ended_at is NULL while it is ongoing.
+CREATE VIEW users_emergency_periods AS
+    SELECT username, started_at, ended_at
+    FROM (
+        SELECT username,
+               self_perception,
+               creation_timestamp AS started_at,
+               lead(creation_timestamp) OVER (PARTITION BY username ORDER BY creation_timestamp) AS ended_at
+        FROM users_state_history
+    ) AS transitions
+    WHERE self_perception = 'Emergency';
+
+DO
+$do$
+BEGIN
+    IF EXISTS (
+        SELECT FROM pg_catalog.pg_roles
+        WHERE  rolname = 'app'
+    ) THEN
+        GRANT SELECT, INSERT ON device_telemetry, device_telemetry_default TO app;
+        GRANT SELECT ON users_emergency_periods TO app;
+    END IF;
+END
+$do$;
+
+-- migrate:down
+
+DROP VIEW users_emergency_periods;
+
+DROP FUNCTION create_device_telemetry_partition;
+
+-- Still present when the retention worker did not finish moving its rows.
+CREATE TABLE IF NOT EXISTS device_telemetry_unpartitioned (LIKE device_telemetry INCLUDING DEFAULTS);
+INSERT INTO device_telemetry_unpartitioned SELECT * FROM device_telemetry;
+DROP TABLE device_telemetry;
+ALTER TABLE device_telemetry_unpartitioned RENAME TO device_telemetry;
+
+ALTER TABLE device_telemetry
+  DROP CONSTRAINT IF EXISTS device_telemetry_unpartitioned_username_fkey;
+ALTER TABLE device_telemetry
+  ADD CONSTRAINT device_telemetry_username_fkey
+  FOREIGN KEY (username)
+  REFERENCES users(username)
+  ON DELETE CASCADE;
+
+CREATE INDEX device_telemetry_timestamp_idx ON device_telemetry (creation_timestamp);
@@ -0,0 +1,22 @@
+-- migrate:up
+
+ALTER TABLE users_settings
+    ADD COLUMN historical_access_hours smallint NOT NULL DEFAULT 168,
+    ADD COLUMN historical_grace_hours smallint NOT NULL DEFAULT 24;
+
+-- Users that changed state before users_state_history existed.
+INSERT INTO users_state_history (username, self_perception, creation_timestamp)
+    SELECT username, self_perception, update_timestamp FROM users_state
+    WHERE NOT EXISTS (
+        SELECT 1 FROM users_state_history
+        WHERE users_state_history.username = users_state.username
+    );
+
+-- migrate:down
+
+ALTER TABLE users_settings
+    DROP COLUMN historical_access_hours,
+    DROP COLUMN historical_grace_hours;
+
+-- The backfill of users_state_history is not undone, its rows can't be told
+-- apart from the transitions stored since and they match users_state anyway.
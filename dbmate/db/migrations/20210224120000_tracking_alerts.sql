@@ -0,0 +1,9 @@
+-- migrate:up
+
+ALTER TABLE users_settings
+    ADD COLUMN tracking_alerts boolean NOT NULL DEFAULT false;
+
+-- migrate:down
+
+ALTER TABLE users_settings
+    DROP COLUMN tracking_alerts;
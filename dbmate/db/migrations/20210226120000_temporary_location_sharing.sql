@@ -0,0 +1,14 @@
+-- migrate:up
+
+ALTER TABLE users_followers
+    ADD COLUMN sharing_expires_at timestamp without time zone;
+
+CREATE INDEX users_followers_sharing_expires_at_idx
+    ON users_followers (sharing_expires_at) WHERE sharing_expires_at IS NOT NULL;
+
+-- migrate:down
+
+DROP INDEX users_followers_sharing_expires_at_idx;
+
+ALTER TABLE users_followers
+    DROP COLUMN sharing_expires_at;
@@ -0,0 +1,13 @@
+-- migrate:up
+
+CREATE TABLE users_data_exports (
+    username character varying(255) NOT NULL REFERENCES users(username) ON DELETE CASCADE,
+    device_id character varying(255) NOT NULL,
+    creation_timestamp timestamp without time zone DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+
+CREATE INDEX users_data_exports_username_idx ON users_data_exports (username, creation_timestamp);
+
+-- migrate:down
+
+DROP TABLE users_data_exports;
@@ -0,0 +1,23 @@
+-- migrate:up
+
+ALTER TYPE link_invitation_state ADD VALUE 'REVOKED';
+
+CREATE INDEX link_invitations_creator_username_idx
+    ON link_invitations (creator_username, creation_timestamp);
+
+-- migrate:down
+
+DROP INDEX link_invitations_creator_username_idx;
+
+UPDATE link_invitations SET state = 'EXPIRED' WHERE state = 'REVOKED';
+
+ALTER TYPE link_invitation_state RENAME TO link_invitation_state_old;
+
+CREATE TYPE link_invitation_state AS ENUM ('CREATED', 'ACCEPTED', 'REJECTED', 'EXPIRED');
+
+ALTER TABLE link_invitations
+    ALTER COLUMN state DROP DEFAULT,
+    ALTER COLUMN state TYPE link_invitation_state USING state::text::link_invitation_state,
+    ALTER COLUMN state SET DEFAULT 'CREATED';
+
+DROP TYPE link_invitation_state_old;
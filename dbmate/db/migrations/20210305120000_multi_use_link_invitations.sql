@@ -0,0 +1,29 @@
+-- migrate:up
+
+ALTER TABLE link_invitations
+    ADD COLUMN max_uses integer DEFAULT 1 NOT NULL,
+    ADD COLUMN uses integer DEFAULT 0 NOT NULL,
+    ADD CONSTRAINT link_invitations_uses_check CHECK (uses >= 0 AND uses <= max_uses);
+
+CREATE TABLE link_invitations_uses (
+    invitation_id character varying(255) NOT NULL REFERENCES link_invitations(id) ON DELETE CASCADE,
+    username character varying(255) NOT NULL REFERENCES users(username) ON DELETE CASCADE,
+    creation_timestamp timestamp without time zone DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    PRIMARY KEY (invitation_id, username)
+);
+
+INSERT INTO link_invitations_uses (invitation_id, username)
+    SELECT id, recipient_username FROM link_invitations
+    WHERE state = 'ACCEPTED' AND recipient_username IS NOT NULL;
+
+UPDATE link_invitations SET uses = 1
+    WHERE state = 'ACCEPTED' AND recipient_username IS NOT NULL;
+
+-- migrate:down
+
+DROP TABLE link_invitations_uses;
+
+ALTER TABLE link_invitations
+    DROP CONSTRAINT link_invitations_uses_check,
+    DROP COLUMN uses,
+    DROP COLUMN max_uses;
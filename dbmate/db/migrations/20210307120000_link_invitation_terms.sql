@@ -0,0 +1,13 @@
+-- migrate:up
+
+ALTER TABLE link_invitations
+    ADD COLUMN access_type accesstype DEFAULT 'Permanent' NOT NULL,
+    ADD COLUMN is_emergency_contact boolean DEFAULT true NOT NULL,
+    ADD COLUMN follow_back boolean DEFAULT true NOT NULL;
+
+-- migrate:down
+
+ALTER TABLE link_invitations
+    DROP COLUMN follow_back,
+    DROP COLUMN is_emergency_contact,
+    DROP COLUMN access_type;
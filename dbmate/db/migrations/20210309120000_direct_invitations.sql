@@ -0,0 +1,37 @@
+-- migrate:up
+
+CREATE INDEX invitations_target_username_idx
+    ON invitations (target_username, status);
+
+-- Matched against the email or phone number of the users that register.
+CREATE INDEX invitations_unmatched_targets_idx
+    ON invitations (target_email, target_phone_number)
+    WHERE target_username IS NULL AND status = 'created';
+
+-- Older duplicates of a pending invitation are cancelled, the newest one is kept.
+UPDATE invitations SET status = 'canceled', update_timestamp = now()
+WHERE status = 'created' AND id IN (
+    SELECT id FROM (
+        SELECT id, row_number() OVER (
+            PARTITION BY creator_username, type, COALESCE(target_email, target_phone_number)
+            ORDER BY creation_timestamp DESC, id DESC
+        ) AS position
+        FROM invitations
+        WHERE status = 'created'
+            AND COALESCE(target_email, target_phone_number) IS NOT NULL
+    ) pending
+    WHERE position > 1
+);
+
+-- A single pending invitation for each person invited by a user.
+CREATE UNIQUE INDEX invitations_pending_target_idx
+    ON invitations (creator_username, type, COALESCE(target_email, target_phone_number))
+    WHERE status = 'created';
+
+-- migrate:down
+
+DROP INDEX invitations_pending_target_idx;
+
+DROP INDEX invitations_unmatched_targets_idx;
+
+DROP INDEX invitations_target_username_idx;
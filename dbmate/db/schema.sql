@@ -117,7 +117,8 @@ CREATE TYPE public.link_invitation_state AS ENUM (
     'CREATED',
     'ACCEPTED',
     'REJECTED',
-    'EXPIRED'
+    'EXPIRED',
+    'REVOKED'
 );
 
 
@@ -192,6 +193,31 @@ INSERT INTO users_followers_state(username, username_follower)
 $$;
 
 
+--
+-- Name: create_device_telemetry_partition(date); Type: FUNCTION; Schema: public; Owner: -
+--
+
+CREATE FUNCTION public.create_device_telemetry_partition(day date) RETURNS boolean
+    LANGUAGE plpgsql
+    AS $$
+    DECLARE
+        partition_name text := 'device_telemetry_p' || to_char(day, 'YYYYMMDD');
+    BEGIN
+        IF to_regclass(partition_name) IS NOT NULL OR EXISTS (
+            SELECT 1 FROM device_telemetry_default
+            WHERE creation_timestamp >= day AND creation_timestamp < day + 1
+        ) THEN
+            RETURN false;
+        END IF;
+        EXECUTE format(
+            'CREATE TABLE %I PARTITION OF device_telemetry FOR VALUES FROM (%L) TO (%L)',
+            partition_name, day, day + 1
+        );
+        RETURN true;
+    END;
+$$;
+
+
 --
 -- Name: device_history(); Type: FUNCTION; Schema: public; Owner: -
 --
@@ -465,6 +491,41 @@ CREATE TABLE public.device_telemetry (
     charging_state public.chargingstate DEFAULT 'UNKNOWN'::public.chargingstate NOT NULL,
     battery_level double precision DEFAULT 0 NOT NULL,
     is_charging boolean DEFAULT false NOT NULL
+)
+PARTITION BY RANGE (creation_timestamp);
+
+
+--
+-- Name: device_telemetry_default; Type: TABLE; Schema: public; Owner: -
+--
+
+CREATE TABLE public.device_telemetry_default (
+    username character varying(255) NOT NULL,
+    recipient_username character varying(255) NOT NULL,
+    encrypted_location text NOT NULL,
+    device_id character varying(255) NOT NULL,
+    creation_timestamp timestamp without time zone DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    app_state public.appstate DEFAULT 'UNKNOWN'::public.appstate NOT NULL,
+    charging_state public.chargingstate DEFAULT 'UNKNOWN'::public.chargingstate NOT NULL,
+    battery_level double precision DEFAULT 0 NOT NULL,
+    is_charging boolean DEFAULT false NOT NULL
+);
+
+
+--
+-- Name: device_telemetry_unpartitioned; Type: TABLE; Schema: public; Owner: -
+--
+
+CREATE TABLE public.device_telemetry_unpartitioned (
+    username character varying(255) NOT NULL,
+    recipient_username character varying(255) NOT NULL,
+    encrypted_location text NOT NULL,
+    device_id character varying(255) NOT NULL,
+    creation_timestamp timestamp without time zone DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    app_state public.appstate DEFAULT 'UNKNOWN'::public.appstate NOT NULL,
+    charging_state public.chargingstate DEFAULT 'UNKNOWN'::public.chargingstate NOT NULL,
+    battery_level double precision DEFAULT 0 NOT NULL,
+    is_charging boolean DEFAULT false NOT NULL
 );
 
 
@@ -552,7 +613,24 @@ CREATE TABLE public.link_invitations (
     creation_timestamp timestamp without time zone DEFAULT CURRENT_TIMESTAMP,
     expiration_timestamp timestamp without time zone NOT NULL,
     creator_username character varying(255) NOT NULL,
-    recipient_username character varying(255) DEFAULT NULL::character varying
+    recipient_username character varying(255) DEFAULT NULL::character varying,
+    max_uses integer DEFAULT 1 NOT NULL,
+    uses integer DEFAULT 0 NOT NULL,
+    access_type public.accesstype DEFAULT 'Permanent'::public.accesstype NOT NULL,
+    is_emergency_contact boolean DEFAULT true NOT NULL,
+    follow_back boolean DEFAULT true NOT NULL,
+    CONSTRAINT link_invitations_uses_check CHECK (((uses >= 0) AND (uses <= max_uses)))
+);
+
+
+--
+-- Name: link_invitations_uses; Type: TABLE; Schema: public; Owner: -
+--
+
+CREATE TABLE public.link_invitations_uses (
+    invitation_id character varying(255) NOT NULL,
+    username character varying(255) NOT NULL,
+    creation_timestamp timestamp without time zone DEFAULT CURRENT_TIMESTAMP NOT NULL
 );
 
 
@@ -592,6 +670,17 @@ CREATE TABLE public.users (
 );
 
 
+--
+-- Name: users_data_exports; Type: TABLE; Schema: public; Owner: -
+--
+
+CREATE TABLE public.users_data_exports (
+    username character varying(255) NOT NULL,
+    device_id character varying(255) NOT NULL,
+    creation_timestamp timestamp without time zone DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+
+
 --
 -- Name: users_devices; Type: TABLE; Schema: public; Owner: -
 --
@@ -615,7 +704,8 @@ CREATE TABLE public.users_followers (
     access_type public.accesstype DEFAULT 'EmergencyOnly'::public.accesstype NOT NULL,
     is_emergency_contact boolean DEFAULT false NOT NULL,
     creation_timestamp timestamp without time zone DEFAULT CURRENT_TIMESTAMP NOT NULL,
-    update_timestamp timestamp without time zone DEFAULT CURRENT_TIMESTAMP NOT NULL
+    update_timestamp timestamp without time zone DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    sharing_expires_at timestamp without time zone
 );
 
 
@@ -682,7 +772,11 @@ CREATE TABLE public.users_settings (
     username character varying(255) NOT NULL,
     followers_to_declare_emergency smallint DEFAULT 2 NOT NULL,
     creation_timestamp timestamp without time zone DEFAULT CURRENT_TIMESTAMP NOT NULL,
-    update_timestamp timestamp without time zone DEFAULT CURRENT_TIMESTAMP NOT NULL
+    update_timestamp timestamp without time zone DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    historical_access_hours smallint DEFAULT 168 NOT NULL,
+    historical_grace_hours smallint DEFAULT 24 NOT NULL,
+    low_battery_alerts boolean DEFAULT false NOT NULL,
+    tracking_alerts boolean DEFAULT false NOT NULL
 );
 
 
@@ -709,6 +803,22 @@ CREATE TABLE public.users_state_history (
 );
 
 
+--
+-- Name: users_emergency_periods; Type: VIEW; Schema: public; Owner: -
+--
+
+CREATE VIEW public.users_emergency_periods AS
+ SELECT transitions.username,
+    transitions.started_at,
+    transitions.ended_at
+   FROM ( SELECT users_state_history.username,
+            users_state_history.self_perception,
+            users_state_history.creation_timestamp AS started_at,
+            lead(users_state_history.creation_timestamp) OVER (PARTITION BY users_state_history.username ORDER BY users_state_history.creation_timestamp) AS ended_at
+           FROM public.users_state_history) transitions
+  WHERE (transitions.self_perception = 'Emergency'::public.userstate);
+
+
 --
 -- Name: users_verification; Type: TABLE; Schema: public; Owner: -
 --
@@ -723,10 +833,18 @@ CREATE TABLE public.users_verification (
     expiration_timestamp timestamp without time zone NOT NULL,
     public_key text DEFAULT ''::text NOT NULL,
     phone_number character varying(25) DEFAULT NULL::character varying,
+    failed_attempts smallint DEFAULT 0 NOT NULL,
     CONSTRAINT phone_or_email_constraint CHECK (((phone_number IS NOT NULL) OR (email IS NOT NULL)))
 );
 
 
+--
+-- Name: device_telemetry_default; Type: TABLE ATTACH; Schema: public; Owner: -
+--
+
+ALTER TABLE ONLY public.device_telemetry ATTACH PARTITION public.device_telemetry_default DEFAULT;
+
+
 --
 -- Name: device_geofence geofence_id; Type: DEFAULT; Schema: public; Owner: -
 --
@@ -796,6 +914,14 @@ ALTER TABLE ONLY public.link_invitations
     ADD CONSTRAINT link_invitations_pkey PRIMARY KEY (id);
 
 
+--
+-- Name: link_invitations_uses link_invitations_uses_pkey; Type: CONSTRAINT; Schema: public; Owner: -
+--
+
+ALTER TABLE ONLY public.link_invitations_uses
+    ADD CONSTRAINT link_invitations_uses_pkey PRIMARY KEY (invitation_id, username);
+
+
 --
 -- Name: schema_migrations schema_migrations_pkey; Type: CONSTRAINT; Schema: public; Owner: -
 --
@@ -917,10 +1043,66 @@ ALTER TABLE ONLY public.users_verification
 
 
 --
--- Name: device_telemetry_timestamp_idx; Type: INDEX; Schema: public; Owner: -
+-- Name: device_telemetry_history_idx; Type: INDEX; Schema: public; Owner: -
+--
+
+CREATE INDEX device_telemetry_history_idx ON ONLY public.device_telemetry USING btree (username, recipient_username, creation_timestamp);
+
+
+--
+-- Name: device_telemetry_default_username_recipient_username_creati_idx; Type: INDEX; Schema: public; Owner: -
+--
+
+CREATE INDEX device_telemetry_default_username_recipient_username_creati_idx ON public.device_telemetry_default USING btree (username, recipient_username, creation_timestamp);
+
+
+--
+-- Name: device_telemetry_default_username_recipient_username_creati_idx; Type: INDEX ATTACH; Schema: public; Owner: -
 --
 
-CREATE INDEX device_telemetry_timestamp_idx ON public.device_telemetry USING btree (creation_timestamp);
+ALTER INDEX public.device_telemetry_history_idx ATTACH PARTITION public.device_telemetry_default_username_recipient_username_creati_idx;
+
+
+--
+-- Name: invitations_pending_target_idx; Type: INDEX; Schema: public; Owner: -
+--
+
+CREATE UNIQUE INDEX invitations_pending_target_idx ON public.invitations USING btree (creator_username, type, COALESCE(target_email, target_phone_number)) WHERE (status = 'created'::public.invitation_status);
+
+
+--
+-- Name: invitations_target_username_idx; Type: INDEX; Schema: public; Owner: -
+--
+
+CREATE INDEX invitations_target_username_idx ON public.invitations USING btree (target_username, status);
+
+
+--
+-- Name: invitations_unmatched_targets_idx; Type: INDEX; Schema: public; Owner: -
+--
+
+CREATE INDEX invitations_unmatched_targets_idx ON public.invitations USING btree (target_email, target_phone_number) WHERE ((target_username IS NULL) AND (status = 'created'::public.invitation_status));
+
+
+--
+-- Name: link_invitations_creator_username_idx; Type: INDEX; Schema: public; Owner: -
+--
+
+CREATE INDEX link_invitations_creator_username_idx ON public.link_invitations USING btree (creator_username, creation_timestamp);
+
+
+--
+-- Name: users_data_exports_username_idx; Type: INDEX; Schema: public; Owner: -
+--
+
+CREATE INDEX users_data_exports_username_idx ON public.users_data_exports USING btree (username, creation_timestamp);
+
+
+--
+-- Name: users_followers_sharing_expires_at_idx; Type: INDEX; Schema: public; Owner: -
+--
+
+CREATE INDEX users_followers_sharing_expires_at_idx ON public.users_followers USING btree (sharing_expires_at) WHERE (sharing_expires_at IS NOT NULL);
 
 
 --
@@ -957,10 +1139,18 @@ ALTER TABLE ONLY public.device_geofence
 -- Name: device_telemetry device_telemetry_username_fkey; Type: FK CONSTRAINT; Schema: public; Owner: -
 --
 
-ALTER TABLE ONLY public.device_telemetry
+ALTER TABLE public.device_telemetry
     ADD CONSTRAINT device_telemetry_username_fkey FOREIGN KEY (username) REFERENCES public.users(username) ON DELETE CASCADE;
 
 
+--
+-- Name: device_telemetry_unpartitioned device_telemetry_unpartitioned_username_fkey; Type: FK CONSTRAINT; Schema: public; Owner: -
+--
+
+ALTER TABLE ONLY public.device_telemetry_unpartitioned
+    ADD CONSTRAINT device_telemetry_unpartitioned_username_fkey FOREIGN KEY (username) REFERENCES public.users(username) ON DELETE CASCADE;
+
+
 --
 -- Name: device_settings fk_device; Type: FK CONSTRAINT; Schema: public; Owner: -
 --
@@ -1025,6 +1215,22 @@ ALTER TABLE ONLY public.link_invitations
     ADD CONSTRAINT link_invitations_recipient_username_fkey FOREIGN KEY (recipient_username) REFERENCES public.users(username) ON DELETE CASCADE;
 
 
+--
+-- Name: link_invitations_uses link_invitations_uses_invitation_id_fkey; Type: FK CONSTRAINT; Schema: public; Owner: -
+--
+
+ALTER TABLE ONLY public.link_invitations_uses
+    ADD CONSTRAINT link_invitations_uses_invitation_id_fkey FOREIGN KEY (invitation_id) REFERENCES public.link_invitations(id) ON DELETE CASCADE;
+
+
+--
+-- Name: link_invitations_uses link_invitations_uses_username_fkey; Type: FK CONSTRAINT; Schema: public; Owner: -
+--
+
+ALTER TABLE ONLY public.link_invitations_uses
+    ADD CONSTRAINT link_invitations_uses_username_fkey FOREIGN KEY (username) REFERENCES public.users(username) ON DELETE CASCADE;
+
+
 --
 -- Name: user_details user_details_target_username_fkey; Type: FK CONSTRAINT; Schema: public; Owner: -
 --
@@ -1033,6 +1239,14 @@ ALTER TABLE ONLY public.user_details
     ADD CONSTRAINT user_details_target_username_fkey FOREIGN KEY (username) REFERENCES public.users(username) ON UPDATE CASCADE ON DELETE CASCADE;
 
 
+--
+-- Name: users_data_exports users_data_exports_username_fkey; Type: FK CONSTRAINT; Schema: public; Owner: -
+--
+
+ALTER TABLE ONLY public.users_data_exports
+    ADD CONSTRAINT users_data_exports_username_fkey FOREIGN KEY (username) REFERENCES public.users(username) ON DELETE CASCADE;
+
+
 --
 -- Name: users_devices users_devices_target_username_fkey; Type: FK CONSTRAINT; Schema: public; Owner: -
 --
@@ -1170,4 +1384,15 @@ INSERT INTO public.schema_migrations (version) VALUES
     ('20201208004535'),
     ('20210105150805'),
     ('20210117221453'),
-    ('20210130172100');
+    ('20210130172100'),
+    ('20210210120000'),
+    ('20210215120000'),
+    ('20210220120000'),
+    ('20210222120000'),
+    ('20210224120000'),
+    ('20210226120000'),
+    ('20210301120000'),
+    ('20210303120000'),
+    ('20210305120000'),
+    ('20210307120000'),
+    ('20210309120000');
@@ -16,6 +16,7 @@ import { DBClient, withDB } from "./db";
 import {
     DBClientWithConnection,
     DevicePushNotificationRegistration,
+    DevicePushToken,
     Username,
     UserState,
 } from "../types";
@@ -138,6 +139,18 @@ export const registerDeviceForPushNotifications = async (
         }
     });
 
+// Only clears the token if the device did not register a new one in the meantime.
+export const invalidatePushToken = async (
+    { deviceId, pushToken }: DevicePushToken,
+    database: DBClient,
+) =>
+    withDB(database).then(async (d: DBClientWithConnection) => {
+        await d.connection.query(
+            "UPDATE devices SET push_token = NULL WHERE device_id = $1 AND push_token = $2",
+            [deviceId, pushToken],
+        );
+    });
+
 export const getPushNotificationTokensForDevices = async (
     deviceIdentifiers: string[],
     database: DBClient,
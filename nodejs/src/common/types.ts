@@ -209,6 +209,11 @@ export interface DevicePushNotificationRegistration {
     username: string;
 }
 
+export interface DevicePushToken {
+    pushToken: string;
+    deviceId: string;
+}
+
 export interface DeviceId {
     deviceId: String;
 }
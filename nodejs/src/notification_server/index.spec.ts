@@ -5,6 +5,7 @@ process.env.LABS_MOBILE_USERNAME = "blah";
 process.env.LABS_MOBILE_PASSWORD = "blah";
 
 import { SMSSender } from "./sms-sender";
+import { hasInvalidPushToken } from "./invalid-tokens";
 
 describe("Notifications Server", () => {
     describe("SMSSender", () => {
@@ -43,4 +44,21 @@ describe("Notifications Server", () => {
             expect(labsMobile).toHaveLength(1);
         });
     });
+    describe("hasInvalidPushToken", () => {
+        const result = (errorMsg?: string) => ({
+            method: "apn",
+            multicastId: [],
+            success: errorMsg ? 0 : 1,
+            failure: errorMsg ? 1 : 0,
+            message: [{ regId: "token", errorMsg }],
+        });
+        test("Unregistered tokens are invalid", () => {
+            expect(hasInvalidPushToken([result("Unregistered")])).toBe(true);
+            expect(hasInvalidPushToken([result(), result("NotRegistered")])).toBe(true);
+        });
+        test("Delivered notifications and transient errors keep the token", () => {
+            expect(hasInvalidPushToken([result()])).toBe(false);
+            expect(hasInvalidPushToken([result("ServiceUnavailable")])).toBe(false);
+        });
+    });
 });
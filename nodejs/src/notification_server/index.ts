@@ -25,6 +25,7 @@ import { check, validationResult } from "express-validator";
 import { DBClient } from "../common/db/db";
 import { Service } from "../common/service";
 import { SMSSender, SmsRequest } from "./sms-sender";
+import { hasInvalidPushToken } from "./invalid-tokens";
 import {
     IOS_BUNDLES,
     IOS_PLATFORM,
@@ -42,6 +43,7 @@ import {
     registerDeviceForPushNotifications,
     getPushNotificationTokensForDevices,
     getEmailAndFullName,
+    invalidatePushToken,
 } from "../common/db/device-management";
 import { createError } from "../common/sanitizer";
 import { RabbitClient, QueueOptions } from "../common/rabbit-helpers";
@@ -264,12 +266,21 @@ export class NotificationServer implements Service {
                     if (!pushToken) {
                         return Promise.reject(`error retrieving token for device ${deviceId}`);
                     } else if (push) {
+                        const send = (payload: PushNotifications.Data) =>
+                            push.send(pushToken, payload).then(async (results) => {
+                                if (hasInvalidPushToken(results)) {
+                                    logger.info(`clearing invalid token of device ${deviceId}`);
+                                    await invalidatePushToken(
+                                        { deviceId, pushToken },
+                                        this.pgClient,
+                                    );
+                                }
+                                return results;
+                            });
                         // If iOS, send 1 notification per bundle:
                         return os === IOS_PLATFORM
-                            ? IOS_BUNDLES.map((topic) => ({ ...data, topic })).flatMap((payload) =>
-                                  push.send(pushToken, payload),
-                              )
-                            : push.send(pushToken, data);
+                            ? IOS_BUNDLES.map((topic) => ({ ...data, topic })).flatMap(send)
+                            : send(data);
                     } else {
                         return Promise.reject("There's no push notifications client");
                     }
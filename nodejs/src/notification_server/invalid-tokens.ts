@@ -0,0 +1,26 @@
+/**
+ * Copyright [2018] [Dario Alessandro Lencina Talarico]
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * y ou may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ * http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+import PushNotifications from "node-pushnotifications";
+
+// APNs and FCM errors meaning that the token will never be valid again.
+const INVALID_TOKEN_ERRORS = [
+    "BadDeviceToken",
+    "Unregistered",
+    "NotRegistered",
+    "InvalidRegistration",
+];
+
+export const hasInvalidPushToken = (results: PushNotifications.Result[]): boolean =>
+    results.some(({ message }) =>
+        message.some(({ errorMsg }) => !!errorMsg && INVALID_TOKEN_ERRORS.includes(errorMsg)),
+    );
@@ -0,0 +1,57 @@
+use std::env;
+use std::thread;
+
+use lib::controllers::followers::expire_location_sharing;
+use lib::db::get_pool;
+use rocket_sentry_logger::{self as logger, InitConfig};
+
+use log::{debug, error, info};
+/**
+Location sharing is a program that has the following jobs:
+
+1. Revoke the temporary location sharing grants that expired.
+
+2. Notify the user and the follower of every revoked grant.
+**/
+
+fn main() {
+    env_logger::init();
+    info!("Starting");
+    let dsn = std::env::var("SENTRY_DSN");
+    if let Ok(dsn) = dsn {
+        let _guard = logger::init(
+            dsn,
+            Some(InitConfig {
+                service: Some("LocationSharing"),
+                ..Default::default()
+            }),
+        );
+    } else {
+        debug!("SENTRY_DSN env var not found so not using sentry.");
+    }
+
+    let redis_url = env::var("REDIS_URL").expect("REDIS_URL must be set");
+    let poll_period_seconds: u64 = env::var("POLL_PERIOD_SECONDS")
+        .expect("POLL_PERIOD_SECONDS must be set")
+        .parse()
+        .expect("POLL_PERIOD_SECONDS was in a bad format. Must be u64");
+    start_run_loop(&redis_url, &poll_period_seconds);
+}
+
+fn start_run_loop(redis_url: &String, poll_period_seconds: &u64) {
+    let db_client = get_pool();
+    let redis_client =
+        redis::Client::open(redis_url.clone()).expect("Failed to open redis client.");
+    loop {
+        debug!("on tick");
+        let mut client = db_client.get().expect("Failed to open db client.");
+        let mut redis_connection = redis_client
+            .get_connection()
+            .expect("Failed to connect to redis server.");
+        match expire_location_sharing(&mut client, &mut redis_connection) {
+            Ok(expired) => info!("revoked {} location sharing grants", expired),
+            Err(err) => error!("location sharing error {:?}", err),
+        }
+        thread::sleep(std::time::Duration::from_secs(*poll_period_seconds));
+    }
+}
@@ -0,0 +1,84 @@
+use chrono::Utc;
+use std::env;
+use std::thread;
+
+use lib::constants::{MAX_HISTORICAL_ACCESS_HOURS, TELEMETRY_MAX_BACKFILL_DAYS};
+use lib::controllers::retention::apply_retention_policy;
+use lib::db::get_pool;
+use lib::model::telemetry::RetentionPolicy;
+use rocket_sentry_logger::{self as logger, InitConfig};
+
+use log::{debug, error, info};
+/**
+Telemetry retention is a program that has the following jobs:
+
+1. Move the rows that the partitioning migration left in
+device_telemetry_unpartitioned, in small batches.
+
+2. Create the daily device_telemetry partitions before they are needed.
+
+3. Drop the telemetry that is older than the retention policy, keeping the
+locations around emergencies for longer.
+**/
+
+fn env_var<T: std::str::FromStr>(name: &str) -> T {
+    env::var(name)
+        .unwrap_or_else(|_| panic!("{} must be set", name))
+        .parse()
+        .unwrap_or_else(|_| panic!("{} was in a bad format", name))
+}
+
+fn main() {
+    env_logger::init();
+    info!("Starting");
+    let dsn = std::env::var("SENTRY_DSN");
+    if let Ok(dsn) = dsn {
+        let _guard = logger::init(
+            dsn,
+            Some(InitConfig {
+                service: Some("TelemetryRetention"),
+                ..Default::default()
+            }),
+        );
+    } else {
+        debug!("SENTRY_DSN env var not found so not using sentry.");
+    }
+
+    let policy = RetentionPolicy {
+        retention_days: env_var("TELEMETRY_RETENTION_DAYS"),
+        emergency_retention_days: env_var("TELEMETRY_EMERGENCY_RETENTION_DAYS"),
+        emergency_window_hours: env_var("TELEMETRY_EMERGENCY_WINDOW_HOURS"),
+        partitions_ahead_days: env_var("TELEMETRY_PARTITIONS_AHEAD_DAYS"),
+    };
+    // Telemetry uploads can be backfilled up to TELEMETRY_MAX_BACKFILL_DAYS.
+    assert!(
+        policy.retention_days > TELEMETRY_MAX_BACKFILL_DAYS,
+        "TELEMETRY_RETENTION_DAYS must be greater than {}",
+        TELEMETRY_MAX_BACKFILL_DAYS
+    );
+    assert!(
+        policy.emergency_retention_days >= policy.retention_days,
+        "TELEMETRY_EMERGENCY_RETENTION_DAYS must not be less than TELEMETRY_RETENTION_DAYS"
+    );
+    // Followers can read the locations from before the emergency, see assert_valid_location_historical_start
+    assert!(
+        policy.emergency_window_hours >= i32::from(MAX_HISTORICAL_ACCESS_HOURS),
+        "TELEMETRY_EMERGENCY_WINDOW_HOURS must be at least {}",
+        MAX_HISTORICAL_ACCESS_HOURS
+    );
+    let poll_period_seconds: u64 = env_var("POLL_PERIOD_SECONDS");
+    start_run_loop(&policy, &poll_period_seconds);
+}
+
+fn start_run_loop(policy: &RetentionPolicy, poll_period_seconds: &u64) {
+    let db_client = get_pool();
+    loop {
+        debug!("on tick");
+        let mut client = db_client.get().expect("Failed to open db client.");
+        match apply_retention_policy(&mut client, policy, Utc::today().naive_utc()) {
+            Ok(report) => info!("retention applied {:?}", report),
+            Err(err) => error!("retention error {:?}", err),
+        }
+        thread::sleep(std::time::Duration::from_secs(*poll_period_seconds));
+    }
+}
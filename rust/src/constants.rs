@@ -4,16 +4,129 @@ pub const TELEMETRY_LAST_SEEN_SET: &str = "telemetry_last_seen";
 
 pub const FORCE_LOCATION_HYSTERESIS: i64 = 30;
 
+pub const TELEMETRY_UPLOAD_PREFIX: &str = "telemetry_upload";
+
+/// Retries of a telemetry batch are detected for this long.
+pub const TELEMETRY_UPLOAD_WINDOW_SECONDS: usize = 600;
+
+/// Locations buffered while the phone was offline are accepted for this long.
+pub const TELEMETRY_MAX_BACKFILL_DAYS: i64 = 7;
+
+/// Rows of device_telemetry_unpartitioned moved to device_telemetry per transaction.
+pub const TELEMETRY_MIGRATION_BATCH_SIZE: i64 = 5000;
+
+/// Tolerance for phones whose clock is ahead of the server.
+pub const TELEMETRY_MAX_CLOCK_SKEW_SECONDS: i64 = 300;
+
 pub const GENERIC_EMAIL_TEMPLATE: &str = "d-f4c36d6358cd445e9a873e103c3efe05";
 
+pub const VERIFICATION_EMAIL_TEMPLATE: &str = "d-fac72b3d96894b5bb5a0f5944102f891";
+
+pub const VERIFICATION_CODE_EXPIRATION_MINUTES: i64 = 60;
+
+/// Wrong codes accepted before the pending verifications of an email or phone stop working.
+pub const VERIFICATION_CODE_MAX_ATTEMPTS: i16 = 5;
+
+pub const DEFAULT_FOLLOWERS_TO_DECLARE_EMERGENCY: i16 = 2;
+
+/// Followers can read the locations sent this many hours before an emergency began,
+/// the telemetry retention keeps the locations around emergencies for at least this long.
+pub const DEFAULT_HISTORICAL_ACCESS_HOURS: i16 = 168;
+
+pub const MAX_HISTORICAL_ACCESS_HOURS: i16 = 168;
+
+/// Followers can read the history of an emergency for this many hours after it ended.
+pub const DEFAULT_HISTORICAL_GRACE_HOURS: i16 = 24;
+
+pub const MAX_HISTORICAL_GRACE_HOURS: i16 = 168;
+
+/// Battery percentage below which the emergency contacts are alerted.
+pub const LOW_BATTERY_ALERT_LEVEL: f64 = 15.0;
+
+pub const LOW_BATTERY_ALERT_PREFIX: &str = "low_battery_alert";
+
+/// A device that stays below LOW_BATTERY_ALERT_LEVEL is alerted again after this long.
+pub const LOW_BATTERY_ALERT_COOLDOWN_SECONDS: usize = 86400;
+
+/// Users without low battery alerts are not checked again for this long while the battery is low.
+pub const LOW_BATTERY_ALERT_DISABLED_SECONDS: usize = 600;
+
+/// The battery trend of a device is computed with the pings of the last hours.
+pub const DEVICE_HEALTH_TREND_HOURS: i64 = 6;
+
+/// Change of the battery level, in percentage points per hour, considered stable.
+pub const DEVICE_HEALTH_STABLE_BATTERY_RATE: f64 = 1.0;
+
+/// Devices that did not ping for longer are reported as unreliable.
+pub const DEVICE_HEALTH_STALE_PING_MINUTES: i64 = 60;
+
+/// Updates per page of the device settings history.
+pub const DEVICE_SETTINGS_HISTORY_PAGE_SIZE: i64 = 50;
+
+pub const DEVICE_SETTINGS_HISTORY_MAX_PAGE_SIZE: i64 = 500;
+
+/// Friends per page of the friends, followers and following lists.
+pub const FRIENDS_PAGE_SIZE: i64 = 50;
+
+pub const FRIENDS_MAX_PAGE_SIZE: i64 = 200;
+
+/// Longest temporary location sharing grant, enough for a trip.
+pub const LOCATION_SHARING_MAX_MINUTES: i32 = 10080;
+
+/// Data exports a user can request every 24 hours.
+pub const DATA_EXPORT_MAX_PER_DAY: i64 = 3;
+
+/// Locations per page of the telemetry in the data export.
+pub const DATA_EXPORT_TELEMETRY_PAGE_SIZE: i64 = 1000;
+
+/// Size of the devices.push_token column.
+pub const PUSH_TOKEN_MAX_LENGTH: usize = 255;
+
+/// APNs device tokens are at least 32 bytes, sent as hex.
+pub const APNS_TOKEN_MIN_LENGTH: usize = 64;
+
+pub const FCM_TOKEN_MIN_LENGTH: usize = 32;
+
+/// Largest profile picture accepted by the upload endpoint.
+pub const PROFILE_PICTURE_MAX_BYTES: u64 = 5 * 1024 * 1024;
+
+/// Larger pictures are rejected before decoding them.
+pub const PROFILE_PICTURE_MAX_DIMENSION: u32 = 8192;
+
+/// Square sizes stored for every profile picture, the first one is the picture itself.
+pub const PROFILE_PICTURE_SIZES: [u32; 3] = [512, 256, 96];
+
+/// Default base url of the profile pictures, see storage::picture_url.
 pub const CS_PROFILE_IMAGE_PATH: &str = "https://storage.cloud.google.com/rescuelink_user_pictures";
 
 pub const WEB_URL: &str = "https://armore.dev";
 
 pub const INV_ENDPOINT: &str = "https://armore.dev/invitations";
 
+/// Most friends a single invitation link can add, enough for a family group chat.
+pub const INVITATION_MAX_USES: i32 = 50;
+
 pub const ASIMOV_LIVES: &str = "asimovlives";
 
+/// Signs the historical location exports with EXPORT_SIGNING_KEY.
+pub const EXPORT_SIGNATURE_ALGORITHM: jsonwebtoken::Algorithm = jsonwebtoken::Algorithm::RS512;
+
+/// Locations per page of the historical location export.
+pub const HISTORICAL_EXPORT_PAGE_SIZE: i64 = 500;
+
+pub const HISTORICAL_EXPORT_MAX_PAGE_SIZE: i64 = 2000;
+
+/// Locations per page of the historical location endpoint.
+pub const HISTORICAL_LOCATION_PAGE_SIZE: i64 = 500;
+
+pub const HISTORICAL_LOCATION_MAX_PAGE_SIZE: i64 = 2000;
+
+/// Locations read from postgres at a time while streaming the historical location.
+pub const HISTORICAL_STREAM_BATCH_SIZE: i64 = 500;
+
+/// Longest downsampling interval of the historical location in seconds.
+pub const HISTORICAL_MAX_INTERVAL_SECONDS: i64 = 86400;
+
 pub static DATE_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%.3fZ";
 
 pub static DEFAULT_NOTIFICATION_ICON: &str = "ic_stat_logo";
@@ -21,3 +134,7 @@ pub static DEFAULT_NOTIFICATION_ICON: &str = "ic_stat_logo";
 pub static NOTIFICATIONS_EXCHANGE: &str = "notifications.exchange";
 
 pub static NOTIFICATIONS_ROUTING_KEY: &str = "notifications";
+
+/// Lets clients pick the API version without changing the route prefix,
+/// see `ApiVersion::negotiate`.
+pub const API_VERSION_HEADER: &str = "X-Armore-API-Version";
@@ -0,0 +1,403 @@
+use crate::constants::{
+    DEFAULT_FOLLOWERS_TO_DECLARE_EMERGENCY, VERIFICATION_CODE_EXPIRATION_MINUTES,
+    VERIFICATION_CODE_MAX_ATTEMPTS, VERIFICATION_EMAIL_TEMPLATE, WEB_URL,
+};
+use crate::controllers::telemetry::get_user_details;
+use crate::db::api_transaction;
+use crate::lang::{get_messages, MessageIds, TranslationIds};
+use crate::messaging::{get_rabbitmq_uri, send_notification};
+use crate::model::{
+    auth::VerificationTarget,
+    emergency::UserState,
+    invitations::DirectInvitationStatus,
+    notifications::{DynamicEmailTemplateData, Email, Sms},
+    requests::{RegistrationRequest, VerificationRequest},
+    responses::Errors::APIInternalError,
+    PostgresConnection, UserDetails,
+};
+use amiquip::Connection as RabbitConnection;
+use dynfmt::{Format, SimpleCurlyFormat};
+use postgres::{error::SqlState, GenericClient};
+use rand::Rng;
+
+const CODE_LENGTH: usize = 5;
+const CODE_NUMBERS: &[u8] = b"456789123";
+const CODE_LETTERS: &[u8] = b"abcdefghijklMNOPQRSTUVWXYZ0123ABCDEFGHIJKLmnopqrstuvwxyz";
+
+/// Creates the user and its default state and settings, then issues the first
+/// verification code, all in a single transaction.
+///
+/// @return the verification code that must be delivered to the user
+pub fn register_user(
+    conn: &mut PostgresConnection,
+    req: &RegistrationRequest,
+    target: &VerificationTarget,
+    language: &str,
+) -> Result<String, APIInternalError> {
+    let username = req.username.trim().to_lowercase();
+    let (email, phone_number) = match target {
+        VerificationTarget::Email(email) => (Some(email), None),
+        VerificationTarget::Phone(phone) => (None, Some(phone)),
+    };
+    api_transaction(conn, |ts| {
+        ts.execute(
+            "INSERT INTO users (username, email, phone_number) VALUES ($1, $2, $3)",
+            &[&username, &email, &phone_number],
+        )
+        .map_err(map_registration_err)?;
+        ts.execute(
+            "INSERT INTO user_details
+                (username, first_name, last_name, picture, language, creation_timestamp, updated_timestamp)
+             VALUES ($1, $2, $3, $4, $5, now(), now())",
+            &[
+                &username,
+                &req.firstName.trim(),
+                &req.lastName.trim(),
+                &req.picture,
+                &language,
+            ],
+        )
+        .map_err(APIInternalError::from_db_err)?;
+        ts.execute(
+            "INSERT INTO users_state (username, self_perception) VALUES ($1, $2)",
+            &[&username, &UserState::Normal],
+        )
+        .map_err(APIInternalError::from_db_err)?;
+        ts.execute(
+            "INSERT INTO users_settings (username, followers_to_declare_emergency) VALUES ($1, $2)",
+            &[&username, &DEFAULT_FOLLOWERS_TO_DECLARE_EMERGENCY],
+        )
+        .map_err(APIInternalError::from_db_err)?;
+        insert_verification(ts, target, &req.publicKey).map_err(APIInternalError::from_db_err)
+    })
+}
+
+/// Issues a new verification code for an existing user. Nothing is issued for an
+/// unknown email or phone number, callers must answer as if it was so the response
+/// does not tell whether there is an account.
+///
+/// @return the verification code that must be delivered to the user, if any
+pub fn create_verification(
+    conn: &mut PostgresConnection,
+    target: &VerificationTarget,
+    public_key: &str,
+) -> Result<Option<String>, APIInternalError> {
+    api_transaction(conn, |ts| {
+        let users = ts
+            .query(
+                "SELECT username FROM users WHERE email = $1 OR phone_number = $1",
+                &[target.value()],
+            )
+            .map_err(APIInternalError::from_db_err)?;
+        if users.is_empty() {
+            return Ok(None);
+        }
+        insert_verification(ts, target, public_key)
+            .map(Some)
+            .map_err(APIInternalError::from_db_err)
+    })
+}
+
+/// Send the verification code by email or sms depending on the target.
+pub fn send_verification(
+    conn: &mut PostgresConnection,
+    target: &VerificationTarget,
+    code: &str,
+    language: &str,
+) -> Result<(), APIInternalError> {
+    let link = format!("{}/user/verify/{}", WEB_URL, code);
+    let notification = match target {
+        VerificationTarget::Email(email) => {
+            let username = get_username(&mut **conn, target)?;
+            let details = get_user_details(&username, conn)
+                .map_err(APIInternalError::from_db_err)?
+                .ok_or(APIInternalError::backend_issue("Unable to fetch user info"))?;
+            let body = format_translation(
+                language,
+                MessageIds::VerificationEmailBody,
+                &[&details.firstName],
+            )?;
+            let title = translate(language, MessageIds::VerificationEmailTitle);
+            json!([Email {
+                username: Some(username),
+                email: email.clone(),
+                templateId: VERIFICATION_EMAIL_TEMPLATE.to_string(),
+                dynamicTemplateData: DynamicEmailTemplateData {
+                    title: format!("Armore: {}", title),
+                    body,
+                    linkTitle: translate(language, MessageIds::VerificationEmailButtonText),
+                    picture: None,
+                    link: Some(link),
+                    code: Some(code.to_string()),
+                },
+            }])
+        }
+        VerificationTarget::Phone(phone) => {
+            let body = format_translation(
+                language,
+                MessageIds::SmsVerificationBody,
+                &[code, link.as_str()],
+            )?;
+            json!([Sms {
+                to: phone.clone(),
+                body
+            }])
+        }
+    };
+
+    RabbitConnection::insecure_open(&get_rabbitmq_uri())
+        .and_then(|mut connection| {
+            let channel = connection.open_channel(None)?;
+            let result = send_notification(&channel, notification.to_string());
+            let _ = channel.close();
+            result
+        })
+        .map_err(APIInternalError::backend_issue)
+}
+
+/// Consume the verification code, register the device as owned by the user
+/// and store the public key used to sign future tokens. Pending invitations sent
+/// to the email or phone number are given to the user now that it is proven to
+/// be theirs. Every wrong code counts
+/// against the pending verifications of the target, after
+/// VERIFICATION_CODE_MAX_ATTEMPTS of them a new code must be requested.
+///
+/// @return the details of the verified user
+pub fn verify_code(
+    conn: &mut PostgresConnection,
+    target: &VerificationTarget,
+    req: &VerificationRequest,
+) -> Result<UserDetails, APIInternalError> {
+    let username = api_transaction(conn, |ts| {
+        let updated = ts
+            .execute(
+                "UPDATE users_verification
+                 SET used = true, updated_timestamp = NOW()
+                 WHERE (email = $1 OR phone_number = $1)
+                   AND verification_code = $2
+                   AND expiration_timestamp > NOW()
+                   AND used = false
+                   AND failed_attempts < $3",
+                &[
+                    target.value(),
+                    &req.code.trim(),
+                    &VERIFICATION_CODE_MAX_ATTEMPTS,
+                ],
+            )
+            .map_err(APIInternalError::from_db_err)?;
+        if updated < 1 {
+            return Ok(None);
+        }
+        let username = get_username(ts, target)?;
+        register_device(ts, &username, req)?;
+        ts.execute(
+            "INSERT INTO users_identity (username, public_key, update_timestamp)
+             VALUES ($1, $2, now())
+             ON CONFLICT (username)
+                DO UPDATE
+                SET public_key = $2, update_timestamp = now()",
+            &[&username, &req.publicKey.trim()],
+        )
+        .map_err(APIInternalError::from_db_err)?;
+        ts.execute(
+            "UPDATE invitations SET target_username = $1, update_timestamp = now()
+             WHERE target_username IS NULL AND status = $2
+               AND (target_email = $3 OR target_phone_number = $3)",
+            &[&username, &DirectInvitationStatus::Created, target.value()],
+        )
+        .map_err(APIInternalError::from_db_err)?;
+        Ok(Some(username))
+    })?;
+    let username = match username {
+        Some(username) => username,
+        None => {
+            record_failed_attempt(conn, target)?;
+            return Err(APIInternalError {
+                msg: TranslationIds::VerificationFailure,
+                engineering_error: None,
+            });
+        }
+    };
+
+    get_user_details(&username, conn)
+        .map_err(APIInternalError::from_db_err)?
+        .ok_or(APIInternalError::backend_issue("Unable to fetch user info"))
+}
+
+fn record_failed_attempt(
+    conn: &mut PostgresConnection,
+    target: &VerificationTarget,
+) -> Result<(), APIInternalError> {
+    conn.execute(
+        "UPDATE users_verification
+         SET failed_attempts = failed_attempts + 1, updated_timestamp = NOW()
+         WHERE (email = $1 OR phone_number = $1)
+           AND expiration_timestamp > NOW()
+           AND used = false",
+        &[target.value()],
+    )
+    .map(|_| ())
+    .map_err(APIInternalError::from_db_err)
+}
+
+/// Creates a verification code alternating numbers and letters,
+/// starting with a number.
+pub fn create_verification_code() -> String {
+    let mut rng = rand::thread_rng();
+    (0..CODE_LENGTH)
+        .map(|i| {
+            let charset = if i % 2 == 0 {
+                CODE_NUMBERS
+            } else {
+                CODE_LETTERS
+            };
+            charset[rng.gen_range(0..charset.len())] as char
+        })
+        .collect()
+}
+
+fn insert_verification<C: GenericClient>(
+    client: &mut C,
+    target: &VerificationTarget,
+    public_key: &str,
+) -> Result<String, postgres::Error> {
+    let column = match target {
+        VerificationTarget::Email(_) => "email",
+        VerificationTarget::Phone(_) => "phone_number",
+    };
+    let code = create_verification_code();
+    client
+        .execute(
+            format!(
+                "INSERT INTO users_verification
+                    (verification_code, {}, public_key, used, creation_timestamp, updated_timestamp, expiration_timestamp)
+                 VALUES ($1, $2, $3, false, NOW(), NOW(), NOW() + interval '{} minute')",
+                column, VERIFICATION_CODE_EXPIRATION_MINUTES
+            )
+            .as_str(),
+            &[&code, target.value(), &public_key.trim()],
+        )
+        .map(|_| code)
+}
+
+fn get_username<C: GenericClient>(
+    client: &mut C,
+    target: &VerificationTarget,
+) -> Result<String, APIInternalError> {
+    client
+        .query(
+            "SELECT username FROM users WHERE email = $1 OR phone_number = $1",
+            &[target.value()],
+        )
+        .map_err(APIInternalError::from_db_err)
+        .and_then(|rows| {
+            rows.into_iter()
+                .next()
+                .map(|row| row.get("username"))
+                .ok_or(APIInternalError {
+                    msg: TranslationIds::BadRequest,
+                    engineering_error: Some("Username does not exist".to_string()),
+                })
+        })
+}
+
+fn register_device<C: GenericClient>(
+    client: &mut C,
+    username: &String,
+    req: &VerificationRequest,
+) -> Result<(), APIInternalError> {
+    let device_id = req.deviceId.trim();
+    let current_owner = client
+        .query(
+            "SELECT username FROM users_devices WHERE device_id = $1 AND owner = true",
+            &[&device_id],
+        )
+        .map_err(APIInternalError::from_db_err)?
+        .into_iter()
+        .next()
+        .map(|row| row.get::<_, String>("username"));
+
+    match current_owner {
+        Some(owner) if &owner == username => Ok(()),
+        Some(_) => Err(APIInternalError {
+            msg: TranslationIds::DeviceIsRegisteredToAnotherUser,
+            engineering_error: None,
+        }),
+        None => {
+            let other_devices = client
+                .query(
+                    "SELECT device_id FROM users_devices WHERE username = $1 AND owner = true",
+                    &[username],
+                )
+                .map_err(APIInternalError::from_db_err)?;
+            if !other_devices.is_empty() {
+                return Err(APIInternalError {
+                    msg: TranslationIds::ThereIsAnotherDeviceRegistered,
+                    engineering_error: None,
+                });
+            }
+            client
+                .execute(
+                    "INSERT INTO users_devices (username, device_id, owner, access_enabled, permissions)
+                     VALUES ($1, $2, true, true, '{\"permanentAccess\": true}'::jsonb)",
+                    &[username, &device_id],
+                )
+                .map_err(APIInternalError::from_db_err)?;
+            client
+                .execute(
+                    "INSERT INTO devices (device_id, role, name, os, os_version, model, app_version)
+                     VALUES ($1, 'phone', $1, $2, $3, $4, $5)",
+                    &[
+                        &device_id,
+                        &req.os,
+                        &req.osVersion,
+                        &req.model,
+                        &req.appVersion,
+                    ],
+                )
+                .map_err(APIInternalError::from_db_err)
+                .map(|_| ())
+        }
+    }
+}
+
+fn map_registration_err(err: postgres::Error) -> APIInternalError {
+    let constraint = err
+        .as_db_error()
+        .filter(|db_err| db_err.code() == &SqlState::UNIQUE_VIOLATION)
+        .and_then(|db_err| db_err.constraint())
+        .map(|constraint| constraint.to_string());
+    match constraint.as_deref() {
+        Some("users_pkey") => APIInternalError {
+            msg: TranslationIds::TryAnotherUsername,
+            engineering_error: None,
+        },
+        Some("users_email_key") => APIInternalError {
+            msg: TranslationIds::TryAnotherEmail,
+            engineering_error: None,
+        },
+        Some("users_phone_number_key") => APIInternalError {
+            msg: TranslationIds::TryAnotherPhone,
+            engineering_error: None,
+        },
+        _ => APIInternalError::from_db_err(err),
+    }
+}
+
+pub(crate) fn translate(language: &str, id: MessageIds) -> String {
+    get_messages(language)
+        .get(&id)
+        .unwrap_or(&"Unknown error getting translated string")
+        .to_string()
+}
+
+pub(crate) fn format_translation<A: serde::Serialize>(
+    language: &str,
+    id: MessageIds,
+    args: &[A],
+) -> Result<String, APIInternalError> {
+    SimpleCurlyFormat
+        .format(&translate(language, id), args)
+        .map(|text| text.into_owned())
+        .map_err(APIInternalError::backend_issue)
+}
@@ -10,18 +10,20 @@
  * See the License for the specific language governing permissions and
  * limitations under the License.
  */
+use chrono::NaiveDateTime;
 use postgres::{NoTls, Row, Statement};
 use r2d2::PooledConnection;
 use r2d2_postgres::PostgresConnectionManager;
 
+use crate::constants::DATE_FORMAT;
 use crate::lang::TranslationIds;
 use crate::model::{
-    devices::{Device, OS},
+    devices::{Device, DeviceSettingsSnapshot, OS},
     responses::Errors::APIInternalError,
 };
 
-fn row_to_device(row: &Row) -> Option<Device> {
-    return Option::Some(Device {
+pub fn row_to_device(row: &Row) -> Device {
+    Device {
         deviceId: row.get("device_id"),
         role: row.get("role"),
         name: row.get("name"),
@@ -36,7 +38,7 @@ fn row_to_device(row: &Row) -> Option<Device> {
         model: row.get("model"),
         pushToken: row.get("push_token"),
         appVersion: row.get("app_version"),
-    });
+    }
 }
 
 pub fn get_device_by_id(
@@ -57,7 +59,7 @@ pub fn get_device_by_id(
         .query(&latest_device_settings_statement, &[&device_id])
         .map_err(APIInternalError::from_db_err)?
         .iter()
-        .fold(None, |_acc, row| row_to_device(row))
+        .fold(None, |_acc, row| Some(row_to_device(row)))
     {
         Some(d) => Ok(d),
         None => Err(APIInternalError {
@@ -71,8 +73,20 @@ pub fn update_device_settings(
     device_update: Device,
     client: &mut PooledConnection<PostgresConnectionManager<NoTls>>,
 ) -> Result<bool, APIInternalError> {
-    let insert_statement = client
-        .prepare(
+    let mut transaction = client
+        .transaction()
+        .map_err(APIInternalError::from_db_err)?;
+    // A token belongs to a single app install, the device that had it before is stale.
+    if let Some(push_token) = &device_update.pushToken {
+        transaction
+            .execute(
+                "UPDATE devices SET push_token = NULL WHERE push_token = $1 AND device_id <> $2",
+                &[push_token, &device_update.deviceId],
+            )
+            .map_err(APIInternalError::from_db_err)?;
+    }
+    let new_device = transaction
+        .query(
             "
              UPDATE devices SET
                  app_version=$2,
@@ -83,16 +97,11 @@ pub fn update_device_settings(
                  location_permission_state=$7,
                  model=$8,
                  os=$9,
-                 os_version=$10
+                 os_version=$10,
+                 push_token=$11
              WHERE device_id = $1
              RETURNING *
          ",
-        )
-        .map_err(APIInternalError::from_db_err)?;
-
-    let new_device = client
-        .query(
-            &insert_statement,
             &[
                 &device_update.deviceId,
                 &device_update.appVersion,
@@ -104,11 +113,15 @@ pub fn update_device_settings(
                 &device_update.model,
                 &device_update.os,
                 &device_update.osVersion,
+                &device_update.pushToken,
             ],
         )
         .map_err(APIInternalError::from_db_err)?
         .iter()
-        .fold(None, |_acc, row| row_to_device(row));
+        .fold(None, |_acc, row| Some(row_to_device(row)));
+    transaction
+        .commit()
+        .map_err(APIInternalError::from_db_err)?;
 
     match new_device {
         Some(_) => Ok(true),
@@ -119,6 +132,26 @@ pub fn update_device_settings(
     }
 }
 
+/// Stops sending push notifications to the device, e.g. when the user logs out.
+pub fn clear_push_token(
+    device_id: &str,
+    client: &mut PooledConnection<PostgresConnectionManager<NoTls>>,
+) -> Result<bool, APIInternalError> {
+    let updated = client
+        .execute(
+            "UPDATE devices SET push_token = NULL WHERE device_id = $1",
+            &[&device_id],
+        )
+        .map_err(APIInternalError::from_db_err)?;
+    if updated == 0 {
+        return Err(APIInternalError {
+            msg: TranslationIds::DeviceNotUpdated,
+            engineering_error: None,
+        });
+    }
+    Ok(true)
+}
+
 pub fn get_subscriber_device_ids(
     client: &mut PooledConnection<PostgresConnectionManager<NoTls>>,
     username: &String,
@@ -146,3 +179,60 @@ pub fn get_subscriber_device_ids(
             .collect()
     })
 }
+
+/// Settings of a device owned by the user after each of its last `limit` updates,
+/// newest first, with the tracking issues that every update introduced.
+pub fn get_device_settings_history(
+    client: &mut PooledConnection<PostgresConnectionManager<NoTls>>,
+    username: &String,
+    device_id: &String,
+    limit: i64,
+) -> Result<Vec<DeviceSettingsSnapshot>, APIInternalError> {
+    // One more row to know the regressions of the oldest update.
+    let rows = client
+        .query(
+            "SELECT device_settings.*
+             FROM device_settings
+             INNER JOIN users_devices ON users_devices.device_id = device_settings.device_id
+             WHERE users_devices.username = $1
+             AND users_devices.device_id = $2
+             AND users_devices.owner = true
+             ORDER BY device_settings.creation_timestamp DESC
+             LIMIT $3",
+            &[username, device_id, &(limit + 1)],
+        )
+        .map_err(APIInternalError::from_db_err)?;
+    if rows.is_empty() {
+        return Err(APIInternalError {
+            msg: TranslationIds::DeviceNotFound,
+            engineering_error: None,
+        });
+    }
+    let devices: Vec<(Device, NaiveDateTime)> = rows
+        .iter()
+        .map(|row| (row_to_device(row), row.get("creation_timestamp")))
+        .collect();
+    Ok(devices
+        .iter()
+        .enumerate()
+        .take(limit as usize)
+        .map(|(index, (device, timestamp))| {
+            // The first settings of the device are not a regression.
+            let regressions = devices.get(index + 1).map_or(vec![], |(previous, _)| {
+                device.tracking_regressions(previous)
+            });
+            DeviceSettingsSnapshot {
+                timestamp: timestamp.format(DATE_FORMAT).to_string(),
+                locationPermissionState: device.locationPermissionState,
+                isNotificationsEnabled: device.isNotificationsEnabled,
+                isBackgroundRefreshOn: device.isBackgroundRefreshOn,
+                isLocationServicesOn: device.isLocationServicesOn,
+                isPowerSaveModeOn: device.isPowerSaveModeOn,
+                osVersion: device.osVersion.clone(),
+                appVersion: device.appVersion.clone(),
+                trackingIssues: device.tracking_issues(),
+                regressions,
+            }
+        })
+        .collect())
+}
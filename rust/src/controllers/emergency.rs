@@ -2,17 +2,25 @@ use crate::constants::DEFAULT_NOTIFICATION_ICON;
 use crate::messaging::get_rabbitmq_uri;
 use crate::model::{
     auth::AuthInfo,
-    emergency::UserState,
-    telemetry::{DateTimeRange, Location},
+    emergency::{
+        EmergencySettings, ExportedDevice, ExportedLocation, HistoricalLocationExport,
+        SignedExport, StateTransition, UserState,
+    },
+    telemetry::{
+        DateTimeRange, HistoricalLocationPage, Location, TelemetryCursor, TelemetryFilter,
+    },
 };
 use crate::server::validators::{
-    datetime::assert_valid_location_historical_start, emergency_user::assert_emergency_user,
+    datetime::assert_valid_location_historical_start, emergency_user::assert_readable_emergency,
     friends::assert_not_friends,
 };
 use crate::{
-    constants::{CS_PROFILE_IMAGE_PATH, GENERIC_EMAIL_TEMPLATE, WEB_URL},
+    constants::{
+        DATE_FORMAT, DEFAULT_HISTORICAL_ACCESS_HOURS, DEFAULT_HISTORICAL_GRACE_HOURS,
+        EXPORT_SIGNATURE_ALGORITHM, GENERIC_EMAIL_TEMPLATE, HISTORICAL_STREAM_BATCH_SIZE, WEB_URL,
+    },
     controllers::telemetry::get_user_details,
-    lang::{get_glossary, TranslationIds},
+    lang::{get_messages, MessageIds},
     messaging::{build_user_push_notifications, send_notification},
     model::{
         notifications::{
@@ -20,30 +28,284 @@ use crate::{
             PushNotification,
         },
         responses::Errors::APIInternalError,
-        PostgresConnection, UserDetails,
+        PostgresConnection, PostgresPool, UserDetails,
     },
+    storage::picture_url,
 };
 
 use amiquip::{Connection as RabbitConnection, Result};
+use chrono::{NaiveDateTime, Utc};
 use dynfmt::{Format, SimpleCurlyFormat};
+use jsonwebtoken::{crypto, EncodingKey};
 use log::error;
 use rocket_contrib::json::JsonValue;
+use std::env;
+use std::io::{self, Read};
 
 /// Get the historical location of a user for a given range
-/// Assert if location range is valid, user is in emergency and
-/// both users are friends.
+/// Assert if both users are friends, user is in emergency or was
+/// during the grace period and the range starts inside the access window.
+/// Without a limit every location after the cursor is returned in one page.
 ///
-/// @return APIResult<Vec<Location>>
+/// @return APIResult<HistoricalLocationPage>
 pub fn get_historical_location(
     conn: &mut PostgresConnection,
     auth_info: &AuthInfo,
     emergency_user: &String,
     range: &DateTimeRange,
-) -> Result<Vec<Location>, APIInternalError> {
-    assert_valid_location_historical_start(&range.start_time)
-        .and_then(|_| assert_not_friends(conn, &auth_info.username, &emergency_user))
-        .and_then(|_| assert_emergency_user(conn, &emergency_user))
-        .and_then(|_| get_historical_telemetry(conn, &auth_info.username, &emergency_user, &range))
+    filter: &TelemetryFilter,
+    cursor: Option<&TelemetryCursor>,
+    limit: Option<i64>,
+) -> Result<HistoricalLocationPage, APIInternalError> {
+    let range = readable_range(conn, auth_info, emergency_user, range)?;
+    let mut page = get_historical_telemetry(
+        conn,
+        &auth_info.username,
+        &emergency_user,
+        &range,
+        filter,
+        cursor,
+        limit.map(|limit| limit + 1),
+    )?;
+    let next_cursor = match limit {
+        Some(limit) if page.len() as i64 > limit => {
+            page.truncate(limit as usize);
+            page.last().map(|(cursor, _)| cursor.to_string())
+        }
+        _ => None,
+    };
+    Ok(HistoricalLocationPage {
+        locations: page.into_iter().map(|(_, location)| location).collect(),
+        nextCursor: next_cursor,
+    })
+}
+
+/// Same as get_historical_location but the locations are read in batches
+/// while they are written to the response as newline delimited JSON.
+/// Each batch takes a connection from the pool and returns it once read, so
+/// a slow client doesn't hold one for the whole stream.
+pub fn stream_historical_location(
+    pool: &PostgresPool,
+    auth_info: &AuthInfo,
+    emergency_user: &String,
+    range: &DateTimeRange,
+    filter: &TelemetryFilter,
+    cursor: Option<&TelemetryCursor>,
+) -> Result<HistoricalLocationStream, APIInternalError> {
+    let mut conn = pool.get().map_err(APIInternalError::backend_issue)?;
+    let range = readable_range(&mut conn, auth_info, emergency_user, range)?;
+    Ok(HistoricalLocationStream {
+        pool: pool.clone(),
+        req_user: auth_info.username.clone(),
+        emergency_user: emergency_user.clone(),
+        range,
+        filter: filter.clone(),
+        cursor: cursor.cloned(),
+        buffer: vec![],
+        position: 0,
+        done: false,
+    })
+}
+
+pub struct HistoricalLocationStream {
+    pool: PostgresPool,
+    req_user: String,
+    emergency_user: String,
+    range: DateTimeRange,
+    filter: TelemetryFilter,
+    cursor: Option<TelemetryCursor>,
+    buffer: Vec<u8>,
+    position: usize,
+    done: bool,
+}
+
+impl HistoricalLocationStream {
+    fn fill_buffer(&mut self) -> io::Result<()> {
+        let mut conn = self
+            .pool
+            .get()
+            .map_err(|err| io::Error::new(io::ErrorKind::Other, err.to_string()))?;
+        let page = get_historical_telemetry(
+            &mut conn,
+            &self.req_user,
+            &self.emergency_user,
+            &self.range,
+            &self.filter,
+            self.cursor.as_ref(),
+            Some(HISTORICAL_STREAM_BATCH_SIZE),
+        )
+        .map_err(|err| {
+            io::Error::new(
+                io::ErrorKind::Other,
+                err.engineering_error
+                    .unwrap_or_else(|| "Unable to read the telemetry".to_string()),
+            )
+        })?;
+        self.done = (page.len() as i64) < HISTORICAL_STREAM_BATCH_SIZE;
+        self.buffer.clear();
+        self.position = 0;
+        for (cursor, location) in page {
+            serde_json::to_writer(&mut self.buffer, &location)?;
+            self.buffer.push(b'\n');
+            self.cursor = Some(cursor);
+        }
+        Ok(())
+    }
+}
+
+impl Read for HistoricalLocationStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.position == self.buffer.len() && !self.done {
+            self.fill_buffer()?;
+        }
+        let read = (&self.buffer[self.position..]).read(buf)?;
+        self.position += read;
+        Ok(read)
+    }
+}
+
+/// Part of `range` that `auth_info` can read, it ends when the emergency ended.
+fn readable_range(
+    conn: &mut PostgresConnection,
+    auth_info: &AuthInfo,
+    emergency_user: &String,
+    range: &DateTimeRange,
+) -> Result<DateTimeRange, APIInternalError> {
+    assert_not_friends(conn, &auth_info.username, &emergency_user)?;
+    let settings = get_emergency_settings(conn, emergency_user)?;
+    let emergency = assert_readable_emergency(conn, &emergency_user, &settings)?;
+    assert_valid_location_historical_start(&range.start_time, &emergency, &settings)?;
+    Ok(DateTimeRange {
+        start_time: range.start_time,
+        end_time: emergency
+            .ended_at
+            .map_or(range.end_time, |ended_at| ended_at.min(range.end_time)),
+    })
+}
+
+/// Settings of the user, or the defaults for users created before they existed.
+pub fn get_emergency_settings(
+    conn: &mut PostgresConnection,
+    username: &String,
+) -> Result<EmergencySettings, APIInternalError> {
+    conn.query(
+        "SELECT historical_access_hours, historical_grace_hours, low_battery_alerts,
+                tracking_alerts
+         FROM users_settings WHERE username = $1",
+        &[username],
+    )
+    .map_err(APIInternalError::from_db_err)
+    .map(|rows| {
+        rows.into_iter().next().map_or(
+            EmergencySettings {
+                historicalAccessHours: DEFAULT_HISTORICAL_ACCESS_HOURS,
+                historicalGraceHours: DEFAULT_HISTORICAL_GRACE_HOURS,
+                lowBatteryAlerts: false,
+                trackingAlerts: false,
+            },
+            |row| EmergencySettings {
+                historicalAccessHours: row.get("historical_access_hours"),
+                historicalGraceHours: row.get("historical_grace_hours"),
+                lowBatteryAlerts: row.get("low_battery_alerts"),
+                trackingAlerts: row.get("tracking_alerts"),
+            },
+        )
+    })
+}
+
+pub fn update_emergency_settings(
+    conn: &mut PostgresConnection,
+    username: &String,
+    settings: &EmergencySettings,
+) -> Result<(), APIInternalError> {
+    conn.execute(
+        "INSERT INTO users_settings
+            (username, historical_access_hours, historical_grace_hours, low_battery_alerts,
+             tracking_alerts)
+         VALUES ($1, $2, $3, $4, $5)
+         ON CONFLICT (username) DO UPDATE
+         SET historical_access_hours = $2, historical_grace_hours = $3,
+             low_battery_alerts = $4, tracking_alerts = $5, update_timestamp = now()",
+        &[
+            username,
+            &settings.historicalAccessHours,
+            &settings.historicalGraceHours,
+            &settings.lowBatteryAlerts,
+            &settings.trackingAlerts,
+        ],
+    )
+    .map_err(APIInternalError::from_db_err)
+    .map(|_| ())
+}
+
+/// Export a page of the historical location of a user in emergency with the
+/// devices, battery state and state transitions of the range.
+/// Same checks as get_historical_location, the page is signed by the server
+/// so it can be handed to the authorities.
+///
+/// @return APIResult<SignedExport>
+pub fn export_historical_location(
+    conn: &mut PostgresConnection,
+    auth_info: &AuthInfo,
+    emergency_user: &String,
+    range: &DateTimeRange,
+    cursor: Option<&TelemetryCursor>,
+    limit: i64,
+) -> Result<SignedExport, APIInternalError> {
+    let range = &readable_range(conn, auth_info, emergency_user, range)?;
+    let mut page = get_exported_telemetry(
+        conn,
+        &auth_info.username,
+        emergency_user,
+        range,
+        cursor,
+        limit + 1,
+    )?;
+    let next_cursor = if page.len() as i64 > limit {
+        page.truncate(limit as usize);
+        page.last().map(|(cursor, _)| cursor.to_string())
+    } else {
+        None
+    };
+    let locations: Vec<ExportedLocation> = page.into_iter().map(|(_, location)| location).collect();
+    let mut device_ids: Vec<String> = locations
+        .iter()
+        .map(|location| location.deviceId.clone())
+        .collect();
+    device_ids.sort();
+    device_ids.dedup();
+
+    let export = HistoricalLocationExport {
+        username: emergency_user.clone(),
+        requestedBy: auth_info.username.clone(),
+        startTime: range.start_time.format(DATE_FORMAT).to_string(),
+        endTime: range.end_time.format(DATE_FORMAT).to_string(),
+        generatedAt: Utc::now().format(DATE_FORMAT).to_string(),
+        devices: get_exported_devices(conn, &device_ids)?,
+        locations,
+        stateTransitions: get_state_transitions(conn, emergency_user, range)?,
+        nextCursor: next_cursor,
+    };
+    let payload = serde_json::to_string(&export).map_err(APIInternalError::backend_issue)?;
+    let signature = sign_export(&payload)?;
+    Ok(SignedExport {
+        payload,
+        signature,
+        algorithm: format!("{:?}", EXPORT_SIGNATURE_ALGORITHM),
+    })
+}
+
+fn sign_export(payload: &str) -> Result<String, APIInternalError> {
+    let key = env::var("EXPORT_SIGNING_KEY")
+        .map_err(|_| APIInternalError::backend_issue("EXPORT_SIGNING_KEY must be set"))?;
+    let key = EncodingKey::from_rsa_pem(key.as_bytes()).map_err(APIInternalError::backend_issue)?;
+    crypto::sign(payload, &key, EXPORT_SIGNATURE_ALGORITHM).map_err(APIInternalError::backend_issue)
+}
+
+/// Public key that verifies the signature of the exports.
+pub fn get_export_verification_key() -> Result<String, APIInternalError> {
+    env::var("EXPORT_VERIFICATION_KEY")
+        .map_err(|_| APIInternalError::backend_issue("EXPORT_VERIFICATION_KEY must be set"))
 }
 
 /// Set user state to Normal or Emergency.
@@ -167,24 +429,21 @@ fn build_emergency_email(
     state: &UserState,
 ) -> Email {
     let data = build_notification_data_from_recipient(sender, recipient, state);
-    let link = get_glossary(&recipient.language.clone().unwrap())
-        .get(&TranslationIds::PushNotificationActionView)
+    let link = get_messages(&recipient.language.clone().unwrap())
+        .get(&MessageIds::PushNotificationActionView)
         .unwrap()
         .to_string();
     Email {
-        username: recipient.username.clone(),
+        username: Some(recipient.username.clone()),
         email: recipient.email.clone().unwrap(),
         templateId: GENERIC_EMAIL_TEMPLATE.to_string(),
         dynamicTemplateData: DynamicEmailTemplateData {
             title: data.title,
             body: data.body,
-            picture: if let Some(picture) = sender.picture.clone() {
-                Some(format!("{}/{}", CS_PROFILE_IMAGE_PATH, picture))
-            } else {
-                None
-            },
+            picture: sender.picture.as_deref().map(picture_url),
             link: Some(WEB_URL.to_string()),
             linkTitle: link,
+            code: None,
         },
     }
 }
@@ -194,10 +453,10 @@ fn build_notification_data_from_recipient(
     recipient: &UserDetails,
     state: &UserState,
 ) -> NotificationData {
-    let body = get_glossary(&recipient.language.clone().unwrap())
+    let body = get_messages(&recipient.language.clone().unwrap())
         .get(match state {
-            UserState::Emergency => &TranslationIds::EmergencyModePushNotificationBody,
-            UserState::Normal => &TranslationIds::NormalModePushNotificationBody,
+            UserState::Emergency => &MessageIds::EmergencyModePushNotificationBody,
+            UserState::Normal => &MessageIds::NormalModePushNotificationBody,
         })
         .unwrap();
     let body = &SimpleCurlyFormat
@@ -242,22 +501,164 @@ fn get_historical_telemetry(
     req_user: &String,
     emergency_user: &String,
     range: &DateTimeRange,
-) -> Result<Vec<Location>, APIInternalError> {
+    filter: &TelemetryFilter,
+    cursor: Option<&TelemetryCursor>,
+    limit: Option<i64>,
+) -> Result<Vec<(TelemetryCursor, Location)>, APIInternalError> {
+    // The cursor also bounds the inner scan so pages don't rescan the whole range. When
+    // downsampling, the scan starts at the cursor's bucket so its first sample is still known.
     conn.query(
-        "SELECT encrypted_location, device_id, creation_timestamp AS timestamp
-         FROM device_telemetry WHERE username = $1 AND recipient_username = $4
-         AND creation_timestamp > $2 AND creation_timestamp < $3
-         ORDER BY timestamp ASC",
-        &[emergency_user, &range.start_time, &range.end_time, req_user],
+        "SELECT encrypted_location, device_id, creation_timestamp AS timestamp FROM (
+            SELECT encrypted_location, device_id, creation_timestamp,
+                   row_number() OVER (
+                       PARTITION BY device_id,
+                                    floor(extract(epoch FROM creation_timestamp) / $6::float8)
+                       ORDER BY creation_timestamp
+                   ) AS sample
+            FROM device_telemetry WHERE username = $1 AND recipient_username = $4
+            AND creation_timestamp > $2 AND creation_timestamp < $3
+            AND ($5::text IS NULL OR device_id = $5)
+            AND ($7::timestamp IS NULL
+                 OR ($6::float8 IS NULL AND (creation_timestamp, device_id) > ($7, $8::text))
+                 OR ($6::float8 IS NOT NULL AND creation_timestamp >= 'epoch'::timestamp
+                     + floor(extract(epoch FROM $7::timestamp) / $6) * $6 * interval '1 second'))
+         ) AS telemetry
+         WHERE ($6::float8 IS NULL OR sample = 1)
+         AND ($7::timestamp IS NULL OR (creation_timestamp, device_id) > ($7, $8::text))
+         ORDER BY timestamp ASC, device_id ASC
+         LIMIT $9",
+        &[
+            emergency_user,
+            &range.start_time,
+            &range.end_time,
+            req_user,
+            &filter.device_id,
+            &filter.interval_seconds.map(|seconds| seconds as f64),
+            &cursor.map(|cursor| cursor.timestamp),
+            &cursor.map(|cursor| cursor.device_id.clone()),
+            &limit,
+        ],
     )
     .map_err(APIInternalError::from_db_err)
     .map(|results| {
         results
             .into_iter()
-            .map(|row| Location {
-                data: row.get("encrypted_location"),
-                device_id: row.get("device_id"),
-                timestamp: row.get("timestamp"),
+            .map(|row| {
+                let location = Location {
+                    data: row.get("encrypted_location"),
+                    device_id: row.get("device_id"),
+                    timestamp: row.get("timestamp"),
+                };
+                let cursor = TelemetryCursor {
+                    timestamp: location.timestamp,
+                    device_id: location.device_id.clone(),
+                };
+                (cursor, location)
+            })
+            .collect()
+    })
+}
+
+fn get_exported_telemetry(
+    conn: &mut PostgresConnection,
+    req_user: &String,
+    emergency_user: &String,
+    range: &DateTimeRange,
+    cursor: Option<&TelemetryCursor>,
+    limit: i64,
+) -> Result<Vec<(TelemetryCursor, ExportedLocation)>, APIInternalError> {
+    conn.query(
+        "SELECT encrypted_location, device_id, creation_timestamp, app_state,
+                battery_level, charging_state, is_charging
+         FROM device_telemetry WHERE username = $1 AND recipient_username = $2
+         AND creation_timestamp > $3 AND creation_timestamp < $4
+         AND ($5::timestamp IS NULL OR (creation_timestamp, device_id) > ($5, $6))
+         ORDER BY creation_timestamp ASC, device_id ASC
+         LIMIT $7",
+        &[
+            emergency_user,
+            req_user,
+            &range.start_time,
+            &range.end_time,
+            &cursor.map(|cursor| cursor.timestamp),
+            &cursor.map(|cursor| cursor.device_id.clone()),
+            &limit,
+        ],
+    )
+    .map_err(APIInternalError::from_db_err)
+    .map(|rows| {
+        rows.into_iter()
+            .map(|row| {
+                let timestamp: NaiveDateTime = row.get("creation_timestamp");
+                let device_id: String = row.get("device_id");
+                (
+                    TelemetryCursor {
+                        timestamp,
+                        device_id: device_id.clone(),
+                    },
+                    ExportedLocation {
+                        data: row.get("encrypted_location"),
+                        deviceId: device_id,
+                        timestamp: timestamp.format(DATE_FORMAT).to_string(),
+                        appState: row.get("app_state"),
+                        batteryLevel: row.get("battery_level"),
+                        chargingState: row.get("charging_state"),
+                        isCharging: row.get("is_charging"),
+                    },
+                )
+            })
+            .collect()
+    })
+}
+
+fn get_exported_devices(
+    conn: &mut PostgresConnection,
+    device_ids: &[String],
+) -> Result<Vec<ExportedDevice>, APIInternalError> {
+    conn.query(
+        "SELECT device_id, name, os, os_version, model, app_version
+         FROM devices WHERE device_id = ANY($1) ORDER BY device_id",
+        &[&device_ids],
+    )
+    .map_err(APIInternalError::from_db_err)
+    .map(|rows| {
+        rows.into_iter()
+            .map(|row| ExportedDevice {
+                deviceId: row.get("device_id"),
+                name: row.get("name"),
+                os: row.get("os"),
+                osVersion: row.get("os_version"),
+                model: row.get("model"),
+                appVersion: row.get("app_version"),
+            })
+            .collect()
+    })
+}
+
+fn get_state_transitions(
+    conn: &mut PostgresConnection,
+    username: &String,
+    range: &DateTimeRange,
+) -> Result<Vec<StateTransition>, APIInternalError> {
+    conn.query(
+        "(SELECT self_perception, creation_timestamp FROM users_state_history
+          WHERE username = $1 AND creation_timestamp <= $2
+          ORDER BY creation_timestamp DESC LIMIT 1)
+         UNION ALL
+         (SELECT self_perception, creation_timestamp FROM users_state_history
+          WHERE username = $1 AND creation_timestamp > $2 AND creation_timestamp < $3)
+         ORDER BY creation_timestamp ASC",
+        &[username, &range.start_time, &range.end_time],
+    )
+    .map_err(APIInternalError::from_db_err)
+    .map(|rows| {
+        rows.into_iter()
+            .map(|row| {
+                let timestamp: NaiveDateTime = row.get("creation_timestamp");
+                StateTransition {
+                    state: row.get("self_perception"),
+                    timestamp: timestamp.format(DATE_FORMAT).to_string(),
+                }
             })
             .collect()
     })
@@ -0,0 +1,395 @@
+use crate::constants::{DATE_FORMAT, TELEMETRY_LAST_SEEN_SET};
+use crate::controllers::{
+    health::{build_alert_notifications, publish_notifications},
+    telemetry::redis_hash_map_name,
+};
+use crate::lang::{MessageIds, TranslationIds};
+use crate::model::{
+    emergency::{AccessType, FollowerAccess, LocationSharing, UserState},
+    friends::{Friend, FriendsFilter, FriendsPage},
+    responses::Errors::APIInternalError,
+    PostgresConnection, UserDetails,
+};
+use chrono::NaiveDateTime;
+use redis::Commands;
+
+/// Page of the friends of `username` ordered by username, optionally only the
+/// ones whose username or name contains `search`. The cursor is the last
+/// username of the previous page.
+pub fn get_friends(
+    conn: &mut PostgresConnection,
+    redis: &mut redis::Connection,
+    username: &String,
+    filter: FriendsFilter,
+    search: Option<&str>,
+    cursor: Option<&String>,
+    limit: i64,
+) -> Result<FriendsPage, APIInternalError> {
+    let condition = match filter {
+        FriendsFilter::All => "(follower.username IS NOT NULL OR following.username IS NOT NULL)",
+        FriendsFilter::Followers => "follower.username IS NOT NULL",
+        FriendsFilter::Following => "following.username IS NOT NULL",
+    };
+    let pattern = search
+        .map(str::trim)
+        .filter(|search| !search.is_empty())
+        .map(|search| {
+            format!(
+                "%{}%",
+                search
+                    .replace('\\', "\\\\")
+                    .replace('%', "\\%")
+                    .replace('_', "\\_")
+            )
+        });
+    let rows = conn
+        .query(
+            format!(
+                "SELECT users.username, users.email, users.phone_number, user_details.first_name,
+                    user_details.last_name, user_details.picture, user_details.language,
+                    users_identity.public_key,
+                    follower.access_type AS follower_access_type,
+                    follower.is_emergency_contact AS follower_is_emergency_contact,
+                    following.access_type AS following_access_type,
+                    following.is_emergency_contact AS following_is_emergency_contact,
+                    COALESCE(following.access_type = 'Permanent'
+                        OR users_state.self_perception = 'Emergency'
+                        OR following.sharing_expires_at > now(), false) AS can_see_location
+                 FROM users
+                 INNER JOIN user_details ON user_details.username = users.username
+                 LEFT JOIN users_identity ON users_identity.username = users.username
+                 LEFT JOIN users_state ON users_state.username = users.username
+                 LEFT JOIN users_followers follower
+                    ON follower.username = $1 AND follower.username_follower = users.username
+                 LEFT JOIN users_followers following
+                    ON following.username = users.username AND following.username_follower = $1
+                 WHERE {}
+                 AND users.username > $2
+                 AND ($3::text IS NULL OR users.username ILIKE $3
+                    OR user_details.first_name ILIKE $3 OR user_details.last_name ILIKE $3)
+                 ORDER BY users.username
+                 LIMIT $4",
+                condition
+            )
+            .as_str(),
+            &[
+                username,
+                &cursor.cloned().unwrap_or_default(),
+                &pattern,
+                &(limit + 1),
+            ],
+        )
+        .map_err(APIInternalError::from_db_err)?;
+
+    let has_more = rows.len() as i64 > limit;
+    let rows = &rows[..rows.len().min(limit as usize)];
+    // Last seen of the friends whose location the user can see, in one round trip.
+    let mut pipe = redis::pipe();
+    for row in rows {
+        pipe.zscore(TELEMETRY_LAST_SEEN_SET, row.get::<_, String>("username"));
+    }
+    let last_seen: Vec<Option<i64>> = pipe.query(redis).map_err(APIInternalError::from_db_err)?;
+
+    let friends: Vec<Friend> = rows
+        .iter()
+        .zip(last_seen)
+        .map(|(row, last_seen)| {
+            let can_see_location: bool = row.get("can_see_location");
+            Friend {
+                userDetails: UserDetails::from_complete_details(row),
+                publicKey: row.get("public_key"),
+                follower: row
+                    .get::<_, Option<AccessType>>("follower_access_type")
+                    .map(|access_type| FollowerAccess {
+                        accessType: access_type,
+                        isEmergencyContact: row.get("follower_is_emergency_contact"),
+                    }),
+                following: row
+                    .get::<_, Option<AccessType>>("following_access_type")
+                    .map(|access_type| FollowerAccess {
+                        accessType: access_type,
+                        isEmergencyContact: row.get("following_is_emergency_contact"),
+                    }),
+                lastSeen: last_seen.filter(|_| can_see_location).map(|timestamp| {
+                    NaiveDateTime::from_timestamp(timestamp, 0)
+                        .format(DATE_FORMAT)
+                        .to_string()
+                }),
+            }
+        })
+        .collect();
+    let next_cursor = if has_more {
+        friends
+            .last()
+            .map(|friend| friend.userDetails.username.clone())
+    } else {
+        None
+    };
+    Ok(FriendsPage {
+        friends,
+        nextCursor: next_cursor,
+    })
+}
+
+/// Changes what `follower` can see of `username` and notifies the follower of
+/// every change. Followers demoted to EmergencyOnly outside of an emergency lose
+/// the last location cached for them, the next pings are not sent to them.
+pub fn update_follower_access(
+    conn: &mut PostgresConnection,
+    redis: &mut redis::Connection,
+    username: &String,
+    follower: &String,
+    access: &FollowerAccess,
+) -> Result<(), APIInternalError> {
+    let mut transaction = conn.transaction().map_err(APIInternalError::from_db_err)?;
+    let row = transaction
+        .query(
+            "SELECT users_followers.access_type, users_followers.is_emergency_contact,
+                    users_state.self_perception,
+                    COALESCE(users_followers.sharing_expires_at > now(), false) AS is_sharing
+             FROM users_followers
+             INNER JOIN users_state ON users_state.username = users_followers.username
+             WHERE users_followers.username = $1 AND users_followers.username_follower = $2
+             FOR UPDATE OF users_followers",
+            &[username, follower],
+        )
+        .map_err(APIInternalError::from_db_err)?
+        .into_iter()
+        .next()
+        .ok_or(APIInternalError {
+            msg: TranslationIds::InvitationsYouAreNotFriends,
+            engineering_error: None,
+        })?;
+    let previous = FollowerAccess {
+        accessType: row.get("access_type"),
+        isEmergencyContact: row.get("is_emergency_contact"),
+    };
+    let state: UserState = row.get("self_perception");
+    let is_sharing: bool = row.get("is_sharing");
+    // Followers with Permanent access do not need a temporary grant.
+    transaction
+        .execute(
+            "UPDATE users_followers SET access_type = $3, is_emergency_contact = $4,
+                sharing_expires_at = CASE WHEN $3 = 'Permanent'::accesstype
+                    THEN NULL ELSE sharing_expires_at END
+             WHERE username = $1 AND username_follower = $2",
+            &[
+                username,
+                follower,
+                &access.accessType,
+                &access.isEmergencyContact,
+            ],
+        )
+        .map_err(APIInternalError::from_db_err)?;
+    transaction
+        .commit()
+        .map_err(APIInternalError::from_db_err)?;
+
+    if access.accessType == AccessType::EmergencyOnly && state == UserState::Normal && !is_sharing {
+        forget_cached_location(redis, username, follower)?;
+    }
+
+    send_follower_access_notifications(conn, username, follower, &previous, access)
+        .map_err(|err| err.log_err("Unable to notify the follower of the new access"))
+        .ok();
+    Ok(())
+}
+
+/// Lets an EmergencyOnly follower get the location of `username` for the next
+/// `minutes`, replacing the previous grant. See expire_location_sharing.
+pub fn share_location(
+    conn: &mut PostgresConnection,
+    username: &String,
+    follower: &String,
+    minutes: i32,
+) -> Result<LocationSharing, APIInternalError> {
+    let access_type: AccessType = conn
+        .query(
+            "SELECT access_type FROM users_followers
+             WHERE username = $1 AND username_follower = $2",
+            &[username, follower],
+        )
+        .map_err(APIInternalError::from_db_err)?
+        .into_iter()
+        .next()
+        .ok_or(APIInternalError {
+            msg: TranslationIds::InvitationsYouAreNotFriends,
+            engineering_error: None,
+        })?
+        .get("access_type");
+    if access_type == AccessType::Permanent {
+        return Err(APIInternalError {
+            msg: TranslationIds::BadRequest,
+            engineering_error: Some(format!("{} already has Permanent access", follower)),
+        });
+    }
+    let row = conn
+        .query_one(
+            "UPDATE users_followers SET sharing_expires_at = now() + make_interval(mins => $3)
+             WHERE username = $1 AND username_follower = $2
+             RETURNING sharing_expires_at",
+            &[username, follower, &minutes],
+        )
+        .map_err(APIInternalError::from_db_err)?;
+    let expires_at: NaiveDateTime = row.get("sharing_expires_at");
+    Ok(LocationSharing {
+        expiresAt: expires_at.format(DATE_FORMAT).to_string(),
+    })
+}
+
+/// Ends the temporary grant of `follower` before it expires.
+///
+/// @return the access the follower keeps
+pub fn stop_sharing_location(
+    conn: &mut PostgresConnection,
+    redis: &mut redis::Connection,
+    username: &String,
+    follower: &String,
+) -> Result<FollowerAccess, APIInternalError> {
+    let row = conn
+        .query(
+            "UPDATE users_followers SET sharing_expires_at = NULL
+             FROM users_state
+             WHERE users_state.username = users_followers.username
+             AND users_followers.username = $1 AND users_followers.username_follower = $2
+             RETURNING users_followers.access_type, users_followers.is_emergency_contact,
+                users_state.self_perception",
+            &[username, follower],
+        )
+        .map_err(APIInternalError::from_db_err)?
+        .into_iter()
+        .next()
+        .ok_or(APIInternalError {
+            msg: TranslationIds::InvitationsYouAreNotFriends,
+            engineering_error: None,
+        })?;
+    let access = FollowerAccess {
+        accessType: row.get("access_type"),
+        isEmergencyContact: row.get("is_emergency_contact"),
+    };
+    let state: UserState = row.get("self_perception");
+    if access.accessType == AccessType::EmergencyOnly && state == UserState::Normal {
+        forget_cached_location(redis, username, follower)?;
+    }
+    Ok(access)
+}
+
+/// Revokes the temporary grants that expired and notifies the user and the
+/// follower of each one. Reads check the expiry, so the follower stops getting
+/// the location on time even if this runs late.
+///
+/// @return the number of grants revoked
+pub fn expire_location_sharing(
+    conn: &mut PostgresConnection,
+    redis: &mut redis::Connection,
+) -> Result<usize, APIInternalError> {
+    let expired: Vec<(String, String, UserState)> = conn
+        .query(
+            "UPDATE users_followers SET sharing_expires_at = NULL
+             FROM users_state
+             WHERE users_state.username = users_followers.username
+             AND users_followers.sharing_expires_at <= now()
+             RETURNING users_followers.username, users_followers.username_follower,
+                users_state.self_perception",
+            &[],
+        )
+        .map_err(APIInternalError::from_db_err)?
+        .iter()
+        .map(|row| {
+            (
+                row.get("username"),
+                row.get("username_follower"),
+                row.get("self_perception"),
+            )
+        })
+        .collect();
+
+    let mut notifications = vec![];
+    for (username, follower, state) in &expired {
+        // The grant is already revoked and won't be found again, keep going with
+        // the rest so each of them is cleaned up and notified.
+        if *state == UserState::Normal {
+            if let Err(err) = forget_cached_location(redis, username, follower) {
+                err.log_err("Unable to forget the location of an expired sharing");
+            }
+        }
+        // A user without details only misses the alert.
+        for (sender, recipient, body) in &[
+            (
+                username,
+                follower,
+                MessageIds::LocationSharingExpiredFollowerPushNotificationBody,
+            ),
+            (
+                follower,
+                username,
+                MessageIds::LocationSharingExpiredOwnerPushNotificationBody,
+            ),
+        ] {
+            match build_alert_notifications(
+                conn,
+                sender,
+                &[recipient.to_string()],
+                &MessageIds::FollowerAccessPushNotificationTitle,
+                body,
+                "",
+            ) {
+                Ok(built) => notifications.extend(built),
+                Err(err) => err.log_err("Unable to build the location sharing notification"),
+            }
+        }
+    }
+    publish_notifications(&notifications)
+        .map_err(|err| err.log_err("Unable to notify the end of the location sharing"))
+        .ok();
+    Ok(expired.len())
+}
+
+/// The follower can no longer read the last location of `username`.
+fn forget_cached_location(
+    redis: &mut redis::Connection,
+    username: &String,
+    follower: &String,
+) -> Result<(), APIInternalError> {
+    redis
+        .hdel::<_, _, i64>(redis_hash_map_name(follower), username)
+        .map(|_| ())
+        .map_err(APIInternalError::from_db_err)
+}
+
+fn send_follower_access_notifications(
+    conn: &mut PostgresConnection,
+    username: &String,
+    follower: &String,
+    previous: &FollowerAccess,
+    access: &FollowerAccess,
+) -> Result<bool, APIInternalError> {
+    let mut bodies = vec![];
+    if previous.accessType != access.accessType {
+        bodies.push(match access.accessType {
+            AccessType::EmergencyOnly => {
+                MessageIds::FollowerAccessEmergencyOnlyPushNotificationBody
+            }
+            AccessType::Permanent => MessageIds::FollowerAccessPermanentPushNotificationBody,
+        });
+    }
+    if previous.isEmergencyContact != access.isEmergencyContact {
+        bodies.push(if access.isEmergencyContact {
+            MessageIds::EmergencyContactAddedPushNotificationBody
+        } else {
+            MessageIds::EmergencyContactRemovedPushNotificationBody
+        });
+    }
+    let mut notifications = vec![];
+    for body in &bodies {
+        notifications.extend(build_alert_notifications(
+            conn,
+            username,
+            &[follower.clone()],
+            &MessageIds::FollowerAccessPushNotificationTitle,
+            body,
+            "",
+        )?);
+    }
+    publish_notifications(&notifications)
+}
@@ -0,0 +1,367 @@
+use crate::constants::{
+    DATE_FORMAT, DEFAULT_NOTIFICATION_ICON, DEVICE_HEALTH_STABLE_BATTERY_RATE,
+    DEVICE_HEALTH_STALE_PING_MINUTES, DEVICE_HEALTH_TREND_HOURS,
+    LOW_BATTERY_ALERT_COOLDOWN_SECONDS, LOW_BATTERY_ALERT_DISABLED_SECONDS,
+    LOW_BATTERY_ALERT_LEVEL, LOW_BATTERY_ALERT_PREFIX,
+};
+use crate::controllers::{
+    devices::row_to_device,
+    emergency::{get_emergency_connections, get_emergency_settings},
+    telemetry::get_user_details,
+};
+use crate::lang::{get_messages, MessageIds, TranslationIds};
+use crate::messaging::{build_user_push_notifications, get_rabbitmq_uri, send_notification};
+use crate::model::{
+    auth::AuthInfo,
+    devices::{AppState, BatteryState, BatteryTrend, ChargingState, DeviceHealth, TrackingIssue},
+    emergency::{AccessType, UserState},
+    notifications::{NotificationData, PushNotification},
+    responses::Errors::APIInternalError,
+    PostgresConnection,
+};
+use amiquip::Connection as RabbitConnection;
+use chrono::NaiveDateTime;
+use dynfmt::{Format, SimpleCurlyFormat};
+use postgres::Row;
+use std::collections::HashMap;
+
+/// Battery level of a ping, pings sent without a battery state are not included.
+struct BatterySample {
+    level: f64,
+    is_charging: bool,
+    timestamp: NaiveDateTime,
+}
+
+/// Health of every device of `username` as seen by `follower`: battery trend,
+/// time since the last ping and the settings that make the tracking unreliable.
+/// Followers with EmergencyOnly access can only see it during an emergency or
+/// while the user shares the location with them.
+pub fn get_device_health(
+    conn: &mut PostgresConnection,
+    username: &String,
+    follower: &String,
+) -> Result<Vec<DeviceHealth>, APIInternalError> {
+    assert_can_see_devices(conn, username, follower)?;
+    let mut samples = get_battery_samples(conn, username, follower)?;
+    conn.query(
+        "SELECT devices.*, last_ping.creation_timestamp, last_ping.app_state, last_ping.battery_level,
+                last_ping.charging_state, last_ping.is_charging,
+                extract(epoch FROM now()::timestamp - last_ping.creation_timestamp)::bigint
+                    AS seconds_since_last_ping
+         FROM users_devices
+         INNER JOIN devices ON devices.device_id = users_devices.device_id
+         LEFT JOIN LATERAL (
+            SELECT creation_timestamp, app_state, battery_level, charging_state, is_charging
+            FROM device_telemetry
+            WHERE device_telemetry.username = $1
+            AND device_telemetry.recipient_username = $2
+            AND device_telemetry.device_id = devices.device_id
+            ORDER BY creation_timestamp DESC LIMIT 1
+         ) AS last_ping ON true
+         WHERE users_devices.username = $1 AND users_devices.owner = true
+         ORDER BY devices.device_id",
+        &[username, follower],
+    )
+    .map_err(APIInternalError::from_db_err)
+    .map(|rows| {
+        rows.iter()
+            .map(|row| {
+                let device_id: String = row.get("device_id");
+                let samples = samples.remove(&device_id).unwrap_or_default();
+                row_to_device_health(row, &samples)
+            })
+            .collect()
+    })
+}
+
+fn assert_can_see_devices(
+    conn: &mut PostgresConnection,
+    username: &String,
+    follower: &String,
+) -> Result<(), APIInternalError> {
+    let row = conn
+        .query(
+            "SELECT users_followers.access_type, users_state.self_perception,
+                    COALESCE(users_followers.sharing_expires_at > now(), false) AS is_sharing
+             FROM users_followers
+             INNER JOIN users_state ON users_state.username = users_followers.username
+             WHERE users_followers.username = $1 AND users_followers.username_follower = $2",
+            &[username, follower],
+        )
+        .map_err(APIInternalError::from_db_err)?
+        .into_iter()
+        .next()
+        .ok_or(APIInternalError {
+            msg: TranslationIds::InvitationsYouAreNotFriends,
+            engineering_error: None,
+        })?;
+    let access_type: AccessType = row.get("access_type");
+    let state: UserState = row.get("self_perception");
+    let is_sharing: bool = row.get("is_sharing");
+    match (access_type, state, is_sharing) {
+        (AccessType::EmergencyOnly, UserState::Normal, false) => Err(APIInternalError {
+            msg: TranslationIds::UserNotInEmergency,
+            engineering_error: None,
+        }),
+        _ => Ok(()),
+    }
+}
+
+fn get_battery_samples(
+    conn: &mut PostgresConnection,
+    username: &String,
+    follower: &String,
+) -> Result<HashMap<String, Vec<BatterySample>>, APIInternalError> {
+    let rows = conn
+        .query(
+            "SELECT device_id, battery_level, is_charging, creation_timestamp
+             FROM device_telemetry
+             WHERE username = $1 AND recipient_username = $2
+             AND creation_timestamp > now()::timestamp - make_interval(hours => $3)
+             AND NOT (battery_level = 0 AND charging_state = 'UNKNOWN')
+             ORDER BY creation_timestamp",
+            &[username, follower, &(DEVICE_HEALTH_TREND_HOURS as i32)],
+        )
+        .map_err(APIInternalError::from_db_err)?;
+    let mut samples: HashMap<String, Vec<BatterySample>> = HashMap::new();
+    for row in rows {
+        samples
+            .entry(row.get("device_id"))
+            .or_default()
+            .push(BatterySample {
+                level: row.get("battery_level"),
+                is_charging: row.get("is_charging"),
+                timestamp: row.get("creation_timestamp"),
+            });
+    }
+    Ok(samples)
+}
+
+fn row_to_device_health(row: &Row, samples: &[BatterySample]) -> DeviceHealth {
+    let device = row_to_device(row);
+    let last_ping: Option<NaiveDateTime> = row.get("creation_timestamp");
+    let seconds_since_last_ping: Option<i64> = row.get("seconds_since_last_ping");
+    let charging_state: Option<ChargingState> = row.get("charging_state");
+    let battery_level: Option<f64> = row.get("battery_level");
+    // Telemetry sent without a battery state is stored with the defaults.
+    let has_battery_state =
+        !(battery_level == Some(0.0) && charging_state == Some(ChargingState::UNKNOWN));
+    let (battery_trend, battery_drain) = battery_trend(samples);
+
+    let mut issues = device.tracking_issues();
+    if seconds_since_last_ping.map_or(true, |seconds| {
+        seconds > DEVICE_HEALTH_STALE_PING_MINUTES * 60
+    }) {
+        issues.push(TrackingIssue::NoRecentPing);
+    }
+
+    DeviceHealth {
+        deviceId: device.deviceId,
+        name: device.name,
+        os: device.os,
+        model: device.model,
+        lastPing: last_ping.map(|timestamp| timestamp.format(DATE_FORMAT).to_string()),
+        secondsSinceLastPing: seconds_since_last_ping,
+        appState: row.get::<_, Option<AppState>>("app_state"),
+        batteryLevel: battery_level.filter(|_| has_battery_state),
+        isCharging: row
+            .get::<_, Option<bool>>("is_charging")
+            .filter(|_| has_battery_state),
+        batteryTrend: battery_trend,
+        batteryDrainPerHour: battery_drain,
+        trackingIssues: issues,
+    }
+}
+
+/// Compares the first and last battery level of the samples.
+fn battery_trend(samples: &[BatterySample]) -> (BatteryTrend, Option<f64>) {
+    let last = match samples.last() {
+        Some(last) => last,
+        None => return (BatteryTrend::Unknown, None),
+    };
+    let first = &samples[0];
+    let hours = (last.timestamp - first.timestamp).num_seconds() as f64 / 3600.0;
+    let drain = if hours > 0.0 {
+        Some((first.level - last.level) / hours)
+    } else {
+        None
+    };
+    let trend = match drain {
+        _ if last.is_charging => BatteryTrend::Charging,
+        None => BatteryTrend::Unknown,
+        Some(drain) if drain < -DEVICE_HEALTH_STABLE_BATTERY_RATE => BatteryTrend::Charging,
+        Some(drain) if drain > DEVICE_HEALTH_STABLE_BATTERY_RATE => BatteryTrend::Draining,
+        Some(_) => BatteryTrend::Stable,
+    };
+    (trend, drain)
+}
+
+/// Exists while the emergency contacts must not be alerted about the device,
+/// store_telemetry deletes it once the device has been charged.
+pub fn low_battery_alert_key(auth_info: &AuthInfo) -> String {
+    format!(
+        "{}.{}.{}",
+        LOW_BATTERY_ALERT_PREFIX, auth_info.username, auth_info.deviceId
+    )
+}
+
+/// Level of a battery below LOW_BATTERY_ALERT_LEVEL that is not charging.
+pub fn low_battery_level(battery_state: &BatteryState) -> Option<f64> {
+    match (battery_state.batteryLevel, battery_state.isCharging) {
+        (Some(level), Some(false)) | (Some(level), None) if level < LOW_BATTERY_ALERT_LEVEL => {
+            Some(level)
+        }
+        _ => None,
+    }
+}
+
+/// Alerts the emergency contacts of the user that the battery of the device is
+/// at `level`, called when store_telemetry reports that the alert is due.
+/// The alert is not due again until the device is charged or the cooldown
+/// expires, but only once it was published. Users without the alerts are not
+/// checked again for LOW_BATTERY_ALERT_DISABLED_SECONDS.
+///
+/// @return true if the alert was sent
+pub fn send_low_battery_alert(
+    conn: &mut PostgresConnection,
+    redis: &mut redis::Connection,
+    auth_info: &AuthInfo,
+    level: f64,
+) -> Result<bool, APIInternalError> {
+    let key = low_battery_alert_key(auth_info);
+    if !get_emergency_settings(conn, &auth_info.username)?.lowBatteryAlerts {
+        return redis::cmd("SET")
+            .arg(&key)
+            .arg("disabled")
+            .arg("EX")
+            .arg(LOW_BATTERY_ALERT_DISABLED_SECONDS)
+            .query(redis)
+            .map(|_: ()| false)
+            .map_err(APIInternalError::from_db_err);
+    }
+
+    let recipients: Vec<String> = get_emergency_connections(conn, &auth_info.username)
+        .map_err(APIInternalError::from_db_err)?
+        .into_iter()
+        .map(|recipient| recipient.username)
+        .collect();
+    let notifications = build_alert_notifications(
+        conn,
+        &auth_info.username,
+        &recipients,
+        &MessageIds::LowBatteryPushNotificationTitle,
+        &MessageIds::LowBatteryPushNotificationBody,
+        &format!("{:.0}", level),
+    )?;
+    let sent = publish_notifications(&notifications)?;
+    redis::cmd("SET")
+        .arg(&key)
+        .arg(level)
+        .arg("EX")
+        .arg(LOW_BATTERY_ALERT_COOLDOWN_SECONDS)
+        .query(redis)
+        .map(|_: ()| sent)
+        .map_err(APIInternalError::from_db_err)
+}
+
+/// Alerts the owner of the device that its settings stop the tracking, and the
+/// followers that can see the location if the user enabled the alerts.
+///
+/// @return true if the alert was sent
+pub fn send_tracking_degraded_alert(
+    conn: &mut PostgresConnection,
+    username: &String,
+) -> Result<bool, APIInternalError> {
+    let mut notifications = build_alert_notifications(
+        conn,
+        username,
+        &[username.clone()],
+        &MessageIds::TrackingDegradedPushNotificationTitle,
+        &MessageIds::TrackingDegradedOwnerPushNotificationBody,
+        "",
+    )?;
+    if get_emergency_settings(conn, username)?.trackingAlerts {
+        let followers: Vec<String> = conn
+            .query(
+                "SELECT users_followers.username_follower
+                 FROM users_followers
+                 INNER JOIN users_state ON users_state.username = users_followers.username
+                 WHERE users_followers.username = $1
+                 AND (users_followers.access_type = 'Permanent'
+                    OR users_state.self_perception = 'Emergency'
+                    OR users_followers.sharing_expires_at > now())",
+                &[username],
+            )
+            .map_err(APIInternalError::from_db_err)?
+            .iter()
+            .map(|row| row.get("username_follower"))
+            .collect();
+        notifications.extend(build_alert_notifications(
+            conn,
+            username,
+            &followers,
+            &MessageIds::TrackingDegradedPushNotificationTitle,
+            &MessageIds::TrackingDegradedFollowerPushNotificationBody,
+            "",
+        )?);
+    }
+    publish_notifications(&notifications)
+}
+
+/// Push notifications about `username` for every recipient in their language,
+/// the body is formatted with the name of the user and `detail`.
+pub fn build_alert_notifications(
+    conn: &mut PostgresConnection,
+    username: &String,
+    recipients: &[String],
+    title: &MessageIds,
+    body: &MessageIds,
+    detail: &str,
+) -> Result<Vec<PushNotification>, APIInternalError> {
+    let sender = get_user_details(username, conn)
+        .map_err(APIInternalError::from_db_err)?
+        .ok_or(APIInternalError {
+            msg: TranslationIds::NoUserForKey,
+            engineering_error: None,
+        })?;
+    let mut notifications = vec![];
+    for recipient in recipients {
+        let language = get_user_details(recipient, conn)
+            .ok()
+            .flatten()
+            .and_then(|details| details.language)
+            .unwrap_or_else(|| "en".to_string());
+        let messages = get_messages(&language);
+        let body = SimpleCurlyFormat
+            .format(
+                messages[body],
+                &[sender.firstName.as_str(), sender.lastName.as_str(), detail],
+            )
+            .map(|body| body.into_owned())
+            .map_err(APIInternalError::backend_issue)?;
+        let data = NotificationData {
+            username: recipient.clone(),
+            title: messages[title].to_string(),
+            body,
+            icon: Some(DEFAULT_NOTIFICATION_ICON.to_string()),
+        };
+        notifications.extend(build_user_push_notifications(&data, conn, None));
+    }
+    Ok(notifications)
+}
+
+/// @return false if there was nothing to send
+pub fn publish_notifications(notifications: &[PushNotification]) -> Result<bool, APIInternalError> {
+    if notifications.is_empty() {
+        return Ok(false);
+    }
+    RabbitConnection::insecure_open(&get_rabbitmq_uri())
+        .and_then(|mut connection| {
+            let channel = connection.open_channel(None)?;
+            let res = send_notification(&channel, json!(notifications).to_string());
+            let _ = channel.close();
+            res
+        })
+        .map(|_| true)
+        .map_err(APIInternalError::backend_issue)
+}
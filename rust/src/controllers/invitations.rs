@@ -1,20 +1,31 @@
 use crate::constants::DEFAULT_NOTIFICATION_ICON;
-use crate::constants::INV_ENDPOINT;
-use crate::db::transaction;
-use crate::lang::{get_glossary, TranslationIds};
+use crate::constants::{DATE_FORMAT, GENERIC_EMAIL_TEMPLATE, INV_ENDPOINT, WEB_URL};
+use crate::controllers::auth::{format_translation, translate};
+use crate::controllers::telemetry::get_user_details;
+use crate::db::api_transaction;
+use crate::lang::{get_messages, MessageIds, TranslationIds};
 use crate::messaging::{build_user_push_notifications, get_rabbitmq_uri, send_notification};
 use crate::model::{
-    invitations::{InvitationState, LinkActionData, LinkCreationData},
-    notifications::{AcceptedNotificationData, NotificationData},
+    auth::VerificationTarget,
+    invitations::{
+        DirectInvitation, DirectInvitationStatus, InvitationCreator, InvitationState,
+        InvitationTerms, LinkActionData, LinkCreationData, LinkInvitation,
+    },
+    notifications::{
+        AcceptedNotificationData, DynamicEmailTemplateData, Email, NotificationData, Sms,
+    },
     responses::Errors::APIInternalError,
     responses::AcceptInvitationResponse,
-    PostgresConnection,
+    PostgresConnection, UserDetails,
 };
+use crate::storage::picture_url;
 use amiquip::Connection as RabbitConnection;
 use amiquip::Result;
-use postgres::{error::SqlState, row::Row};
+use chrono::{NaiveDateTime, Utc};
+use postgres::{error::SqlState, row::Row, GenericClient, Transaction};
 use rocket_contrib::json::JsonValue;
 use std::time::SystemTime;
+use uuid::Uuid;
 
 /// Try to insert the data into the Database
 /// If OK, generate the link and return it
@@ -22,66 +33,289 @@ pub fn create_invitation(
     mut conn: PostgresConnection,
     data: LinkCreationData,
 ) -> Result<String, APIInternalError> {
-    conn.query("INSERT INTO link_invitations(id, expiration_timestamp, creator_username) VALUES ($1, $2, $3)",
-        &[&data.uuid, &SystemTime::from(data.exp_date), &data.username],
+    conn.query(
+        "INSERT INTO link_invitations
+            (id, expiration_timestamp, creator_username, max_uses, access_type,
+            is_emergency_contact, follow_back)
+         VALUES ($1, $2, $3, $4, $5, $6, $7)",
+        &[
+            &data.uuid,
+            &SystemTime::from(data.exp_date),
+            &data.username,
+            &data.max_uses,
+            &data.terms.accessType,
+            &data.terms.isEmergencyContact,
+            &data.terms.followBack,
+        ],
     )
     .and_then(|_| Ok(format!("{}/{}", INV_ENDPOINT, data.uuid)))
     .map_err(APIInternalError::from_db_err)
 }
 
+/// Invitation links created by the user, newest first.
+pub fn list_invitations(
+    conn: &mut PostgresConnection,
+    username: &String,
+) -> Result<Vec<LinkInvitation>, APIInternalError> {
+    let now = Utc::now().naive_utc();
+    conn.query(
+        "SELECT id, state, creation_timestamp, expiration_timestamp, recipient_username,
+            max_uses, uses, access_type, is_emergency_contact, follow_back,
+            ARRAY(SELECT username FROM link_invitations_uses
+                WHERE invitation_id = link_invitations.id
+                ORDER BY creation_timestamp, username) AS recipients
+         FROM link_invitations
+         WHERE creator_username = $1
+         ORDER BY creation_timestamp DESC NULLS LAST, id",
+        &[username],
+    )
+    .map_err(APIInternalError::from_db_err)
+    .map(|rows| {
+        rows.iter()
+            .map(|row| {
+                let id: String = row.get("id");
+                let state: InvitationState = row.get("state");
+                let expiration: NaiveDateTime = row.get("expiration_timestamp");
+                let creation: Option<NaiveDateTime> = row.get("creation_timestamp");
+                LinkInvitation {
+                    link: format!("{}/{}", INV_ENDPOINT, id),
+                    id,
+                    state: if state == InvitationState::CREATED && expiration <= now {
+                        InvitationState::EXPIRED
+                    } else {
+                        state
+                    },
+                    creationTimestamp: creation
+                        .map(|creation| creation.format(DATE_FORMAT).to_string()),
+                    expirationTimestamp: expiration.format(DATE_FORMAT).to_string(),
+                    recipientUsername: row.get("recipient_username"),
+                    maxUses: row.get("max_uses"),
+                    uses: row.get("uses"),
+                    recipients: row.get("recipients"),
+                    terms: InvitationTerms::from_row(row),
+                }
+            })
+            .collect()
+    })
+}
+
+/// Cancels a link of the user that nobody used yet.
+pub fn revoke_invitation(
+    conn: &mut PostgresConnection,
+    data: &LinkActionData,
+) -> Result<(), APIInternalError> {
+    let updated = conn
+        .execute(
+            "UPDATE link_invitations SET state = $3
+             WHERE id = $1 AND creator_username = $2 AND state = $4",
+            &[
+                &data.uuid,
+                &data.username,
+                &InvitationState::REVOKED,
+                &InvitationState::CREATED,
+            ],
+        )
+        .map_err(APIInternalError::from_db_err)?;
+    if updated == 0 {
+        return Err(not_updated_error(conn, &data.uuid, &data.username));
+    }
+    Ok(())
+}
+
+/// Gives a link of the user that nobody used yet a new expiration date, so it
+/// can be sent again. Expired links become valid again.
+pub fn resend_invitation(
+    conn: &mut PostgresConnection,
+    data: &LinkCreationData,
+) -> Result<String, APIInternalError> {
+    let updated = conn
+        .execute(
+            "UPDATE link_invitations SET state = $4, expiration_timestamp = $3
+             WHERE id = $1 AND creator_username = $2 AND state IN ($4, $5)",
+            &[
+                &data.uuid,
+                &data.username,
+                &SystemTime::from(data.exp_date),
+                &InvitationState::CREATED,
+                &InvitationState::EXPIRED,
+            ],
+        )
+        .map_err(APIInternalError::from_db_err)?;
+    if updated == 0 {
+        return Err(not_updated_error(conn, &data.uuid, &data.username));
+    }
+    Ok(format!("{}/{}", INV_ENDPOINT, data.uuid))
+}
+
+/// Links of other users are reported as missing, links that were already used
+/// or rejected as no longer valid.
+fn not_updated_error(
+    conn: &mut PostgresConnection,
+    id: &String,
+    username: &String,
+) -> APIInternalError {
+    match conn.query_opt(
+        "SELECT state FROM link_invitations WHERE id = $1 AND creator_username = $2",
+        &[id, username],
+    ) {
+        Ok(Some(row)) => {
+            let state: InvitationState = row.get("state");
+            APIInternalError {
+                msg: if state == InvitationState::REVOKED {
+                    TranslationIds::InvitationsInvitationWasRevoked
+                } else {
+                    TranslationIds::InvitationsInvitationIsNoLongerValid
+                },
+                engineering_error: None,
+            }
+        }
+        Ok(None) => APIInternalError {
+            msg: TranslationIds::InvitationsInvitationDoesNotExist,
+            engineering_error: None,
+        },
+        Err(err) => APIInternalError::from_db_err(err),
+    }
+}
+
 /// Tries to set the State of an invitation
-/// to REJECTED
+/// to REJECTED. Links with several uses can't be rejected, they stay valid for
+/// the rest of the group.
 pub fn reject_invitation(
     mut conn: PostgresConnection,
     data: LinkActionData,
 ) -> Result<(), APIInternalError> {
-    conn.execute(
-        "UPDATE link_invitations SET state = $3, recipient_username = $1 WHERE id = $2",
-        &[&data.username, &data.uuid, &InvitationState::REJECTED],
-    )
-    .and_then(|_| Ok(()))
-    .map_err(APIInternalError::from_db_err)
+    let updated = conn
+        .execute(
+            "UPDATE link_invitations SET state = $3, recipient_username = $1
+             WHERE id = $2 AND max_uses = 1 AND state = $4",
+            &[
+                &data.username,
+                &data.uuid,
+                &InvitationState::REJECTED,
+                &InvitationState::CREATED,
+            ],
+        )
+        .map_err(APIInternalError::from_db_err)?;
+    if updated > 0 {
+        return Ok(());
+    }
+    let shared = conn
+        .query_opt(
+            "SELECT 1 FROM link_invitations WHERE id = $1 AND max_uses > 1",
+            &[&data.uuid],
+        )
+        .map_err(APIInternalError::from_db_err)?
+        .is_some();
+    Err(APIInternalError {
+        msg: if shared {
+            TranslationIds::InvitationsSharedInvitationCannotBeRejected
+        } else {
+            TranslationIds::InvitationsInvitationIsNoLongerValid
+        },
+        engineering_error: None,
+    })
 }
 
-/// Try to set the State of an invitation to ACCEPTED.
-/// Start a transaction to add following users
+/// Takes a use of the invitation, which becomes ACCEPTED with the last one.
+/// Start a transaction to make the user follow the creator on the terms of the
+/// invitation, and the creator follow the user back when the terms say so
 pub fn accept_invitation(
     conn: &mut PostgresConnection,
     data: &LinkActionData,
 ) -> Result<AcceptInvitationResponse, APIInternalError> {
-    transaction(conn, |ts| {
-        ts.execute(
-            "UPDATE link_invitations SET state = $1, recipient_username = $2 WHERE id = $3",
-            &[&InvitationState::ACCEPTED, &data.username, &data.uuid],
-        )?;
-        let invite_row = ts.query_one(
-            "SELECT * FROM link_invitations WHERE id = $1",
-            &[&data.uuid],
-        )?;
+    api_transaction(conn, |ts| {
+        // The link may have been revoked or exhausted since it was validated.
+        let invite_row = ts
+            .query_opt(
+                "UPDATE link_invitations SET
+                    uses = uses + 1,
+                    state = CASE WHEN uses + 1 >= max_uses THEN $1 ELSE state END,
+                    recipient_username = CASE WHEN max_uses = 1 THEN $2 ELSE NULL END
+                 WHERE id = $3 AND state = $4 AND uses < max_uses
+                    AND expiration_timestamp > now()
+                 RETURNING creator_username, access_type, is_emergency_contact, follow_back",
+                &[
+                    &InvitationState::ACCEPTED,
+                    &data.username,
+                    &data.uuid,
+                    &InvitationState::CREATED,
+                ],
+            )
+            .map_err(accept_db_err)?
+            .ok_or(APIInternalError {
+                msg: TranslationIds::InvitationsInvitationIsNoLongerValid,
+                engineering_error: None,
+            })?;
         let user1: String = invite_row.get("creator_username");
-        let user2: String = invite_row.get("recipient_username");
-        let pk_row = ts.query_one(
+        let user2 = &data.username;
+        ts.execute(
+            "INSERT INTO link_invitations_uses (invitation_id, username) VALUES ($1, $2)",
+            &[&data.uuid, user2],
+        )
+        .map_err(accept_db_err)?;
+        let terms = InvitationTerms::from_row(&invite_row);
+        follow_on_terms(ts, user1, user2, &terms)
+    })
+}
+
+/// Makes `follower` follow `creator` on the terms of the invitation, and
+/// `creator` follow back when the terms say so.
+fn follow_on_terms(
+    ts: &mut Transaction,
+    creator: String,
+    follower: &String,
+    terms: &InvitationTerms,
+) -> Result<AcceptInvitationResponse, APIInternalError> {
+    let pk_row = ts
+        .query_one(
             "SELECT public_key FROM users_identity WHERE username = $1",
-            &[&user1],
-        )?;
-        let pk: String = pk_row.get("public_key");
-        ts.execute("call add_friend($1, $2)", &[&user1, &user2])?;
-        Ok(AcceptInvitationResponse{
-            message: String::from("Ok"),
-            username: user1,
-            publicKey: pk
+            &[&creator],
+        )
+        .map_err(accept_db_err)?;
+    let pk: String = pk_row.get("public_key");
+    ts.execute("call follow_user($1, $2)", &[&creator, follower])
+        .and_then(|_| {
+            ts.execute(
+                "UPDATE users_followers SET access_type = $3, is_emergency_contact = $4
+                 WHERE username = $1 AND username_follower = $2",
+                &[
+                    &creator,
+                    follower,
+                    &terms.accessType,
+                    &terms.isEmergencyContact,
+                ],
+            )
         })
+        .map_err(accept_db_err)?;
+    if terms.followBack {
+        ts.execute("call follow_user($1, $2)", &[follower, &creator])
+            .map_err(accept_db_err)?;
+    }
+    Ok(AcceptInvitationResponse {
+        message: String::from("Ok"),
+        username: creator,
+        publicKey: pk,
     })
-    .map_err(|err| {
-        let res = err.code();
-        match res {
-            Some(code) if code == &SqlState::UNIQUE_VIOLATION => APIInternalError {
-                msg: TranslationIds::InvitationsAlreadyFriends,
+}
+
+fn accept_db_err(err: postgres::Error) -> APIInternalError {
+    let constraint = err.as_db_error().and_then(|db_err| db_err.constraint());
+    match err.code() {
+        Some(code)
+            if code == &SqlState::UNIQUE_VIOLATION
+                && constraint == Some("link_invitations_uses_pkey") =>
+        {
+            APIInternalError {
+                msg: TranslationIds::InvitationsInvitationAlreadyUsed,
                 engineering_error: None,
-            },
-            _ => APIInternalError::from_db_err(err),
+            }
         }
-    })
+        Some(code) if code == &SqlState::UNIQUE_VIOLATION => APIInternalError {
+            msg: TranslationIds::InvitationsAlreadyFriends,
+            engineering_error: None,
+        },
+        _ => APIInternalError::from_db_err(err),
+    }
 }
 
 pub fn notify_accepted(
@@ -96,10 +330,10 @@ pub fn notify_accepted(
             recipient_user_details.first_name as first_name
          FROM link_invitations inv
          INNER JOIN user_details recipient_user_details
-            ON inv.id = $1 AND inv.recipient_username = recipient_user_details.username
+            ON inv.id = $1 AND recipient_user_details.username = $2
          INNER JOIN user_details creator_user_details
             ON inv.id = $1 AND inv.creator_username = creator_user_details.username",
-            &[&data.uuid],
+            &[&data.uuid, &data.username],
         )
         .and_then(|row| {
             Ok(AcceptedNotificationData {
@@ -117,7 +351,10 @@ fn push_accepted_notification(
     data: &AcceptedNotificationData,
 ) -> Result<(), APIInternalError> {
     let notification = build_inv_accepted_notification(conn, data);
+    publish_notification(&notification)
+}
 
+fn publish_notification(notification: &JsonValue) -> Result<(), APIInternalError> {
     RabbitConnection::insecure_open(&get_rabbitmq_uri())
         .and_then(|mut connection| {
             let channel = connection.open_channel(None)?;
@@ -149,14 +386,16 @@ pub fn get_invitation_creator(
         Ok(json!({
             "username": username,
             "firstName": first_name,
-            "lastName": last_name
+            "lastName": last_name,
+            "terms": InvitationTerms::from_row(&row)
         }))
     })
 }
 
 fn get_inv_creator(conn: &mut PostgresConnection, id: &str) -> Result<Row, APIInternalError> {
     conn.query(
-        "SELECT ud.first_name, ud.last_name, ud.username
+        "SELECT ud.first_name, ud.last_name, ud.username,
+            lnk.access_type, lnk.is_emergency_contact, lnk.follow_back
         FROM link_invitations lnk 
         INNER JOIN user_details ud
         ON lnk.id = $1 AND ud.username = lnk.creator_username",
@@ -175,11 +414,11 @@ fn build_inv_accepted_notification(
     conn: &mut PostgresConnection,
     data: &AcceptedNotificationData,
 ) -> JsonValue {
-    let push_inv_title = get_glossary(&data.language)
-        .get(&TranslationIds::PushNotificationInvitationAcceptedTitle)
+    let push_inv_title = get_messages(&data.language)
+        .get(&MessageIds::PushNotificationInvitationAcceptedTitle)
         .unwrap_or(&"Unknown error getting translated string");
-    let push_inv_body = get_glossary(&data.language)
-        .get(&TranslationIds::PushNotificationInvitationAcceptedBody)
+    let push_inv_body = get_messages(&data.language)
+        .get(&MessageIds::PushNotificationInvitationAcceptedBody)
         .unwrap_or(&"Unknown error getting translated string");
     let title = format!("{} {}", &data.recipient, push_inv_title);
     let body = format!("{} {}", &data.recipient, push_inv_body);
@@ -195,3 +434,334 @@ fn build_inv_accepted_notification(
         None
     ))
 }
+
+/// Invites the owner of an email or phone number, who may not be a user yet.
+/// Users that verified the email or phone number are matched right away and
+/// the rest when they verify it.
+///
+/// @return the id of the invitation and the invited user, if there is one
+pub fn create_direct_invitation(
+    conn: &mut PostgresConnection,
+    username: &String,
+    target: &VerificationTarget,
+    terms: &InvitationTerms,
+) -> Result<(String, Option<String>), APIInternalError> {
+    let (email, phone_number) = match target {
+        VerificationTarget::Email(email) => (Some(email), None),
+        VerificationTarget::Phone(phone) => (None, Some(phone)),
+    };
+    let terms = serde_json::to_string(terms).map_err(APIInternalError::backend_issue)?;
+    api_transaction(conn, |ts| {
+        let target_user = ts
+            .query_opt(
+                "SELECT username, EXISTS (
+                    SELECT 1 FROM users_verification
+                    WHERE used AND (email = $1 OR phone_number = $1)
+                 ) AS verified
+                 FROM users WHERE email = $1 OR phone_number = $1",
+                &[target.value()],
+            )
+            .map_err(APIInternalError::from_db_err)?;
+        if let Some(row) = &target_user {
+            if row.get::<_, String>("username") == *username {
+                return Err(APIInternalError {
+                    msg: TranslationIds::CannotUseOwnInvitation,
+                    engineering_error: None,
+                });
+            }
+        }
+        let target_username: Option<String> = target_user
+            .filter(|row| row.get("verified"))
+            .map(|row| row.get("username"));
+        let row = ts
+            .query_one(
+                "INSERT INTO invitations
+                    (id, creator_username, target_username, target_email, target_phone_number,
+                    status, type, invitation)
+                 VALUES (uuid_generate_v4(), $1, $2, $3, $4, $5, 'follower', $6::text::jsonb)
+                 RETURNING id::text AS id",
+                &[
+                    username,
+                    &target_username,
+                    &email,
+                    &phone_number,
+                    &DirectInvitationStatus::Created,
+                    &terms,
+                ],
+            )
+            .map_err(|err| match err.code() {
+                Some(code) if code == &SqlState::UNIQUE_VIOLATION => APIInternalError {
+                    msg: TranslationIds::InvitationsInvitationAlreadySent,
+                    engineering_error: None,
+                },
+                _ => APIInternalError::from_db_err(err),
+            })?;
+        Ok((row.get("id"), target_username))
+    })
+}
+
+/// Lets the invited person know about the invitation: users get a push
+/// notification in their language, everybody else an email or sms in the
+/// language of the creator.
+pub fn send_direct_invitation(
+    conn: &mut PostgresConnection,
+    username: &String,
+    target: &VerificationTarget,
+    target_username: &Option<String>,
+) -> Result<(), APIInternalError> {
+    let creator = fetch_user_details(conn, username)?;
+    let names = [&creator.firstName, &creator.lastName];
+    let notification = match target_username {
+        Some(target_username) => {
+            let language = user_language(&fetch_user_details(conn, target_username)?);
+            let data = NotificationData {
+                username: target_username.clone(),
+                title: translate(&language, MessageIds::DirectInvitationTitle),
+                body: format_translation(
+                    &language,
+                    MessageIds::DirectInvitationPushNotificationBody,
+                    &names,
+                )?,
+                icon: Some(DEFAULT_NOTIFICATION_ICON.to_string()),
+            };
+            json!(build_user_push_notifications(&data, conn, None))
+        }
+        None => {
+            let language = user_language(&creator);
+            match target {
+                VerificationTarget::Email(email) => json!([Email {
+                    username: None,
+                    email: email.clone(),
+                    templateId: GENERIC_EMAIL_TEMPLATE.to_string(),
+                    dynamicTemplateData: DynamicEmailTemplateData {
+                        title: format!(
+                            "Armore: {}",
+                            translate(&language, MessageIds::DirectInvitationTitle)
+                        ),
+                        body: format_translation(
+                            &language,
+                            MessageIds::DirectInvitationEmailBody,
+                            &names,
+                        )?,
+                        linkTitle: translate(
+                            &language,
+                            MessageIds::DirectInvitationEmailButtonText,
+                        ),
+                        picture: creator.picture.as_deref().map(picture_url),
+                        link: Some(WEB_URL.to_string()),
+                        code: None,
+                    },
+                }]),
+                VerificationTarget::Phone(phone) => json!([Sms {
+                    to: phone.clone(),
+                    body: format_translation(
+                        &language,
+                        MessageIds::SmsDirectInvitationBody,
+                        &[names[0], names[1], &WEB_URL.to_string()],
+                    )?,
+                }]),
+            }
+        }
+    };
+    publish_notification(&notification)
+}
+
+fn fetch_user_details(
+    conn: &mut PostgresConnection,
+    username: &str,
+) -> Result<UserDetails, APIInternalError> {
+    get_user_details(username, conn)
+        .map_err(APIInternalError::from_db_err)?
+        .ok_or(APIInternalError::backend_issue("Unable to fetch user info"))
+}
+
+fn user_language(details: &UserDetails) -> String {
+    details.language.clone().unwrap_or_else(|| "en".to_string())
+}
+
+/// Pending invitations sent to the user, newest first.
+pub fn list_received_direct_invitations(
+    conn: &mut PostgresConnection,
+    username: &String,
+) -> Result<Vec<DirectInvitation>, APIInternalError> {
+    conn.query(
+        "SELECT inv.id::text AS id, inv.invitation::text AS terms, inv.creation_timestamp,
+            ud.username, ud.first_name, ud.last_name, ud.picture
+         FROM invitations inv
+         INNER JOIN user_details ud ON ud.username = inv.creator_username
+         WHERE inv.target_username = $1 AND inv.status = $2 AND inv.type = 'follower'
+         ORDER BY inv.creation_timestamp DESC, inv.id",
+        &[username, &DirectInvitationStatus::Created],
+    )
+    .map_err(APIInternalError::from_db_err)
+    .map(|rows| {
+        rows.iter()
+            .map(|row| {
+                let terms: String = row.get("terms");
+                let creation: NaiveDateTime = row.get("creation_timestamp");
+                let picture: Option<String> = row.get("picture");
+                DirectInvitation {
+                    id: row.get("id"),
+                    creator: InvitationCreator {
+                        username: row.get("username"),
+                        firstName: row.get("first_name"),
+                        lastName: row.get("last_name"),
+                        picture: picture.as_deref().map(picture_url),
+                    },
+                    // Invitations created before the terms could be chosen
+                    // hold other data.
+                    terms: serde_json::from_str(&terms).unwrap_or_default(),
+                    creationTimestamp: creation.format(DATE_FORMAT).to_string(),
+                }
+            })
+            .collect()
+    })
+}
+
+/// Answers a pending invitation sent to the user and starts following its
+/// creator on the terms of the invitation.
+pub fn accept_direct_invitation(
+    conn: &mut PostgresConnection,
+    id: &str,
+    username: &String,
+) -> Result<AcceptInvitationResponse, APIInternalError> {
+    let id = direct_invitation_id(id)?;
+    api_transaction(conn, |ts| {
+        let row = ts
+            .query_opt(
+                "UPDATE invitations SET status = $3, update_timestamp = now()
+                 WHERE id = $1 AND target_username = $2 AND status = $4
+                    AND type = 'follower'
+                 RETURNING creator_username, invitation::text AS terms",
+                &[
+                    &id,
+                    username,
+                    &DirectInvitationStatus::Accepted,
+                    &DirectInvitationStatus::Created,
+                ],
+            )
+            .map_err(APIInternalError::from_db_err)?;
+        match row {
+            Some(row) => {
+                let terms: String = row.get("terms");
+                let terms: InvitationTerms = serde_json::from_str(&terms).unwrap_or_default();
+                follow_on_terms(ts, row.get("creator_username"), username, &terms)
+            }
+            None => Err(direct_not_updated_error(ts, &id, username)),
+        }
+    })
+}
+
+/// Declines a pending invitation sent to the user.
+pub fn reject_direct_invitation(
+    conn: &mut PostgresConnection,
+    id: &str,
+    username: &String,
+) -> Result<(), APIInternalError> {
+    let id = direct_invitation_id(id)?;
+    let updated = conn
+        .execute(
+            "UPDATE invitations SET status = $3, update_timestamp = now()
+             WHERE id = $1 AND target_username = $2 AND status = $4",
+            &[
+                &id,
+                username,
+                &DirectInvitationStatus::Rejected,
+                &DirectInvitationStatus::Created,
+            ],
+        )
+        .map_err(APIInternalError::from_db_err)?;
+    if updated == 0 {
+        return Err(direct_not_updated_error(&mut **conn, &id, username));
+    }
+    Ok(())
+}
+
+/// Cancels an invitation of the user that was not answered yet.
+pub fn cancel_direct_invitation(
+    conn: &mut PostgresConnection,
+    id: &str,
+    username: &String,
+) -> Result<(), APIInternalError> {
+    let id = direct_invitation_id(id)?;
+    let updated = conn
+        .execute(
+            "UPDATE invitations SET status = $3, update_timestamp = now()
+             WHERE id = $1 AND creator_username = $2 AND status = $4
+            &[
+                &id,
+                username,
+                &DirectInvitationStatus::Canceled,
+                &DirectInvitationStatus::Created,
+            ],
+        )
+        .map_err(APIInternalError::from_db_err)?;
+    if updated == 0 {
+        return Err(direct_not_updated_error(&mut **conn, &id, username));
+    }
+    Ok(())
+}
+
+/// Ids that are not a UUID can't belong to any invitation.
+fn direct_invitation_id(id: &str) -> Result<Uuid, APIInternalError> {
+    Uuid::parse_str(id).map_err(|_| APIInternalError {
+        msg: TranslationIds::InvitationsInvitationDoesNotExist,
+        engineering_error: None,
+    })
+}
+
+/// Invitations the user did not send or receive, or can't answer, are reported
+/// as missing, answered ones as no longer valid.
+fn direct_not_updated_error<C: GenericClient>(
+    client: &mut C,
+    id: &Uuid,
+    username: &String,
+) -> APIInternalError {
+    match client.query_opt(
+        "SELECT status FROM invitations
+         WHERE id = $1 AND (creator_username = $2 OR target_username = $2)
+        &[&id, username],
+    ) {
+        Ok(Some(row)) => {
+            let status: DirectInvitationStatus = row.get("status");
+            APIInternalError {
+                msg: match status {
+                    DirectInvitationStatus::Created => {
+                        TranslationIds::InvitationsInvitationDoesNotExist
+                    }
+                    DirectInvitationStatus::Canceled => {
+                        TranslationIds::InvitationsInvitationWasRevoked
+                    }
+                    _ => TranslationIds::InvitationsInvitationIsNoLongerValid,
+                },
+                engineering_error: None,
+            }
+        }
+        Ok(None) => APIInternalError {
+            msg: TranslationIds::InvitationsInvitationDoesNotExist,
+            engineering_error: None,
+        },
+        Err(err) => APIInternalError::from_db_err(err),
+    }
+}
+
+/// Tells the creator of a direct invitation that it was accepted.
+pub fn notify_direct_accepted(
+    conn: &mut PostgresConnection,
+    creator: &str,
+    recipient: &str,
+) -> Result<(), APIInternalError> {
+    conn.query_one(
+        "SELECT COALESCE(creator_details.language, 'en') AS lang, recipient_details.first_name
+         FROM user_details creator_details, user_details recipient_details
+         WHERE creator_details.username = $1 AND recipient_details.username = $2",
+        &[&creator, &recipient],
+    )
+    .map(|row| AcceptedNotificationData {
+        creator: creator.to_string(),
+        language: row.get("lang"),
+        recipient: row.get("first_name"),
+    })
+    .map_err(APIInternalError::backend_issue)
+    .and_then(|data| push_accepted_notification(conn, &data))
+}
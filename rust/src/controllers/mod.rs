@@ -1,4 +1,9 @@
+pub mod auth;
 pub mod devices;
 pub mod emergency;
+pub mod followers;
+pub mod health;
 pub mod invitations;
+pub mod profile;
+pub mod retention;
 pub mod telemetry;
@@ -0,0 +1,473 @@
+use crate::constants::{
+    DATA_EXPORT_MAX_PER_DAY, DATA_EXPORT_TELEMETRY_PAGE_SIZE, DATE_FORMAT, NANNY_RETRY_HASH_MAP,
+    PROFILE_PICTURE_SIZES, TELEMETRY_LAST_SEEN_SET,
+};
+use crate::controllers::{
+    health::{build_alert_notifications, publish_notifications},
+    telemetry::{get_user_details, redis_hash_map_name},
+};
+use crate::lang::{MessageIds, TranslationIds};
+use crate::model::{
+    auth::AuthInfo,
+    profile::{PictureThumbnail, ProfilePicture, TelemetryExportPage, UserDataExport},
+    requests::ProfileUpdateRequest,
+    responses::Errors::APIInternalError,
+    telemetry::TelemetryCursor,
+    PostgresConnection, UserDetails,
+};
+use crate::storage::{picture_url, PictureStorage};
+use chrono::Utc;
+use image::{imageops::FilterType, DynamicImage, ImageFormat, ImageOutputFormat};
+use postgres::{GenericClient, Transaction};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+/// Updates the fields of the profile sent in the request, the new language is
+/// used by every response and notification from now on.
+pub fn update_profile(
+    conn: &mut PostgresConnection,
+    username: &String,
+    profile: &ProfileUpdateRequest,
+) -> Result<UserDetails, APIInternalError> {
+    let updated = conn
+        .execute(
+            "UPDATE user_details SET
+                first_name = COALESCE($2, first_name),
+                last_name = COALESCE($3, last_name),
+                language = COALESCE($4, language),
+                updated_timestamp = now()
+             WHERE username = $1",
+            &[
+                username,
+                &profile.firstName.as_ref().map(|name| name.trim()),
+                &profile.lastName.as_ref().map(|name| name.trim()),
+                &profile.language,
+            ],
+        )
+        .map_err(APIInternalError::from_db_err)?;
+    if updated == 0 {
+        return Err(APIInternalError {
+            msg: TranslationIds::NoUserForKey,
+            engineering_error: None,
+        });
+    }
+    get_user_details(username, conn)
+        .map_err(APIInternalError::from_db_err)?
+        .ok_or(APIInternalError {
+            msg: TranslationIds::NoUserForKey,
+            engineering_error: None,
+        })
+}
+
+/// Stores the picture resized to every PROFILE_PICTURE_SIZES square, under the
+/// sha256 of the upload, and makes it the picture of the user. The previous
+/// picture is deleted when no other user has it.
+pub fn update_profile_picture(
+    conn: &mut PostgresConnection,
+    storage: &dyn PictureStorage,
+    username: &String,
+    format: ImageFormat,
+    bytes: &[u8],
+) -> Result<ProfilePicture, APIInternalError> {
+    let hash = format!("{:x}", Sha256::digest(bytes));
+    let keys = picture_keys(&hash);
+    let picture = keys[0].1.clone();
+    let previous = get_picture(conn, username)?;
+
+    let mut transaction = conn.transaction().map_err(APIInternalError::from_db_err)?;
+    lock_picture(&mut transaction, &picture)?;
+    // The same upload was already processed, possibly by another user.
+    if !keys.iter().all(|(_, key)| storage.exists(key)) {
+        let image =
+            image::load_from_memory_with_format(bytes, format).map_err(|err| APIInternalError {
+                msg: TranslationIds::InvalidProfilePicture,
+                engineering_error: Some(err.to_string()),
+            })?;
+        for (size, key) in &keys {
+            let resized = image.resize_to_fill(*size, *size, FilterType::Lanczos3);
+            let mut jpeg = Vec::new();
+            DynamicImage::ImageRgb8(resized.to_rgb8())
+                .write_to(&mut jpeg, ImageOutputFormat::Jpeg(85))
+                .map_err(APIInternalError::backend_issue)?;
+            storage
+                .put(key, &jpeg)
+                .map_err(APIInternalError::backend_issue)?;
+        }
+    }
+
+    let updated = transaction
+        .execute(
+            "UPDATE user_details SET picture = $2, updated_timestamp = now() WHERE username = $1",
+            &[username, &picture],
+        )
+        .map_err(APIInternalError::from_db_err)?;
+    if updated == 0 {
+        return Err(APIInternalError {
+            msg: TranslationIds::NoUserForKey,
+            engineering_error: None,
+        });
+    }
+    transaction
+        .commit()
+        .map_err(APIInternalError::from_db_err)?;
+
+    if let Some(previous) = previous.filter(|previous| *previous != picture) {
+        release_picture(conn, storage, &previous)
+            .map_err(|err| err.log_err("Unable to delete the previous profile picture"))
+            .ok();
+    }
+    Ok(ProfilePicture {
+        url: picture_url(&picture),
+        thumbnails: keys
+            .iter()
+            .skip(1)
+            .map(|(size, key)| PictureThumbnail {
+                size: *size,
+                url: picture_url(key),
+            })
+            .collect(),
+        picture,
+    })
+}
+
+/// `<sha256>.jpg` for the picture and `<sha256>_<size>.jpg` for the thumbnails,
+/// so clients can derive the thumbnails from user_details.picture.
+fn picture_keys(hash: &str) -> Vec<(u32, String)> {
+    PROFILE_PICTURE_SIZES
+        .iter()
+        .enumerate()
+        .map(|(index, size)| {
+            if index == 0 {
+                (*size, format!("{}.jpg", hash))
+            } else {
+                (*size, format!("{}_{}.jpg", hash, size))
+            }
+        })
+        .collect()
+}
+
+fn get_picture(
+    conn: &mut PostgresConnection,
+    username: &String,
+) -> Result<Option<String>, APIInternalError> {
+    Ok(conn
+        .query_opt(
+            "SELECT picture FROM user_details WHERE username = $1",
+            &[username],
+        )
+        .map_err(APIInternalError::from_db_err)?
+        .and_then(|row| row.get("picture")))
+}
+
+/// The rows of user_details pointing to a picture are its references. Storing a
+/// picture and deleting an unreferenced one hold this lock until the end of the
+/// transaction, so a picture is never deleted while another user takes it.
+fn lock_picture(transaction: &mut Transaction, picture: &str) -> Result<(), APIInternalError> {
+    transaction
+        .execute("SELECT pg_advisory_xact_lock(hashtext($1))", &[&picture])
+        .map(|_| ())
+        .map_err(APIInternalError::from_db_err)
+}
+
+/// Deletes the picture and its thumbnails when no user has it anymore. Pictures
+/// that were not stored by update_profile_picture are left alone.
+fn release_picture(
+    conn: &mut PostgresConnection,
+    storage: &dyn PictureStorage,
+    picture: &str,
+) -> Result<(), APIInternalError> {
+    let hash = match picture.strip_suffix(".jpg") {
+        Some(hash) if hash.len() == 64 && hash.chars().all(|c| c.is_ascii_hexdigit()) => hash,
+        _ => return Ok(()),
+    };
+    let mut transaction = conn.transaction().map_err(APIInternalError::from_db_err)?;
+    lock_picture(&mut transaction, picture)?;
+    let referenced = transaction
+        .query_opt(
+            "SELECT 1 FROM user_details WHERE picture = $1 LIMIT 1",
+            &[&picture],
+        )
+        .map_err(APIInternalError::from_db_err)?
+        .is_some();
+    if !referenced {
+        for (_, key) in picture_keys(hash) {
+            storage
+                .delete(&key)
+                .map_err(APIInternalError::backend_issue)?;
+        }
+    }
+    transaction.commit().map_err(APIInternalError::from_db_err)
+}
+
+/// Deletes the account and everything stored about the user: telemetry sent
+/// and received, devices with their push tokens, friendships, verifications,
+/// the profile picture unless another user has it and the redis caches. The
+/// friends are notified once the account is gone.
+pub fn delete_account(
+    conn: &mut PostgresConnection,
+    redis: &mut redis::Connection,
+    storage: &dyn PictureStorage,
+    username: &String,
+) -> Result<(), APIInternalError> {
+    let friends: Vec<String> = conn
+        .query(
+            "SELECT username_follower AS friend FROM users_followers WHERE username = $1
+             UNION
+             SELECT username AS friend FROM users_followers WHERE username_follower = $1",
+            &[username],
+        )
+        .map_err(APIInternalError::from_db_err)?
+        .iter()
+        .map(|row| row.get("friend"))
+        .collect();
+    // Built before the user details are deleted.
+    let notifications = build_alert_notifications(
+        conn,
+        username,
+        &friends,
+        &MessageIds::AccountDeletedPushNotificationTitle,
+        &MessageIds::AccountDeletedPushNotificationBody,
+        "",
+    )
+    .map_err(|err| err.log_err("Unable to build the account deletion notifications"))
+    .unwrap_or_default();
+    let picture = get_picture(conn, username)?;
+
+    let mut transaction = conn.transaction().map_err(APIInternalError::from_db_err)?;
+    let statements = [
+        "DELETE FROM device_telemetry WHERE username = $1 OR recipient_username = $1",
+        "DELETE FROM devices WHERE device_id IN (
+            SELECT device_id FROM users_devices WHERE username = $1 AND owner = true)",
+        "DELETE FROM users_followers_state WHERE username = $1 OR username_follower = $1",
+        "DELETE FROM users_state_history WHERE username = $1",
+        "DELETE FROM users_geofences WHERE username = $1",
+        "DELETE FROM commands WHERE username = $1 OR recipient_username = $1",
+        "DELETE FROM command_log WHERE username = $1",
+        "DELETE FROM users_verification USING users
+         WHERE users.username = $1
+         AND (users_verification.email = users.email
+            OR users_verification.phone_number = users.phone_number)",
+        // Cascades to the details, state, settings, identity, friendships and invitations.
+        "DELETE FROM users WHERE username = $1",
+    ];
+    for statement in statements.iter() {
+        transaction
+            .execute(*statement, &[username])
+            .map_err(APIInternalError::from_db_err)?;
+    }
+    transaction
+        .commit()
+        .map_err(APIInternalError::from_db_err)?;
+
+    let mut pipe = redis::pipe();
+    pipe.del(redis_hash_map_name(username))
+        .zrem(TELEMETRY_LAST_SEEN_SET, username)
+        .hdel(NANNY_RETRY_HASH_MAP, username);
+    for friend in &friends {
+        pipe.hdel(redis_hash_map_name(friend), username);
+    }
+    pipe.query::<()>(redis)
+        .map_err(APIInternalError::from_db_err)?;
+
+    if let Some(picture) = picture {
+        release_picture(conn, storage, &picture)
+            .map_err(|err| err.log_err("Unable to delete the profile picture"))
+            .ok();
+    }
+    publish_notifications(&notifications)
+        .map_err(|err| err.log_err("Unable to notify the friends of the account deletion"))
+        .ok();
+    Ok(())
+}
+
+/// Gathers everything stored about the user. Every export is recorded in
+/// users_data_exports, which also caps how many can be generated per day.
+/// Only the first page of the telemetry is included, the rest is read with
+/// export_telemetry_page.
+pub fn export_user_data(
+    conn: &mut PostgresConnection,
+    auth_info: &AuthInfo,
+) -> Result<UserDataExport, APIInternalError> {
+    let username = &auth_info.username;
+    let mut transaction = conn.transaction().map_err(APIInternalError::from_db_err)?;
+    let exports_today: i64 = transaction
+        .query_one(
+            "SELECT count(*) AS exports FROM users_data_exports
+             WHERE username = $1 AND creation_timestamp > now() - interval '1 day'",
+            &[username],
+        )
+        .map_err(APIInternalError::from_db_err)?
+        .get("exports");
+    if exports_today >= DATA_EXPORT_MAX_PER_DAY {
+        return Err(APIInternalError {
+            msg: TranslationIds::TooManyRequests,
+            engineering_error: Some(format!(
+                "{} already exported their data {} times today",
+                username, exports_today
+            )),
+        });
+    }
+    transaction
+        .execute(
+            "INSERT INTO users_data_exports (username, device_id) VALUES ($1, $2)",
+            &[username, &auth_info.deviceId],
+        )
+        .map_err(APIInternalError::from_db_err)?;
+
+    let user = export_section(
+        &mut transaction,
+        "SELECT row_to_json(t)::text AS section FROM (
+            SELECT users.username, users.email, users.phone_number,
+                user_details.first_name, user_details.last_name, user_details.picture,
+                user_details.language, user_details.creation_timestamp,
+                user_details.updated_timestamp
+            FROM users
+            LEFT JOIN user_details ON user_details.username = users.username
+            WHERE users.username = $1) t",
+        username,
+    )?;
+    let devices = export_rows(
+        &mut transaction,
+        "SELECT devices.*, users_devices.owner, users_devices.access_enabled
+         FROM devices
+         INNER JOIN users_devices ON users_devices.device_id = devices.device_id
+         WHERE users_devices.username = $1 AND users_devices.owner = true
+         ORDER BY devices.device_id",
+        username,
+    )?;
+    let device_settings = export_rows(
+        &mut transaction,
+        "SELECT device_settings.* FROM device_settings
+         INNER JOIN users_devices ON users_devices.device_id = device_settings.device_id
+         WHERE users_devices.username = $1 AND users_devices.owner = true
+         ORDER BY device_settings.creation_timestamp",
+        username,
+    )?;
+    let followers = export_rows(
+        &mut transaction,
+        "SELECT * FROM users_followers
+         WHERE username = $1 OR username_follower = $1
+         ORDER BY creation_timestamp",
+        username,
+    )?;
+    let link_invitations = export_rows(
+        &mut transaction,
+        "SELECT * FROM link_invitations
+         WHERE creator_username = $1 OR recipient_username = $1
+            OR id IN (SELECT invitation_id FROM link_invitations_uses WHERE username = $1)
+         ORDER BY creation_timestamp",
+        username,
+    )?;
+    let commands = export_rows(
+        &mut transaction,
+        "SELECT * FROM commands
+         WHERE username = $1 OR recipient_username = $1
+         ORDER BY request_timestamp",
+        username,
+    )?;
+    let state_history = export_rows(
+        &mut transaction,
+        "SELECT * FROM users_state_history WHERE username = $1 ORDER BY creation_timestamp",
+        username,
+    )?;
+    let telemetry = export_telemetry_page(&mut transaction, username, None)?;
+    transaction
+        .commit()
+        .map_err(APIInternalError::from_db_err)?;
+
+    Ok(UserDataExport {
+        generatedAt: Utc::now().format(DATE_FORMAT).to_string(),
+        user,
+        devices,
+        deviceSettings: device_settings,
+        followers,
+        linkInvitations: link_invitations,
+        commands,
+        stateHistory: state_history,
+        telemetry: Value::from(telemetry.telemetry),
+        telemetryNextCursor: telemetry.nextCursor,
+    })
+}
+
+/// Telemetry sent by the user after the cursor. The locations are encrypted for
+/// each follower, only the metadata is readable.
+pub fn export_telemetry_page<C: GenericClient>(
+    client: &mut C,
+    username: &String,
+    cursor: Option<&TelemetryCursor>,
+) -> Result<TelemetryExportPage, APIInternalError> {
+    let mut rows = client
+        .query(
+            "SELECT row_to_json(t)::text AS location, t.creation_timestamp, t.device_id FROM (
+                SELECT device_id, creation_timestamp, app_state, charging_state,
+                    battery_level, is_charging,
+                    json_agg(recipient_username ORDER BY recipient_username) AS recipients
+                FROM device_telemetry
+                WHERE username = $1
+                  AND ($2::timestamp IS NULL OR (creation_timestamp, device_id) > ($2, $3::text))
+                GROUP BY device_id, creation_timestamp, app_state, charging_state,
+                    battery_level, is_charging
+                ORDER BY creation_timestamp, device_id
+                LIMIT $4) t
+             ORDER BY t.creation_timestamp, t.device_id",
+            &[
+                username,
+                &cursor.map(|cursor| cursor.timestamp),
+                &cursor.map(|cursor| cursor.device_id.clone()),
+                &(DATA_EXPORT_TELEMETRY_PAGE_SIZE + 1),
+            ],
+        )
+        .map_err(APIInternalError::from_db_err)?;
+    let next_cursor = if rows.len() as i64 > DATA_EXPORT_TELEMETRY_PAGE_SIZE {
+        rows.truncate(DATA_EXPORT_TELEMETRY_PAGE_SIZE as usize);
+        rows.last().map(|row| {
+            TelemetryCursor {
+                timestamp: row.get("creation_timestamp"),
+                device_id: row.get("device_id"),
+            }
+            .to_string()
+        })
+    } else {
+        None
+    };
+    let telemetry = rows
+        .iter()
+        .map(|row| serde_json::from_str(row.get("location")))
+        .collect::<Result<Vec<Value>, _>>()
+        .map_err(APIInternalError::backend_issue)?;
+    Ok(TelemetryExportPage {
+        telemetry,
+        nextCursor: next_cursor,
+    })
+}
+
+/// Runs a query returning one `section` column with json text, NULL becomes null.
+fn export_section(
+    transaction: &mut Transaction,
+    query: &str,
+    username: &String,
+) -> Result<Value, APIInternalError> {
+    let section: Option<String> = transaction
+        .query_opt(query, &[username])
+        .map_err(APIInternalError::from_db_err)?
+        .and_then(|row| row.get("section"));
+    section
+        .map(|json| serde_json::from_str(&json).map_err(APIInternalError::backend_issue))
+        .unwrap_or(Ok(Value::Null))
+}
+
+/// Aggregates the rows of the query into a json array.
+fn export_rows(
+    transaction: &mut Transaction,
+    query: &str,
+    username: &String,
+) -> Result<Value, APIInternalError> {
+    export_section(
+        transaction,
+        &format!(
+            "SELECT COALESCE(json_agg(t), '[]')::text AS section FROM ({}) t",
+            query
+        ),
+        username,
+    )
+}
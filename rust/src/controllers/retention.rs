@@ -0,0 +1,176 @@
+use crate::constants::TELEMETRY_MIGRATION_BATCH_SIZE;
+use crate::model::{
+    responses::Errors::APIInternalError,
+    telemetry::{RetentionPolicy, RetentionReport},
+    PostgresConnection,
+};
+use chrono::{Duration, NaiveDate, NaiveDateTime};
+
+const PARTITION_PREFIX: &str = "device_telemetry_p";
+const DEFAULT_PARTITION: &str = "device_telemetry_default";
+
+/// device_telemetry_p20210215 => 2021-02-15
+fn partition_day(name: &str) -> Option<NaiveDate> {
+    name.strip_prefix(PARTITION_PREFIX)
+        .and_then(|day| NaiveDate::parse_from_str(day, "%Y%m%d").ok())
+}
+
+/// Creates the daily partitions of the next `partitions_ahead_days`, the partitioning
+/// migration created the days that phones can backfill. Past days are never created,
+/// their rows would have to be moved out of the default partition.
+///
+/// @return the number of partitions created
+pub fn create_telemetry_partitions(
+    conn: &mut PostgresConnection,
+    policy: &RetentionPolicy,
+    today: NaiveDate,
+) -> Result<usize, APIInternalError> {
+    let mut created = 0;
+    let mut day = today.succ();
+    while day <= today + Duration::days(policy.partitions_ahead_days) {
+        let row = conn
+            .query_one("SELECT create_device_telemetry_partition($1)", &[&day])
+            .map_err(APIInternalError::from_db_err)?;
+        if row.get::<_, bool>(0) {
+            created += 1;
+        }
+        day = day.succ();
+    }
+    Ok(created)
+}
+
+/// Moves the rows that the partitioning migration left in device_telemetry_unpartitioned,
+/// each batch in its own transaction, and drops the table once it is empty.
+///
+/// @return the number of rows moved
+pub fn migrate_unpartitioned_telemetry(
+    conn: &mut PostgresConnection,
+    batch_size: i64,
+) -> Result<u64, APIInternalError> {
+    let pending: bool = conn
+        .query_one(
+            "SELECT to_regclass('device_telemetry_unpartitioned') IS NOT NULL",
+            &[],
+        )
+        .map_err(APIInternalError::from_db_err)?
+        .get(0);
+    if !pending {
+        return Ok(0);
+    }
+    let mut migrated = 0;
+    loop {
+        let moved = conn
+            .execute(
+                "WITH moved AS (
+                    DELETE FROM device_telemetry_unpartitioned
+                    WHERE ctid = ANY(ARRAY(SELECT ctid FROM device_telemetry_unpartitioned LIMIT $1))
+                    RETURNING *
+                 )
+                 INSERT INTO device_telemetry
+                    (username, recipient_username, encrypted_location, device_id, creation_timestamp,
+                     app_state, charging_state, battery_level, is_charging)
+                 SELECT username, recipient_username, encrypted_location, device_id, creation_timestamp,
+                    app_state, charging_state, battery_level, is_charging
+                 FROM moved",
+                &[&batch_size],
+            )
+            .map_err(APIInternalError::from_db_err)?;
+        if moved == 0 {
+            break;
+        }
+        migrated += moved;
+    }
+    conn.batch_execute("DROP TABLE device_telemetry_unpartitioned")
+        .map_err(APIInternalError::from_db_err)?;
+    Ok(migrated)
+}
+
+/// Daily partitions of device_telemetry with the day they store.
+fn telemetry_partitions(
+    conn: &mut PostgresConnection,
+) -> Result<Vec<(String, NaiveDate)>, APIInternalError> {
+    conn.query(
+        "SELECT child.relname::text FROM pg_inherits
+         JOIN pg_class parent ON parent.oid = pg_inherits.inhparent
+         JOIN pg_class child ON child.oid = pg_inherits.inhrelid
+         WHERE parent.relname = 'device_telemetry'",
+        &[],
+    )
+    .map_err(APIInternalError::from_db_err)
+    .map(|rows| {
+        rows.iter()
+            .filter_map(|row| {
+                let name: String = row.get(0);
+                partition_day(&name).map(|day| (name, day))
+            })
+            .collect()
+    })
+}
+
+/// Deletes the rows of `table` captured before `cutoff` that are not within
+/// `emergency_window_hours` of an emergency of their sender.
+fn delete_outside_emergencies(
+    conn: &mut PostgresConnection,
+    table: &str,
+    cutoff: &NaiveDateTime,
+    policy: &RetentionPolicy,
+) -> Result<u64, APIInternalError> {
+    conn.execute(
+        format!(
+            "DELETE FROM \"{}\" AS telemetry
+             WHERE telemetry.creation_timestamp < $1
+             AND NOT EXISTS (
+                SELECT 1 FROM users_emergency_periods AS emergency
+                WHERE emergency.username = telemetry.username
+                AND telemetry.creation_timestamp
+                    >= emergency.started_at - make_interval(hours => $2)
+                AND telemetry.creation_timestamp
+                    <= COALESCE(emergency.ended_at, now()::timestamp) + make_interval(hours => $2)
+             )",
+            table
+        )
+        .as_str(),
+        &[cutoff, &policy.emergency_window_hours],
+    )
+    .map_err(APIInternalError::from_db_err)
+}
+
+/// Finishes the partitioning migration, creates the upcoming partitions and removes
+/// the expired telemetry: partitions older than `emergency_retention_days` are dropped,
+/// and partitions older than `retention_days` only keep the rows close to an emergency.
+pub fn apply_retention_policy(
+    conn: &mut PostgresConnection,
+    policy: &RetentionPolicy,
+    today: NaiveDate,
+) -> Result<RetentionReport, APIInternalError> {
+    let mut report = RetentionReport {
+        migrated_rows: migrate_unpartitioned_telemetry(conn, TELEMETRY_MIGRATION_BATCH_SIZE)?,
+        created_partitions: create_telemetry_partitions(conn, policy, today)?,
+        ..Default::default()
+    };
+
+    let retention_cutoff = (today - Duration::days(policy.retention_days)).and_hms(0, 0, 0);
+    let emergency_cutoff =
+        (today - Duration::days(policy.emergency_retention_days)).and_hms(0, 0, 0);
+    for (name, day) in telemetry_partitions(conn)? {
+        let partition_end = day.succ().and_hms(0, 0, 0);
+        if partition_end <= emergency_cutoff {
+            conn.batch_execute(format!("DROP TABLE \"{}\"", name).as_str())
+                .map_err(APIInternalError::from_db_err)?;
+            report.dropped_partitions.push(name);
+        } else if partition_end <= retention_cutoff {
+            report.deleted_rows += delete_outside_emergencies(conn, &name, &partition_end, policy)?;
+        }
+    }
+
+    // Rows older than the first partitions or stored before their partition existed.
+    report.deleted_rows += conn
+        .execute(
+            "DELETE FROM device_telemetry_default WHERE creation_timestamp < $1",
+            &[&emergency_cutoff],
+        )
+        .map_err(APIInternalError::from_db_err)?;
+    report.deleted_rows +=
+        delete_outside_emergencies(conn, DEFAULT_PARTITION, &retention_cutoff, policy)?;
+    Ok(report)
+}
@@ -11,8 +11,10 @@
  * limitations under the License.
  */
 use crate::constants::{DATE_FORMAT, NANNY_RETRY_HASH_MAP, TELEMETRY_LAST_SEEN_SET};
+use crate::constants::{TELEMETRY_UPLOAD_PREFIX, TELEMETRY_UPLOAD_WINDOW_SECONDS};
 use crate::constants::{NOTIFICATIONS_EXCHANGE, NOTIFICATIONS_ROUTING_KEY};
 use crate::controllers::devices::get_subscriber_device_ids;
+use crate::controllers::health::{low_battery_alert_key, low_battery_level};
 use crate::lang::TranslationIds;
 use crate::messaging::get_rabbitmq_uri;
 use crate::model::{
@@ -22,14 +24,16 @@ use crate::model::{
     emergency::{AccessType, UserState},
     requests::TelemetryRequest,
     responses::{CommandResponse, Errors::APIInternalError, TelemetryResponse},
-    telemetry::{Command, CommandState, Connection, FollowerKey, Telemetry},
+    telemetry::{
+        Command, CommandState, Connection, FollowerKey, StoredTelemetry, Telemetry, TelemetryUpdate,
+    },
     UserDetails,
 };
 use amiquip::{
     Connection as RabbitConnection, ExchangeDeclareOptions, ExchangeType, Publish,
     Result as RabbitResult,
 };
-use chrono::{Local, Utc};
+use chrono::{Local, NaiveDateTime};
 use postgres::error::Error;
 use postgres::{NoTls, Row};
 use r2d2::{Pool, PooledConnection};
@@ -66,69 +70,250 @@ pub fn redis_hash_map_name(username: &str) -> String {
     return format!("telemetry.{username}", username = username);
 }
 
+/// Replaces the last location sent by ARGV[1] to every recipient, unless the
+/// cached one is newer, marks the user as seen and tracks the low battery alert.
+/// KEYS: the telemetry hash of every recipient, the last seen set, the nanny
+/// retry map and the low battery alert key.
+/// ARGV: username, last seen score, the telemetry and timestamp of every
+/// recipient, then the battery: 'low', 'normal' or empty when it is unknown.
+/// Returns the 1-based index of the recipients that were updated and 1 if the
+/// low battery alert is due.
+const STORE_LAST_LOCATION_SCRIPT: &str = r#"
+local username = ARGV[1]
+local recipients = #KEYS - 3
+local battery = ARGV[#ARGV]
+local updated = {}
+for i = 1, recipients do
+    local telemetry = ARGV[2 * i + 1]
+    local timestamp = ARGV[2 * i + 2]
+    local current = redis.call('HGET', KEYS[i], username)
+    local previous = nil
+    if current then
+        local ok, decoded = pcall(cjson.decode, current)
+        if ok and type(decoded) == 'table' then
+            previous = decoded['timestamp']
+        end
+    end
+    if type(previous) ~= 'string' or previous < timestamp then
+        redis.call('HSET', KEYS[i], username, telemetry)
+        table.insert(updated, i)
+    end
+end
+if recipients > 0 then
+    redis.call('ZADD', KEYS[recipients + 1], ARGV[2], username)
+    redis.call('HDEL', KEYS[recipients + 2], username)
+end
+local alert = 0
+if battery == 'low' then
+    alert = 1 - redis.call('EXISTS', KEYS[recipients + 3])
+elseif battery == 'normal' then
+    redis.call('DEL', KEYS[recipients + 3])
+end
+return {updated, alert}
+"#;
+
+/// Stores the location sent to every recipient with a single multi-row insert
+/// at the time it was captured, and updates the last location cache with one
+/// atomic redis script, so the cost of a ping does not grow with the number of followers.
+///
+/// The same script tells if the emergency contacts must be alerted about a low
+/// battery, so the alert costs no extra round trip.
+///
+/// @return the updates that are now the last location known by their recipient,
+/// backfilled locations older than the cached ones are not included
 pub fn store_telemetry(
     telemetry_request: &Json<TelemetryRequest>,
     auth_info: &AuthInfo,
     client: &mut PooledConnection<PostgresConnectionManager<NoTls>>,
     redis: &mut redis::Connection,
-) -> Result<String, APIInternalError> {
-    for telemetry in &telemetry_request.telemetry {
-        let statement = client
-             .prepare(
-                 "insert into device_telemetry
-         (username, device_id, recipient_username, encrypted_location, creation_timestamp, app_state,
-         charging_state, battery_level, is_charging)
-          values ($1, $2, $3, $4, now(), $5, $6, $7, $8)",
-             )
-             .map_err(APIInternalError::from_db_err)?;
-
-        let battery_state = telemetry_request
-            .batteryState
-            .as_ref()
-            .unwrap_or(&BatteryState {
-                batteryLevel: Option::Some(0.0),
-                chargingState: Option::Some(ChargingState::UNKNOWN),
-                isCharging: Option::Some(false),
-            });
-        let _rows = client
-            .query(
-                &statement,
+) -> Result<StoredTelemetry, APIInternalError> {
+    let low_battery = telemetry_request
+        .batteryState
+        .as_ref()
+        .map(low_battery_level);
+    if telemetry_request.telemetry.is_empty() && low_battery.is_none() {
+        return Ok(StoredTelemetry {
+            latest: vec![],
+            low_battery_alert: None,
+        });
+    }
+    let battery_state = telemetry_request
+        .batteryState
+        .as_ref()
+        .unwrap_or(&BatteryState {
+            batteryLevel: Option::Some(0.0),
+            chargingState: Option::Some(ChargingState::UNKNOWN),
+            isCharging: Option::Some(false),
+        });
+    let recipients: Vec<&str> = telemetry_request
+        .telemetry
+        .iter()
+        .map(|telemetry| telemetry.recipientUsername.as_str())
+        .collect();
+    let locations: Vec<&str> = telemetry_request
+        .telemetry
+        .iter()
+        .map(|telemetry| telemetry.data.as_str())
+        .collect();
+    let captured_at: Vec<Option<NaiveDateTime>> = telemetry_request
+        .telemetry
+        .iter()
+        .map(|telemetry| telemetry.captured_at().map(|date| date.naive_utc()))
+        .collect();
+
+    if !telemetry_request.telemetry.is_empty() {
+        let mut transaction = client
+            .transaction()
+            .map_err(APIInternalError::from_db_err)?;
+        transaction
+            .execute(
+                "INSERT INTO device_telemetry
+                (username, device_id, recipient_username, encrypted_location, creation_timestamp,
+                 app_state, charging_state, battery_level, is_charging)
+             SELECT $1, $2, recipient_username, encrypted_location, COALESCE(captured_at, now()),
+                    $6, $7, $8, $9
+             FROM unnest($3::text[], $4::text[], $5::timestamp[])
+                AS t(recipient_username, encrypted_location, captured_at)",
                 &[
                     &auth_info.username,
                     &auth_info.deviceId,
-                    &telemetry.recipientUsername,
-                    &telemetry.data,
+                    &recipients,
+                    &locations,
+                    &captured_at,
                     &telemetry_request.appState.unwrap_or(UNKNOWN),
-                    &battery_state.chargingState.unwrap(),
-                    &battery_state.batteryLevel.unwrap(),
-                    &battery_state.isCharging.unwrap(),
+                    &battery_state
+                        .chargingState
+                        .unwrap_or(ChargingState::UNKNOWN),
+                    &battery_state.batteryLevel.unwrap_or(0.0),
+                    &battery_state.isCharging.unwrap_or(false),
                 ],
             )
             .map_err(APIInternalError::from_db_err)?;
-        let hash_map_name = redis_hash_map_name(&telemetry.recipientUsername);
-        let utc_time = Utc::now();
-        let local_now = Local::now();
-        let telemetry = json!(Telemetry {
+        transaction
+            .commit()
+            .map_err(APIInternalError::from_db_err)?;
+    }
+
+    let script = redis::Script::new(STORE_LAST_LOCATION_SCRIPT);
+    let mut invocation = script.prepare_invoke();
+    invocation
+        .arg(&auth_info.username)
+        .arg(Local::now().timestamp());
+    for telemetry in &telemetry_request.telemetry {
+        let timestamp = telemetry.formatted_timestamp();
+        let telemetry_string = json!(Telemetry {
             data: telemetry.data.clone(),
-            timestamp: utc_time.format(DATE_FORMAT.as_ref()).to_string(),
+            timestamp: timestamp.clone(),
             batteryState: telemetry_request.batteryState.clone()
+        })
+        .to_string();
+        invocation
+            .key(redis_hash_map_name(&telemetry.recipientUsername))
+            .arg(telemetry_string)
+            .arg(timestamp);
+    }
+    invocation
+        .key(TELEMETRY_LAST_SEEN_SET)
+        .key(NANNY_RETRY_HASH_MAP)
+        .key(low_battery_alert_key(auth_info))
+        .arg(match low_battery {
+            Some(Some(_)) => "low",
+            Some(None) => "normal",
+            None => "",
         });
-        let telemetry_string = serde_json::to_string(&telemetry).unwrap();
-        redis
-            .hset(hash_map_name, &auth_info.username, &telemetry_string)
-            .map_err(APIInternalError::from_db_err)?;
-        redis
-            .zadd(
-                &TELEMETRY_LAST_SEEN_SET.to_string(),
-                &auth_info.username,
-                local_now.timestamp(),
+    let (updated, alert): (Vec<usize>, bool) = invocation
+        .invoke(redis)
+        .map_err(APIInternalError::from_db_err)?;
+    Ok(StoredTelemetry {
+        latest: updated
+            .into_iter()
+            .filter_map(|index| telemetry_request.telemetry.get(index - 1).cloned())
+            .collect(),
+        low_battery_alert: low_battery.flatten().filter(|_| alert),
+    })
+}
+
+/// State of a telemetry batch identified by its idempotency key.
+pub enum TelemetryUpload {
+    /// First time the batch is received, it must be stored.
+    New,
+    /// A previous upload of the batch is still being stored.
+    InProgress,
+    /// The batch was already stored, contains the original response.
+    Completed(Option<TelemetryResponse>),
+}
+
+const TELEMETRY_UPLOAD_IN_PROGRESS: &str = "in_progress";
+
+/// Retries are detected by the idempotency key, or the client timestamp of the
+/// batch for clients that do not send one.
+pub fn telemetry_upload_key(
+    telemetry_request: &TelemetryRequest,
+    auth_info: &AuthInfo,
+) -> Option<String> {
+    telemetry_request
+        .idempotencyKey
+        .as_ref()
+        .map(|key| format!("key.{}", key))
+        .or_else(|| {
+            telemetry_request
+                .clientTimestamp
+                .as_ref()
+                .map(|timestamp| format!("ts.{}", timestamp))
+        })
+        .map(|batch| {
+            format!(
+                "{}.{}.{}.{}",
+                TELEMETRY_UPLOAD_PREFIX, auth_info.username, auth_info.deviceId, batch
             )
-            .map_err(APIInternalError::from_db_err)?;
-        redis
-            .hdel(&NANNY_RETRY_HASH_MAP.to_string(), &auth_info.username)
-            .map_err(APIInternalError::from_db_err)?;
+        })
+}
+
+/// Marks the batch as in progress unless it was received before.
+pub fn begin_telemetry_upload(
+    redis: &mut redis::Connection,
+    key: &str,
+) -> Result<TelemetryUpload, APIInternalError> {
+    let created: Option<String> = redis::cmd("SET")
+        .arg(key)
+        .arg(TELEMETRY_UPLOAD_IN_PROGRESS)
+        .arg("NX")
+        .arg("EX")
+        .arg(TELEMETRY_UPLOAD_WINDOW_SECONDS)
+        .query(redis)
+        .map_err(APIInternalError::from_db_err)?;
+    if created.is_some() {
+        return Ok(TelemetryUpload::New);
     }
-    Ok("Ok".to_string())
+    let previous: Option<String> = redis.get(key).map_err(APIInternalError::from_db_err)?;
+    match previous.as_deref() {
+        // Expired between both calls.
+        None => begin_telemetry_upload(redis, key),
+        Some(TELEMETRY_UPLOAD_IN_PROGRESS) => Ok(TelemetryUpload::InProgress),
+        Some(response) => serde_json::from_str(response)
+            .map(TelemetryUpload::Completed)
+            .map_err(APIInternalError::backend_issue),
+    }
+}
+
+/// Stores the response returned to the first upload for the retries.
+pub fn complete_telemetry_upload(
+    redis: &mut redis::Connection,
+    key: &str,
+    response: &Option<TelemetryResponse>,
+) -> Result<(), APIInternalError> {
+    let response = serde_json::to_string(response).map_err(APIInternalError::backend_issue)?;
+    redis
+        .set_ex(key, response, TELEMETRY_UPLOAD_WINDOW_SECONDS)
+        .map_err(APIInternalError::from_db_err)
+}
+
+/// Lets the client retry a batch that could not be stored.
+pub fn abort_telemetry_upload(
+    redis: &mut redis::Connection,
+    key: &str,
+) -> Result<(), APIInternalError> {
+    redis.del(key).map_err(APIInternalError::from_db_err)
 }
 
 pub fn get_follower_keys(
@@ -200,7 +385,9 @@ pub fn get_followers(
 ) -> Result<HashMap<String, Connection>, APIInternalError> {
     let followers = client
         .query(
-            "SELECT * FROM users_followers \
+            "SELECT *, CASE WHEN users_followers.sharing_expires_at > now()
+                THEN users_followers.sharing_expires_at END AS active_sharing_expires_at
+             FROM users_followers
              INNER JOIN users
              ON users.username = users_followers.username_follower
              INNER JOIN user_details
@@ -218,6 +405,7 @@ pub fn get_followers(
             let username: String = row.get("username_follower");
             let access_type: Option<AccessType> = row.get("access_type");
             let public_key: Option<String> = row.get("public_key");
+            let sharing_expires_at: Option<NaiveDateTime> = row.get("active_sharing_expires_at");
             (
                 username.clone(),
                 Connection {
@@ -231,6 +419,8 @@ pub fn get_followers(
                         language: Option::None,
                     },
                     accessType: access_type,
+                    sharingExpiresAt: sharing_expires_at
+                        .map(|expires_at| expires_at.format(DATE_FORMAT).to_string()),
                     telemetry: Option::None,
                     state: Option::None,
                     publicKey: public_key,
@@ -268,6 +458,8 @@ pub fn getFollowingLastLocation(
      SELECT users_followers.username,
         users_followers.username_follower,
         users_followers.access_type,
+        CASE WHEN users_followers.sharing_expires_at > now()
+            THEN users_followers.sharing_expires_at END AS active_sharing_expires_at,
         user_details.first_name,
         user_details.last_name,
         user_details.picture,
@@ -318,10 +510,13 @@ pub fn getFollowingLastLocation(
 
             let accessType: Option<AccessType> = head_row.get("access_type");
             let state: Option<UserState> = head_row.get("self_perception");
+            let sharingExpiresAt: Option<String> = head_row
+                .get::<_, Option<NaiveDateTime>>("active_sharing_expires_at")
+                .map(|expires_at| expires_at.format(DATE_FORMAT).to_string());
 
             // Get telemetry
-            let telemetry = match (accessType.clone(), state.clone()) {
-                (Some(AccessType::EmergencyOnly), Some(UserState::Normal)) => None,
+            let telemetry = match (accessType.clone(), state.clone(), &sharingExpiresAt) {
+                (Some(AccessType::EmergencyOnly), Some(UserState::Normal), None) => None,
                 _ => {
                     let w = get_last_user_location(redis, &username, &follower.to_string());
                     w
@@ -333,6 +528,7 @@ pub fn getFollowingLastLocation(
                 Connection {
                     userDetails,
                     accessType,
+                    sharingExpiresAt,
                     telemetry,
                     state,
                     publicKey: None,
@@ -69,3 +69,26 @@ where
             action(&mut transaction).and_then(|res| transaction.commit().map(|_| res))
         })
 }
+
+/// Same as `transaction` but the action can bail out with an APIInternalError,
+/// in which case nothing done inside the transaction is committed.
+pub fn api_transaction<F, T>(
+    conn: &mut PostgresConnection,
+    action: F,
+) -> Result<T, APIInternalError>
+where
+    F: FnOnce(&mut Transaction) -> Result<T, APIInternalError>,
+{
+    conn.build_transaction()
+        .isolation_level(IsolationLevel::Serializable)
+        .start()
+        .map_err(APIInternalError::from_db_err)
+        .and_then(|mut transaction| {
+            action(&mut transaction).and_then(|res| {
+                transaction
+                    .commit()
+                    .map(|_| res)
+                    .map_err(APIInternalError::from_db_err)
+            })
+        })
+}
@@ -1,4 +1,4 @@
-use super::TranslationIds;
+use super::{MessageIds, TranslationIds};
 use std::collections::HashMap;
 
 lazy_static! {
@@ -10,22 +10,71 @@ lazy_static! {
         (TranslationIds::InvitationsAlreadyFriends, "You are already friends with this user"),
         (TranslationIds::InvitationsYouAreNotFriends, "You are not friends with this user"),
         (TranslationIds::InvitationsInvitationDoesNotExist, "There is no invitation with that id"),
+        (TranslationIds::InvitationsInvitationWasRevoked, "The invitation was cancelled by the person who sent it"),
+        (TranslationIds::InvitationsInvitationAlreadyUsed, "You already accepted this invitation"),
+        (TranslationIds::InvitationsInvitationAlreadySent, "You already invited this person, wait for them to answer"),
+        (TranslationIds::InvitationsSharedInvitationCannotBeRejected, "This invitation was shared with several people, ignore it if you don't want to accept it"),
         (TranslationIds::InvitationsInvitationIsNoLongerValid, "The invitation is no longer valid"),
         (TranslationIds::DatabaseError, "Database error, an engineer will be assigned to this issue"),
-        (TranslationIds::NannyNotificationAttention, "Attention"),
-        (TranslationIds::NannyNotificationBody,
-         "{} {}'s phone is not sending it's location, please contact this person to make sure that is ok"),
-        (TranslationIds::NannyNotificationOfflinePhoneOwnerBody,
-         "Your phone is not sending it's location, please open Armore to fix this"),
-        (TranslationIds::PushNotificationInvitationAcceptedTitle, "accepted your invitation"),
-        (TranslationIds::PushNotificationInvitationAcceptedBody, "is now friends with you"),
         (TranslationIds::UserAlreadyInNormal, "Cannot end the emergency"),
         (TranslationIds::UserAlreadyInEmergency, "Cannot report the emergency"),
         (TranslationIds::UserNotInEmergency, "This user is not in an emergency"),
-        (TranslationIds::EmergencyModePushNotificationBody, "{} {} is in an EMERGENCY! Please CONFIRM that they are okay!"),
-        (TranslationIds::NormalModePushNotificationBody, "{} {} is no longer in an emergency."),
-        (TranslationIds::InvalidHistoricalLocationStartTime, "It is not possible to obtain the location from more than a week ago."),
-        (TranslationIds::PushNotificationActionView, "Go to app"),
-        (TranslationIds::CannotUseOwnInvitation, "You are using an invitation that you've created.\nArmore is designed for you to share your location with the people you love.\nTo achieve this, you must send the invitation (link) to the person you want to follow you.\nIf you have any questions, go to the profile section and ask us anything by email or discord.")
+        (TranslationIds::InvalidHistoricalLocationStartTime, "It is not possible to obtain the location from that long before the emergency began."),
+        (TranslationIds::CannotUseOwnInvitation, "You are using an invitation that you've created.\nArmore is designed for you to share your location with the people you love.\nTo achieve this, you must send the invitation (link) to the person you want to follow you.\nIf you have any questions, go to the profile section and ask us anything by email or discord."),
+        (TranslationIds::BadRequest, "The phone sent a bad request, this is an app error and has been logged"),
+        (TranslationIds::InvalidUsername, "Invalid Username"),
+        (TranslationIds::InvalidFirstName, "Invalid First Name"),
+        (TranslationIds::InvalidLastName, "Invalid Last Name"),
+        (TranslationIds::InvalidEmail, "Email is not in a valid format"),
+        (TranslationIds::InvalidPhoneNumber, "Invalid Phone Number"),
+        (TranslationIds::TryAnotherUsername, "The selected username is not available."),
+        (TranslationIds::TryAnotherEmail, "The selected email is not available."),
+        (TranslationIds::TryAnotherPhone, "The selected phone number is not available."),
+        (TranslationIds::VerificationFailure, "Unable to verify ownership of this account, please, send another code or try to login again."),
+        (TranslationIds::DeviceIsRegisteredToAnotherUser, "Device is already registered for a different user."),
+        (TranslationIds::ThereIsAnotherDeviceRegistered, "There is another device registered in your profile, please unregister that device first before attempting to login."),
+        (TranslationIds::TooManyRequests, "Too many requests, please wait a moment and try again."),
+        (TranslationIds::InvalidProfilePicture, "The picture must be a JPEG or PNG image of up to 5 MB"),
+        (TranslationIds::InvalidCreationTimestamp, "Invalid creation timestamp"),
+        (TranslationIds::InvalidInvitationMaxUses, "The invitation can't be used by that many people"),
+        (TranslationIds::InvalidPageSize, "Too many items requested at once"),
+        (TranslationIds::InvalidLocationSharingDuration, "The location can't be shared for that long"),
+        (TranslationIds::InvalidHistoricalInterval, "The interval between locations is not valid"),
+    ].into_iter().collect();
+    pub static ref ENGLISH_MESSAGES: HashMap<MessageIds, &'static str> = vec![
+        (MessageIds::NannyNotificationAttention, "Attention"),
+        (MessageIds::NannyNotificationBody,
+         "{} {}'s phone is not sending it's location, please contact this person to make sure that is ok"),
+        (MessageIds::NannyNotificationOfflinePhoneOwnerBody,
+         "Your phone is not sending it's location, please open Armore to fix this"),
+        (MessageIds::PushNotificationInvitationAcceptedTitle, "accepted your invitation"),
+        (MessageIds::PushNotificationInvitationAcceptedBody, "is now friends with you"),
+        (MessageIds::EmergencyModePushNotificationBody, "{} {} is in an EMERGENCY! Please CONFIRM that they are okay!"),
+        (MessageIds::NormalModePushNotificationBody, "{} {} is no longer in an emergency."),
+        (MessageIds::PushNotificationActionView, "Go to app"),
+        (MessageIds::VerificationCreatedSuccessfully, "Successfully created verification request"),
+        (MessageIds::VerificationEmailTitle, "Email Verification Required"),
+        (MessageIds::VerificationEmailBody, "Hello {}, please click the link below or use the code to verify that you are the owner of this account."),
+        (MessageIds::VerificationEmailButtonText, "Verify"),
+        (MessageIds::SmsVerificationBody, "Your Armore verification code is: {}\n{}"),
+        (MessageIds::DirectInvitationTitle, "New invitation"),
+        (MessageIds::DirectInvitationPushNotificationBody, "{} {} invited you to share your locations"),
+        (MessageIds::DirectInvitationEmailBody, "{} {} invited you to Armore to share your locations. Download the app and sign up with this email to accept the invitation."),
+        (MessageIds::DirectInvitationEmailButtonText, "Get Armore"),
+        (MessageIds::SmsDirectInvitationBody, "{} {} invited you to Armore, sign up with this phone number to accept: {}"),
+        (MessageIds::LowBatteryPushNotificationTitle, "Low battery"),
+        (MessageIds::LowBatteryPushNotificationBody, "{} {}'s phone battery is at {}%, it may stop sending its location soon."),
+        (MessageIds::TrackingDegradedPushNotificationTitle, "Location sharing interrupted"),
+        (MessageIds::TrackingDegradedOwnerPushNotificationBody, "The settings of your phone changed and Armore may not be able to share your location, please open Armore to fix this"),
+        (MessageIds::TrackingDegradedFollowerPushNotificationBody, "The settings of {} {}'s phone changed and their location may not be shared"),
+        (MessageIds::FollowerAccessPushNotificationTitle, "Location sharing updated"),
+        (MessageIds::FollowerAccessEmergencyOnlyPushNotificationBody, "{} {} will only share their location with you during an emergency"),
+        (MessageIds::FollowerAccessPermanentPushNotificationBody, "{} {} is sharing their location with you at all times"),
+        (MessageIds::EmergencyContactAddedPushNotificationBody, "{} {} added you as an emergency contact"),
+        (MessageIds::EmergencyContactRemovedPushNotificationBody, "{} {} removed you from their emergency contacts"),
+        (MessageIds::LocationSharingExpiredFollowerPushNotificationBody, "{} {} stopped sharing their location with you"),
+        (MessageIds::LocationSharingExpiredOwnerPushNotificationBody, "Your location is no longer shared with {} {}"),
+        (MessageIds::AccountDeletedPushNotificationTitle, "Account deleted"),
+        (MessageIds::AccountDeletedPushNotificationBody, "{} {} deleted their Armore account and no longer shares their location with you"),
     ].into_iter().collect();
 }
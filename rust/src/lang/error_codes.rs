@@ -0,0 +1,94 @@
+use super::TranslationIds;
+use rocket::http::Status;
+
+/// Stable, machine readable error code and the HTTP status sent with it.
+/// Codes are part of the public API, never rename one that has been released.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ErrorCode {
+    pub code: &'static str,
+    pub status: Status,
+}
+
+fn error(code: &'static str, status: Status) -> ErrorCode {
+    ErrorCode { code, status }
+}
+
+impl TranslationIds {
+    pub fn error_code(&self) -> ErrorCode {
+        match self {
+            TranslationIds::BackendIssue => error("BACKEND_ISSUE", Status::InternalServerError),
+            TranslationIds::DatabaseError => error("DATABASE_ERROR", Status::ServiceUnavailable),
+            TranslationIds::BadRequest => error("BAD_REQUEST", Status::BadRequest),
+            TranslationIds::TooManyRequests => error("TOO_MANY_REQUESTS", Status::TooManyRequests),
+            TranslationIds::InvitationsYouAreNotFriends => error("NOT_FRIENDS", Status::Forbidden),
+            TranslationIds::InvitationsInvitationDoesNotExist => {
+                error("INVITATION_NOT_FOUND", Status::NotFound)
+            }
+            TranslationIds::InvitationsInvitationIsNoLongerValid => {
+                error("INVITATION_NO_LONGER_VALID", Status::Gone)
+            }
+            TranslationIds::InvitationsInvitationWasRevoked => {
+                error("INVITATION_REVOKED", Status::Gone)
+            }
+            TranslationIds::InvitationsInvitationAlreadyUsed => {
+                error("INVITATION_ALREADY_USED", Status::Conflict)
+            }
+            TranslationIds::InvitationsInvitationAlreadySent => {
+                error("INVITATION_ALREADY_SENT", Status::Conflict)
+            }
+            TranslationIds::InvitationsSharedInvitationCannotBeRejected => {
+                error("SHARED_INVITATION_NOT_REJECTABLE", Status::Conflict)
+            }
+            TranslationIds::InvitationsAlreadyFriends => error("ALREADY_FRIENDS", Status::Conflict),
+            TranslationIds::CannotUseOwnInvitation => {
+                error("CANNOT_USE_OWN_INVITATION", Status::BadRequest)
+            }
+            TranslationIds::DeviceNotFound => error("DEVICE_NOT_FOUND", Status::NotFound),
+            TranslationIds::DeviceNotUpdated => error("DEVICE_NOT_UPDATED", Status::NotFound),
+            TranslationIds::NoUserForKey => error("USER_NOT_FOUND", Status::NotFound),
+            TranslationIds::UserAlreadyInEmergency => {
+                error("USER_ALREADY_IN_EMERGENCY", Status::Conflict)
+            }
+            TranslationIds::UserAlreadyInNormal => {
+                error("USER_ALREADY_IN_NORMAL", Status::Conflict)
+            }
+            TranslationIds::UserNotInEmergency => error("USER_NOT_IN_EMERGENCY", Status::Conflict),
+            TranslationIds::InvalidHistoricalLocationStartTime => {
+                error("INVALID_TIME_RANGE", Status::BadRequest)
+            }
+            TranslationIds::InvalidUsername => error("INVALID_USERNAME", Status::BadRequest),
+            TranslationIds::InvalidFirstName => error("INVALID_FIRST_NAME", Status::BadRequest),
+            TranslationIds::InvalidLastName => error("INVALID_LAST_NAME", Status::BadRequest),
+            TranslationIds::InvalidEmail => error("INVALID_EMAIL", Status::BadRequest),
+            TranslationIds::InvalidPhoneNumber => error("INVALID_PHONE_NUMBER", Status::BadRequest),
+            TranslationIds::TryAnotherUsername => error("USERNAME_TAKEN", Status::Conflict),
+            TranslationIds::TryAnotherEmail => error("EMAIL_TAKEN", Status::Conflict),
+            TranslationIds::TryAnotherPhone => error("PHONE_NUMBER_TAKEN", Status::Conflict),
+            TranslationIds::VerificationFailure => {
+                error("VERIFICATION_FAILED", Status::Unauthorized)
+            }
+            TranslationIds::DeviceIsRegisteredToAnotherUser => {
+                error("DEVICE_REGISTERED_TO_ANOTHER_USER", Status::Conflict)
+            }
+            TranslationIds::ThereIsAnotherDeviceRegistered => {
+                error("ANOTHER_DEVICE_REGISTERED", Status::Conflict)
+            }
+            TranslationIds::InvalidProfilePicture => {
+                error("INVALID_PROFILE_PICTURE", Status::BadRequest)
+            }
+            TranslationIds::InvalidCreationTimestamp => {
+                error("INVALID_CREATION_TIMESTAMP", Status::BadRequest)
+            }
+            TranslationIds::InvalidInvitationMaxUses => {
+                error("INVALID_INVITATION_MAX_USES", Status::BadRequest)
+            }
+            TranslationIds::InvalidPageSize => error("INVALID_PAGE_SIZE", Status::BadRequest),
+            TranslationIds::InvalidLocationSharingDuration => {
+                error("INVALID_LOCATION_SHARING_DURATION", Status::BadRequest)
+            }
+            TranslationIds::InvalidHistoricalInterval => {
+                error("INVALID_HISTORICAL_INTERVAL", Status::BadRequest)
+            }
+        }
+    }
+}
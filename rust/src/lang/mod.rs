@@ -1,40 +1,103 @@
 mod english;
+pub mod error_codes;
 mod spanish;
 
-use english::ENGLISH;
+use english::{ENGLISH, ENGLISH_MESSAGES};
 use serde::Serialize;
-use spanish::SPANISH;
+use spanish::{SPANISH, SPANISH_MESSAGES};
 use std::collections::HashMap;
 
+/// Errors reported to the clients, each one has an error code.
 #[derive(Debug, Serialize, PartialEq, Eq, Hash)]
 pub enum TranslationIds {
-    NannyNotificationAttention,
-    NannyNotificationBody,
-    NannyNotificationOfflinePhoneOwnerBody,
     BackendIssue,
     DatabaseError,
     InvitationsYouAreNotFriends,
     InvitationsInvitationDoesNotExist,
     InvitationsInvitationIsNoLongerValid,
+    InvitationsInvitationWasRevoked,
+    InvitationsInvitationAlreadyUsed,
+    InvitationsInvitationAlreadySent,
+    InvitationsSharedInvitationCannotBeRejected,
     DeviceNotFound,
     NoUserForKey,
     DeviceNotUpdated,
-    PushNotificationInvitationAcceptedTitle,
-    PushNotificationInvitationAcceptedBody,
     UserAlreadyInEmergency,
     UserAlreadyInNormal,
     UserNotInEmergency,
-    NormalModePushNotificationBody,
-    EmergencyModePushNotificationBody,
-    PushNotificationActionView,
     InvalidHistoricalLocationStartTime,
     CannotUseOwnInvitation,
     InvitationsAlreadyFriends,
+    BadRequest,
+    InvalidUsername,
+    InvalidFirstName,
+    InvalidLastName,
+    InvalidEmail,
+    InvalidPhoneNumber,
+    TryAnotherUsername,
+    TryAnotherEmail,
+    TryAnotherPhone,
+    VerificationFailure,
+    DeviceIsRegisteredToAnotherUser,
+    ThereIsAnotherDeviceRegistered,
+    TooManyRequests,
+    InvalidProfilePicture,
+    InvalidCreationTimestamp,
+    InvalidInvitationMaxUses,
+    InvalidPageSize,
+    InvalidLocationSharingDuration,
+    InvalidHistoricalInterval,
+}
+
+/// Texts for notifications, emails and success responses.
+#[derive(Debug, Serialize, PartialEq, Eq, Hash)]
+pub enum MessageIds {
+    NannyNotificationAttention,
+    NannyNotificationBody,
+    NannyNotificationOfflinePhoneOwnerBody,
+    PushNotificationInvitationAcceptedTitle,
+    PushNotificationInvitationAcceptedBody,
+    NormalModePushNotificationBody,
+    EmergencyModePushNotificationBody,
+    PushNotificationActionView,
+    VerificationCreatedSuccessfully,
+    VerificationEmailTitle,
+    VerificationEmailBody,
+    VerificationEmailButtonText,
+    SmsVerificationBody,
+    DirectInvitationTitle,
+    DirectInvitationPushNotificationBody,
+    DirectInvitationEmailBody,
+    DirectInvitationEmailButtonText,
+    SmsDirectInvitationBody,
+    LowBatteryPushNotificationTitle,
+    LowBatteryPushNotificationBody,
+    TrackingDegradedPushNotificationTitle,
+    TrackingDegradedOwnerPushNotificationBody,
+    TrackingDegradedFollowerPushNotificationBody,
+    FollowerAccessPushNotificationTitle,
+    FollowerAccessEmergencyOnlyPushNotificationBody,
+    FollowerAccessPermanentPushNotificationBody,
+    EmergencyContactAddedPushNotificationBody,
+    EmergencyContactRemovedPushNotificationBody,
+    LocationSharingExpiredFollowerPushNotificationBody,
+    LocationSharingExpiredOwnerPushNotificationBody,
+    AccountDeletedPushNotificationTitle,
+    AccountDeletedPushNotificationBody,
 }
 
+pub const SUPPORTED_LANGUAGES: [&str; 2] = ["en", "es"];
+
 pub fn get_glossary(language: &str) -> &'static HashMap<TranslationIds, &'static str> {
     match language {
         "es" => (&SPANISH as &HashMap<TranslationIds, &'static str>),
         _ => (&ENGLISH as &HashMap<TranslationIds, &'static str>),
     }
 }
+
+pub fn get_messages(language: &str) -> &'static HashMap<MessageIds, &'static str> {
+    match language {
+        "es" => (&SPANISH_MESSAGES as &HashMap<MessageIds, &'static str>),
+        _ => (&ENGLISH_MESSAGES as &HashMap<MessageIds, &'static str>),
+    }
+}
@@ -1,4 +1,4 @@
-use super::TranslationIds;
+use super::{MessageIds, TranslationIds};
 use std::collections::HashMap;
 
 lazy_static! {
@@ -10,22 +10,71 @@ lazy_static! {
         (TranslationIds::InvitationsYouAreNotFriends, "Usted no es amig@ de este usuario"),
         (TranslationIds::InvitationsAlreadyFriends, "Usted ya es amigo/a de este usuario"),
         (TranslationIds::InvitationsInvitationDoesNotExist, "La invitación seleccionada no existe"),
+        (TranslationIds::InvitationsInvitationWasRevoked, "La invitación fue cancelada por quien la envió"),
+        (TranslationIds::InvitationsInvitationAlreadyUsed, "Ya aceptaste esta invitación"),
+        (TranslationIds::InvitationsInvitationAlreadySent, "Ya invitaste a esta persona, espera a que responda"),
+        (TranslationIds::InvitationsSharedInvitationCannotBeRejected, "Esta invitación fue compartida con varias personas, ignórala si no quieres aceptarla"),
         (TranslationIds::InvitationsInvitationIsNoLongerValid, "La invitación ha expirado"),
         (TranslationIds::DatabaseError, "Error de base de datos, un ingeniero será asignado a este problema"),
-        (TranslationIds::NannyNotificationAttention, "Atención"),
-        (TranslationIds::NannyNotificationBody,
-         "El teléfono de {} {} no está mandando su ubicación, por favor contáctel@"),
-        (TranslationIds::NannyNotificationOfflinePhoneOwnerBody,
-         "Tu teléfono no está mandando su ubicación, por favor, abre Armore para arreglar esto"),
-        (TranslationIds::PushNotificationInvitationAcceptedTitle, "aceptó tu invitación"),
-        (TranslationIds::PushNotificationInvitationAcceptedBody, "ahora es tu amig@"),
         (TranslationIds::UserAlreadyInNormal, "No se pudo parar la emergencia"),
         (TranslationIds::UserAlreadyInEmergency, "No se pudo reportar la emergencia"),
         (TranslationIds::UserNotInEmergency, "El usuario no se encuentra en una emergencia"),
-        (TranslationIds::EmergencyModePushNotificationBody, "¡{} {} está en una EMERGENCIA!. ¡Por favor CONFIRME que está bien!"),
-        (TranslationIds::NormalModePushNotificationBody, "{} {} ya no está en una emergencia."),
-        (TranslationIds::InvalidHistoricalLocationStartTime, "No es posible obtener la localización de hace más de una semana"),
-        (TranslationIds::PushNotificationActionView, "Ir a la app"),
+        (TranslationIds::InvalidHistoricalLocationStartTime, "No es posible obtener la localización de tanto tiempo antes de que comenzara la emergencia"),
         (TranslationIds::CannotUseOwnInvitation, "Estás tratando de usar una invitación que tu creaste.\nArmore está diseñado para que compartas tu ubicación con las personas que amas.\nPara lograr esto, debes de mandar la invitación (link) a la persona que quieres que te siga.\nSi tienes dudas, ve a la sección de perfil y pregúntanos lo que sea por email o discord."),
+        (TranslationIds::BadRequest, "El teléfono envió una solicitud incorrecta, este es un error de la app y ha sido registrado"),
+        (TranslationIds::InvalidUsername, "Usuario inválido"),
+        (TranslationIds::InvalidFirstName, "Nombre inválido"),
+        (TranslationIds::InvalidLastName, "Apellido inválido"),
+        (TranslationIds::InvalidEmail, "El email no tiene un formato válido"),
+        (TranslationIds::InvalidPhoneNumber, "Número de teléfono inválido"),
+        (TranslationIds::TryAnotherUsername, "El usuario seleccionado no está disponible"),
+        (TranslationIds::TryAnotherEmail, "El email seleccionado no está disponible."),
+        (TranslationIds::TryAnotherPhone, "El número de teléfono seleccionado no está disponible."),
+        (TranslationIds::VerificationFailure, "No se pudo verificar la cuenta, por favor, envíe un código nuevo."),
+        (TranslationIds::DeviceIsRegisteredToAnotherUser, "Este teléfono está registrado a otro usuario"),
+        (TranslationIds::ThereIsAnotherDeviceRegistered, "Hay otro dispositivo registrado en su cuenta, para poder entrar, debe de removerlo."),
+        (TranslationIds::TooManyRequests, "Demasiadas solicitudes, por favor espere un momento e intente de nuevo."),
+        (TranslationIds::InvalidProfilePicture, "La foto debe ser una imagen JPEG o PNG de hasta 5 MB"),
+        (TranslationIds::InvalidCreationTimestamp, "Fecha de creación inválida"),
+        (TranslationIds::InvalidInvitationMaxUses, "La invitación no puede ser usada por tantas personas"),
+        (TranslationIds::InvalidPageSize, "Se pidieron demasiados elementos a la vez"),
+        (TranslationIds::InvalidLocationSharingDuration, "La ubicación no puede compartirse por tanto tiempo"),
+        (TranslationIds::InvalidHistoricalInterval, "El intervalo entre ubicaciones no es válido"),
+    ].into_iter().collect();
+    pub static ref SPANISH_MESSAGES: HashMap<MessageIds, &'static str> = vec![
+        (MessageIds::NannyNotificationAttention, "Atención"),
+        (MessageIds::NannyNotificationBody,
+         "El teléfono de {} {} no está mandando su ubicación, por favor contáctel@"),
+        (MessageIds::NannyNotificationOfflinePhoneOwnerBody,
+         "Tu teléfono no está mandando su ubicación, por favor, abre Armore para arreglar esto"),
+        (MessageIds::PushNotificationInvitationAcceptedTitle, "aceptó tu invitación"),
+        (MessageIds::PushNotificationInvitationAcceptedBody, "ahora es tu amig@"),
+        (MessageIds::EmergencyModePushNotificationBody, "¡{} {} está en una EMERGENCIA!. ¡Por favor CONFIRME que está bien!"),
+        (MessageIds::NormalModePushNotificationBody, "{} {} ya no está en una emergencia."),
+        (MessageIds::PushNotificationActionView, "Ir a la app"),
+        (MessageIds::VerificationCreatedSuccessfully, "Solicitud de verificación exitosa"),
+        (MessageIds::VerificationEmailTitle, "Se requiere verificación por correo electrónico"),
+        (MessageIds::VerificationEmailBody, "Hola {}, haga clic en el enlace a continuación o use el código para verificar que usted es el propietario de esta cuenta."),
+        (MessageIds::VerificationEmailButtonText, "Verificar"),
+        (MessageIds::SmsVerificationBody, "Código de Armore es: {}\n{}"),
+        (MessageIds::DirectInvitationTitle, "Nueva invitación"),
+        (MessageIds::DirectInvitationPushNotificationBody, "{} {} te invitó a compartir sus ubicaciones"),
+        (MessageIds::DirectInvitationEmailBody, "{} {} te invitó a Armore para compartir sus ubicaciones. Descarga la aplicación y regístrate con este correo para aceptar la invitación."),
+        (MessageIds::DirectInvitationEmailButtonText, "Descargar Armore"),
+        (MessageIds::SmsDirectInvitationBody, "{} {} te invitó a Armore, regístrate con este número para aceptar: {}"),
+        (MessageIds::LowBatteryPushNotificationTitle, "Batería baja"),
+        (MessageIds::LowBatteryPushNotificationBody, "El teléfono de {} {} tiene {}% de batería, podría dejar de enviar su ubicación pronto."),
+        (MessageIds::TrackingDegradedPushNotificationTitle, "Ubicación interrumpida"),
+        (MessageIds::TrackingDegradedOwnerPushNotificationBody, "La configuración de tu teléfono cambió y Armore podría no compartir tu ubicación, por favor abre Armore para arreglarlo"),
+        (MessageIds::TrackingDegradedFollowerPushNotificationBody, "La configuración del teléfono de {} {} cambió y su ubicación podría no compartirse"),
+        (MessageIds::FollowerAccessPushNotificationTitle, "Ubicación compartida actualizada"),
+        (MessageIds::FollowerAccessEmergencyOnlyPushNotificationBody, "{} {} solo compartirá su ubicación contigo durante una emergencia"),
+        (MessageIds::FollowerAccessPermanentPushNotificationBody, "{} {} comparte su ubicación contigo en todo momento"),
+        (MessageIds::EmergencyContactAddedPushNotificationBody, "{} {} te agregó como contacto de emergencia"),
+        (MessageIds::EmergencyContactRemovedPushNotificationBody, "{} {} te quitó de sus contactos de emergencia"),
+        (MessageIds::LocationSharingExpiredFollowerPushNotificationBody, "{} {} dejó de compartir su ubicación contigo"),
+        (MessageIds::LocationSharingExpiredOwnerPushNotificationBody, "Tu ubicación ya no se comparte con {} {}"),
+        (MessageIds::AccountDeletedPushNotificationTitle, "Cuenta eliminada"),
+        (MessageIds::AccountDeletedPushNotificationBody, "{} {} eliminó su cuenta de Armore y ya no comparte su ubicación contigo"),
     ].into_iter().collect();
 }
@@ -30,4 +30,5 @@ pub mod lang;
 pub mod messaging;
 pub mod model;
 pub mod server;
+pub mod storage;
 pub mod utils;
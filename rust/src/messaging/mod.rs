@@ -13,7 +13,7 @@
  *
  *
  */
-use crate::constants::{DATE_FORMAT, NOTIFICATIONS_EXCHANGE, NOTIFICATIONS_ROUTING_KEY};
+use crate::constants::{NOTIFICATIONS_EXCHANGE, NOTIFICATIONS_ROUTING_KEY};
 use crate::controllers::devices::get_subscriber_device_ids;
 use crate::model::{
     notifications::{NotificationData, PushNotification},
@@ -22,7 +22,6 @@ use crate::model::{
 use amiquip::{
     Channel, Connection, ExchangeDeclareOptions, ExchangeType, Publish, Result as RabbitResult,
 };
-use chrono::Utc;
 use postgres::NoTls;
 use r2d2::PooledConnection;
 use r2d2_postgres::PostgresConnectionManager;
@@ -66,7 +65,7 @@ pub fn send_ws_message(
     let telemetry_update = json!(TelemetryWebsocketUpdate {
         data: telemetry.data.to_string(),
         recipientUsername: telemetry.recipientUsername.to_string(),
-        timestamp: Utc::now().format(DATE_FORMAT.as_ref()).to_string(),
+        timestamp: telemetry.formatted_timestamp(),
         username: username.to_string()
     });
     let message = telemetry_update.to_string();
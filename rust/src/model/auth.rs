@@ -16,3 +16,24 @@ pub struct AuthInfo {
     pub deviceId: String,
     pub language: String,
 }
+
+/// Language picked by the client through the Accept-Language header.
+#[derive(Clone, Debug)]
+pub struct Language(pub String);
+
+/// Where a verification code is delivered, normalized the same way
+/// the users table stores it.
+#[derive(Debug, Clone, PartialEq)]
+pub enum VerificationTarget {
+    Email(String),
+    Phone(String),
+}
+
+impl VerificationTarget {
+    pub fn value(&self) -> &String {
+        match self {
+            VerificationTarget::Email(email) => email,
+            VerificationTarget::Phone(phone) => phone,
+        }
+    }
+}
@@ -1,4 +1,5 @@
 use postgres_types::{FromSql, ToSql};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 #[allow(non_snake_case)]
@@ -20,7 +21,41 @@ pub struct Device {
     pub appVersion: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug, ToSql, FromSql, PartialEq, Eq, Copy)]
+impl Device {
+    /// Settings of the device that stop its locations from arriving.
+    pub fn tracking_issues(&self) -> Vec<TrackingIssue> {
+        let mut issues = vec![];
+        if matches!(
+            self.locationPermissionState,
+            LocationPermissionState::USING
+                | LocationPermissionState::ASK
+                | LocationPermissionState::NEVER
+        ) {
+            issues.push(TrackingIssue::LocationPermission);
+        }
+        if self.isLocationServicesOn == Some(false) {
+            issues.push(TrackingIssue::LocationServicesOff);
+        }
+        if self.isBackgroundRefreshOn == Some(false) {
+            issues.push(TrackingIssue::BackgroundRefreshOff);
+        }
+        if self.isPowerSaveModeOn == Some(true) {
+            issues.push(TrackingIssue::PowerSaveMode);
+        }
+        issues
+    }
+
+    /// Tracking issues of the device that `previous` did not have.
+    pub fn tracking_regressions(&self, previous: &Device) -> Vec<TrackingIssue> {
+        let previous_issues = previous.tracking_issues();
+        self.tracking_issues()
+            .into_iter()
+            .filter(|issue| !previous_issues.contains(issue))
+            .collect()
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, ToSql, FromSql, PartialEq, Eq, Copy, JsonSchema)]
 #[postgres(name = "locationpermissionstate")]
 pub enum LocationPermissionState {
     ALWAYS,
@@ -30,7 +65,7 @@ pub enum LocationPermissionState {
     UNKNOWN,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug, ToSql, FromSql, PartialEq, Eq, Copy)]
+#[derive(Serialize, Deserialize, Clone, Debug, ToSql, FromSql, PartialEq, Eq, Copy, JsonSchema)]
 #[postgres(name = "appstate")]
 pub enum AppState {
     Background,
@@ -38,7 +73,7 @@ pub enum AppState {
     UNKNOWN,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug, ToSql, FromSql, PartialEq, Eq, Copy)]
+#[derive(Serialize, Deserialize, Clone, Debug, ToSql, FromSql, PartialEq, Eq, Copy, JsonSchema)]
 #[postgres(name = "chargingstate")]
 pub enum ChargingState {
     ChargingUsb,
@@ -47,7 +82,7 @@ pub enum ChargingState {
     UNKNOWN,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug, ToSql, FromSql, PartialEq, Eq, Copy)]
+#[derive(Serialize, Deserialize, Clone, Debug, ToSql, FromSql, PartialEq, Eq, Copy, JsonSchema)]
 #[allow(non_snake_case)]
 #[allow(non_camel_case_types)]
 #[postgres(name = "os")]
@@ -58,9 +93,63 @@ pub enum OS {
 }
 
 #[allow(non_snake_case)]
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
 pub struct BatteryState {
     pub batteryLevel: Option<f64>,
     pub chargingState: Option<ChargingState>,
     pub isCharging: Option<bool>,
 }
+
+/// Direction of the battery level of a device during the last hours.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Copy, JsonSchema)]
+pub enum BatteryTrend {
+    Charging,
+    Draining,
+    Stable,
+    Unknown,
+}
+
+/// Reasons why the locations of a device may not arrive.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Copy, JsonSchema)]
+pub enum TrackingIssue {
+    LocationPermission,
+    LocationServicesOff,
+    BackgroundRefreshOff,
+    PowerSaveMode,
+    NoRecentPing,
+}
+
+#[allow(non_snake_case)]
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+pub struct DeviceHealth {
+    pub deviceId: String,
+    pub name: String,
+    pub os: OS,
+    pub model: Option<String>,
+    pub lastPing: Option<String>,
+    pub secondsSinceLastPing: Option<i64>,
+    pub appState: Option<AppState>,
+    pub batteryLevel: Option<f64>,
+    pub isCharging: Option<bool>,
+    pub batteryTrend: BatteryTrend,
+    /// Percentage points lost per hour, negative while charging.
+    pub batteryDrainPerHour: Option<f64>,
+    pub trackingIssues: Vec<TrackingIssue>,
+}
+
+/// Settings of a device after one of its updates.
+#[allow(non_snake_case)]
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+pub struct DeviceSettingsSnapshot {
+    pub timestamp: String,
+    pub locationPermissionState: LocationPermissionState,
+    pub isNotificationsEnabled: Option<bool>,
+    pub isBackgroundRefreshOn: Option<bool>,
+    pub isLocationServicesOn: Option<bool>,
+    pub isPowerSaveModeOn: Option<bool>,
+    pub osVersion: Option<String>,
+    pub appVersion: Option<String>,
+    pub trackingIssues: Vec<TrackingIssue>,
+    /// Tracking issues that appeared with this update.
+    pub regressions: Vec<TrackingIssue>,
+}
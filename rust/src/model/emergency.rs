@@ -1,21 +1,130 @@
+use super::devices::{AppState, ChargingState, OS};
+use chrono::NaiveDateTime;
 use postgres_types::{FromSql, ToSql};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
 pub struct UpdateState {
     pub new_state: UserState,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug, ToSql, FromSql, PartialEq, Eq, Copy)]
+#[derive(Serialize, Deserialize, Clone, Debug, ToSql, FromSql, PartialEq, Eq, Copy, JsonSchema)]
 #[postgres(name = "accesstype")]
 pub enum AccessType {
     Permanent,
     EmergencyOnly,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug, ToSql, FromSql, PartialEq, Eq, Copy)]
+/// What a follower can see of the user, chosen by the user for each follower.
+#[allow(non_snake_case)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, JsonSchema)]
+pub struct FollowerAccess {
+    /// EmergencyOnly followers only get the location while the user is in emergency.
+    pub accessType: AccessType,
+    /// Emergency contacts are notified when the user declares an emergency.
+    pub isEmergencyContact: bool,
+}
+
+/// Temporary access of an EmergencyOnly follower to the location.
+#[allow(non_snake_case)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, JsonSchema)]
+pub struct LocationSharingRequest {
+    pub durationMinutes: i32,
+}
+
+#[allow(non_snake_case)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, JsonSchema)]
+pub struct LocationSharing {
+    pub expiresAt: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, ToSql, FromSql, PartialEq, Eq, Copy, JsonSchema)]
 #[postgres(name = "userstate")]
 pub enum UserState {
     Normal,
     Emergency,
 }
+
+/// How much of the history of the user followers can read during an emergency,
+/// and which alerts are sent to the emergency contacts.
+#[allow(non_snake_case)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, JsonSchema)]
+pub struct EmergencySettings {
+    /// Hours before the emergency began.
+    pub historicalAccessHours: i16,
+    /// Hours after the emergency ended.
+    pub historicalGraceHours: i16,
+    /// Alert the emergency contacts when the battery of a device is low.
+    #[serde(default)]
+    pub lowBatteryAlerts: bool,
+    /// Alert the followers when the settings of a device stop the tracking.
+    #[serde(default)]
+    pub trackingAlerts: bool,
+}
+
+/// Emergency whose history followers can read, `ended_at` is None while it is ongoing.
+#[derive(Debug, Clone)]
+pub struct EmergencyPeriod {
+    pub started_at: NaiveDateTime,
+    pub ended_at: Option<NaiveDateTime>,
+}
+
+#[allow(non_snake_case)]
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct StateTransition {
+    pub state: UserState,
+    pub timestamp: String,
+}
+
+#[allow(non_snake_case)]
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct ExportedDevice {
+    pub deviceId: String,
+    pub name: String,
+    pub os: OS,
+    pub osVersion: Option<String>,
+    pub model: Option<String>,
+    pub appVersion: Option<String>,
+}
+
+/// Location as stored in device_telemetry, `data` is still encrypted for the requester.
+#[allow(non_snake_case)]
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct ExportedLocation {
+    pub data: String,
+    pub deviceId: String,
+    pub timestamp: String,
+    pub appState: AppState,
+    pub batteryLevel: f64,
+    pub chargingState: ChargingState,
+    pub isCharging: bool,
+}
+
+/// Page of the trail of a user in emergency, the app decrypts the locations
+/// to build a GPX or GeoJSON file for the authorities.
+#[allow(non_snake_case)]
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct HistoricalLocationExport {
+    pub username: String,
+    pub requestedBy: String,
+    pub startTime: String,
+    pub endTime: String,
+    pub generatedAt: String,
+    pub devices: Vec<ExportedDevice>,
+    pub locations: Vec<ExportedLocation>,
+    /// Changes of state between the start and the end, preceded by the state at the start.
+    pub stateTransitions: Vec<StateTransition>,
+    /// Send it as `cursor` to get the next page, None on the last page.
+    pub nextCursor: Option<String>,
+}
+
+/// A `HistoricalLocationExport` serialized as JSON in `payload`, the signature is
+/// computed over `payload` as sent and verified with GET /v1/emergency/export/key.
+#[allow(non_snake_case)]
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct SignedExport {
+    pub payload: String,
+    pub signature: String,
+    pub algorithm: String,
+}
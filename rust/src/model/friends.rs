@@ -0,0 +1,33 @@
+use super::{emergency::FollowerAccess, UserDetails};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Which side of the friendships to list.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FriendsFilter {
+    /// Users that follow the user or that the user follows.
+    All,
+    Followers,
+    Following,
+}
+
+#[allow(non_snake_case)]
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+pub struct Friend {
+    pub userDetails: UserDetails,
+    pub publicKey: Option<String>,
+    /// What the friend can see of the user, None if they do not follow the user.
+    pub follower: Option<FollowerAccess>,
+    /// What the user can see of the friend, None if the user does not follow them.
+    pub following: Option<FollowerAccess>,
+    /// Last telemetry sent by the friend, only while the user can see their location.
+    pub lastSeen: Option<String>,
+}
+
+#[allow(non_snake_case)]
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+pub struct FriendsPage {
+    pub friends: Vec<Friend>,
+    /// Send it as `cursor` to get the next page, None on the last page.
+    pub nextCursor: Option<String>,
+}
@@ -1,15 +1,110 @@
-use super::responses::APIJsonResponse;
+use super::emergency::AccessType;
+use super::responses::APIInternalError;
+use crate::constants::INVITATION_MAX_USES;
+use crate::lang::TranslationIds;
 use chrono::{DateTime, Utc};
 use postgres_types::{FromSql, ToSql};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize, Clone, Debug, ToSql, FromSql, PartialEq, Eq, Copy)]
+#[derive(Serialize, Deserialize, Clone, Debug, ToSql, FromSql, PartialEq, Eq, Copy, JsonSchema)]
 #[postgres(name = "link_invitation_state")]
 pub enum InvitationState {
     CREATED,
     REJECTED,
     ACCEPTED,
     EXPIRED,
+    /// Cancelled by its creator before anybody used it.
+    REVOKED,
+}
+
+/// Relationship created when the invitation is accepted, chosen by its creator.
+#[allow(non_snake_case)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct InvitationTerms {
+    /// What the person accepting gets of the creator's location.
+    pub accessType: AccessType,
+    /// Whether the person accepting is notified of the creator's emergencies.
+    pub isEmergencyContact: bool,
+    /// Whether the creator also follows the person accepting, with Permanent
+    /// access until they change it.
+    pub followBack: bool,
+}
+
+impl Default for InvitationTerms {
+    /// Same friendship created before the terms could be chosen.
+    fn default() -> Self {
+        InvitationTerms {
+            accessType: AccessType::Permanent,
+            isEmergencyContact: true,
+            followBack: true,
+        }
+    }
+}
+
+impl InvitationTerms {
+    pub fn from_row(row: &postgres::Row) -> Self {
+        InvitationTerms {
+            accessType: row.get("access_type"),
+            isEmergencyContact: row.get("is_emergency_contact"),
+            followBack: row.get("follow_back"),
+        }
+    }
+}
+
+/// Invitation link as seen by its creator.
+#[allow(non_snake_case)]
+#[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
+pub struct LinkInvitation {
+    pub id: String,
+    pub link: String,
+    /// CREATED links past their expiration date are reported as EXPIRED, links
+    /// become ACCEPTED once every use was taken.
+    pub state: InvitationState,
+    pub creationTimestamp: Option<String>,
+    pub expirationTimestamp: String,
+    /// Only set for single use links, see `recipients`.
+    pub recipientUsername: Option<String>,
+    pub maxUses: i32,
+    pub uses: i32,
+    /// Users that accepted the link, oldest first.
+    pub recipients: Vec<String>,
+    pub terms: InvitationTerms,
+}
+
+/// Status of the invitations sent to a specific email or phone number.
+#[derive(Serialize, Deserialize, Clone, Debug, ToSql, FromSql, PartialEq, Eq, Copy, JsonSchema)]
+#[postgres(name = "invitation_status")]
+#[serde(rename_all = "lowercase")]
+pub enum DirectInvitationStatus {
+    #[postgres(name = "created")]
+    Created,
+    #[postgres(name = "accepted")]
+    Accepted,
+    #[postgres(name = "rejected")]
+    Rejected,
+    /// Cancelled by its creator before it was answered.
+    #[postgres(name = "canceled")]
+    Canceled,
+}
+
+#[allow(non_snake_case)]
+#[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
+pub struct InvitationCreator {
+    pub username: String,
+    pub firstName: String,
+    pub lastName: String,
+    pub picture: Option<String>,
+}
+
+/// Pending invitation sent to the email or phone number of the user.
+#[allow(non_snake_case)]
+#[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
+pub struct DirectInvitation {
+    pub id: String,
+    pub creator: InvitationCreator,
+    pub terms: InvitationTerms,
+    pub creationTimestamp: String,
 }
 
 #[derive(Debug, Clone)]
@@ -23,24 +118,51 @@ pub struct LinkCreationData {
     pub username: String,
     pub exp_date: DateTime<Utc>,
     pub uuid: String,
+    pub max_uses: i32,
+    pub terms: InvitationTerms,
 }
 
 impl LinkCreationData {
-    pub fn new(uuid: String, username: String, exp_date: String) -> Result<Self, APIJsonResponse> {
+    pub fn new(uuid: String, username: String, exp_date: String) -> Result<Self, APIInternalError> {
         if let Some(date_time) = Self::assert_exp_date(&exp_date) {
             Ok(Self {
                 uuid,
                 exp_date: date_time,
                 username,
+                max_uses: 1,
+                terms: InvitationTerms::default(),
             })
         } else {
-            return Err(APIJsonResponse::api_error(
-                "Invalid creation timestamp".to_string(),
-                None,
-            ));
+            return Err(APIInternalError {
+                msg: TranslationIds::InvalidCreationTimestamp,
+                engineering_error: None,
+            });
         }
     }
 
+    /// Lets up to `max_uses` people accept the link, it is single use when None.
+    pub fn with_max_uses(mut self, max_uses: Option<i32>) -> Result<Self, APIInternalError> {
+        match max_uses {
+            None => Ok(self),
+            Some(max_uses) if max_uses >= 1 && max_uses <= INVITATION_MAX_USES => {
+                self.max_uses = max_uses;
+                Ok(self)
+            }
+            Some(_) => Err(APIInternalError {
+                msg: TranslationIds::InvalidInvitationMaxUses,
+                engineering_error: Some(format!(
+                    "maxUses must be between 1 and {}",
+                    INVITATION_MAX_USES
+                )),
+            }),
+        }
+    }
+
+    pub fn with_terms(mut self, terms: Option<InvitationTerms>) -> Self {
+        self.terms = terms.unwrap_or_default();
+        self
+    }
+
     fn assert_exp_date(exp_date: &str) -> Option<DateTime<Utc>> {
         let now = Utc::now();
         if let Ok(exp) = DateTime::parse_from_rfc3339(exp_date) {
@@ -13,11 +13,14 @@
 pub mod auth;
 pub mod devices;
 pub mod emergency;
+pub mod friends;
 pub mod invitations;
 pub mod notifications;
+pub mod profile;
 pub mod requests;
 pub mod responses;
 pub mod telemetry;
+pub mod versioning;
 
 use postgres::NoTls;
 use r2d2::Pool;
@@ -25,6 +28,7 @@ use r2d2::PooledConnection;
 use r2d2_postgres::PostgresConnectionManager;
 use responses::{APIJsonResponse, APIResponse};
 use rocket_contrib::json::Json;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 pub type APIResult<T> = Result<Json<APIResponse<Option<T>>>, APIJsonResponse>;
@@ -32,7 +36,7 @@ pub type PostgresPool = Pool<PostgresConnectionManager<NoTls>>;
 pub type PostgresConnection = PooledConnection<PostgresConnectionManager<NoTls>>;
 
 #[allow(non_snake_case)]
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
 pub struct UserDetails {
     pub username: String,
     pub firstName: String,
@@ -59,7 +63,7 @@ impl UserDetails {
 }
 
 #[allow(non_snake_case)]
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
 pub struct Message<T>
 where
     T: Serialize,
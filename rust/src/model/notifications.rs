@@ -4,7 +4,10 @@ use serde::{Deserialize, Serialize};
 #[allow(non_snake_case)]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Email {
-    pub username: String,
+    /// The email is sent to the stored address of the user when set, people
+    /// that are not users yet only have `email`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
     pub email: String,
     pub templateId: String,
     pub dynamicTemplateData: DynamicEmailTemplateData,
@@ -18,6 +21,14 @@ pub struct DynamicEmailTemplateData {
     pub linkTitle: String,
     pub picture: Option<String>,
     pub link: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Sms {
+    pub to: String,
+    pub body: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
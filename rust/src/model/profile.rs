@@ -0,0 +1,50 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Everything stored about a user, every section is the list of rows of the
+/// table it is named after.
+#[allow(non_snake_case)]
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+pub struct UserDataExport {
+    pub generatedAt: String,
+    /// Row of `users` joined with `user_details`.
+    pub user: Value,
+    pub devices: Value,
+    /// Settings of the devices after each update.
+    pub deviceSettings: Value,
+    /// Friendships where the user is either the followed user or the follower.
+    pub followers: Value,
+    pub linkInvitations: Value,
+    pub commands: Value,
+    pub stateHistory: Value,
+    /// First page of the telemetry sent by the user, without the encrypted
+    /// locations.
+    pub telemetry: Value,
+    /// Cursor for GET /v1/account/export/telemetry when there are more pages.
+    pub telemetryNextCursor: Option<String>,
+}
+
+/// Locations sent by the user, each one with the followers it was sent to.
+#[allow(non_snake_case)]
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+pub struct TelemetryExportPage {
+    pub telemetry: Vec<Value>,
+    pub nextCursor: Option<String>,
+}
+
+#[allow(non_snake_case)]
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+pub struct PictureThumbnail {
+    pub size: u32,
+    pub url: String,
+}
+
+#[allow(non_snake_case)]
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+pub struct ProfilePicture {
+    /// Key stored in user_details.picture.
+    pub picture: String,
+    pub url: String,
+    pub thumbnails: Vec<PictureThumbnail>,
+}
@@ -1,9 +1,11 @@
-use super::devices::{AppState, BatteryState, LocationPermissionState};
+use super::devices::{AppState, BatteryState, LocationPermissionState, OS};
+use super::invitations::InvitationTerms;
 use super::telemetry::TelemetryUpdate;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 #[allow(non_snake_case)]
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
 pub struct DeviceUpdateRequest {
     pub locationPermissionState: LocationPermissionState,
     pub isNotificationsEnabled: Option<bool>,
@@ -12,20 +14,97 @@ pub struct DeviceUpdateRequest {
     pub isPowerSaveModeOn: Option<bool>,
     pub osVersion: Option<String>,
     pub appVersion: Option<String>,
+    /// Replaces the push token of the device, the current one is kept when missing.
+    #[serde(default)]
+    pub pushToken: Option<String>,
 }
 
+/// Fields of the profile to change, the missing ones are kept.
 #[allow(non_snake_case)]
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
+pub struct ProfileUpdateRequest {
+    pub firstName: Option<String>,
+    pub lastName: Option<String>,
+    /// Language of the notifications and messages, one of SUPPORTED_LANGUAGES.
+    pub language: Option<String>,
+}
+
+#[allow(non_snake_case)]
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
 pub struct TelemetryRequest {
     pub returnFriendLocations: bool,
     pub telemetry: Vec<TelemetryUpdate>,
     pub appState: Option<AppState>,
     pub batteryState: Option<BatteryState>,
     pub correlationId: Option<String>,
+    /// Retries of the same batch must send the same key, see `TelemetryUpload`.
+    #[serde(default)]
+    pub idempotencyKey: Option<String>,
+    /// RFC 3339 time the batch was created, used to detect retries when there is no key.
+    #[serde(default)]
+    pub clientTimestamp: Option<String>,
 }
 
 #[allow(non_snake_case)]
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
 pub struct InvitationRequest {
     pub expirationDate: String,
+    /// How many people can accept the link, one when missing.
+    #[serde(default)]
+    pub maxUses: Option<i32>,
+    /// Relationship created on acceptance, a two way Permanent friendship when missing.
+    #[serde(default)]
+    pub terms: Option<InvitationTerms>,
+}
+
+/// New expiration of a link sent again, its uses and terms can't change.
+#[allow(non_snake_case)]
+#[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
+pub struct ResendInvitationRequest {
+    pub expirationDate: String,
+}
+
+/// Invitation for the owner of an email or phone number, exactly one must be set.
+#[allow(non_snake_case)]
+#[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
+pub struct DirectInvitationRequest {
+    #[serde(default)]
+    pub email: Option<String>,
+    #[serde(default)]
+    pub phoneNumber: Option<String>,
+    /// Relationship created on acceptance, a two way Permanent friendship when missing.
+    #[serde(default)]
+    pub terms: Option<InvitationTerms>,
+}
+
+#[allow(non_snake_case)]
+#[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
+pub struct RegistrationRequest {
+    pub username: String,
+    pub firstName: String,
+    pub lastName: String,
+    pub email: Option<String>,
+    pub phoneNumber: Option<String>,
+    pub picture: Option<String>,
+    pub publicKey: String,
+}
+
+#[allow(non_snake_case)]
+#[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
+pub struct LoginRequest {
+    pub email: Option<String>,
+    pub phoneNumber: Option<String>,
+    pub publicKey: String,
+}
+
+#[allow(non_snake_case)]
+#[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
+pub struct VerificationRequest {
+    pub code: String,
+    pub publicKey: String,
+    pub deviceId: String,
+    pub os: OS,
+    pub osVersion: String,
+    pub model: String,
+    pub appVersion: Option<String>,
 }
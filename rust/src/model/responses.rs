@@ -1,25 +1,29 @@
 use self::Errors::*;
 use super::telemetry::{CommandState, Connection};
-use crate::lang;
+use super::versioning::ApiVersion;
+use crate::lang::{self, error_codes::ErrorCode, TranslationIds};
 use rocket::http::{ContentType, Status};
 use rocket::response::Responder;
 use rocket::{response, Request, Response};
 use rocket_contrib::json::JsonValue;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
 #[allow(non_snake_case)]
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
 pub struct APIResponse<A> {
     pub success: bool,
     pub result: A,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 #[allow(non_snake_case)]
 pub struct APIJsonResponse {
     pub json: JsonValue,
+    /// Status sent to clients that did not opt in to error codes.
     pub status: Status,
+    pub error_code: Option<ErrorCode>,
 }
 
 impl APIJsonResponse {
@@ -33,6 +37,7 @@ impl APIJsonResponse {
             translated_error.unwrap_or(&"Unknown error").to_string(),
             internal_error.engineering_error,
         )
+        .with_code(&internal_error.msg)
     }
     pub fn api_error(msg: String, eng_err: Option<String>) -> APIJsonResponse {
         APIJsonResponse {
@@ -40,16 +45,38 @@ impl APIJsonResponse {
                 success: false,
                 result: APIError {
                     message: msg.to_string(),
-                    engineeringError: eng_err
+                    engineeringError: eng_err,
+                    code: None
                 }
             }),
             status: Status::Ok,
+            error_code: None,
         }
     }
+
+    pub fn with_code(mut self, id: &TranslationIds) -> APIJsonResponse {
+        self.error_code = Some(id.error_code());
+        self
+    }
+}
+
+/// Older clients expect every error with status 200, clients on v2 receive
+/// real status codes and the error code catalog.
+pub fn uses_error_codes(req: &Request) -> bool {
+    ApiVersion::negotiate(req) >= ApiVersion::V2
 }
 
 impl<'r> Responder<'r> for APIJsonResponse {
-    fn respond_to(self, req: &Request) -> response::Result<'r> {
+    fn respond_to(mut self, req: &Request) -> response::Result<'r> {
+        if let (Some(error_code), true) = (self.error_code, uses_error_codes(req)) {
+            if let Some(result) = self.json.get_mut("result").and_then(|r| r.as_object_mut()) {
+                result.insert(
+                    "code".to_string(),
+                    serde_json::Value::String(error_code.code.to_string()),
+                );
+            }
+            self.status = error_code.status;
+        }
         Response::build_from(self.json.respond_to(&req).unwrap())
             .status(self.status)
             .header(ContentType::JSON)
@@ -58,24 +85,29 @@ impl<'r> Responder<'r> for APIJsonResponse {
 }
 
 #[allow(non_snake_case)]
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
 pub struct TelemetryResponse {
     pub followers: HashMap<String, Connection>,
     pub following: HashMap<String, Connection>,
 }
 
 #[allow(non_snake_case)]
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
 pub struct DeviceUpdateResponse {
     pub updated: bool,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
 pub struct CreateInvitationResponse {
     pub link: String,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
+pub struct CreateDirectInvitationResponse {
+    pub id: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
 #[allow(non_snake_case)]
 pub struct AcceptInvitationResponse {
     // message property is required to avoid breaking clients on older versions of Armore
@@ -84,7 +116,29 @@ pub struct AcceptInvitationResponse {
     pub publicKey: String
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+/// Responses whose shape changed in v2, see `ApiVersion`.
+pub mod v2 {
+    use schemars::JsonSchema;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
+    #[allow(non_snake_case)]
+    pub struct AcceptInvitationResponse {
+        pub username: String,
+        pub publicKey: String,
+    }
+
+    impl From<super::AcceptInvitationResponse> for AcceptInvitationResponse {
+        fn from(response: super::AcceptInvitationResponse) -> Self {
+            AcceptInvitationResponse {
+                username: response.username,
+                publicKey: response.publicKey,
+            }
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
 #[allow(non_snake_case)]
 pub struct CommandResponse {
     pub correlation_id: Option<String>,
@@ -98,13 +152,17 @@ pub mod Errors {
     use crate::model::emergency::UserState;
     use rocket_sentry_logger as logger;
     use rocket_sentry_logger::LogLevel;
+    use schemars::JsonSchema;
     use serde::{Deserialize, Serialize};
 
-    #[derive(Debug, Serialize, Deserialize, Clone)]
+    #[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
     #[allow(non_snake_case)]
     pub struct APIError {
         pub message: String,
         pub engineeringError: Option<String>,
+        /// Only sent to clients that opted in, see `uses_error_codes`.
+        #[serde(skip_serializing_if = "Option::is_none", default)]
+        pub code: Option<String>,
     }
 
     #[derive(Debug, Serialize)]
@@ -4,13 +4,14 @@ use super::{
     UserDetails,
 };
 use crate::constants::DATE_FORMAT;
-use chrono::NaiveDateTime;
+use chrono::{DateTime, NaiveDateTime, Utc};
 use postgres_types::{FromSql, ToSql};
+use schemars::{gen::SchemaGenerator, schema::Schema, JsonSchema};
 use serde::ser::{SerializeStruct, Serializer};
 use serde::{Deserialize, Serialize};
 
 #[allow(non_snake_case)]
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
 pub struct Telemetry {
     pub data: String,
     pub timestamp: String,
@@ -18,17 +19,46 @@ pub struct Telemetry {
 }
 
 #[allow(non_snake_case)]
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
 pub struct FollowerKey {
     pub username: String,
     pub key: String,
 }
 
 #[allow(non_snake_case)]
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
 pub struct TelemetryUpdate {
     pub data: String,
     pub recipientUsername: String,
+    /// RFC 3339 time the location was captured, locations buffered while the
+    /// phone was offline are stored at this time instead of the upload time.
+    #[serde(default)]
+    pub timestamp: Option<String>,
+}
+
+/// Result of storing a telemetry request.
+pub struct StoredTelemetry {
+    /// Updates that are now the last location known by their recipient.
+    pub latest: Vec<TelemetryUpdate>,
+    /// Battery level the emergency contacts must be alerted about, if any.
+    pub low_battery_alert: Option<f64>,
+}
+
+impl TelemetryUpdate {
+    pub fn captured_at(&self) -> Option<DateTime<Utc>> {
+        self.timestamp
+            .as_ref()
+            .and_then(|timestamp| DateTime::parse_from_rfc3339(timestamp).ok())
+            .map(|timestamp| timestamp.with_timezone(&Utc))
+    }
+
+    /// Capture time in DATE_FORMAT, the upload time for clients that do not send it.
+    pub fn formatted_timestamp(&self) -> String {
+        self.captured_at()
+            .unwrap_or_else(Utc::now)
+            .format(DATE_FORMAT)
+            .to_string()
+    }
 }
 
 #[allow(non_snake_case)]
@@ -41,22 +71,34 @@ pub struct TelemetryWebsocketUpdate {
 }
 
 #[allow(non_snake_case)]
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
 pub struct Connection {
     pub userDetails: UserDetails,
     pub accessType: Option<AccessType>,
+    /// EmergencyOnly followers get the location until then, see share_location.
+    #[serde(default)]
+    pub sharingExpiresAt: Option<String>,
     pub telemetry: Option<Telemetry>,
     pub state: Option<UserState>,
     pub publicKey: Option<String>,
 }
 
+impl Connection {
+    /// True if the follower gets the location of a user in `state`.
+    pub fn can_see_location(&self, state: &UserState) -> bool {
+        self.accessType == Some(AccessType::Permanent)
+            || *state == UserState::Emergency
+            || self.sharingExpiresAt.is_some()
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, ToSql, FromSql, PartialEq, Eq, Copy)]
 #[postgres(name = "command")]
 pub enum Command {
     RefreshTelemetry,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug, ToSql, FromSql, PartialEq, Eq, Copy)]
+#[derive(Serialize, Deserialize, Clone, Debug, ToSql, FromSql, PartialEq, Eq, Copy, JsonSchema)]
 #[postgres(name = "commandstate")]
 pub enum CommandState {
     Created,
@@ -84,6 +126,81 @@ impl Serialize for Location {
     }
 }
 
+/// Shape of the custom `Serialize` implementation of `Location`.
+#[derive(JsonSchema)]
+#[allow(dead_code)]
+struct LocationSchema {
+    data: String,
+    device_id: String,
+    timestamp: String,
+}
+
+impl JsonSchema for Location {
+    fn schema_name() -> String {
+        "Location".to_string()
+    }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        LocationSchema::json_schema(gen)
+    }
+}
+
+/// Optional filters of the historical location endpoints.
+#[derive(Debug, Clone, Default)]
+pub struct TelemetryFilter {
+    pub device_id: Option<String>,
+    /// Keep the first location of every device in each interval of this many seconds.
+    pub interval_seconds: Option<i64>,
+}
+
+#[allow(non_snake_case)]
+#[derive(Debug, Serialize, Clone, JsonSchema)]
+pub struct HistoricalLocationPage {
+    pub locations: Vec<Location>,
+    /// Send it as `cursor` to get the next page, None on the last page.
+    pub nextCursor: Option<String>,
+}
+
+/// Position in the telemetry of a user ordered by time and device,
+/// sent to clients as `<timestamp>_<device_id>`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TelemetryCursor {
+    pub timestamp: NaiveDateTime,
+    pub device_id: String,
+}
+
+/// Unlike DATE_FORMAT it keeps the microseconds stored by postgres,
+/// so rows in the same millisecond are not skipped.
+const CURSOR_DATE_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%.6fZ";
+
+impl TelemetryCursor {
+    pub fn from_str(cursor: &str) -> Result<TelemetryCursor, String> {
+        let mut parts = cursor.splitn(2, '_');
+        match (parts.next(), parts.next()) {
+            (Some(timestamp), Some(device_id)) if !device_id.is_empty() => {
+                NaiveDateTime::parse_from_str(timestamp, CURSOR_DATE_FORMAT)
+                    .map(|timestamp| TelemetryCursor {
+                        timestamp,
+                        device_id: device_id.to_string(),
+                    })
+                    .map_err(|err| err.to_string())
+            }
+            _ => Err(String::from("Invalid cursor")),
+        }
+    }
+}
+
+impl std::fmt::Display for TelemetryCursor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}_{}",
+            self.timestamp.format(CURSOR_DATE_FORMAT),
+            self.device_id
+        )
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct DateTimeRange {
     pub start_time: NaiveDateTime,
@@ -106,3 +223,25 @@ impl DateTimeRange {
         }
     }
 }
+
+/// How long device_telemetry rows are kept, see `controllers::retention`.
+#[derive(Debug, Clone)]
+pub struct RetentionPolicy {
+    /// Rows older than this are deleted unless they are close to an emergency.
+    pub retention_days: i64,
+    /// Rows close to an emergency are deleted after this.
+    pub emergency_retention_days: i64,
+    /// Rows captured this many hours before an emergency began or after it
+    /// ended are kept for `emergency_retention_days`.
+    pub emergency_window_hours: i32,
+    /// Daily partitions are created in advance for this many days.
+    pub partitions_ahead_days: i64,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct RetentionReport {
+    pub migrated_rows: u64,
+    pub created_partitions: usize,
+    pub dropped_partitions: Vec<String>,
+    pub deleted_rows: u64,
+}
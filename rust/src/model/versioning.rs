@@ -0,0 +1,57 @@
+use crate::constants::API_VERSION_HEADER;
+use rocket::Request;
+
+/// Versions are ordered, newer versions compare greater than older ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ApiVersion {
+    V1,
+    V2,
+}
+
+impl ApiVersion {
+    pub const ALL: [ApiVersion; 2] = [ApiVersion::V1, ApiVersion::V2];
+
+    pub fn number(&self) -> u32 {
+        match self {
+            ApiVersion::V1 => 1,
+            ApiVersion::V2 => 2,
+        }
+    }
+
+    pub fn from_number(number: u32) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .find(|version| version.number() == number)
+            .copied()
+    }
+
+    /// Path prefix where the routes of this version are mounted, ie: /v2
+    pub fn prefix(&self) -> String {
+        format!("/v{}", self.number())
+    }
+
+    /// Version from the first segment of the path, ie: /v2/invitations
+    pub fn from_path(path: &str) -> Option<Self> {
+        path.trim_start_matches('/')
+            .split('/')
+            .next()
+            .and_then(|segment| segment.strip_prefix('v'))
+            .and_then(|number| number.parse().ok())
+            .and_then(Self::from_number)
+    }
+
+    pub fn from_header(req: &Request) -> Option<Self> {
+        req.headers()
+            .get_one(API_VERSION_HEADER)
+            .and_then(|version| version.trim().parse().ok())
+            .and_then(Self::from_number)
+    }
+
+    /// The newest of the versions requested in the path and the header.
+    /// Clients on /v1 routes can opt in to v2 behaviors that do not depend on
+    /// the route, like error codes, by sending the header.
+    pub fn negotiate(req: &Request) -> Self {
+        let from_path = Self::from_path(req.uri().path()).unwrap_or(ApiVersion::V1);
+        Self::from_header(req).map_or(from_path, |from_header| from_header.max(from_path))
+    }
+}
@@ -0,0 +1,175 @@
+use super::middleware::{
+    catchers::catchers,
+    cors::options,
+    rate_limit::{limit_subject, RateLimit, SendVerificationCode, VerifyCode},
+    versioning::Deprecation,
+};
+use super::openapi::{mount_openapi, Operation};
+use super::validators::auth::{
+    assert_valid_login, assert_valid_registration, assert_valid_verification,
+    sanitize_email_or_phone,
+};
+use super::versioning::mount_versions;
+use crate::controllers::auth::{
+    create_verification, register_user, send_verification, verify_code,
+};
+use crate::lang::MessageIds;
+use crate::utils::sentry::log_api_err;
+use crate::{
+    db::{get_connection, get_pool},
+    lang::get_messages,
+    model::{
+        auth::Language,
+        requests::{LoginRequest, RegistrationRequest, VerificationRequest},
+        responses::{APIJsonResponse, APIResponse},
+        versioning::ApiVersion,
+        APIResult, Message, Storage, UserDetails,
+    },
+};
+use rocket::{Rocket, State};
+use rocket_contrib::json::Json;
+use std::env;
+
+fn verification_created(language: &Language) -> Message<String> {
+    Message {
+        message: get_messages(&language.0)
+            .get(&MessageIds::VerificationCreatedSuccessfully)
+            .unwrap_or(&"Ok")
+            .to_string(),
+    }
+}
+
+/// Create a new user and send the verification code
+/// to the email or phone number used to register.
+#[post("/register", format = "application/json", data = "<registration_req>")]
+fn register(
+    registration_req: Json<RegistrationRequest>,
+    language: Language,
+    state: State<Storage>,
+    _rate_limit: RateLimit<SendVerificationCode>,
+) -> APIResult<Message<String>> {
+    assert_valid_registration(&registration_req)
+        .and_then(|target| {
+            limit_subject::<SendVerificationCode>(
+                state.inner(),
+                &format!("target.{}", target.value()),
+            )?;
+            let mut conn = get_connection(state)?;
+            let code = register_user(&mut conn, &registration_req, &target, &language.0)?;
+            send_verification(&mut conn, &target, &code, &language.0)?;
+            Ok(Json(APIResponse {
+                success: true,
+                result: Some(verification_created(&language)),
+            }))
+        })
+        .map_err(|err| {
+            log_api_err("POST /v1/auth/register", &err, None);
+            APIJsonResponse::api_error_with_internal_error(err, &language.0)
+        })
+}
+
+/// Start a new verification for an existing user, unknown emails and phone
+/// numbers get the same response but nothing is sent.
+#[post("/login", format = "application/json", data = "<login_req>")]
+fn login(
+    login_req: Json<LoginRequest>,
+    language: Language,
+    state: State<Storage>,
+    _rate_limit: RateLimit<SendVerificationCode>,
+) -> APIResult<Message<String>> {
+    assert_valid_login(&login_req)
+        .and_then(|target| {
+            limit_subject::<SendVerificationCode>(
+                state.inner(),
+                &format!("target.{}", target.value()),
+            )?;
+            let mut conn = get_connection(state)?;
+            if let Some(code) = create_verification(&mut conn, &target, &login_req.publicKey)? {
+                send_verification(&mut conn, &target, &code, &language.0)?;
+            }
+            Ok(Json(APIResponse {
+                success: true,
+                result: Some(verification_created(&language)),
+            }))
+        })
+        .map_err(|err| {
+            log_api_err("POST /v1/auth/login", &err, None);
+            APIJsonResponse::api_error_with_internal_error(err, &language.0)
+        })
+}
+
+/// Verify the code sent to the user, register the device and public key.
+/// Returns the user details
+#[post(
+    "/verify/<email_or_phone>",
+    format = "application/json",
+    data = "<verification_req>"
+)]
+fn verify(
+    email_or_phone: String,
+    verification_req: Json<VerificationRequest>,
+    language: Language,
+    state: State<Storage>,
+    _rate_limit: RateLimit<VerifyCode>,
+) -> APIResult<UserDetails> {
+    assert_valid_verification(&verification_req)
+        .and_then(|_| sanitize_email_or_phone(&email_or_phone))
+        .and_then(|target| {
+            limit_subject::<VerifyCode>(state.inner(), &format!("target.{}", target.value()))?;
+            let mut conn = get_connection(state)?;
+            let details = verify_code(&mut conn, &target, &verification_req)?;
+            Ok(Json(APIResponse {
+                success: true,
+                result: Some(details),
+            }))
+        })
+        .map_err(|err| {
+            log_api_err("POST /v1/auth/verify", &err, None);
+            APIJsonResponse::api_error_with_internal_error(err, &language.0)
+        })
+}
+
+pub fn rocket() -> Rocket {
+    let database = get_pool();
+    let redis = redis::Client::open(env::var("REDIS_URL").expect("REDIS_URL must be set"))
+        .expect("Failed to open redis client.");
+    let routes = routes![register, login, verify];
+    let rocket = rocket::ignite()
+        .register(catchers())
+        .attach(options())
+        .attach(Deprecation::from_env())
+        .manage(Storage {
+            redis: Some(redis),
+            database,
+        });
+    let rocket = mount_versions(
+        rocket,
+        "/auth",
+        vec![(ApiVersion::V1, routes.clone()), (ApiVersion::V2, routes)],
+    );
+    mount_openapi(rocket, "Armore Auth API", &operations())
+}
+
+pub fn operations() -> Vec<Operation> {
+    vec![
+        Operation::new(
+            "register",
+            "Create a new user and send the verification code",
+        )
+        .handler(register)
+        .body::<RegistrationRequest>()
+        .returns::<Message<String>>()
+        .public(),
+        Operation::new("login", "Send a verification code to an existing user")
+            .handler(login)
+            .body::<LoginRequest>()
+            .returns::<Message<String>>()
+            .public(),
+        Operation::new("verify", "Verify the code and register the device")
+            .handler(verify)
+            .param::<String>("email_or_phone")
+            .body::<VerificationRequest>()
+            .returns::<UserDetails>()
+            .public(),
+    ]
+}
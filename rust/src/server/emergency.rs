@@ -1,19 +1,44 @@
-use super::middleware::{catchers::catchers, cors::options};
-use super::validators::friends::assert_not_friends;
+use super::middleware::{
+    catchers::catchers,
+    cors::options,
+    rate_limit::{FriendReport, RateLimit},
+    versioning::Deprecation,
+};
+use super::openapi::{mount_openapi, Operation};
+use super::validators::{
+    emergency_settings::assert_valid_emergency_settings, friends::assert_not_friends,
+};
+use super::versioning::mount_versions;
 use crate::{
-    controllers::emergency::{get_historical_location, update_user_state},
+    constants::{
+        HISTORICAL_EXPORT_MAX_PAGE_SIZE, HISTORICAL_EXPORT_PAGE_SIZE,
+        HISTORICAL_LOCATION_MAX_PAGE_SIZE, HISTORICAL_LOCATION_PAGE_SIZE,
+        HISTORICAL_MAX_INTERVAL_SECONDS,
+    },
+    controllers::emergency::{
+        export_historical_location, get_emergency_settings, get_export_verification_key,
+        get_historical_location, stream_historical_location, update_emergency_settings,
+        update_user_state, HistoricalLocationStream,
+    },
     db::{get_connection, get_pool},
+    lang::TranslationIds,
     model::{
         auth::AuthInfo,
-        emergency::{UpdateState, UserState},
-        responses::{APIJsonResponse, APIResponse},
-        telemetry::{DateTimeRange, Location},
+        emergency::{EmergencySettings, SignedExport, UpdateState, UserState},
+        responses::{APIInternalError, APIJsonResponse, APIResponse},
+        telemetry::{
+            DateTimeRange, HistoricalLocationPage, Location, TelemetryCursor, TelemetryFilter,
+        },
+        versioning::ApiVersion,
         APIResult, Message, Storage,
     },
 };
+use rocket::http::ContentType;
+use rocket::response::{content::Content, Stream};
 use rocket::{Rocket, State};
 use rocket_contrib::json::Json;
 use crate::utils::sentry::log_api_err;
+use std::env;
 
 #[post("/state", format = "application/json", data = "<update_state>")]
 fn update_state(
@@ -44,6 +69,7 @@ fn update_friend_state(
     auth_info: AuthInfo,
     username: String,
     storage: State<Storage>,
+    _rate_limit: RateLimit<FriendReport>,
 ) -> APIResult<Message<UserState>> {
     get_connection(storage)
         .and_then(|mut conn| {
@@ -66,28 +92,170 @@ fn update_friend_state(
         })
 }
 
-#[get("/<username>/telemetry?<start_time>&<end_time>")]
+fn bad_request(msg: TranslationIds, err: String) -> APIInternalError {
+    APIInternalError {
+        msg,
+        engineering_error: Some(err),
+    }
+}
+
+/// Parses the query of the historical location endpoints.
+fn history_query(
+    start_time: &str,
+    end_time: &str,
+    device_id: Option<String>,
+    interval: Option<i64>,
+    cursor: Option<&String>,
+) -> Result<(DateTimeRange, TelemetryFilter, Option<TelemetryCursor>), APIInternalError> {
+    let date_range = DateTimeRange::from_str(start_time, end_time)
+        .map_err(|err| bad_request(TranslationIds::BadRequest, err))?;
+    if let Some(interval) = interval {
+        if !(1..=HISTORICAL_MAX_INTERVAL_SECONDS).contains(&interval) {
+            return Err(bad_request(
+                TranslationIds::InvalidHistoricalInterval,
+                format!(
+                    "interval must be between 1 and {} seconds",
+                    HISTORICAL_MAX_INTERVAL_SECONDS
+                ),
+            ));
+        }
+    }
+    let cursor = cursor
+        .map(|cursor| TelemetryCursor::from_str(cursor))
+        .transpose()
+        .map_err(|err| bad_request(TranslationIds::BadRequest, err))?;
+    let filter = TelemetryFilter {
+        device_id,
+        interval_seconds: interval,
+    };
+    Ok((date_range, filter, cursor))
+}
+
+/// Every location of the range, v2 clients get them by pages.
+#[get("/<username>/telemetry?<start_time>&<end_time>&<device_id>&<interval>")]
 fn get_user_historical_location(
     username: String,
     start_time: String,
     end_time: String,
+    device_id: Option<String>,
+    interval: Option<i64>,
     auth_info: AuthInfo,
     storage: State<Storage>,
 ) -> APIResult<Vec<Location>> {
-    let date_range = DateTimeRange::from_str(&start_time, &end_time)
-        .map_err(|err| APIJsonResponse::api_error(err, None))?;
-    get_connection(storage)
-        .and_then(|mut conn| {
-            let historical =
-                get_historical_location(&mut conn, &auth_info, &username, &date_range)?;
+    history_query(&start_time, &end_time, device_id, interval, None)
+        .and_then(|(date_range, filter, _)| {
+            let historical = get_historical_location(
+                &mut get_connection(storage)?,
+                &auth_info,
+                &username,
+                &date_range,
+                &filter,
+                None,
+                None,
+            )?;
+            Ok(Json(APIResponse {
+                success: true,
+                result: Some(historical.locations),
+            }))
+        })
+        .map_err(|err| {
+            log_api_err(
+                &format!(
+                    "GET /v1/emergency/{}/telemetry?{}&{}",
+                    username, start_time, end_time
+                ),
+                &err,
+                Some(&auth_info),
+            );
+            APIJsonResponse::api_error_with_internal_error(err, &auth_info.language)
+        })
+}
+
+#[get("/<username>/telemetry?<start_time>&<end_time>&<device_id>&<interval>&<cursor>&<limit>")]
+#[allow(clippy::too_many_arguments)]
+fn get_user_historical_location_page(
+    username: String,
+    start_time: String,
+    end_time: String,
+    device_id: Option<String>,
+    interval: Option<i64>,
+    cursor: Option<String>,
+    limit: Option<i64>,
+    auth_info: AuthInfo,
+    storage: State<Storage>,
+) -> APIResult<HistoricalLocationPage> {
+    let limit = limit.unwrap_or(HISTORICAL_LOCATION_PAGE_SIZE);
+    history_query(&start_time, &end_time, device_id, interval, cursor.as_ref())
+        .and_then(|(date_range, filter, cursor)| {
+            if !(1..=HISTORICAL_LOCATION_MAX_PAGE_SIZE).contains(&limit) {
+                return Err(bad_request(
+                    TranslationIds::InvalidPageSize,
+                    format!(
+                        "limit must be between 1 and {}",
+                        HISTORICAL_LOCATION_MAX_PAGE_SIZE
+                    ),
+                ));
+            }
+            let page = get_historical_location(
+                &mut get_connection(storage)?,
+                &auth_info,
+                &username,
+                &date_range,
+                &filter,
+                cursor.as_ref(),
+                Some(limit),
+            )?;
             Ok(Json(APIResponse {
                 success: true,
-                result: Some(historical),
+                result: Some(page),
             }))
         })
         .map_err(|err| {
             log_api_err(
-                &format!("GET /v1/emergency/{}/telemetry?{}&{}", username, start_time, end_time),
+                &format!(
+                    "GET /v2/emergency/{}/telemetry?{}&{}",
+                    username, start_time, end_time
+                ),
+                &err,
+                Some(&auth_info),
+            );
+            APIJsonResponse::api_error_with_internal_error(err, &auth_info.language)
+        })
+}
+
+/// Every location of the range as newline delimited JSON, one Location per line.
+#[get("/<username>/telemetry/stream?<start_time>&<end_time>&<device_id>&<interval>&<cursor>")]
+#[allow(clippy::too_many_arguments)]
+fn stream_user_historical_location(
+    username: String,
+    start_time: String,
+    end_time: String,
+    device_id: Option<String>,
+    interval: Option<i64>,
+    cursor: Option<String>,
+    auth_info: AuthInfo,
+    storage: State<Storage>,
+) -> Result<Content<Stream<HistoricalLocationStream>>, APIJsonResponse> {
+    history_query(&start_time, &end_time, device_id, interval, cursor.as_ref())
+        .and_then(|(date_range, filter, cursor)| {
+            stream_historical_location(
+                &storage.database,
+                &auth_info,
+                &username,
+                &date_range,
+                &filter,
+                cursor.as_ref(),
+            )
+        })
+        .map(|stream| {
+            Content(
+                ContentType::new("application", "x-ndjson"),
+                Stream::from(stream),
+            )
+        })
+        .map_err(|err| {
+            log_api_err(
+                &format!("GET /v1/emergency/{}/telemetry/stream", username),
                 &err,
                 Some(&auth_info),
             );
@@ -95,22 +263,211 @@ fn get_user_historical_location(
         })
 }
 
+#[get("/<username>/telemetry/export?<start_time>&<end_time>&<cursor>&<limit>")]
+fn export_user_historical_location(
+    username: String,
+    start_time: String,
+    end_time: String,
+    cursor: Option<String>,
+    limit: Option<i64>,
+    auth_info: AuthInfo,
+    storage: State<Storage>,
+) -> APIResult<SignedExport> {
+    let limit = limit.unwrap_or(HISTORICAL_EXPORT_PAGE_SIZE);
+    history_query(&start_time, &end_time, None, None, cursor.as_ref())
+        .and_then(|(date_range, _, cursor)| {
+            if !(1..=HISTORICAL_EXPORT_MAX_PAGE_SIZE).contains(&limit) {
+                return Err(bad_request(
+                    TranslationIds::InvalidPageSize,
+                    format!(
+                        "limit must be between 1 and {}",
+                        HISTORICAL_EXPORT_MAX_PAGE_SIZE
+                    ),
+                ));
+            }
+            let export = export_historical_location(
+                &mut get_connection(storage)?,
+                &auth_info,
+                &username,
+                &date_range,
+                cursor.as_ref(),
+                limit,
+            )?;
+            Ok(Json(APIResponse {
+                success: true,
+                result: Some(export),
+            }))
+        })
+        .map_err(|err| {
+            log_api_err(
+                &format!("GET /v1/emergency/{}/telemetry/export", username),
+                &err,
+                Some(&auth_info),
+            );
+            APIJsonResponse::api_error_with_internal_error(err, &auth_info.language)
+        })
+}
+
+#[get("/export/key")]
+fn get_export_key(auth_info: AuthInfo) -> APIResult<Message<String>> {
+    get_export_verification_key()
+        .map(|key| {
+            Json(APIResponse {
+                success: true,
+                result: Some(Message { message: key }),
+            })
+        })
+        .map_err(|err| {
+            log_api_err("GET /v1/emergency/export/key", &err, Some(&auth_info));
+            APIJsonResponse::api_error_with_internal_error(err, &auth_info.language)
+        })
+}
+
+#[get("/settings")]
+fn get_settings(auth_info: AuthInfo, storage: State<Storage>) -> APIResult<EmergencySettings> {
+    get_connection(storage)
+        .and_then(|mut conn| {
+            let settings = get_emergency_settings(&mut conn, &auth_info.username)?;
+            Ok(Json(APIResponse {
+                success: true,
+                result: Some(settings),
+            }))
+        })
+        .map_err(|err| {
+            log_api_err("GET /v1/emergency/settings", &err, Some(&auth_info));
+            APIJsonResponse::api_error_with_internal_error(err, &auth_info.language)
+        })
+}
+
+#[put("/settings", format = "application/json", data = "<settings>")]
+fn update_settings(
+    auth_info: AuthInfo,
+    settings: Json<EmergencySettings>,
+    storage: State<Storage>,
+) -> APIResult<EmergencySettings> {
+    let settings = settings.into_inner();
+    assert_valid_emergency_settings(&settings)
+        .and_then(|_| get_connection(storage))
+        .and_then(|mut conn| {
+            update_emergency_settings(&mut conn, &auth_info.username, &settings)?;
+            Ok(Json(APIResponse {
+                success: true,
+                result: Some(settings),
+            }))
+        })
+        .map_err(|err| {
+            log_api_err("PUT /v1/emergency/settings", &err, Some(&auth_info));
+            APIJsonResponse::api_error_with_internal_error(err, &auth_info.language)
+        })
+}
+
 pub fn rocket() -> Rocket {
     let database = get_pool();
+    let redis = redis::Client::open(env::var("REDIS_URL").expect("REDIS_URL must be set"))
+        .expect("Failed to open redis client.");
 
-    rocket::ignite()
-        .mount(
-            "/v1/emergency",
-            routes![
-                update_state,
-                get_user_historical_location,
-                update_friend_state
-            ],
-        )
+    let routes = routes![
+        update_state,
+        stream_user_historical_location,
+        export_user_historical_location,
+        get_export_key,
+        get_settings,
+        update_settings,
+        update_friend_state
+    ];
+    let v1_routes = [routes.clone(), routes![get_user_historical_location]].concat();
+    let v2_routes = [routes, routes![get_user_historical_location_page]].concat();
+    let rocket = rocket::ignite()
         .register(catchers())
         .attach(options())
+        .attach(Deprecation::from_env())
         .manage(Storage {
-            redis: None,
+            redis: Some(redis),
             database,
-        })
+        });
+    let rocket = mount_versions(
+        rocket,
+        "/emergency",
+        vec![(ApiVersion::V1, v1_routes), (ApiVersion::V2, v2_routes)],
+    );
+    mount_openapi(rocket, "Armore Emergency API", &operations())
+}
+
+pub fn operations() -> Vec<Operation> {
+    vec![
+        Operation::new("update_state", "Update the state of the user")
+            .handler(update_state)
+            .body::<UpdateState>()
+            .returns::<Message<UserState>>(),
+        Operation::new(
+            "get_user_historical_location",
+            "Locations of a friend in emergency, dates use the format %Y-%m-%dT%H:%M:%S%.3fZ",
+        )
+        .handler(get_user_historical_location)
+        .param::<String>("username")
+        .param::<String>("start_time")
+        .param::<String>("end_time")
+        .param::<Option<String>>("device_id")
+        .param::<Option<i64>>("interval")
+        .returns::<Vec<Location>>(),
+        Operation::new(
+            "get_user_historical_location_page",
+            "Page of the locations of a friend in emergency, optionally of one device and one location per interval",
+        )
+        .handler(get_user_historical_location_page)
+        .param::<String>("username")
+        .param::<String>("start_time")
+        .param::<String>("end_time")
+        .param::<Option<String>>("device_id")
+        .param::<Option<i64>>("interval")
+        .param::<Option<String>>("cursor")
+        .param::<Option<i64>>("limit")
+        .returns::<HistoricalLocationPage>(),
+        Operation::new(
+            "stream_user_historical_location",
+            "Locations of a friend in emergency as newline delimited JSON",
+        )
+        .handler(stream_user_historical_location)
+        .param::<String>("username")
+        .param::<String>("start_time")
+        .param::<String>("end_time")
+        .param::<Option<String>>("device_id")
+        .param::<Option<i64>>("interval")
+        .param::<Option<String>>("cursor")
+        .streams::<Location, HistoricalLocationStream>(),
+        Operation::new(
+            "export_user_historical_location",
+            "Signed page of the locations, devices and state transitions of a friend in emergency",
+        )
+        .handler(export_user_historical_location)
+        .param::<String>("username")
+        .param::<String>("start_time")
+        .param::<String>("end_time")
+        .param::<Option<String>>("cursor")
+        .param::<Option<i64>>("limit")
+        .returns::<SignedExport>(),
+        Operation::new(
+            "get_export_key",
+            "Public key that verifies the signature of the exports",
+        )
+        .handler(get_export_key)
+        .returns::<Message<String>>(),
+        Operation::new(
+            "get_settings",
+            "Location history that followers can read around an emergency and the alerts of the user",
+        )
+        .handler(get_settings)
+        .returns::<EmergencySettings>(),
+        Operation::new(
+            "update_settings",
+            "Update the location history that followers can read and the alerts of the user",
+        )
+        .handler(update_settings)
+        .body::<EmergencySettings>()
+        .returns::<EmergencySettings>(),
+        Operation::new("update_friend_state", "Report that a friend is in an emergency")
+            .handler(update_friend_state)
+            .param::<String>("username")
+            .returns::<Message<UserState>>(),
+    ]
 }
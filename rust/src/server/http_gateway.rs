@@ -1,27 +1,62 @@
-use super::middleware::{catchers::catchers, cors};
-use crate::controllers::devices::{get_device_by_id, update_device_settings};
+use super::middleware::{
+    catchers::catchers,
+    cors,
+    rate_limit::{ForceRefresh, RateLimit},
+    versioning::Deprecation,
+};
+use super::openapi::{mount_openapi, Operation};
+use super::validators::devices::assert_valid_push_token;
+use super::validators::profile::{assert_valid_profile_picture, assert_valid_profile_update};
+use super::validators::telemetry::assert_valid_telemetry_request;
+use super::versioning::mount_versions;
+use crate::constants::{
+    DEVICE_SETTINGS_HISTORY_MAX_PAGE_SIZE, DEVICE_SETTINGS_HISTORY_PAGE_SIZE,
+    FRIENDS_MAX_PAGE_SIZE, FRIENDS_PAGE_SIZE, LOCATION_SHARING_MAX_MINUTES,
+    PROFILE_PICTURE_MAX_BYTES,
+};
+use crate::controllers::devices::{
+    clear_push_token, get_device_by_id, get_device_settings_history, update_device_settings,
+};
+use crate::controllers::followers::{
+    get_friends, share_location, stop_sharing_location, update_follower_access,
+};
+use crate::controllers::health::{
+    get_device_health, send_low_battery_alert, send_tracking_degraded_alert,
+};
+use crate::controllers::profile::{
+    delete_account, export_telemetry_page, export_user_data, update_profile, update_profile_picture,
+};
 use crate::controllers::telemetry::{
-    close_command, force_refresh_telemetry_internal, get_connections, get_follower_keys,
-    get_user_state, store_telemetry, username_has_follower,
+    abort_telemetry_upload, begin_telemetry_upload, close_command, complete_telemetry_upload,
+    force_refresh_telemetry_internal, get_connections, get_follower_keys, get_user_state,
+    store_telemetry, telemetry_upload_key, username_has_follower, TelemetryUpload,
 };
 use crate::db::get_pool;
+use crate::lang::TranslationIds;
 use crate::messaging::{get_rabbitmq_uri, send_ws_message};
 use crate::model::{
     auth::AuthInfo,
-    emergency::AccessType,
-    emergency::UserState,
-    requests::{DeviceUpdateRequest, TelemetryRequest},
+    devices::{DeviceHealth, DeviceSettingsSnapshot},
+    emergency::{FollowerAccess, LocationSharing, LocationSharingRequest},
+    friends::{FriendsFilter, FriendsPage},
+    profile::{ProfilePicture, TelemetryExportPage, UserDataExport},
+    requests::{DeviceUpdateRequest, ProfileUpdateRequest, TelemetryRequest},
     responses::{
-        APIJsonResponse, APIResponse, CommandResponse, DeviceUpdateResponse, TelemetryResponse,
+        APIJsonResponse, APIResponse, CommandResponse, DeviceUpdateResponse,
+        Errors::APIInternalError, TelemetryResponse,
     },
-    telemetry::{CommandState, FollowerKey},
-    Storage,
+    telemetry::{CommandState, FollowerKey, TelemetryCursor},
+    versioning::ApiVersion,
+    Message, PostgresConnection, Storage, UserDetails,
 };
+use crate::storage::{picture_storage_from_env, PictureStore};
 use crate::utils::sentry::log_api_err;
 use amiquip::Connection;
-use rocket::{Rocket, State};
+use rocket::http::ContentType;
+use rocket::{Data, Rocket, State};
 use rocket_contrib::json::Json;
 use std::env;
+use std::io::Read;
 
 #[allow(unused_must_use)]
 #[post(
@@ -45,15 +80,74 @@ fn post_telemetry(
         .get_connection()
         .expect("Unable to get redis connection from state.");
 
-    store_telemetry(&telemetry_request, &auth_info, &mut client, &mut redis).map_err(|err| {
-        log_api_err("POST /v1/telemetry", &err, Some(&auth_info));
+    let upload_key = assert_valid_telemetry_request(&telemetry_request)
+        .and_then(|_| {
+            let key = telemetry_upload_key(&telemetry_request, &auth_info);
+            let upload = match &key {
+                Some(key) => begin_telemetry_upload(&mut redis, key)?,
+                None => TelemetryUpload::New,
+            };
+            Ok((key, upload))
+        })
+        .map_err(|err| {
+            log_api_err("POST /v1/telemetry", &err, Some(&auth_info));
+            APIJsonResponse::api_error_with_internal_error(err, &auth_info.language)
+        })?;
+
+    let result = match upload_key {
+        (_, TelemetryUpload::Completed(result)) => Ok(result),
+        // The first upload has not finished, skip the insert and only return the friends.
+        (_, TelemetryUpload::InProgress) if telemetry_request.returnFriendLocations => {
+            get_connections(&auth_info.username, &mut client, &mut redis)
+                .map(Some)
+                .map_err(|err| {
+                    log_api_err("POST /v1/telemetry", &err, Some(&auth_info));
+                    APIJsonResponse::api_error_with_internal_error(err, &auth_info.language)
+                })
+        }
+        (_, TelemetryUpload::InProgress) => Ok(None),
+        (key, TelemetryUpload::New) => {
+            let result = ingest_telemetry(&telemetry_request, &auth_info, &mut client, &mut redis);
+            if let Some(key) = key {
+                let _ = match &result {
+                    Ok(response) => complete_telemetry_upload(&mut redis, &key, response),
+                    Err(_) => abort_telemetry_upload(&mut redis, &key),
+                }
+                .map_err(|err| err.log_err("Unable to update the telemetry upload"));
+            }
+            result
+        }
+    }?;
+
+    Ok(Json(APIResponse {
+        success: true,
+        result,
+    }))
+}
+
+/// Stores the telemetry and notifies the followers.
+#[allow(unused_must_use)]
+fn ingest_telemetry(
+    telemetry_request: &Json<TelemetryRequest>,
+    auth_info: &AuthInfo,
+    client: &mut PostgresConnection,
+    redis: &mut redis::Connection,
+) -> Result<Option<TelemetryResponse>, APIJsonResponse> {
+    // Backfilled locations that are older than the last known one are stored but not published.
+    let stored = store_telemetry(telemetry_request, auth_info, client, redis).map_err(|err| {
+        log_api_err("POST /v1/telemetry", &err, Some(auth_info));
         APIJsonResponse::api_error_with_internal_error(err, &auth_info.language)
     })?;
 
+    if let Some(level) = stored.low_battery_alert {
+        send_low_battery_alert(client, redis, auth_info, level)
+            .map_err(|err| err.log_err("Unable to send the low battery alert"));
+    }
+
     // This request was force pushed due to a ForcePush Request, store the result.
     if telemetry_request.correlationId.is_some() {
         let error = close_command(
-            &mut client,
+            client,
             &CommandState::Completed,
             &telemetry_request.correlationId.as_ref().unwrap(),
         )
@@ -64,26 +158,22 @@ fn post_telemetry(
     }
 
     // TODO: send message to geofence service.
-    let all_friends =
-        get_connections(&auth_info.username, &mut client, &mut redis).map_err(|err| {
-            log_api_err("POST /v1/telemetry", &err, Some(&auth_info));
-            APIJsonResponse::api_error_with_internal_error(err, &auth_info.language)
-        })?;
+    let all_friends = get_connections(&auth_info.username, client, redis).map_err(|err| {
+        log_api_err("POST /v1/telemetry", &err, Some(auth_info));
+        APIJsonResponse::api_error_with_internal_error(err, &auth_info.language)
+    })?;
 
-    let user_state = get_user_state(&auth_info.username, &mut client).map_err(|err| {
-        log_api_err("POST /v1/telemetry", &err, Some(&auth_info));
+    let user_state = get_user_state(&auth_info.username, client).map_err(|err| {
+        log_api_err("POST /v1/telemetry", &err, Some(auth_info));
         APIJsonResponse::api_error_with_internal_error(err, &auth_info.language)
     })?;
 
     match (user_state, Connection::insecure_open(&get_rabbitmq_uri())) {
         (Some(state), Ok(mut connection)) => {
-            for telemetry in &telemetry_request.telemetry {
+            for telemetry in &stored.latest {
                 let follower = all_friends.followers.get(&telemetry.recipientUsername);
-                if follower.is_some() {
-                    let access_type = follower.unwrap().accessType.unwrap();
-                    if access_type == AccessType::Permanent || state == UserState::Emergency {
-                        send_ws_message(&mut connection, telemetry, &auth_info.username);
-                    }
+                if follower.is_some() && follower.unwrap().can_see_location(&state) {
+                    send_ws_message(&mut connection, telemetry, &auth_info.username);
                 }
             }
             connection.close();
@@ -93,14 +183,11 @@ fn post_telemetry(
         }
     }
 
-    Ok(Json(APIResponse {
-        success: true,
-        result: if telemetry_request.returnFriendLocations {
-            Some(all_friends)
-        } else {
-            None
-        },
-    }))
+    Ok(if telemetry_request.returnFriendLocations {
+        Some(all_friends)
+    } else {
+        None
+    })
 }
 
 #[get("/followers/keys")]
@@ -124,11 +211,256 @@ fn get_keys(
     }))
 }
 
+/// Shared by the friends, followers and following lists.
+fn friends_page(
+    route: &str,
+    filter: FriendsFilter,
+    search: Option<String>,
+    cursor: Option<String>,
+    limit: Option<i64>,
+    state: State<Storage>,
+    auth_info: AuthInfo,
+) -> Result<Json<APIResponse<FriendsPage>>, APIJsonResponse> {
+    let limit = limit.unwrap_or(FRIENDS_PAGE_SIZE);
+    if !(1..=FRIENDS_MAX_PAGE_SIZE).contains(&limit) {
+        let err = APIInternalError {
+            msg: TranslationIds::InvalidPageSize,
+            engineering_error: Some(format!(
+                "limit must be between 1 and {}",
+                FRIENDS_MAX_PAGE_SIZE
+            )),
+        };
+        return Err(APIJsonResponse::api_error_with_internal_error(
+            err,
+            &auth_info.language,
+        ));
+    }
+    let mut client = state
+        .database
+        .get()
+        .expect("Unable to get database connection from state.");
+    let mut redis = state
+        .redis
+        .clone()
+        .expect("Unable to clone redis from state.")
+        .get_connection()
+        .expect("Unable to get redis connection from state.");
+
+    get_friends(
+        &mut client,
+        &mut redis,
+        &auth_info.username,
+        filter,
+        search.as_deref(),
+        cursor.as_ref(),
+        limit,
+    )
+    .map(|page| {
+        Json(APIResponse {
+            success: true,
+            result: page,
+        })
+    })
+    .map_err(|err| {
+        log_api_err(route, &err, Some(&auth_info));
+        APIJsonResponse::api_error_with_internal_error(err, &auth_info.language)
+    })
+}
+
+#[get("/friends?<search>&<cursor>&<limit>")]
+fn get_user_friends(
+    search: Option<String>,
+    cursor: Option<String>,
+    limit: Option<i64>,
+    state: State<Storage>,
+    auth_info: AuthInfo,
+) -> Result<Json<APIResponse<FriendsPage>>, APIJsonResponse> {
+    friends_page(
+        "GET /v1/friends",
+        FriendsFilter::All,
+        search,
+        cursor,
+        limit,
+        state,
+        auth_info,
+    )
+}
+
+#[get("/followers?<search>&<cursor>&<limit>")]
+fn get_user_followers(
+    search: Option<String>,
+    cursor: Option<String>,
+    limit: Option<i64>,
+    state: State<Storage>,
+    auth_info: AuthInfo,
+) -> Result<Json<APIResponse<FriendsPage>>, APIJsonResponse> {
+    friends_page(
+        "GET /v1/followers",
+        FriendsFilter::Followers,
+        search,
+        cursor,
+        limit,
+        state,
+        auth_info,
+    )
+}
+
+#[get("/following?<search>&<cursor>&<limit>")]
+fn get_user_following(
+    search: Option<String>,
+    cursor: Option<String>,
+    limit: Option<i64>,
+    state: State<Storage>,
+    auth_info: AuthInfo,
+) -> Result<Json<APIResponse<FriendsPage>>, APIJsonResponse> {
+    friends_page(
+        "GET /v1/following",
+        FriendsFilter::Following,
+        search,
+        cursor,
+        limit,
+        state,
+        auth_info,
+    )
+}
+
+#[put(
+    "/followers/<follower>/access",
+    format = "application/json",
+    data = "<access>"
+)]
+fn update_access(
+    follower: String,
+    access: Json<FollowerAccess>,
+    state: State<Storage>,
+    auth_info: AuthInfo,
+) -> Result<Json<APIResponse<FollowerAccess>>, APIJsonResponse> {
+    let mut client = state
+        .database
+        .get()
+        .expect("Unable to get database connection from state.");
+    let mut redis = state
+        .redis
+        .clone()
+        .expect("Unable to clone redis from state.")
+        .get_connection()
+        .expect("Unable to get redis connection from state.");
+
+    update_follower_access(
+        &mut client,
+        &mut redis,
+        &auth_info.username,
+        &follower,
+        &access,
+    )
+    .map(|_| {
+        Json(APIResponse {
+            success: true,
+            result: access.into_inner(),
+        })
+    })
+    .map_err(|err| {
+        log_api_err(
+            &format!("PUT /v1/followers/{}/access", follower),
+            &err,
+            Some(&auth_info),
+        );
+        APIJsonResponse::api_error_with_internal_error(err, &auth_info.language)
+    })
+}
+
+/// Shares the location with an EmergencyOnly follower for a while, e.g. for a night out.
+#[put(
+    "/followers/<follower>/sharing",
+    format = "application/json",
+    data = "<sharing>"
+)]
+fn start_location_sharing(
+    follower: String,
+    sharing: Json<LocationSharingRequest>,
+    state: State<Storage>,
+    auth_info: AuthInfo,
+) -> Result<Json<APIResponse<LocationSharing>>, APIJsonResponse> {
+    if !(1..=LOCATION_SHARING_MAX_MINUTES).contains(&sharing.durationMinutes) {
+        let err = APIInternalError {
+            msg: TranslationIds::InvalidLocationSharingDuration,
+            engineering_error: Some(format!(
+                "durationMinutes must be between 1 and {}",
+                LOCATION_SHARING_MAX_MINUTES
+            )),
+        };
+        return Err(APIJsonResponse::api_error_with_internal_error(
+            err,
+            &auth_info.language,
+        ));
+    }
+    let mut client = state
+        .database
+        .get()
+        .expect("Unable to get database connection from state.");
+
+    share_location(
+        &mut client,
+        &auth_info.username,
+        &follower,
+        sharing.durationMinutes,
+    )
+    .map(|sharing| {
+        Json(APIResponse {
+            success: true,
+            result: sharing,
+        })
+    })
+    .map_err(|err| {
+        log_api_err(
+            &format!("PUT /v1/followers/{}/sharing", follower),
+            &err,
+            Some(&auth_info),
+        );
+        APIJsonResponse::api_error_with_internal_error(err, &auth_info.language)
+    })
+}
+
+#[delete("/followers/<follower>/sharing")]
+fn stop_location_sharing(
+    follower: String,
+    state: State<Storage>,
+    auth_info: AuthInfo,
+) -> Result<Json<APIResponse<FollowerAccess>>, APIJsonResponse> {
+    let mut client = state
+        .database
+        .get()
+        .expect("Unable to get database connection from state.");
+    let mut redis = state
+        .redis
+        .clone()
+        .expect("Unable to clone redis from state.")
+        .get_connection()
+        .expect("Unable to get redis connection from state.");
+
+    stop_sharing_location(&mut client, &mut redis, &auth_info.username, &follower)
+        .map(|access| {
+            Json(APIResponse {
+                success: true,
+                result: access,
+            })
+        })
+        .map_err(|err| {
+            log_api_err(
+                &format!("DELETE /v1/followers/{}/sharing", follower),
+                &err,
+                Some(&auth_info),
+            );
+            APIJsonResponse::api_error_with_internal_error(err, &auth_info.language)
+        })
+}
+
 #[get("/telemetry/<recipient_username>")]
 fn force_refresh_telemetry(
     recipient_username: String,
     state: State<Storage>,
     auth_info: AuthInfo,
+    _rate_limit: RateLimit<ForceRefresh>,
 ) -> Result<Json<APIResponse<CommandResponse>>, APIJsonResponse> {
     let mut client = state
         .database
@@ -178,6 +510,34 @@ fn force_refresh_telemetry(
     }))
 }
 
+#[get("/telemetry/<username>/health")]
+fn get_user_device_health(
+    username: String,
+    state: State<Storage>,
+    auth_info: AuthInfo,
+) -> Result<Json<APIResponse<Vec<DeviceHealth>>>, APIJsonResponse> {
+    let mut client = state
+        .database
+        .get()
+        .expect("Unable to get database connection from state.");
+
+    get_device_health(&mut client, &username, &auth_info.username)
+        .map(|health| {
+            Json(APIResponse {
+                success: true,
+                result: health,
+            })
+        })
+        .map_err(|err| {
+            log_api_err(
+                &format!("GET /v1/telemetry/{}/health", &username),
+                &err,
+                Some(&auth_info),
+            );
+            APIJsonResponse::api_error_with_internal_error(err, &auth_info.language)
+        })
+}
+
 #[post(
     "/device/settings",
     data = "<device_update_request>",
@@ -198,6 +558,7 @@ fn update_device(
             log_api_err("POST /v1/device/settings", &error, Some(&auth_info));
             APIJsonResponse::api_error_with_internal_error(error, &auth_info.language)
         })?;
+    let previous_device = device_update.clone();
 
     device_update.locationPermissionState = device_update_request.0.locationPermissionState;
     device_update.isBackgroundRefreshOn = device_update_request.0.isBackgroundRefreshOn;
@@ -206,9 +567,21 @@ fn update_device(
     device_update.isPowerSaveModeOn = device_update_request.0.isPowerSaveModeOn;
     device_update.osVersion = device_update_request.0.osVersion.clone();
     device_update.appVersion = device_update_request.0.appVersion.clone();
+    if let Some(push_token) = &device_update_request.0.pushToken {
+        assert_valid_push_token(push_token, &device_update.os).map_err(|error| {
+            log_api_err("POST /v1/device/settings", &error, Some(&auth_info));
+            APIJsonResponse::api_error_with_internal_error(error, &auth_info.language)
+        })?;
+        device_update.pushToken = Some(push_token.clone());
+    }
 
+    let regressions = device_update.tracking_regressions(&previous_device);
     update_device_settings(device_update, &mut client)
         .and_then(|u| {
+            if !regressions.is_empty() {
+                let _ = send_tracking_degraded_alert(&mut client, &auth_info.username)
+                    .map_err(|err| err.log_err("Unable to send the tracking degraded alert"));
+            }
             Ok(Json(APIResponse {
                 success: true,
                 result: DeviceUpdateResponse { updated: u },
@@ -220,6 +593,232 @@ fn update_device(
         })
 }
 
+#[delete("/device/push_token")]
+fn delete_push_token(
+    state: State<Storage>,
+    auth_info: AuthInfo,
+) -> Result<Json<APIResponse<DeviceUpdateResponse>>, APIJsonResponse> {
+    let mut client = state
+        .database
+        .get()
+        .expect("Unable to get database connection from state.");
+
+    clear_push_token(&auth_info.deviceId, &mut client)
+        .map(|u| {
+            Json(APIResponse {
+                success: true,
+                result: DeviceUpdateResponse { updated: u },
+            })
+        })
+        .map_err(|error| {
+            log_api_err("DELETE /v1/device/push_token", &error, Some(&auth_info));
+            APIJsonResponse::api_error_with_internal_error(error, &auth_info.language)
+        })
+}
+
+#[get("/device/settings/history?<device_id>&<limit>")]
+fn get_user_device_settings_history(
+    device_id: Option<String>,
+    limit: Option<i64>,
+    state: State<Storage>,
+    auth_info: AuthInfo,
+) -> Result<Json<APIResponse<Vec<DeviceSettingsSnapshot>>>, APIJsonResponse> {
+    let limit = limit.unwrap_or(DEVICE_SETTINGS_HISTORY_PAGE_SIZE);
+    if !(1..=DEVICE_SETTINGS_HISTORY_MAX_PAGE_SIZE).contains(&limit) {
+        let err = APIInternalError {
+            msg: TranslationIds::InvalidPageSize,
+            engineering_error: Some(format!(
+                "limit must be between 1 and {}",
+                DEVICE_SETTINGS_HISTORY_MAX_PAGE_SIZE
+            )),
+        };
+        return Err(APIJsonResponse::api_error_with_internal_error(
+            err,
+            &auth_info.language,
+        ));
+    }
+    let mut client = state
+        .database
+        .get()
+        .expect("Unable to get database connection from state.");
+    let device_id = device_id.unwrap_or_else(|| auth_info.deviceId.clone());
+
+    get_device_settings_history(&mut client, &auth_info.username, &device_id, limit)
+        .map(|history| {
+            Json(APIResponse {
+                success: true,
+                result: history,
+            })
+        })
+        .map_err(|err| {
+            log_api_err("GET /v1/device/settings/history", &err, Some(&auth_info));
+            APIJsonResponse::api_error_with_internal_error(err, &auth_info.language)
+        })
+}
+
+#[put("/profile", format = "application/json", data = "<profile>")]
+fn update_user_profile(
+    profile: Json<ProfileUpdateRequest>,
+    state: State<Storage>,
+    auth_info: AuthInfo,
+) -> Result<Json<APIResponse<UserDetails>>, APIJsonResponse> {
+    let mut client = state
+        .database
+        .get()
+        .expect("Unable to get database connection from state.");
+
+    assert_valid_profile_update(&profile)
+        .and_then(|_| update_profile(&mut client, &auth_info.username, &profile))
+        .map(|details| {
+            Json(APIResponse {
+                success: true,
+                result: details,
+            })
+        })
+        .map_err(|err| {
+            log_api_err("PUT /v1/profile", &err, Some(&auth_info));
+            APIJsonResponse::api_error_with_internal_error(err, &auth_info.language)
+        })
+}
+
+/// Uploads a JPEG or PNG picture, sent as the raw body, as the profile picture.
+#[put("/profile/picture", data = "<picture>")]
+fn update_user_profile_picture(
+    picture: Data,
+    content_type: Option<&ContentType>,
+    state: State<Storage>,
+    pictures: State<PictureStore>,
+    auth_info: AuthInfo,
+) -> Result<Json<APIResponse<ProfilePicture>>, APIJsonResponse> {
+    let mut client = state
+        .database
+        .get()
+        .expect("Unable to get database connection from state.");
+    let content_type = content_type.map(|content_type| {
+        format!("{}/{}", content_type.top(), content_type.sub()).to_lowercase()
+    });
+    // One byte over the limit is enough to reject it.
+    let mut bytes = Vec::new();
+    picture
+        .open()
+        .take(PROFILE_PICTURE_MAX_BYTES + 1)
+        .read_to_end(&mut bytes)
+        .map_err(APIInternalError::backend_issue)
+        .and_then(|_| assert_valid_profile_picture(content_type.as_deref(), &bytes))
+        .and_then(|format| {
+            update_profile_picture(
+                &mut client,
+                pictures.inner().as_ref(),
+                &auth_info.username,
+                format,
+                &bytes,
+            )
+        })
+        .map(|picture| {
+            Json(APIResponse {
+                success: true,
+                result: picture,
+            })
+        })
+        .map_err(|err| {
+            log_api_err("PUT /v1/profile/picture", &err, Some(&auth_info));
+            APIJsonResponse::api_error_with_internal_error(err, &auth_info.language)
+        })
+}
+
+/// Deletes the account of the user and everything stored about them.
+#[delete("/account")]
+fn delete_user_account(
+    state: State<Storage>,
+    pictures: State<PictureStore>,
+    auth_info: AuthInfo,
+) -> Result<Json<APIResponse<Message<String>>>, APIJsonResponse> {
+    let mut client = state
+        .database
+        .get()
+        .expect("Unable to get database connection from state.");
+    let mut redis = state
+        .redis
+        .clone()
+        .expect("Unable to clone redis from state.")
+        .get_connection()
+        .expect("Unable to get redis connection from state.");
+
+    delete_account(
+        &mut client,
+        &mut redis,
+        pictures.inner().as_ref(),
+        &auth_info.username,
+    )
+    .map(|_| {
+        Json(APIResponse {
+            success: true,
+            result: Message {
+                message: auth_info.username.clone(),
+            },
+        })
+    })
+    .map_err(|err| {
+        log_api_err("DELETE /v1/account", &err, Some(&auth_info));
+        APIJsonResponse::api_error_with_internal_error(err, &auth_info.language)
+    })
+}
+
+/// Everything stored about the user in a machine-readable archive. The daily
+/// cap on exports is enforced by export_user_data.
+#[get("/account/export")]
+fn export_user_account(
+    state: State<Storage>,
+    auth_info: AuthInfo,
+) -> Result<Json<APIResponse<UserDataExport>>, APIJsonResponse> {
+    let mut client = state
+        .database
+        .get()
+        .expect("Unable to get database connection from state.");
+
+    export_user_data(&mut client, &auth_info)
+        .map(|export| {
+            Json(APIResponse {
+                success: true,
+                result: export,
+            })
+        })
+        .map_err(|err| {
+            log_api_err("GET /v1/account/export", &err, Some(&auth_info));
+            APIJsonResponse::api_error_with_internal_error(err, &auth_info.language)
+        })
+}
+
+/// Telemetry of the data export after the cursor of the previous page.
+#[get("/account/export/telemetry?<cursor>")]
+fn export_user_account_telemetry(
+    cursor: String,
+    state: State<Storage>,
+    auth_info: AuthInfo,
+) -> Result<Json<APIResponse<TelemetryExportPage>>, APIJsonResponse> {
+    let mut client = state
+        .database
+        .get()
+        .expect("Unable to get database connection from state.");
+
+    TelemetryCursor::from_str(&cursor)
+        .map_err(|err| APIInternalError {
+            msg: TranslationIds::BadRequest,
+            engineering_error: Some(err),
+        })
+        .and_then(|cursor| export_telemetry_page(&mut *client, &auth_info.username, Some(&cursor)))
+        .map(|page| {
+            Json(APIResponse {
+                success: true,
+                result: page,
+            })
+        })
+        .map_err(|err| {
+            log_api_err("GET /v1/account/export/telemetry", &err, Some(&auth_info));
+            APIJsonResponse::api_error_with_internal_error(err, &auth_info.language)
+        })
+}
+
 pub fn rocket() -> Rocket {
     let database = get_pool();
     let redis = redis::Client::open(env::var("REDIS_URL").expect("REDIS_URL must be set"))
@@ -228,17 +827,127 @@ pub fn rocket() -> Rocket {
         redis: Some(redis),
         database,
     };
-    rocket::ignite()
-        .mount(
-            "/v1",
-            routes![
-                post_telemetry,
-                get_keys,
-                force_refresh_telemetry,
-                update_device
-            ],
-        )
+    let routes = routes![
+        post_telemetry,
+        get_keys,
+        get_user_friends,
+        get_user_followers,
+        get_user_following,
+        update_access,
+        start_location_sharing,
+        stop_location_sharing,
+        force_refresh_telemetry,
+        get_user_device_health,
+        get_user_device_settings_history,
+        update_device,
+        delete_push_token,
+        update_user_profile,
+        update_user_profile_picture,
+        delete_user_account,
+        export_user_account,
+        export_user_account_telemetry
+    ];
+    let rocket = rocket::ignite()
         .register(catchers())
         .attach(cors::options())
+        .attach(Deprecation::from_env())
         .manage(storage)
+        .manage(picture_storage_from_env());
+    let rocket = mount_versions(
+        rocket,
+        "",
+        vec![(ApiVersion::V1, routes.clone()), (ApiVersion::V2, routes)],
+    );
+    mount_openapi(rocket, "Armore HTTP Gateway", &operations())
+}
+
+pub fn operations() -> Vec<Operation> {
+    vec![
+        Operation::new(
+            "post_telemetry",
+            "Store encrypted locations for the followers",
+        ).handler(post_telemetry)
+        .body::<TelemetryRequest>()
+        .returns_json::<APIResponse<Option<TelemetryResponse>>>(),
+        Operation::new("get_keys", "Public keys of the followers").handler(get_keys)
+            .returns_json::<APIResponse<Vec<FollowerKey>>>(),
+        Operation::new(
+            "get_user_friends",
+            "Page of the followers and followed users, optionally matching a search",
+        ).handler(get_user_friends).param::<Option<String>>("search").param::<Option<String>>("cursor").param::<Option<i64>>("limit")
+        .returns_json::<APIResponse<FriendsPage>>(),
+        Operation::new("get_user_followers", "Page of the followers of the user").handler(get_user_followers).param::<Option<String>>("search").param::<Option<String>>("cursor").param::<Option<i64>>("limit")
+            .returns_json::<APIResponse<FriendsPage>>(),
+        Operation::new("get_user_following", "Page of the users followed by the user").handler(get_user_following).param::<Option<String>>("search").param::<Option<String>>("cursor").param::<Option<i64>>("limit")
+            .returns_json::<APIResponse<FriendsPage>>(),
+        Operation::new(
+            "update_access",
+            "Choose when a follower gets the location and if they are an emergency contact",
+        ).handler(update_access).param::<String>("follower")
+        .body::<FollowerAccess>()
+        .returns_json::<APIResponse<FollowerAccess>>(),
+        Operation::new(
+            "start_location_sharing",
+            "Share the location with an EmergencyOnly follower for the next minutes",
+        ).handler(start_location_sharing).param::<String>("follower")
+        .body::<LocationSharingRequest>()
+        .returns_json::<APIResponse<LocationSharing>>(),
+        Operation::new(
+            "stop_location_sharing",
+            "End the temporary location sharing with a follower",
+        ).handler(stop_location_sharing).param::<String>("follower")
+        .returns_json::<APIResponse<FollowerAccess>>(),
+        Operation::new(
+            "force_refresh_telemetry",
+            "Ask a friend's phone for a new location",
+        ).handler(force_refresh_telemetry).param::<String>("recipient_username")
+        .returns_json::<APIResponse<CommandResponse>>(),
+        Operation::new(
+            "get_user_device_health",
+            "Battery trend, last ping and tracking issues of the devices of a friend",
+        ).handler(get_user_device_health).param::<String>("username")
+        .returns_json::<APIResponse<Vec<DeviceHealth>>>(),
+        Operation::new(
+            "update_device",
+            "Update the settings and the push token of the current device",
+        ).handler(update_device)
+        .body::<DeviceUpdateRequest>()
+        .returns_json::<APIResponse<DeviceUpdateResponse>>(),
+        Operation::new(
+            "delete_push_token",
+            "Stop sending push notifications to the current device",
+        ).handler(delete_push_token)
+        .returns_json::<APIResponse<DeviceUpdateResponse>>(),
+        Operation::new(
+            "update_user_profile",
+            "Update the name and the language of the user",
+        ).handler(update_user_profile)
+        .body::<ProfileUpdateRequest>()
+        .returns_json::<APIResponse<UserDetails>>(),
+        Operation::new(
+            "update_user_profile_picture",
+            "Upload a JPEG or PNG profile picture, stored with its thumbnails",
+        ).handler(update_user_profile_picture)
+        .returns_json::<APIResponse<ProfilePicture>>(),
+        Operation::new(
+            "delete_user_account",
+            "Delete the account and everything stored about the user",
+        ).handler(delete_user_account)
+        .returns_json::<APIResponse<Message<String>>>(),
+        Operation::new(
+            "export_user_account",
+            "Machine-readable archive of everything stored about the user",
+        ).handler(export_user_account)
+        .returns_json::<APIResponse<UserDataExport>>(),
+        Operation::new(
+            "export_user_account_telemetry",
+            "Next page of the telemetry of the data export",
+        ).handler(export_user_account_telemetry).param::<String>("cursor")
+        .returns_json::<APIResponse<TelemetryExportPage>>(),
+        Operation::new(
+            "get_user_device_settings_history",
+            "Settings of a device of the user after each update and the tracking issues they introduced",
+        ).handler(get_user_device_settings_history).param::<Option<String>>("device_id").param::<Option<i64>>("limit")
+        .returns_json::<APIResponse<Vec<DeviceSettingsSnapshot>>>(),
+    ]
 }
@@ -1,8 +1,21 @@
-use super::middleware::{catchers::catchers, cors::options};
-use super::validators::{friends::assert_not_friends, invitations::assert_valid_invitation};
+use super::middleware::{
+    catchers::catchers,
+    cors::options,
+    rate_limit::{PublicInvitationCreator, RateLimit, SendDirectInvitation},
+    versioning::Deprecation,
+};
+use super::openapi::{mount_openapi, Operation};
+use super::validators::{
+    friends::assert_not_friends,
+    invitations::{assert_valid_direct_invitation, assert_valid_invitation},
+};
+use super::versioning::mount_versions;
 use crate::controllers::invitations::{
-    accept_invitation, create_invitation, get_invitation_creator, notify_accepted,
-    reject_invitation, remove_friends,
+    accept_direct_invitation, accept_invitation, cancel_direct_invitation,
+    create_direct_invitation, create_invitation, get_invitation_creator, list_invitations,
+    list_received_direct_invitations, notify_accepted, notify_direct_accepted,
+    reject_direct_invitation, reject_invitation, remove_friends, resend_invitation,
+    revoke_invitation, send_direct_invitation,
 };
 use crate::controllers::telemetry::force_refresh_telemetry_internal;
 use crate::utils::sentry::log_api_err;
@@ -10,16 +23,20 @@ use crate::{
     db::{get_connection, get_pool},
     model::{
         auth::AuthInfo,
-        invitations::{LinkActionData, LinkCreationData},
-        requests::InvitationRequest,
+        invitations::{DirectInvitation, LinkActionData, LinkCreationData, LinkInvitation},
+        requests::{DirectInvitationRequest, InvitationRequest, ResendInvitationRequest},
         responses::{
-            APIJsonResponse, APIResponse, AcceptInvitationResponse, CreateInvitationResponse,
+            v2, APIJsonResponse, APIResponse, AcceptInvitationResponse,
+            CreateDirectInvitationResponse, CreateInvitationResponse,
         },
+        versioning::ApiVersion,
         APIResult, Message, Storage,
     },
 };
 use rocket::{Rocket, State};
-use rocket_contrib::json::{Json, JsonValue};
+use rocket_contrib::json::Json;
+use serde_json::Value;
+use std::env;
 use uuid::Uuid;
 
 /// Create a new invitation link from post request
@@ -32,25 +49,101 @@ fn create(
     state: State<Storage>,
 ) -> APIResult<CreateInvitationResponse> {
     // Get the basic data to create a new invitation_link instance
-    let data = LinkCreationData::new(
+    LinkCreationData::new(
         Uuid::new_v4().to_string(),
         auth_info.username.clone(),
         invitation_req.expirationDate.clone(),
-    )?;
+    )
+    .and_then(|data| data.with_max_uses(invitation_req.maxUses))
+    .map(|data| data.with_terms(invitation_req.terms.clone()))
+    .and_then(|data| {
+        let link = create_invitation(get_connection(state)?, data)?;
+        Ok(Json(APIResponse {
+            success: true,
+            result: Some(CreateInvitationResponse { link }),
+        }))
+    })
+    .map_err(|err| {
+        log_api_err("POST /v1/invitations", &err, Some(&auth_info));
+        APIJsonResponse::api_error_with_internal_error(err, &auth_info.language)
+    })
+}
+
+/// Invitation links created by the user with their state, expiration and recipient.
+#[get("/")]
+fn list(auth_info: AuthInfo, state: State<Storage>) -> APIResult<Vec<LinkInvitation>> {
     get_connection(state)
-        .and_then(|conn| {
-            let link = create_invitation(conn, data)?;
+        .and_then(|mut conn| {
+            let invitations = list_invitations(&mut conn, &auth_info.username)?;
             Ok(Json(APIResponse {
                 success: true,
-                result: Some(CreateInvitationResponse { link }),
+                result: Some(invitations),
             }))
         })
         .map_err(|err| {
-            log_api_err("POST /v1/invitations", &err, Some(&auth_info));
+            log_api_err("GET /v1/invitations", &err, Some(&auth_info));
             APIJsonResponse::api_error_with_internal_error(err, &auth_info.language)
         })
 }
 
+/// Cancels an invitation link of the user, accepting it fails from now on.
+#[post("/<id>/revoke")]
+fn revoke(id: String, auth_info: AuthInfo, state: State<Storage>) -> APIResult<Message<String>> {
+    let data = LinkActionData {
+        uuid: id.clone(),
+        username: auth_info.username.clone(),
+    };
+
+    get_connection(state)
+        .and_then(|mut conn| {
+            revoke_invitation(&mut conn, &data)?;
+            Ok(Json(APIResponse {
+                success: true,
+                result: Some(Message {
+                    message: "Ok".to_string(),
+                }),
+            }))
+        })
+        .map_err(|err| {
+            log_api_err(
+                &format!("POST /v1/invitations/{}/revoke", id),
+                &err,
+                Some(&auth_info),
+            );
+            APIJsonResponse::api_error_with_internal_error(err, &auth_info.language)
+        })
+}
+
+/// Extends the expiration of an unused invitation link and returns it to be sent again.
+#[post("/<id>/resend", format = "application/json", data = "<invitation_req>")]
+fn resend(
+    id: String,
+    invitation_req: Json<ResendInvitationRequest>,
+    auth_info: AuthInfo,
+    state: State<Storage>,
+) -> APIResult<CreateInvitationResponse> {
+    LinkCreationData::new(
+        id.clone(),
+        auth_info.username.clone(),
+        invitation_req.expirationDate.clone(),
+    )
+    .and_then(|data| {
+        let link = resend_invitation(&mut get_connection(state)?, &data)?;
+        Ok(Json(APIResponse {
+            success: true,
+            result: Some(CreateInvitationResponse { link }),
+        }))
+    })
+    .map_err(|err| {
+        log_api_err(
+            &format!("POST /v1/invitations/{}/resend", id),
+            &err,
+            Some(&auth_info),
+        );
+        APIJsonResponse::api_error_with_internal_error(err, &auth_info.language)
+    })
+}
+
 #[post("/<id>/reject")]
 fn reject(id: String, auth_info: AuthInfo, state: State<Storage>) -> APIResult<Message<String>> {
     let data = LinkActionData {
@@ -85,8 +178,37 @@ fn accept(
     auth_info: AuthInfo,
     state: State<Storage>,
 ) -> APIResult<AcceptInvitationResponse> {
+    accept_and_notify(&id, &auth_info, state, ApiVersion::V1).map(|res| {
+        Json(APIResponse {
+            success: true,
+            result: Some(res),
+        })
+    })
+}
+
+/// Same as v1 without the `message` property.
+#[post("/<id>/accept")]
+fn accept_v2(
+    id: String,
+    auth_info: AuthInfo,
+    state: State<Storage>,
+) -> APIResult<v2::AcceptInvitationResponse> {
+    accept_and_notify(&id, &auth_info, state, ApiVersion::V2).map(|res| {
+        Json(APIResponse {
+            success: true,
+            result: Some(res.into()),
+        })
+    })
+}
+
+fn accept_and_notify(
+    id: &str,
+    auth_info: &AuthInfo,
+    state: State<Storage>,
+    version: ApiVersion,
+) -> Result<AcceptInvitationResponse, APIJsonResponse> {
     let data = LinkActionData {
-        uuid: id.clone(),
+        uuid: id.to_string(),
         username: auth_info.username.clone(),
     };
 
@@ -98,7 +220,7 @@ fn accept(
             let _ = notify_accepted(&mut conn, &data)
                 .map_err(|w| w.log_err("Error sending notification"));
 
-            let inv_creator_data = get_invitation_creator(&mut conn, &id)?;
+            let inv_creator_data = get_invitation_creator(&mut conn, id)?;
             let creator_username: String = inv_creator_data["username"].as_str().unwrap().into();
 
             let error = force_refresh_telemetry_internal(
@@ -109,16 +231,13 @@ fn accept(
 
             let _ = error.map_err(|w| w.log_err("push refresh error"));
 
-            Ok(Json(APIResponse {
-                success: true,
-                result: Some(res),
-            }))
+            Ok(res)
         })
         .map_err(|err| {
             log_api_err(
-                &format!("POST /v1/invitations/{}/accept", id),
+                &format!("POST {}/invitations/{}/accept", version.prefix(), id),
                 &err,
-                Some(&auth_info),
+                Some(auth_info),
             );
             APIJsonResponse::api_error_with_internal_error(err, &auth_info.language)
         })
@@ -152,13 +271,13 @@ fn remove_friend(
 }
 
 #[get("/<id>/creator")]
-pub fn get_creator(id: String, auth_info: AuthInfo, state: State<Storage>) -> APIResult<JsonValue> {
+pub fn get_creator(id: String, auth_info: AuthInfo, state: State<Storage>) -> APIResult<Value> {
     get_connection(state)
         .and_then(|mut conn| {
             let data = get_invitation_creator(&mut conn, &id)?;
             Ok(Json(APIResponse {
                 success: true,
-                result: Some(data),
+                result: Some(data.0),
             }))
         })
         .map_err(|err| {
@@ -172,13 +291,17 @@ pub fn get_creator(id: String, auth_info: AuthInfo, state: State<Storage>) -> AP
 }
 
 #[get("/public/<id>/creator")]
-pub fn get_creator_public(id: String, state: State<Storage>) -> APIResult<JsonValue> {
+pub fn get_creator_public(
+    id: String,
+    state: State<Storage>,
+    _rate_limit: RateLimit<PublicInvitationCreator>,
+) -> APIResult<Value> {
     get_connection(state)
         .and_then(|mut conn| {
             let data = get_invitation_creator(&mut conn, &id)?;
             Ok(Json(APIResponse {
                 success: true,
-                result: Some(data),
+                result: Some(data.0),
             }))
         })
         .map_err(|err| {
@@ -191,24 +314,323 @@ pub fn get_creator_public(id: String, state: State<Storage>) -> APIResult<JsonVa
         })
 }
 
+/// Invites the owner of an email or phone number, who is notified right away
+/// and, when not a verified user yet, matched with the invitation on verification.
+#[post("/direct", format = "application/json", data = "<invitation_req>")]
+fn create_direct(
+    invitation_req: Json<DirectInvitationRequest>,
+    auth_info: AuthInfo,
+    state: State<Storage>,
+    _rate_limit: RateLimit<SendDirectInvitation>,
+) -> APIResult<CreateDirectInvitationResponse> {
+    get_connection(state)
+        .and_then(|mut conn| {
+            let target = assert_valid_direct_invitation(&invitation_req)?;
+            let terms = invitation_req.terms.clone().unwrap_or_default();
+            let (id, target_username) =
+                create_direct_invitation(&mut conn, &auth_info.username, &target, &terms)?;
+
+            let _ =
+                send_direct_invitation(&mut conn, &auth_info.username, &target, &target_username)
+                    .map_err(|w| w.log_err("Error sending invitation"));
+
+            Ok(Json(APIResponse {
+                success: true,
+                result: Some(CreateDirectInvitationResponse { id }),
+            }))
+        })
+        .map_err(|err| {
+            log_api_err("POST /v1/invitations/direct", &err, Some(&auth_info));
+            APIJsonResponse::api_error_with_internal_error(err, &auth_info.language)
+        })
+}
+
+/// Pending invitations sent to the email or phone number of the user.
+#[get("/direct/received")]
+fn list_received_direct(
+    auth_info: AuthInfo,
+    state: State<Storage>,
+) -> APIResult<Vec<DirectInvitation>> {
+    get_connection(state)
+        .and_then(|mut conn| {
+            let invitations = list_received_direct_invitations(&mut conn, &auth_info.username)?;
+            Ok(Json(APIResponse {
+                success: true,
+                result: Some(invitations),
+            }))
+        })
+        .map_err(|err| {
+            log_api_err(
+                "GET /v1/invitations/direct/received",
+                &err,
+                Some(&auth_info),
+            );
+            APIJsonResponse::api_error_with_internal_error(err, &auth_info.language)
+        })
+}
+
+#[post("/direct/<id>/accept")]
+fn accept_direct(
+    id: String,
+    auth_info: AuthInfo,
+    state: State<Storage>,
+) -> APIResult<AcceptInvitationResponse> {
+    accept_direct_and_notify(&id, &auth_info, state, ApiVersion::V1).map(|res| {
+        Json(APIResponse {
+            success: true,
+            result: Some(res),
+        })
+    })
+}
+
+/// Same as v1 without the `message` property.
+#[post("/direct/<id>/accept")]
+fn accept_direct_v2(
+    id: String,
+    auth_info: AuthInfo,
+    state: State<Storage>,
+) -> APIResult<v2::AcceptInvitationResponse> {
+    accept_direct_and_notify(&id, &auth_info, state, ApiVersion::V2).map(|res| {
+        Json(APIResponse {
+            success: true,
+            result: Some(res.into()),
+        })
+    })
+}
+
+fn accept_direct_and_notify(
+    id: &str,
+    auth_info: &AuthInfo,
+    state: State<Storage>,
+    version: ApiVersion,
+) -> Result<AcceptInvitationResponse, APIJsonResponse> {
+    get_connection(state)
+        .and_then(|mut conn| {
+            let res = accept_direct_invitation(&mut conn, id, &auth_info.username)?;
+
+            let _ = notify_direct_accepted(&mut conn, &res.username, &auth_info.username)
+                .map_err(|w| w.log_err("Error sending notification"));
+
+            let error = force_refresh_telemetry_internal(
+                &mut conn,
+                auth_info.username.clone(),
+                res.username.clone(),
+            );
+
+            let _ = error.map_err(|w| w.log_err("push refresh error"));
+
+            Ok(res)
+        })
+        .map_err(|err| {
+            log_api_err(
+                &format!("POST {}/invitations/direct/{}/accept", version.prefix(), id),
+                &err,
+                Some(auth_info),
+            );
+            APIJsonResponse::api_error_with_internal_error(err, &auth_info.language)
+        })
+}
+
+#[post("/direct/<id>/reject")]
+fn reject_direct(
+    id: String,
+    auth_info: AuthInfo,
+    state: State<Storage>,
+) -> APIResult<Message<String>> {
+    get_connection(state)
+        .and_then(|mut conn| {
+            reject_direct_invitation(&mut conn, &id, &auth_info.username)?;
+            Ok(Json(APIResponse {
+                success: true,
+                result: Some(Message {
+                    message: "Ok".to_string(),
+                }),
+            }))
+        })
+        .map_err(|err| {
+            log_api_err(
+                &format!("POST /v1/invitations/direct/{}/reject", id),
+                &err,
+                Some(&auth_info),
+            );
+            APIJsonResponse::api_error_with_internal_error(err, &auth_info.language)
+        })
+}
+
+/// Cancels an invitation of the user that was not answered yet.
+#[post("/direct/<id>/cancel")]
+fn cancel_direct(
+    id: String,
+    auth_info: AuthInfo,
+    state: State<Storage>,
+) -> APIResult<Message<String>> {
+    get_connection(state)
+        .and_then(|mut conn| {
+            cancel_direct_invitation(&mut conn, &id, &auth_info.username)?;
+            Ok(Json(APIResponse {
+                success: true,
+                result: Some(Message {
+                    message: "Ok".to_string(),
+                }),
+            }))
+        })
+        .map_err(|err| {
+            log_api_err(
+                &format!("POST /v1/invitations/direct/{}/cancel", id),
+                &err,
+                Some(&auth_info),
+            );
+            APIJsonResponse::api_error_with_internal_error(err, &auth_info.language)
+        })
+}
+
 pub fn rocket() -> Rocket {
     let database = get_pool();
-    rocket::ignite()
-        .mount(
-            "/v1/invitations",
-            routes![
-                create,
-                accept,
-                reject,
-                remove_friend,
-                get_creator,
-                get_creator_public
-            ],
-        )
+    let redis = redis::Client::open(env::var("REDIS_URL").expect("REDIS_URL must be set"))
+        .expect("Failed to open redis client.");
+    let v1 = routes![
+        create,
+        list,
+        revoke,
+        resend,
+        accept,
+        reject,
+        remove_friend,
+        get_creator,
+        get_creator_public,
+        create_direct,
+        list_received_direct,
+        accept_direct,
+        reject_direct,
+        cancel_direct
+    ];
+    let v2 = routes![
+        create,
+        list,
+        revoke,
+        resend,
+        accept_v2,
+        reject,
+        remove_friend,
+        get_creator,
+        get_creator_public,
+        create_direct,
+        list_received_direct,
+        accept_direct_v2,
+        reject_direct,
+        cancel_direct
+    ];
+    let rocket = rocket::ignite()
         .register(catchers())
         .attach(options())
+        .attach(Deprecation::from_env())
         .manage(Storage {
-            redis: None,
+            redis: Some(redis),
             database,
-        })
+        });
+    let rocket = mount_versions(
+        rocket,
+        "/invitations",
+        vec![(ApiVersion::V1, v1), (ApiVersion::V2, v2)],
+    );
+    mount_openapi(rocket, "Armore Invitations API", &operations())
+}
+
+pub fn operations() -> Vec<Operation> {
+    vec![
+        Operation::new("create", "Create a new invitation link")
+            .handler(create)
+            .body::<InvitationRequest>()
+            .returns::<CreateInvitationResponse>(),
+        Operation::new("list", "Invitation links created by the user")
+            .handler(list)
+            .returns::<Vec<LinkInvitation>>(),
+        Operation::new("revoke", "Cancel an invitation link that was not used yet")
+            .handler(revoke)
+            .param::<String>("id")
+            .returns::<Message<String>>(),
+        Operation::new(
+            "resend",
+            "Extend the expiration of an unused invitation link to send it again",
+        )
+        .handler(resend)
+        .param::<String>("id")
+        .body::<ResendInvitationRequest>()
+        .returns::<CreateInvitationResponse>(),
+        Operation::new(
+            "accept",
+            "Accept an invitation and start following its creator",
+        )
+        .handler(accept)
+        .param::<String>("id")
+        .returns::<AcceptInvitationResponse>(),
+        Operation::new(
+            "accept_v2",
+            "Accept an invitation and start following its creator",
+        )
+        .handler(accept_v2)
+        .param::<String>("id")
+        .returns::<v2::AcceptInvitationResponse>(),
+        Operation::new("reject", "Reject an invitation")
+            .handler(reject)
+            .param::<String>("id")
+            .returns::<Message<String>>(),
+        Operation::new(
+            "remove_friend",
+            "Stop following and being followed by a user",
+        )
+        .handler(remove_friend)
+        .param::<String>("username")
+        .returns::<Message<String>>(),
+        Operation::new("get_creator", "Details of the creator of an invitation")
+            .handler(get_creator)
+            .param::<String>("id")
+            .returns::<Value>(),
+        Operation::new(
+            "get_creator_public",
+            "Details of the creator of an invitation",
+        )
+        .handler(get_creator_public)
+        .param::<String>("id")
+        .returns::<Value>()
+        .public(),
+        Operation::new(
+            "create_direct",
+            "Invite the owner of an email or phone number",
+        )
+        .handler(create_direct)
+        .body::<DirectInvitationRequest>()
+        .returns::<CreateDirectInvitationResponse>(),
+        Operation::new(
+            "list_received_direct",
+            "Pending invitations sent to the email or phone number of the user",
+        )
+        .handler(list_received_direct)
+        .returns::<Vec<DirectInvitation>>(),
+        Operation::new(
+            "accept_direct",
+            "Accept an invitation sent to the user and start following its creator",
+        )
+        .handler(accept_direct)
+        .param::<String>("id")
+        .returns::<AcceptInvitationResponse>(),
+        Operation::new(
+            "accept_direct_v2",
+            "Accept an invitation sent to the user and start following its creator",
+        )
+        .handler(accept_direct_v2)
+        .param::<String>("id")
+        .returns::<v2::AcceptInvitationResponse>(),
+        Operation::new("reject_direct", "Reject an invitation sent to the user")
+            .handler(reject_direct)
+            .param::<String>("id")
+            .returns::<Message<String>>(),
+        Operation::new(
+            "cancel_direct",
+            "Cancel an invitation sent by the user that was not answered yet",
+        )
+        .handler(cancel_direct)
+        .param::<String>("id")
+        .returns::<Message<String>>(),
+    ]
 }
@@ -13,7 +13,7 @@
 use crate::constants::ASIMOV_LIVES;
 use crate::controllers::telemetry::{get_public_key, get_user_details};
 use crate::model::{
-    auth::{AuthInfo, Claims},
+    auth::{AuthInfo, Claims, Language},
     responses::{APIJsonResponse, Errors::APIError},
     Storage,
 };
@@ -26,121 +26,152 @@ impl<'a, 'r> FromRequest<'a, 'r> for AuthInfo {
     type Error = APIJsonResponse;
 
     fn from_request(request: &'a Request<'r>) -> request::Outcome<AuthInfo, Self::Error> {
-        let keys: Vec<_> = request.headers().get(ASIMOV_LIVES).collect();
+        // The result is cached so other guards, like the rate limiter, can reuse it
+        // without validating the token twice.
+        match request.local_cache(|| authenticate(request)) {
+            Ok(auth_info) => Outcome::Success(auth_info.clone()),
+            Err(failure) => Outcome::Failure(failure.clone()),
+        }
+    }
+}
+
+fn authenticate(request: &Request) -> Result<AuthInfo, (Status, APIJsonResponse)> {
+    let keys: Vec<_> = request.headers().get(ASIMOV_LIVES).collect();
+
+    if keys.len() != 1 {
+        error!("Error parsing token");
+        return Err((
+            Status::Forbidden,
+            APIJsonResponse {
+                json: json!(APIError {
+                    message: "No token, no data".to_string(),
+                    engineeringError: None,
+                    code: None
+                }),
+                status: Status::Forbidden,
+                error_code: None,
+            },
+        ));
+    }
+
+    let storage = request
+        .guard::<State<Storage>>()
+        .expect("no database connection");
 
-        if keys.len() != 1 {
-            error!("Error parsing token");
-            return Outcome::Failure((
+    let pool = &storage.database;
+
+    let public_key;
+
+    let token = keys[0];
+
+    let claims: Claims;
+
+    match dangerous_insecure_decode(&token) {
+        Ok(token_data) => {
+            claims = token_data.claims;
+        }
+        Err(e) => {
+            error!("Error parsing token {}", e);
+            return Err((
                 Status::Forbidden,
                 APIJsonResponse {
                     json: json!(APIError {
-                        message: "No token, no data".to_string(),
-                        engineeringError: None
+                        message: "Error parsing token".to_string(),
+                        engineeringError: None,
+                        code: None
                     }),
                     status: Status::Forbidden,
+                    error_code: None,
                 },
             ));
         }
+    }
 
-        let storage = request
-            .guard::<State<Storage>>()
-            .expect("no database connection");
-
-        let pool = &storage.database;
-
-        let public_key;
-
-        let token = keys[0];
-
-        let claims: Claims;
-
-        match dangerous_insecure_decode(&token) {
-            Ok(token_data) => {
-                claims = token_data.claims;
-            }
-            Err(e) => {
-                error!("Error parsing token {}", e);
-                return Outcome::Failure((
-                    Status::Forbidden,
-                    APIJsonResponse {
-                        json: json!(APIError {
-                            message: "Error parsing token".to_string(),
-                            engineeringError: None
-                        }),
-                        status: Status::Forbidden,
-                    },
-                ));
-            }
+    match get_public_key(&claims.username.to_string(), &pool) {
+        Ok(key) => {
+            public_key = key;
         }
-
-        match get_public_key(&claims.username.to_string(), &pool) {
-            Ok(key) => {
-                public_key = key;
-            }
-            Err(e) => {
-                error!("Error retrieving key, username: {}", &claims.username);
-                let json_error = APIJsonResponse::api_error_with_internal_error(e, "en");
-                return Outcome::Failure((Status::Forbidden, json_error));
-            }
+        Err(e) => {
+            error!("Error retrieving key, username: {}", &claims.username);
+            let json_error = APIJsonResponse::api_error_with_internal_error(e, "en");
+            return Err((Status::Forbidden, json_error));
         }
+    }
 
-        let decoded_key;
-        let key_with_headers = format!(
-            "-----BEGIN PUBLIC KEY-----\n{}-----END PUBLIC KEY-----",
-            public_key
-        );
-        match DecodingKey::from_rsa_pem((&key_with_headers).as_ref()) {
-            Ok(key) => {
-                decoded_key = key;
-            }
-            Err(error) => {
-                error!(
-                    "Client sent an invalid key {} username: {}",
-                    error, &claims.username
-                );
-                return Outcome::Failure((
-                    Status::Forbidden,
-                    APIJsonResponse {
-                        json: json!(APIError {
-                            message: "No token, no data".to_string(),
-                            engineeringError: None
-                        }),
-                        status: Status::Forbidden,
-                    },
-                ));
-            }
+    let decoded_key;
+    let key_with_headers = format!(
+        "-----BEGIN PUBLIC KEY-----\n{}-----END PUBLIC KEY-----",
+        public_key
+    );
+    match DecodingKey::from_rsa_pem((&key_with_headers).as_ref()) {
+        Ok(key) => {
+            decoded_key = key;
         }
-
-        let token_message =
-            decode::<Claims>(&token, &decoded_key, &Validation::new(Algorithm::RS512));
-
-        return match token_message {
-            Ok(token_data) => {
-                let user_details =
-                    get_user_details(&token_data.claims.username, &mut pool.get().unwrap())
-                        .ok()
-                        .flatten();
-
-                let language: String = user_details.map_or("en".into(), |details| {
-                    details.language.unwrap_or("en".into())
-                });
-                Outcome::Success(AuthInfo {
-                    key: token.to_string(),
-                    username: token_data.claims.username,
-                    deviceId: token_data.claims.deviceId,
-                    language,
-                })
-            }
-            _ => Outcome::Failure((
+        Err(error) => {
+            error!(
+                "Client sent an invalid key {} username: {}",
+                error, &claims.username
+            );
+            return Err((
                 Status::Forbidden,
                 APIJsonResponse {
                     json: json!(APIError {
                         message: "No token, no data".to_string(),
-                        engineeringError: None
+                        engineeringError: None,
+                        code: None
                     }),
                     status: Status::Forbidden,
+                    error_code: None,
                 },
-            )),
-        };
+            ));
+        }
+    }
+
+    let token_message = decode::<Claims>(&token, &decoded_key, &Validation::new(Algorithm::RS512));
+
+    return match token_message {
+        Ok(token_data) => {
+            let user_details =
+                get_user_details(&token_data.claims.username, &mut pool.get().unwrap())
+                    .ok()
+                    .flatten();
+
+            let language: String = user_details.map_or("en".into(), |details| {
+                details.language.unwrap_or("en".into())
+            });
+            Ok(AuthInfo {
+                key: token.to_string(),
+                username: token_data.claims.username,
+                deviceId: token_data.claims.deviceId,
+                language,
+            })
+        }
+        _ => Err((
+            Status::Forbidden,
+            APIJsonResponse {
+                json: json!(APIError {
+                    message: "No token, no data".to_string(),
+                    engineeringError: None,
+                    code: None
+                }),
+                status: Status::Forbidden,
+                error_code: None,
+            },
+        )),
+    };
+}
+
+impl<'a, 'r> FromRequest<'a, 'r> for Language {
+    type Error = ();
+
+    fn from_request(request: &'a Request<'r>) -> request::Outcome<Language, Self::Error> {
+        let language = request
+            .headers()
+            .get_one("Accept-Language")
+            .and_then(|header| header.split(|c| c == ',' || c == '-' || c == ';').next())
+            .map(|lang| lang.trim().to_lowercase())
+            .filter(|lang| !lang.is_empty())
+            .unwrap_or("en".to_string());
+        Outcome::Success(Language(language))
     }
 }
@@ -1,30 +1,46 @@
-use crate::model::responses::{APIResponse, Errors::APIError};
+use super::rate_limit::TooManyRequests;
+use crate::model::responses::{uses_error_codes, APIResponse, Errors::APIError};
 use crate::rocket::Catcher;
 use crate::rocket::{self, Request};
 use crate::rocket_contrib::json::Json;
 
+fn catalog_code(req: &Request, code: &str) -> Option<String> {
+    if uses_error_codes(req) {
+        Some(code.to_string())
+    } else {
+        None
+    }
+}
+
 #[catch(403)]
-fn forbidden(_req: &Request) -> Json<APIResponse<APIError>> {
+fn forbidden(req: &Request) -> Json<APIResponse<APIError>> {
     Json(APIResponse {
         success: false,
         result: APIError {
             message: "Unauthorized".to_string(),
             engineeringError: Some("The JWT Token is not good".to_string()),
+            code: catalog_code(req, "UNAUTHORIZED"),
         },
     })
 }
 
 #[catch(404)]
-fn not_found(_req: &Request) -> Json<APIResponse<APIError>> {
+fn not_found(req: &Request) -> Json<APIResponse<APIError>> {
     Json(APIResponse {
         success: false,
         result: APIError {
             message: "Unable to find endpoint".to_string(),
             engineeringError: Some("Unable to find endpoint".to_string()),
+            code: catalog_code(req, "ENDPOINT_NOT_FOUND"),
         },
     })
 }
 
+#[catch(429)]
+fn too_many_requests(req: &Request) -> TooManyRequests {
+    TooManyRequests::from_request(req)
+}
+
 pub fn catchers() -> Vec<Catcher> {
-    catchers![not_found, forbidden]
+    catchers![not_found, forbidden, too_many_requests]
 }
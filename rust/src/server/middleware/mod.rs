@@ -1,3 +1,5 @@
 pub mod auth;
 pub mod catchers;
 pub mod cors;
+pub mod rate_limit;
+pub mod versioning;
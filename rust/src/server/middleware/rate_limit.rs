@@ -0,0 +1,295 @@
+/**
+ * Copyright [2020] [Dario Alessandro Lencina Talarico]
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * y ou may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ * http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+use crate::lang::TranslationIds;
+use crate::model::{
+    auth::{AuthInfo, Language},
+    responses::{APIJsonResponse, Errors::APIInternalError},
+    Storage,
+};
+use chrono::Utc;
+use rocket::http::{Header, Status};
+use rocket::request::{FromRequest, Outcome};
+use rocket::response::{self, Responder};
+use rocket::{request, Request, Response, State};
+use std::env;
+use std::marker::PhantomData;
+use std::net::IpAddr;
+
+/// Token bucket stored in a redis hash, refilled lazily every time it is read.
+/// Returns {allowed, seconds until the next token is available}.
+const TOKEN_BUCKET_SCRIPT: &str = r#"
+local capacity = tonumber(ARGV[1])
+local refill_per_ms = tonumber(ARGV[2])
+local now = tonumber(ARGV[3])
+local bucket = redis.call('HMGET', KEYS[1], 'tokens', 'ts')
+local tokens = tonumber(bucket[1]) or capacity
+local ts = tonumber(bucket[2]) or now
+tokens = math.min(capacity, tokens + math.max(0, now - ts) * refill_per_ms)
+local allowed = 0
+local retry_after = 0
+if tokens >= 1 then
+    tokens = tokens - 1
+    allowed = 1
+else
+    retry_after = math.ceil((1 - tokens) / refill_per_ms / 1000)
+end
+redis.call('HSET', KEYS[1], 'tokens', tokens, 'ts', now)
+redis.call('PEXPIRE', KEYS[1], math.ceil(capacity / refill_per_ms))
+return {allowed, retry_after}
+"#;
+
+/// Limits for a group of routes. The defaults can be overridden with the
+/// RATE_LIMIT_<NAME>_CAPACITY and RATE_LIMIT_<NAME>_REFILL_PER_MINUTE env vars.
+pub trait RateLimitPolicy: Send + Sync + 'static {
+    const NAME: &'static str;
+    /// Max number of requests that can be done in a burst.
+    const CAPACITY: u32;
+    /// Requests added back to the bucket every minute.
+    const REFILL_PER_MINUTE: u32;
+}
+
+/// GET /v1/invitations/public/<id>/creator, does not require a token.
+pub struct PublicInvitationCreator;
+
+impl RateLimitPolicy for PublicInvitationCreator {
+    const NAME: &'static str = "PUBLIC_INVITATION_CREATOR";
+    const CAPACITY: u32 = 20;
+    const REFILL_PER_MINUTE: u32 = 20;
+}
+
+/// GET /v1/telemetry/<recipient_username>, sends a silent push to the recipient.
+pub struct ForceRefresh;
+
+impl RateLimitPolicy for ForceRefresh {
+    const NAME: &'static str = "FORCE_REFRESH";
+    const CAPACITY: u32 = 10;
+    const REFILL_PER_MINUTE: u32 = 6;
+}
+
+/// POST /v1/emergency/<username>/report, notifies every emergency contact.
+pub struct FriendReport;
+
+impl RateLimitPolicy for FriendReport {
+    const NAME: &'static str = "FRIEND_REPORT";
+    const CAPACITY: u32 = 5;
+    const REFILL_PER_MINUTE: u32 = 2;
+}
+
+/// POST /v1/invitations/direct, sends an email, sms or push notification to
+/// the invited person.
+pub struct SendDirectInvitation;
+
+impl RateLimitPolicy for SendDirectInvitation {
+    const NAME: &'static str = "SEND_DIRECT_INVITATION";
+    const CAPACITY: u32 = 10;
+    const REFILL_PER_MINUTE: u32 = 2;
+}
+
+/// POST /v1/auth/register and /v1/auth/login, do not require a token and send a
+/// code by email or sms. Limited per IP and per email or phone number.
+pub struct SendVerificationCode;
+
+impl RateLimitPolicy for SendVerificationCode {
+    const NAME: &'static str = "SEND_VERIFICATION_CODE";
+    const CAPACITY: u32 = 5;
+    const REFILL_PER_MINUTE: u32 = 1;
+}
+
+/// POST /v1/auth/verify/<email_or_phone>, does not require a token.
+/// Limited per IP and per email or phone number.
+pub struct VerifyCode;
+
+impl RateLimitPolicy for VerifyCode {
+    const NAME: &'static str = "VERIFY_CODE";
+    const CAPACITY: u32 = 10;
+    const REFILL_PER_MINUTE: u32 = 2;
+}
+
+/// Request guard that consumes a token from the bucket of the policy `P`.
+/// Buckets are per username when the request carries a valid token and per IP otherwise.
+pub struct RateLimit<P: RateLimitPolicy> {
+    policy: PhantomData<P>,
+}
+
+/// Stored in the request cache when a request is rejected, so the 429 catcher
+/// can build a localized response.
+#[derive(Clone, Debug)]
+pub struct RateLimitExceeded {
+    pub language: String,
+    pub retry_after: i64,
+}
+
+fn limit_from_env(policy: &str, setting: &str, default: u32) -> u32 {
+    env::var(format!("RATE_LIMIT_{}_{}", policy, setting))
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .filter(|value| *value > 0)
+        .unwrap_or(default)
+}
+
+pub fn rate_limit_key(policy: &str, subject: &str) -> String {
+    format!("rate_limit.{}.{}", policy.to_lowercase(), subject)
+}
+
+/// @return the seconds to wait if the bucket is empty
+fn take_token(
+    client: &redis::Client,
+    key: &str,
+    capacity: u32,
+    refill_per_minute: u32,
+) -> redis::RedisResult<Option<i64>> {
+    let mut conn = client.get_connection()?;
+    let (allowed, retry_after): (i64, i64) = redis::Script::new(TOKEN_BUCKET_SCRIPT)
+        .key(key)
+        .arg(capacity)
+        .arg(refill_per_minute as f64 / 60_000.0)
+        .arg(Utc::now().timestamp_millis())
+        .invoke(&mut conn)?;
+    Ok(if allowed == 1 {
+        None
+    } else {
+        Some(retry_after)
+    })
+}
+
+/// Address of the peer, the X-Real-IP header is only honoured when the peer is
+/// one of the comma separated proxies in RATE_LIMIT_TRUSTED_PROXIES, otherwise
+/// clients could get a new bucket by sending a different header.
+fn client_address(request: &Request) -> Option<IpAddr> {
+    let remote = request.remote()?.ip();
+    let behind_proxy = env::var("RATE_LIMIT_TRUSTED_PROXIES")
+        .map(|proxies| {
+            proxies
+                .split(',')
+                .filter_map(|proxy| proxy.trim().parse::<IpAddr>().ok())
+                .any(|proxy| proxy == remote)
+        })
+        .unwrap_or(false);
+    if behind_proxy {
+        request.real_ip().or(Some(remote))
+    } else {
+        Some(remote)
+    }
+}
+
+/// Takes a token from the bucket of `subject`, redis errors let the request through
+/// so the service does not go down with it.
+///
+/// @return the seconds to wait if the bucket is empty
+fn consume<P: RateLimitPolicy>(redis: Option<&redis::Client>, subject: &str) -> Option<i64> {
+    let redis = match redis {
+        Some(redis) => redis,
+        None => {
+            error!("Rate limit {} skipped, redis is not configured", P::NAME);
+            return None;
+        }
+    };
+    let capacity = limit_from_env(P::NAME, "CAPACITY", P::CAPACITY);
+    let refill = limit_from_env(P::NAME, "REFILL_PER_MINUTE", P::REFILL_PER_MINUTE);
+    take_token(redis, &rate_limit_key(P::NAME, subject), capacity, refill).unwrap_or_else(|err| {
+        error!("Rate limit {} skipped, redis error {}", P::NAME, err);
+        None
+    })
+}
+
+/// Limits requests by a value that is only known once the body has been parsed,
+/// like the email or phone number a verification code is sent to.
+pub fn limit_subject<P: RateLimitPolicy>(
+    storage: &Storage,
+    subject: &str,
+) -> Result<(), APIInternalError> {
+    match consume::<P>(storage.redis.as_ref(), subject) {
+        None => Ok(()),
+        Some(retry_after) => Err(APIInternalError {
+            msg: TranslationIds::TooManyRequests,
+            engineering_error: Some(format!("Retry in {} seconds", retry_after)),
+        }),
+    }
+}
+
+impl<'a, 'r, P: RateLimitPolicy> FromRequest<'a, 'r> for RateLimit<P> {
+    type Error = APIJsonResponse;
+
+    fn from_request(request: &'a Request<'r>) -> request::Outcome<Self, Self::Error> {
+        let allowed = Outcome::Success(RateLimit {
+            policy: PhantomData,
+        });
+        let redis = match request.guard::<State<Storage>>() {
+            Outcome::Success(storage) => storage.redis.clone(),
+            _ => None,
+        };
+
+        let auth_info = request.guard::<AuthInfo>().succeeded();
+        let subject = auth_info
+            .as_ref()
+            .map(|info| format!("user.{}", info.username))
+            .or_else(|| client_address(request).map(|ip| format!("ip.{}", ip)))
+            .unwrap_or("ip.unknown".to_string());
+
+        match consume::<P>(redis.as_ref(), &subject) {
+            None => allowed,
+            Some(retry_after) => {
+                let language = auth_info.map(|info| info.language).unwrap_or_else(|| {
+                    request
+                        .guard::<Language>()
+                        .succeeded()
+                        .map_or("en".to_string(), |lang| lang.0)
+                });
+                let response = too_many_requests_error(&language);
+                request.local_cache(|| {
+                    Some(RateLimitExceeded {
+                        language,
+                        retry_after,
+                    })
+                });
+                Outcome::Failure((Status::TooManyRequests, response))
+            }
+        }
+    }
+}
+
+fn too_many_requests_error(language: &str) -> APIJsonResponse {
+    let mut response = APIJsonResponse::api_error_with_internal_error(
+        APIInternalError {
+            msg: TranslationIds::TooManyRequests,
+            engineering_error: None,
+        },
+        language,
+    );
+    response.status = Status::TooManyRequests;
+    response
+}
+
+/// Localized 429 response, includes the Retry-After header when known.
+pub struct TooManyRequests(pub Option<RateLimitExceeded>);
+
+impl TooManyRequests {
+    pub fn from_request(req: &Request) -> Self {
+        TooManyRequests(req.local_cache(|| None::<RateLimitExceeded>).clone())
+    }
+}
+
+impl<'r> Responder<'r> for TooManyRequests {
+    fn respond_to(self, req: &Request) -> response::Result<'r> {
+        let language = self
+            .0
+            .as_ref()
+            .map_or("en".to_string(), |exceeded| exceeded.language.clone());
+        let mut response =
+            Response::build_from(too_many_requests_error(&language).respond_to(req)?);
+        if let Some(exceeded) = self.0 {
+            response.header(Header::new("Retry-After", exceeded.retry_after.to_string()));
+        }
+        response.ok()
+    }
+}
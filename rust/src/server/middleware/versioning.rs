@@ -0,0 +1,55 @@
+use crate::model::versioning::ApiVersion;
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::request::{FromRequest, Outcome};
+use rocket::{request, Request, Response};
+use std::env;
+
+impl<'a, 'r> FromRequest<'a, 'r> for ApiVersion {
+    type Error = ();
+
+    fn from_request(request: &'a Request<'r>) -> request::Outcome<ApiVersion, Self::Error> {
+        Outcome::Success(ApiVersion::negotiate(request))
+    }
+}
+
+/// Adds the Deprecation and Sunset (RFC 8594) headers to the responses of
+/// deprecated versions, the sunset date of each version is read from the
+/// API_V<number>_SUNSET env var as an HTTP date, ie: Sat, 31 Jul 2021 23:59:59 GMT
+pub struct Deprecation {
+    sunsets: Vec<(ApiVersion, String)>,
+}
+
+impl Deprecation {
+    pub fn from_env() -> Self {
+        let sunsets = ApiVersion::ALL
+            .iter()
+            .filter_map(|version| {
+                env::var(format!("API_V{}_SUNSET", version.number()))
+                    .ok()
+                    .filter(|date| !date.trim().is_empty())
+                    .map(|date| (*version, date.trim().to_string()))
+            })
+            .collect();
+        Deprecation { sunsets }
+    }
+}
+
+impl Fairing for Deprecation {
+    fn info(&self) -> Info {
+        Info {
+            name: "API version deprecation",
+            kind: Kind::Response,
+        }
+    }
+
+    fn on_response(&self, request: &Request, response: &mut Response) {
+        let version = match ApiVersion::from_path(request.uri().path()) {
+            Some(version) => version,
+            None => return,
+        };
+        if let Some((_, sunset)) = self.sunsets.iter().find(|(v, _)| *v == version) {
+            response.set_raw_header("Deprecation", "true");
+            response.set_raw_header("Sunset", sunset.clone());
+        }
+    }
+}
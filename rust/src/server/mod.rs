@@ -1,5 +1,8 @@
+pub mod auth;
 pub mod emergency;
 pub mod http_gateway;
 pub mod invitations;
 pub mod middleware;
+pub mod openapi;
 pub mod validators;
+pub mod versioning;
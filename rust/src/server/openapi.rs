@@ -0,0 +1,392 @@
+use crate::constants::ASIMOV_LIVES;
+use crate::model::{
+    responses::{APIJsonResponse, APIResponse, Errors::APIError},
+    versioning::ApiVersion,
+    APIResult,
+};
+use rocket::http::Method;
+use rocket::response::{Content, Stream};
+use rocket::{Rocket, Route, State};
+use rocket_contrib::json::Json;
+use schemars::gen::{SchemaGenerator, SchemaSettings};
+use schemars::schema::Schema;
+use schemars::JsonSchema;
+use serde_json::{json, Map, Value};
+use std::any::type_name;
+use std::io::Read;
+
+type SchemaFn = fn(&mut SchemaGenerator) -> Schema;
+
+fn schema<T: JsonSchema>(gen: &mut SchemaGenerator) -> Schema {
+    gen.subschema_for::<T>()
+}
+
+/// Route handlers of any number of arguments, gives the type they return so
+/// `drift` can compare it with the documented response.
+pub trait Handler<Args> {
+    fn response_type(&self) -> &'static str;
+}
+
+macro_rules! impl_handler {
+    ($($arg:ident),*) => {
+        impl<F, R, $($arg),*> Handler<($($arg,)*)> for F
+        where
+            F: Fn($($arg),*) -> R,
+        {
+            fn response_type(&self) -> &'static str {
+                type_name::<R>()
+            }
+        }
+    };
+}
+
+impl_handler!();
+impl_handler!(A);
+impl_handler!(A, B);
+impl_handler!(A, B, C);
+impl_handler!(A, B, C, D);
+impl_handler!(A, B, C, D, E);
+impl_handler!(A, B, C, D, E, G);
+impl_handler!(A, B, C, D, E, G, H);
+impl_handler!(A, B, C, D, E, G, H, I);
+impl_handler!(A, B, C, D, E, G, H, I, J);
+
+/// Documentation of a route handler, matched with the mounted routes by the
+/// handler name, so every version that shares a handler shares its documentation.
+pub struct Operation {
+    name: &'static str,
+    summary: &'static str,
+    body: Option<SchemaFn>,
+    response: Option<SchemaFn>,
+    /// Type the handler must return to produce `response`.
+    response_type: Option<&'static str>,
+    /// Type the handler actually returns.
+    handler_type: Option<&'static str>,
+    /// Path and query parameters, `Option` types are not required.
+    parameters: Vec<(&'static str, SchemaFn)>,
+    media_type: &'static str,
+    public: bool,
+}
+
+impl Operation {
+    pub fn new(name: &'static str, summary: &'static str) -> Self {
+        Operation {
+            name,
+            summary,
+            body: None,
+            response: None,
+            response_type: None,
+            handler_type: None,
+            parameters: Vec::new(),
+            media_type: "application/json",
+            public: false,
+        }
+    }
+
+    /// The function of the routes, checked against the documented response.
+    pub fn handler<Args, H: Handler<Args>>(mut self, handler: H) -> Self {
+        self.handler_type = Some(handler.response_type());
+        self
+    }
+
+    /// Path or query parameter of the routes, ie: `.param::<Option<i64>>("limit")`
+    pub fn param<T: JsonSchema>(mut self, name: &'static str) -> Self {
+        self.parameters.push((name, schema::<T>));
+        self
+    }
+
+    /// JSON body of the request.
+    pub fn body<T: JsonSchema>(mut self) -> Self {
+        self.body = Some(schema::<T>);
+        self
+    }
+
+    /// Result of handlers returning `APIResult<T>`.
+    pub fn returns<T: JsonSchema>(mut self) -> Self {
+        self.response = Some(schema::<APIResponse<Option<T>>>);
+        self.response_type = Some(type_name::<APIResult<T>>());
+        self
+    }
+
+    /// Handlers that return a JSON type other than `APIResult<T>`.
+    pub fn returns_json<T: JsonSchema>(mut self) -> Self {
+        self.response = Some(schema::<T>);
+        self.response_type = Some(type_name::<Result<Json<T>, APIJsonResponse>>());
+        self
+    }
+
+    /// Handlers that stream newline delimited JSON from `S`, `T` is the type of every line.
+    pub fn streams<T: JsonSchema, S: Read>(mut self) -> Self {
+        self.response = Some(schema::<T>);
+        self.response_type = Some(type_name::<Result<Content<Stream<S>>, APIJsonResponse>>());
+        self.media_type = "application/x-ndjson";
+        self
+    }
+
+    /// Does not require the asimovlives token.
+    pub fn public(mut self) -> Self {
+        self.public = true;
+        self
+    }
+}
+
+/// The document generated for a rocket instance, served at /openapi.json
+pub struct OpenApiDocument(pub Value);
+
+#[get("/openapi.json")]
+fn openapi(document: State<OpenApiDocument>) -> Json<Value> {
+    Json(document.0.clone())
+}
+
+/// Differences between the mounted routes and their documentation.
+#[derive(Debug, Default, PartialEq)]
+pub struct OpenApiDrift {
+    /// Mounted routes without an operation, ie: GET /v1/invitations/<id>/creator
+    pub undocumented: Vec<String>,
+    /// Operations that do not match any mounted route.
+    pub unused: Vec<&'static str>,
+    /// Routes that accept a JSON body without documenting it or the other way around.
+    pub body_mismatch: Vec<String>,
+    /// Routes whose path and query parameters are not the documented ones.
+    pub parameter_mismatch: Vec<String>,
+    /// Operations whose handler does not return the documented response.
+    pub response_mismatch: Vec<&'static str>,
+}
+
+impl OpenApiDrift {
+    pub fn is_empty(&self) -> bool {
+        self == &OpenApiDrift::default()
+    }
+}
+
+fn documented_routes(rocket: &Rocket) -> Vec<&Route> {
+    rocket
+        .routes()
+        .filter(|route| route.name != Some("openapi") && route.method != Method::Options)
+        .collect()
+}
+
+pub fn drift(rocket: &Rocket, operations: &[Operation]) -> OpenApiDrift {
+    let routes = documented_routes(rocket);
+    let find = |route: &Route| {
+        operations
+            .iter()
+            .find(|operation| route.name == Some(operation.name))
+    };
+    let mut drift = OpenApiDrift::default();
+    for route in &routes {
+        let operation = match find(route) {
+            Some(operation) => operation,
+            None => {
+                drift
+                    .undocumented
+                    .push(format!("{} {}", route.method, route.uri));
+                continue;
+            }
+        };
+        if route.format.is_some() != operation.body.is_some() {
+            drift
+                .body_mismatch
+                .push(format!("{} {}", route.method, route.uri));
+        }
+        let mut names = route_parameters(route)
+            .map(|(name, _)| name)
+            .collect::<Vec<&str>>();
+        let mut documented = operation
+            .parameters
+            .iter()
+            .map(|(name, _)| *name)
+            .collect::<Vec<&str>>();
+        names.sort_unstable();
+        documented.sort_unstable();
+        if names != documented {
+            drift
+                .parameter_mismatch
+                .push(format!("{} {}", route.method, route.uri));
+        }
+    }
+    drift.response_mismatch = operations
+        .iter()
+        .filter(|operation| {
+            operation.response_type.is_none() || operation.response_type != operation.handler_type
+        })
+        .map(|operation| operation.name)
+        .collect();
+    drift.unused = operations
+        .iter()
+        .filter(|operation| {
+            !routes
+                .iter()
+                .any(|route| route.name == Some(operation.name))
+        })
+        .map(|operation| operation.name)
+        .collect();
+    drift
+}
+
+/// /v1/emergency/<username>/telemetry => /v1/emergency/{username}/telemetry
+fn openapi_path(path: &str) -> String {
+    path.split('/')
+        .map(|segment| {
+            if segment.starts_with('<') && segment.ends_with('>') {
+                format!(
+                    "{{{}}}",
+                    segment.trim_matches(|c| c == '<' || c == '>' || c == '.')
+                )
+            } else {
+                segment.to_string()
+            }
+        })
+        .collect::<Vec<String>>()
+        .join("/")
+}
+
+/// Names of the dynamic segments of the route with their location.
+fn route_parameters<'a>(route: &'a Route) -> impl Iterator<Item = (&'a str, &'static str)> {
+    let name = |segment: &'a str| segment.trim_matches(|c| c == '<' || c == '>' || c == '.');
+    let path = route
+        .uri
+        .path()
+        .split('/')
+        .filter(|segment| segment.starts_with('<'))
+        .map(move |segment| (name(segment), "path"));
+    let query = route
+        .uri
+        .query()
+        .unwrap_or("")
+        .split('&')
+        .filter(|segment| segment.starts_with('<'))
+        .map(move |segment| (name(segment), "query"));
+    path.chain(query)
+}
+
+/// Parameters take the type documented in the operation, a parameter is
+/// required unless its type is an `Option`. Path parameters are always required.
+fn parameters(
+    route: &Route,
+    operation: Option<&Operation>,
+    gen: &mut SchemaGenerator,
+) -> Vec<Value> {
+    route_parameters(route)
+        .map(|(name, location)| {
+            let documented = operation.and_then(|operation| {
+                operation
+                    .parameters
+                    .iter()
+                    .find(|(parameter, _)| *parameter == name)
+                    .map(|(_, schema)| *schema)
+            });
+            let (schema, optional) = match documented {
+                Some(schema) => {
+                    let mut schema = schema(gen).into_object();
+                    let optional = schema.extensions.remove("nullable").is_some();
+                    (Schema::Object(schema), optional)
+                }
+                None => (gen.subschema_for::<String>(), false),
+            };
+            json!({
+                "name": name,
+                "in": location,
+                "required": location == "path" || !optional,
+                "schema": schema
+            })
+        })
+        .collect()
+}
+
+fn json_content(schema: Schema) -> Value {
+    content("application/json", schema)
+}
+
+fn content(media_type: &str, schema: Schema) -> Value {
+    json!({ media_type: { "schema": schema } })
+}
+
+/// Builds the OpenAPI 3 document of the routes mounted in `rocket`,
+/// routes without an operation are listed without schemas, see `drift`.
+pub fn generate(rocket: &Rocket, title: &str, operations: &[Operation]) -> Value {
+    let mut gen = SchemaSettings::openapi3().into_generator();
+    let error = schema::<APIResponse<APIError>>(&mut gen);
+    let mut paths = Map::new();
+
+    for route in documented_routes(rocket) {
+        let operation = operations
+            .iter()
+            .find(|operation| route.name == Some(operation.name));
+        let version = ApiVersion::from_path(route.uri.path());
+        let operation_id = match (route.name, version) {
+            (Some(name), Some(version)) => format!("{}_v{}", name, version.number()),
+            (Some(name), None) => name.to_string(),
+            _ => format!("{} {}", route.method, route.uri.path()),
+        };
+
+        let mut item = json!({
+            "operationId": operation_id,
+            "summary": operation.map_or("", |operation| operation.summary),
+            "parameters": parameters(route, operation, &mut gen),
+            "responses": {
+                "200": {
+                    "description": "Success, v1 clients also receive errors with this status"
+                },
+                "default": {
+                    "description": "Error, see the error code catalog",
+                    "content": json_content(error.clone())
+                }
+            }
+        });
+        if let Some(operation) = operation {
+            if let Some(response) = operation.response {
+                item["responses"]["200"]["content"] =
+                    content(operation.media_type, response(&mut gen));
+            }
+        }
+        if let Some(body) = operation.and_then(|operation| operation.body) {
+            item["requestBody"] = json!({
+                "required": true,
+                "content": json_content(body(&mut gen))
+            });
+        }
+        if !operation.map_or(false, |operation| operation.public) {
+            item["security"] = json!([{ ASIMOV_LIVES: [] }]);
+        }
+
+        paths
+            .entry(openapi_path(route.uri.path()))
+            .or_insert_with(|| json!({}))[route.method.as_str().to_lowercase()] = item;
+    }
+
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": title,
+            "version": env!("CARGO_PKG_VERSION")
+        },
+        "paths": paths,
+        "components": {
+            "schemas": gen.take_definitions(),
+            "securitySchemes": {
+                ASIMOV_LIVES: {
+                    "type": "apiKey",
+                    "in": "header",
+                    "name": ASIMOV_LIVES
+                }
+            }
+        }
+    })
+}
+
+/// Mounts GET /openapi.json with the document of every route mounted so far.
+pub fn mount_openapi(rocket: Rocket, title: &str, operations: &[Operation]) -> Rocket {
+    let drift = drift(&rocket, operations);
+    if !drift.is_empty() {
+        // Every test that builds the rocket fails, release builds keep serving.
+        if cfg!(debug_assertions) {
+            panic!("OpenAPI document of {} is out of date {:?}", title, drift);
+        }
+        error!("OpenAPI document of {} is out of date {:?}", title, drift);
+    }
+    let document = generate(&rocket, title, operations);
+    rocket
+        .manage(OpenApiDocument(document))
+        .mount("/", routes![openapi])
+}
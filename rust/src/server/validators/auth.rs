@@ -0,0 +1,113 @@
+use crate::lang::TranslationIds;
+use crate::model::{
+    auth::VerificationTarget,
+    requests::{LoginRequest, RegistrationRequest, VerificationRequest},
+    responses::Errors::APIInternalError,
+};
+use regex::Regex;
+
+lazy_static! {
+    static ref USERNAME_REGEX: Regex = Regex::new(r"^[a-z0-9_.\-]{3,255}$").unwrap();
+    static ref EMAIL_REGEX: Regex = Regex::new(r"^[^@\s]+@[^@\s]+\.[^@\s]+$").unwrap();
+    static ref PHONE_REGEX: Regex = Regex::new(r"^\+[1-9][0-9]{6,14}$").unwrap();
+}
+
+fn invalid(msg: TranslationIds, engineering_error: &str) -> APIInternalError {
+    APIInternalError {
+        msg,
+        engineering_error: Some(engineering_error.to_string()),
+    }
+}
+
+pub fn sanitize_email(email: &str) -> Result<String, APIInternalError> {
+    let email = email.trim().to_lowercase();
+    if email.len() > 255 || !EMAIL_REGEX.is_match(&email) {
+        return Err(invalid(TranslationIds::InvalidEmail, "Invalid email"));
+    }
+    Ok(email)
+}
+
+/// Strips the separators clients usually send and expects an E.164 number.
+pub fn sanitize_phone(phone: &str) -> Result<String, APIInternalError> {
+    let phone: String = phone
+        .trim()
+        .chars()
+        .filter(|c| !c.is_whitespace() && !['-', '(', ')', '.'].contains(c))
+        .collect();
+    if !PHONE_REGEX.is_match(&phone) {
+        return Err(invalid(
+            TranslationIds::InvalidPhoneNumber,
+            "Phone number must be in E.164 format",
+        ));
+    }
+    Ok(phone)
+}
+
+/// Accepts the raw path segment of the verify endpoint, which can be either.
+pub fn sanitize_email_or_phone(value: &str) -> Result<VerificationTarget, APIInternalError> {
+    if value.contains('@') {
+        sanitize_email(value).map(VerificationTarget::Email)
+    } else {
+        sanitize_phone(value).map(VerificationTarget::Phone)
+    }
+}
+
+fn target_from(
+    email: &Option<String>,
+    phone_number: &Option<String>,
+) -> Result<VerificationTarget, APIInternalError> {
+    match (email, phone_number) {
+        (Some(email), _) => sanitize_email(email).map(VerificationTarget::Email),
+        (None, Some(phone)) => sanitize_phone(phone).map(VerificationTarget::Phone),
+        (None, None) => Err(invalid(
+            TranslationIds::BadRequest,
+            "email and phone number were both null",
+        )),
+    }
+}
+
+fn assert_valid_public_key(public_key: &str) -> Result<(), APIInternalError> {
+    let len = public_key.trim().len();
+    if len < 3 || len > 4000 {
+        return Err(invalid(TranslationIds::BadRequest, "Invalid public key"));
+    }
+    Ok(())
+}
+
+pub fn assert_name_length(name: &str, msg: TranslationIds) -> Result<(), APIInternalError> {
+    let len = name.trim().chars().count();
+    if len < 3 || len > 255 {
+        return Err(invalid(msg, "Name must be between 3 and 255 characters"));
+    }
+    Ok(())
+}
+
+pub fn assert_valid_registration(
+    req: &RegistrationRequest,
+) -> Result<VerificationTarget, APIInternalError> {
+    if !USERNAME_REGEX.is_match(&req.username.trim().to_lowercase()) {
+        return Err(invalid(TranslationIds::InvalidUsername, "Invalid username"));
+    }
+    assert_name_length(&req.firstName, TranslationIds::InvalidFirstName)?;
+    assert_name_length(&req.lastName, TranslationIds::InvalidLastName)?;
+    assert_valid_public_key(&req.publicKey)?;
+    target_from(&req.email, &req.phoneNumber)
+}
+
+pub fn assert_valid_login(req: &LoginRequest) -> Result<VerificationTarget, APIInternalError> {
+    assert_valid_public_key(&req.publicKey)?;
+    target_from(&req.email, &req.phoneNumber)
+}
+
+pub fn assert_valid_verification(req: &VerificationRequest) -> Result<(), APIInternalError> {
+    assert_valid_public_key(&req.publicKey)?;
+    let code_len = req.code.trim().len();
+    if code_len < 3 || code_len > 44 {
+        return Err(invalid(TranslationIds::VerificationFailure, "Invalid code"));
+    }
+    let device_len = req.deviceId.trim().len();
+    if device_len < 5 || device_len > 100 {
+        return Err(invalid(TranslationIds::BadRequest, "DeviceId is too short"));
+    }
+    Ok(())
+}
@@ -1,14 +1,18 @@
 use crate::lang::TranslationIds;
+use crate::model::emergency::{EmergencyPeriod, EmergencySettings};
 use crate::model::responses::Errors::APIInternalError;
 use chrono::Duration;
 use chrono::NaiveDateTime;
-use chrono::Utc;
 
+/// The history starts `historical_access_hours` before the emergency began.
 pub fn assert_valid_location_historical_start(
     start_time: &NaiveDateTime,
+    emergency: &EmergencyPeriod,
+    settings: &EmergencySettings,
 ) -> Result<(), APIInternalError> {
-    let week_ago: &NaiveDateTime = &(Utc::now() - Duration::weeks(1)).naive_utc();
-    if start_time < week_ago {
+    let history_start =
+        emergency.started_at - Duration::hours(settings.historicalAccessHours.into());
+    if start_time < &history_start {
         return Err(APIInternalError {
             msg: TranslationIds::InvalidHistoricalLocationStartTime,
             engineering_error: None,
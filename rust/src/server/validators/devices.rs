@@ -0,0 +1,47 @@
+use crate::constants::{APNS_TOKEN_MIN_LENGTH, FCM_TOKEN_MIN_LENGTH, PUSH_TOKEN_MAX_LENGTH};
+use crate::lang::TranslationIds;
+use crate::model::{devices::OS, responses::Errors::APIInternalError};
+
+fn bad_request(engineering_error: String) -> APIInternalError {
+    APIInternalError {
+        msg: TranslationIds::BadRequest,
+        engineering_error: Some(engineering_error),
+    }
+}
+
+/// APNs tokens are hex strings, FCM registration tokens are url safe base64 with a
+/// `:` separating the instance id. Devices of an unknown OS must send either of them.
+pub fn assert_valid_push_token(push_token: &str, os: &OS) -> Result<(), APIInternalError> {
+    if push_token.len() > PUSH_TOKEN_MAX_LENGTH {
+        return Err(bad_request(format!(
+            "pushToken must have at most {} characters",
+            PUSH_TOKEN_MAX_LENGTH
+        )));
+    }
+    match os {
+        OS::iOS => {
+            if push_token.len() < APNS_TOKEN_MIN_LENGTH
+                || push_token.len() % 2 != 0
+                || !push_token.chars().all(|c| c.is_ascii_hexdigit())
+            {
+                return Err(bad_request(format!(
+                    "pushToken must be an APNs device token of at least {} hex characters",
+                    APNS_TOKEN_MIN_LENGTH
+                )));
+            }
+        }
+        OS::Android | OS::UNKNOWN => {
+            if push_token.len() < FCM_TOKEN_MIN_LENGTH
+                || !push_token
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | ':'))
+            {
+                return Err(bad_request(format!(
+                    "pushToken must be an FCM registration token of at least {} characters",
+                    FCM_TOKEN_MIN_LENGTH
+                )));
+            }
+        }
+    }
+    Ok(())
+}
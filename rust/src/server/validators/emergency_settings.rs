@@ -0,0 +1,28 @@
+use crate::constants::{MAX_HISTORICAL_ACCESS_HOURS, MAX_HISTORICAL_GRACE_HOURS};
+use crate::lang::TranslationIds;
+use crate::model::{emergency::EmergencySettings, responses::Errors::APIInternalError};
+
+fn bad_request(engineering_error: String) -> APIInternalError {
+    APIInternalError {
+        msg: TranslationIds::BadRequest,
+        engineering_error: Some(engineering_error),
+    }
+}
+
+pub fn assert_valid_emergency_settings(
+    settings: &EmergencySettings,
+) -> Result<(), APIInternalError> {
+    if !(1..=MAX_HISTORICAL_ACCESS_HOURS).contains(&settings.historicalAccessHours) {
+        return Err(bad_request(format!(
+            "historicalAccessHours must be between 1 and {}",
+            MAX_HISTORICAL_ACCESS_HOURS
+        )));
+    }
+    if !(0..=MAX_HISTORICAL_GRACE_HOURS).contains(&settings.historicalGraceHours) {
+        return Err(bad_request(format!(
+            "historicalGraceHours must be between 0 and {}",
+            MAX_HISTORICAL_GRACE_HOURS
+        )));
+    }
+    Ok(())
+}
@@ -1,20 +1,35 @@
 use crate::lang::TranslationIds;
-use crate::model::{emergency::UserState, responses::Errors::APIInternalError, PostgresConnection};
+use crate::model::{
+    emergency::{EmergencyPeriod, EmergencySettings},
+    responses::Errors::APIInternalError,
+    PostgresConnection,
+};
 
-pub fn assert_emergency_user(
+/// The ongoing emergency of the user, or the last one if it ended
+/// less than `historical_grace_hours` ago.
+pub fn assert_readable_emergency(
     conn: &mut PostgresConnection,
     username: &String,
-) -> Result<(), APIInternalError> {
+    settings: &EmergencySettings,
+) -> Result<EmergencyPeriod, APIInternalError> {
     conn.query(
-        "SELECT * FROM users_state WHERE username = $1 AND self_perception = $2",
-        &[username, &UserState::Emergency],
+        "SELECT started_at, ended_at FROM users_emergency_periods
+         WHERE username = $1
+         AND (ended_at IS NULL OR ended_at + make_interval(hours => $2) > now()::timestamp)
+         ORDER BY started_at DESC LIMIT 1",
+        &[username, &i32::from(settings.historicalGraceHours)],
     )
-    .map_err(|w| APIInternalError::from_db_err(w))
+    .map_err(APIInternalError::from_db_err)
     .and_then(|rows| {
-        rows.into_iter().next().ok_or(APIInternalError {
-            msg: TranslationIds::UserNotInEmergency,
-            engineering_error: None,
-        })
+        rows.into_iter()
+            .next()
+            .map(|row| EmergencyPeriod {
+                started_at: row.get("started_at"),
+                ended_at: row.get("ended_at"),
+            })
+            .ok_or(APIInternalError {
+                msg: TranslationIds::UserNotInEmergency,
+                engineering_error: None,
+            })
     })
-    .and_then(|_| Ok(()))
 }
@@ -1,6 +1,9 @@
+use super::auth::{sanitize_email, sanitize_phone};
 use crate::lang::TranslationIds;
 use crate::model::{
+    auth::VerificationTarget,
     invitations::{InvitationState, LinkActionData},
+    requests::DirectInvitationRequest,
     responses::Errors::APIInternalError,
     PostgresConnection,
 };
@@ -12,7 +15,29 @@ pub fn assert_valid_invitation(
     data: &LinkActionData,
 ) -> Result<(), APIInternalError> {
     let row = get_invitation(conn, &data.uuid)?;
-    assert_invitation_creator(&row, &data.username).and_then(|_| assert_link_state(conn, &row))
+    assert_invitation_creator(&row, &data.username)
+        .and_then(|_| assert_not_used_by(conn, &data))
+        .and_then(|_| assert_link_state(conn, &row))
+}
+
+/// Links with several uses can only be accepted once by each person.
+fn assert_not_used_by(
+    conn: &mut PostgresConnection,
+    data: &LinkActionData,
+) -> Result<(), APIInternalError> {
+    let used = conn
+        .query_opt(
+            "SELECT 1 FROM link_invitations_uses WHERE invitation_id = $1 AND username = $2",
+            &[&data.uuid, &data.username],
+        )
+        .map_err(APIInternalError::from_db_err)?;
+    if used.is_some() {
+        return Err(APIInternalError {
+            msg: TranslationIds::InvitationsInvitationAlreadyUsed,
+            engineering_error: None,
+        });
+    }
+    Ok(())
 }
 
 fn assert_invitation_creator(row: &Row, username: &str) -> Result<(), APIInternalError> {
@@ -30,6 +55,10 @@ fn assert_link_state(conn: &mut PostgresConnection, row: &Row) -> Result<(), API
     let state: InvitationState = row.get("state");
     match state {
         InvitationState::CREATED => assert_exp_date(conn, row),
+        InvitationState::REVOKED => Err(APIInternalError {
+            msg: TranslationIds::InvitationsInvitationWasRevoked,
+            engineering_error: None,
+        }),
         _ => Err(APIInternalError {
             msg: TranslationIds::InvitationsInvitationIsNoLongerValid,
             engineering_error: None,
@@ -80,3 +109,17 @@ fn set_invitation_expired(
         })
     })
 }
+
+/// Direct invitations are sent to either an email or a phone number.
+pub fn assert_valid_direct_invitation(
+    req: &DirectInvitationRequest,
+) -> Result<VerificationTarget, APIInternalError> {
+    match (&req.email, &req.phoneNumber) {
+        (Some(email), None) => sanitize_email(email).map(VerificationTarget::Email),
+        (None, Some(phone)) => sanitize_phone(phone).map(VerificationTarget::Phone),
+        _ => Err(APIInternalError {
+            msg: TranslationIds::BadRequest,
+            engineering_error: Some("Exactly one of email or phoneNumber is required".to_string()),
+        }),
+    }
+}
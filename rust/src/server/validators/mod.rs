@@ -1,4 +1,9 @@
+pub mod auth;
 pub mod datetime;
+pub mod devices;
+pub mod emergency_settings;
 pub mod emergency_user;
 pub mod friends;
 pub mod invitations;
+pub mod profile;
+pub mod telemetry;
@@ -0,0 +1,69 @@
+use super::auth::assert_name_length;
+use crate::constants::{PROFILE_PICTURE_MAX_BYTES, PROFILE_PICTURE_MAX_DIMENSION};
+use crate::lang::{TranslationIds, SUPPORTED_LANGUAGES};
+use crate::model::{requests::ProfileUpdateRequest, responses::Errors::APIInternalError};
+use image::{io::Reader, ImageFormat};
+use std::io::Cursor;
+
+pub fn assert_valid_profile_update(req: &ProfileUpdateRequest) -> Result<(), APIInternalError> {
+    if let Some(first_name) = &req.firstName {
+        assert_name_length(first_name, TranslationIds::InvalidFirstName)?;
+    }
+    if let Some(last_name) = &req.lastName {
+        assert_name_length(last_name, TranslationIds::InvalidLastName)?;
+    }
+    if let Some(language) = &req.language {
+        if !SUPPORTED_LANGUAGES.contains(&language.as_str()) {
+            return Err(APIInternalError {
+                msg: TranslationIds::BadRequest,
+                engineering_error: Some(format!(
+                    "language must be one of {}",
+                    SUPPORTED_LANGUAGES.join(", ")
+                )),
+            });
+        }
+    }
+    Ok(())
+}
+
+fn invalid_picture(engineering_error: String) -> APIInternalError {
+    APIInternalError {
+        msg: TranslationIds::InvalidProfilePicture,
+        engineering_error: Some(engineering_error),
+    }
+}
+
+/// The declared content type must match the decoded format, and the dimensions
+/// are checked from the header so huge images are never decoded.
+pub fn assert_valid_profile_picture(
+    content_type: Option<&str>,
+    bytes: &[u8],
+) -> Result<ImageFormat, APIInternalError> {
+    if bytes.is_empty() || bytes.len() as u64 > PROFILE_PICTURE_MAX_BYTES {
+        return Err(invalid_picture(format!(
+            "picture must have between 1 and {} bytes",
+            PROFILE_PICTURE_MAX_BYTES
+        )));
+    }
+    let declared = match content_type {
+        Some("image/jpeg") => ImageFormat::Jpeg,
+        Some("image/png") => ImageFormat::Png,
+        other => {
+            return Err(invalid_picture(format!(
+                "unsupported content type {:?}",
+                other
+            )))
+        }
+    };
+    let reader = Reader::with_format(Cursor::new(bytes), declared);
+    let (width, height) = reader.into_dimensions().map_err(|err| {
+        invalid_picture(format!("picture is not a valid {:?}: {}", declared, err))
+    })?;
+    if width > PROFILE_PICTURE_MAX_DIMENSION || height > PROFILE_PICTURE_MAX_DIMENSION {
+        return Err(invalid_picture(format!(
+            "picture is {}x{}, the maximum is {}x{}",
+            width, height, PROFILE_PICTURE_MAX_DIMENSION, PROFILE_PICTURE_MAX_DIMENSION
+        )));
+    }
+    Ok(declared)
+}
@@ -0,0 +1,38 @@
+use crate::constants::{TELEMETRY_MAX_BACKFILL_DAYS, TELEMETRY_MAX_CLOCK_SKEW_SECONDS};
+use crate::lang::TranslationIds;
+use crate::model::{requests::TelemetryRequest, responses::Errors::APIInternalError};
+use chrono::{DateTime, Duration, Utc};
+
+const MAX_IDEMPOTENCY_KEY_LENGTH: usize = 128;
+
+fn bad_request(engineering_error: &str) -> APIInternalError {
+    APIInternalError {
+        msg: TranslationIds::BadRequest,
+        engineering_error: Some(engineering_error.to_string()),
+    }
+}
+
+pub fn assert_valid_telemetry_request(req: &TelemetryRequest) -> Result<(), APIInternalError> {
+    if let Some(key) = &req.idempotencyKey {
+        if key.trim().is_empty() || key.len() > MAX_IDEMPOTENCY_KEY_LENGTH {
+            return Err(bad_request("Invalid idempotency key"));
+        }
+    }
+    if let Some(timestamp) = &req.clientTimestamp {
+        DateTime::parse_from_rfc3339(timestamp)
+            .map_err(|_| bad_request("Invalid client timestamp"))?;
+    }
+    let now = Utc::now();
+    let oldest = now - Duration::days(TELEMETRY_MAX_BACKFILL_DAYS);
+    let newest = now + Duration::seconds(TELEMETRY_MAX_CLOCK_SKEW_SECONDS);
+    for update in &req.telemetry {
+        if update.timestamp.is_none() {
+            continue;
+        }
+        match update.captured_at() {
+            Some(captured_at) if captured_at >= oldest && captured_at <= newest => {}
+            _ => return Err(bad_request("Invalid telemetry timestamp")),
+        }
+    }
+    Ok(())
+}
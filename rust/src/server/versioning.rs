@@ -0,0 +1,16 @@
+use crate::model::versioning::ApiVersion;
+use rocket::{Rocket, Route};
+
+/// Mounts the route set of every version under /<version><base>, ie: /v2/invitations.
+/// Versions can share handlers, only routes whose contract changed need a new one.
+pub fn mount_versions(
+    rocket: Rocket,
+    base: &str,
+    versions: Vec<(ApiVersion, Vec<Route>)>,
+) -> Rocket {
+    versions
+        .into_iter()
+        .fold(rocket, |rocket, (version, routes)| {
+            rocket.mount(&format!("{}{}", version.prefix(), base), routes)
+        })
+}
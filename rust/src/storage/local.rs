@@ -0,0 +1,35 @@
+use super::PictureStorage;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+
+pub struct LocalPictureStorage {
+    root: PathBuf,
+}
+
+impl LocalPictureStorage {
+    pub fn new<P: Into<PathBuf>>(root: P) -> Self {
+        LocalPictureStorage { root: root.into() }
+    }
+}
+
+impl PictureStorage for LocalPictureStorage {
+    fn put(&self, key: &str, bytes: &[u8]) -> Result<(), String> {
+        fs::create_dir_all(&self.root).map_err(|err| err.to_string())?;
+        // Written next to the destination and renamed, readers never see half a picture.
+        let tmp = self.root.join(format!(".{}.tmp", key));
+        fs::write(&tmp, bytes).map_err(|err| err.to_string())?;
+        fs::rename(&tmp, self.root.join(key)).map_err(|err| err.to_string())
+    }
+
+    fn exists(&self, key: &str) -> bool {
+        self.root.join(key).is_file()
+    }
+
+    fn delete(&self, key: &str) -> Result<(), String> {
+        match fs::remove_file(self.root.join(key)) {
+            Err(err) if err.kind() != ErrorKind::NotFound => Err(err.to_string()),
+            _ => Ok(()),
+        }
+    }
+}
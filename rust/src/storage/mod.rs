@@ -0,0 +1,34 @@
+pub mod local;
+
+use crate::constants::CS_PROFILE_IMAGE_PATH;
+use local::LocalPictureStorage;
+use std::env;
+
+/// Where the profile pictures are written. Keys are content addressed, so a key
+/// always refers to the same bytes and can be cached forever. Users that upload
+/// the same picture share its keys, callers delete them once nobody uses them.
+pub trait PictureStorage: Send + Sync {
+    /// Stores the bytes under `key`, overwriting is a no-op since the key is the hash.
+    fn put(&self, key: &str, bytes: &[u8]) -> Result<(), String>;
+
+    fn exists(&self, key: &str) -> bool;
+
+    /// Removes `key`, deleting a missing key is not an error.
+    fn delete(&self, key: &str) -> Result<(), String>;
+}
+
+pub type PictureStore = Box<dyn PictureStorage>;
+
+/// PROFILE_PICTURES_DIR is a local directory in dev and tests and the mount
+/// of the pictures bucket in production.
+pub fn picture_storage_from_env() -> PictureStore {
+    let root = env::var("PROFILE_PICTURES_DIR").unwrap_or_else(|_| "pictures".to_string());
+    Box::new(LocalPictureStorage::new(root))
+}
+
+/// Public url of a picture stored in user_details.picture.
+pub fn picture_url(key: &str) -> String {
+    let base_url =
+        env::var("PROFILE_PICTURES_URL").unwrap_or_else(|_| CS_PROFILE_IMAGE_PATH.to_string());
+    format!("{}/{}", base_url.trim_end_matches('/'), key)
+}
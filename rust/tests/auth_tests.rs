@@ -0,0 +1,277 @@
+#[macro_use]
+extern crate pretty_assertions;
+
+use amiquip::Connection;
+use lib::constants::{ASIMOV_LIVES, VERIFICATION_CODE_MAX_ATTEMPTS};
+use lib::db::get_pool;
+use lib::messaging::get_rabbitmq_uri;
+use lib::server::auth::rocket;
+use rocket::http::{Header, Status};
+use rocket::local::Client;
+use rocket_contrib::json;
+
+mod common;
+
+use common::{
+    auth::{create_token, register_mock_user, MOCK_PUBLIC_KEY},
+    client::test_client,
+    dbmate::dbmate_rebuild,
+    rabbit::{bind_notifications_queue, consume_message},
+};
+
+fn last_verification_code(email_or_phone: &str) -> String {
+    let pool = get_pool();
+    let mut conn = pool.get().unwrap();
+    conn.query_one(
+        "SELECT verification_code FROM users_verification
+         WHERE email = $1 OR phone_number = $1
+         ORDER BY creation_timestamp DESC LIMIT 1",
+        &[&email_or_phone.to_string()],
+    )
+    .unwrap()
+    .get("verification_code")
+}
+
+fn register_body(username: &str, email: &str) -> String {
+    json!({
+        "username": username,
+        "firstName": "Joe",
+        "lastName": "Rogan",
+        "email": email,
+        "publicKey": MOCK_PUBLIC_KEY
+    })
+    .to_string()
+}
+
+fn verify_body(code: &str, device_id: &str) -> String {
+    json!({
+        "code": code,
+        "publicKey": MOCK_PUBLIC_KEY,
+        "deviceId": device_id,
+        "os": "iOS",
+        "osVersion": "14.4",
+        "model": "iPhone 12",
+        "appVersion": "1.0.0"
+    })
+    .to_string()
+}
+
+#[test]
+fn test_register_with_email() {
+    let client = test_client(rocket(), &[]);
+
+    let mut rabbit = Connection::insecure_open(&get_rabbitmq_uri()).unwrap();
+    let channel = rabbit.open_channel(None).unwrap();
+    let queue = bind_notifications_queue(&channel);
+
+    let mut request = client.post("/v1/auth/register");
+    request.add_header(Header::new("Content-Type", "application/json"));
+    request.set_body(register_body("joerogan", "Joe@Rogan.com"));
+    let mut response = request.dispatch();
+
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(
+        &response.body_string().unwrap(),
+        r#"{"success":true,"result":{"message":"Successfully created verification request"}}"#
+    );
+
+    let code = last_verification_code("joe@rogan.com");
+    assert_eq!(code.len(), 5);
+
+    let message = consume_message(&queue);
+    assert_eq!(
+        String::from_utf8_lossy(&message),
+        format!(
+            "[{{\"dynamicTemplateData\":{{\"body\":\"Hello Joe, please click the link below or use the code \
+            to verify that you are the owner of this account.\",\"code\":\"{0}\",\"link\":\"https://armore.dev/user/verify/{0}\",\
+            \"linkTitle\":\"Verify\",\"picture\":null,\"title\":\"Armore: Email Verification Required\"}},\
+            \"email\":\"joe@rogan.com\",\"templateId\":\"d-fac72b3d96894b5bb5a0f5944102f891\",\"username\":\"joerogan\"}}]",
+            code
+        )
+    );
+}
+
+#[test]
+fn test_register_with_taken_username() {
+    let client = test_client(rocket(), &[]);
+    let mut request = client.post("/v1/auth/register");
+    request.add_header(Header::new("Content-Type", "application/json"));
+    request.set_body(register_body("dario", "someone@else.com"));
+    let mut response = request.dispatch();
+
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(
+        &response.body_string().unwrap(),
+        r#"{"result":{"engineeringError":null,"message":"The selected username is not available."},"success":false}"#
+    );
+}
+
+#[test]
+fn test_register_with_invalid_email_in_spanish() {
+    let client = test_client(rocket(), &[]);
+    let mut request = client.post("/v1/auth/register");
+    request.add_header(Header::new("Content-Type", "application/json"));
+    request.add_header(Header::new("Accept-Language", "es-MX,es;q=0.9"));
+    request.set_body(register_body("joerogan", "not an email"));
+    let mut response = request.dispatch();
+
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(
+        &response.body_string().unwrap(),
+        r#"{"result":{"engineeringError":"Invalid email","message":"El email no tiene un formato válido"},"success":false}"#
+    );
+}
+
+#[test]
+fn test_login_unknown_user() {
+    let client = test_client(rocket(), &[]);
+    let mut request = client.post("/v1/auth/login");
+    request.add_header(Header::new("Content-Type", "application/json"));
+    request.set_body(
+        json!({"phoneNumber": "+1 (555) 123-4567", "publicKey": MOCK_PUBLIC_KEY}).to_string(),
+    );
+    let mut response = request.dispatch();
+
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(
+        &response.body_string().unwrap(),
+        r#"{"success":true,"result":{"message":"Successfully created verification request"}}"#
+    );
+
+    let pool = get_pool();
+    let mut conn = pool.get().unwrap();
+    let verifications: i64 = conn
+        .query_one(
+            "SELECT count(*) FROM users_verification WHERE phone_number = '+15551234567'",
+            &[],
+        )
+        .unwrap()
+        .get(0);
+    assert_eq!(verifications, 0);
+}
+
+#[test]
+fn test_register_and_verify() {
+    let client = test_client(rocket(), &[]);
+    let mut request = client.post("/v1/auth/register");
+    request.add_header(Header::new("Content-Type", "application/json"));
+    request.set_body(register_body("joerogan", "joe@rogan.com"));
+    assert_eq!(request.dispatch().status(), Status::Ok);
+
+    let code = last_verification_code("joe@rogan.com");
+    let mut request = client.post("/v1/auth/verify/joe@rogan.com");
+    request.add_header(Header::new("Content-Type", "application/json"));
+    request.set_body(verify_body(&code, "joe_iphone"));
+    let mut response = request.dispatch();
+
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(
+        &response.body_string().unwrap(),
+        r#"{"success":true,"result":{"username":"joerogan","firstName":"Joe","lastName":"Rogan","email":"joe@rogan.com","phoneNumber":null,"picture":null}}"#
+    );
+
+    let pool = get_pool();
+    let mut conn = pool.get().unwrap();
+    let public_key: String = conn
+        .query_one(
+            "SELECT public_key FROM users_identity WHERE username = 'joerogan'",
+            &[],
+        )
+        .unwrap()
+        .get("public_key");
+    assert_eq!(public_key, MOCK_PUBLIC_KEY);
+    let owners = conn
+        .query(
+            "SELECT * FROM users_devices WHERE username = 'joerogan' AND device_id = 'joe_iphone' AND owner = true",
+            &[],
+        )
+        .unwrap();
+    assert_eq!(owners.len(), 1);
+
+    // The code can only be used once.
+    let mut request = client.post("/v1/auth/verify/joe@rogan.com");
+    request.add_header(Header::new("Content-Type", "application/json"));
+    request.set_body(verify_body(&code, "joe_iphone"));
+    let mut response = request.dispatch();
+    assert_eq!(
+        &response.body_string().unwrap(),
+        r#"{"result":{"engineeringError":null,"message":"Unable to verify ownership of this account, please, send another code or try to login again."},"success":false}"#
+    );
+}
+
+#[test]
+fn test_verification_stops_after_too_many_wrong_codes() {
+    let client = test_client(rocket(), &[]);
+    let mut request = client.post("/v1/auth/register");
+    request.add_header(Header::new("Content-Type", "application/json"));
+    request.set_body(register_body("joerogan", "joe@rogan.com"));
+    assert_eq!(request.dispatch().status(), Status::Ok);
+    let code = last_verification_code("joe@rogan.com");
+
+    for _ in 0..VERIFICATION_CODE_MAX_ATTEMPTS {
+        let mut request = client.post("/v1/auth/verify/joe@rogan.com");
+        request.add_header(Header::new("Content-Type", "application/json"));
+        request.set_body(verify_body("0zzzz", "joe_iphone"));
+        let mut response = request.dispatch();
+        assert!(response
+            .body_string()
+            .unwrap()
+            .contains("Unable to verify ownership of this account"));
+    }
+
+    // The right code no longer works, a new one must be requested.
+    let mut request = client.post("/v1/auth/verify/joe@rogan.com");
+    request.add_header(Header::new("Content-Type", "application/json"));
+    request.set_body(verify_body(&code, "joe_iphone"));
+    let mut response = request.dispatch();
+    assert_eq!(
+        &response.body_string().unwrap(),
+        r#"{"result":{"engineeringError":null,"message":"Unable to verify ownership of this account, please, send another code or try to login again."},"success":false}"#
+    );
+}
+
+#[test]
+fn test_login_and_verify_device_registered_to_another_user() {
+    let client = test_client(rocket(), &[]);
+    register_mock_user("joerogan", "joe@rogan.com", "joe_iphone");
+
+    let mut request = client.post("/v1/auth/login");
+    request.add_header(Header::new("Content-Type", "application/json"));
+    request.set_body(
+        json!({"email": "darioalessandro.lencina@gmail.com", "publicKey": MOCK_PUBLIC_KEY})
+            .to_string(),
+    );
+    let mut response = request.dispatch();
+    assert_eq!(
+        &response.body_string().unwrap(),
+        r#"{"success":true,"result":{"message":"Successfully created verification request"}}"#
+    );
+
+    let code = last_verification_code("darioalessandro.lencina@gmail.com");
+    let mut request = client.post("/v1/auth/verify/darioalessandro.lencina@gmail.com");
+    request.add_header(Header::new("Content-Type", "application/json"));
+    request.set_body(verify_body(&code, "joe_iphone"));
+    let mut response = request.dispatch();
+    assert_eq!(
+        &response.body_string().unwrap(),
+        r#"{"result":{"engineeringError":null,"message":"Device is already registered for a different user."},"success":false}"#
+    );
+}
+
+#[test]
+fn test_registered_mock_user_can_use_the_api() {
+    dbmate_rebuild();
+    register_mock_user("joerogan", "joe@rogan.com", "joe_iphone");
+    let token = create_token("joerogan", "joe_iphone").unwrap();
+
+    let client = Client::new(lib::server::invitations::rocket()).expect("valid rocket instance");
+    let mut request = client.get("/v1/invitations/unknown/creator");
+    request.add_header(Header::new(ASIMOV_LIVES, token));
+    let mut response = request.dispatch();
+
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(
+        &response.body_string().unwrap(),
+        r#"{"result":{"engineeringError":null,"message":"There is no invitation with that id"},"success":false}"#
+    );
+}
@@ -1,6 +1,13 @@
 use chrono::Utc;
 use jsonwebtoken::{encode, Algorithm, EncodingKey, Header};
-use lib::model::auth::Claims;
+use lib::controllers::auth::{create_verification, register_user, verify_code};
+use lib::db::get_pool;
+use lib::model::{
+    auth::{Claims, VerificationTarget},
+    devices::OS,
+    requests::{RegistrationRequest, VerificationRequest},
+    PostgresConnection, UserDetails,
+};
 
 pub static MOCK_PUBLIC_KEY: &str =
     "MIICIjANBgkqhkiG9w0BAQEFAAOCAg8AMIICCgKCAgEA6lORI0goLg5HUlkcnnAO
@@ -164,3 +171,63 @@ pub fn create_token(
 
     encode(&header, &claims, &encoding_key)
 }
+
+/// Registers and verifies a user through the same flow used by the auth service,
+/// tokens created with `create_token(username, device_id)` are valid for it.
+pub fn register_mock_user(username: &str, email: &str, device_id: &str) -> UserDetails {
+    let pool = get_pool();
+    let mut conn = pool.get().unwrap();
+    let target = VerificationTarget::Email(email.to_string());
+    let code = register_user(
+        &mut conn,
+        &RegistrationRequest {
+            username: username.to_string(),
+            firstName: "Mock".to_string(),
+            lastName: "User".to_string(),
+            email: Some(email.to_string()),
+            phoneNumber: None,
+            picture: None,
+            publicKey: MOCK_PUBLIC_KEY.to_string(),
+        },
+        &target,
+        "en",
+    )
+    .unwrap();
+    verify_mock_device(&mut conn, &target, code, MOCK_PUBLIC_KEY, device_id)
+}
+
+/// Starts a login for an existing user, as the auth service does, and verifies it.
+pub fn login_mock_user(
+    conn: &mut PostgresConnection,
+    target: &VerificationTarget,
+    public_key: &str,
+    device_id: &str,
+) -> UserDetails {
+    let code = create_verification(conn, target, public_key)
+        .unwrap()
+        .expect("the user exists");
+    verify_mock_device(conn, target, code, public_key, device_id)
+}
+
+fn verify_mock_device(
+    conn: &mut PostgresConnection,
+    target: &VerificationTarget,
+    code: String,
+    public_key: &str,
+    device_id: &str,
+) -> UserDetails {
+    verify_code(
+        conn,
+        target,
+        &VerificationRequest {
+            code,
+            publicKey: public_key.to_string(),
+            deviceId: device_id.to_string(),
+            os: OS::iOS,
+            osVersion: "14.4".to_string(),
+            model: "iPhone".to_string(),
+            appVersion: Some("1.0.0".to_string()),
+        },
+    )
+    .unwrap()
+}
@@ -0,0 +1,31 @@
+use super::{
+    auth::{create_token, MOCK_PUBLIC_KEY},
+    db::{insert_mock_friends, insert_mock_public_key},
+    dbmate::dbmate_rebuild,
+    redis::{flush_rate_limits, flush_redis, flush_telemetry_uploads},
+};
+use lib::constants::ASIMOV_LIVES;
+use rocket::http::Header;
+use rocket::local::Client;
+use rocket::Rocket;
+
+/// Rebuilds the db, gives dario a public key and makes each pair of users friends.
+pub fn test_client(rocket: Rocket, friends: &[(&str, &str)]) -> Client {
+    dbmate_rebuild();
+    flush_redis();
+    flush_rate_limits();
+    flush_telemetry_uploads();
+    insert_mock_public_key("dario", MOCK_PUBLIC_KEY);
+    for (username, friend) in friends {
+        insert_mock_friends(username, friend);
+    }
+    Client::new(rocket).expect("valid rocket instance")
+}
+
+pub fn gateway_client(friends: &[(&str, &str)]) -> Client {
+    test_client(lib::server::http_gateway::rocket(), friends)
+}
+
+pub fn auth_header(username: &str, device_id: &str) -> Header<'static> {
+    Header::new(ASIMOV_LIVES, create_token(username, device_id).unwrap())
+}
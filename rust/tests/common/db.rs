@@ -1,21 +1,32 @@
+use super::auth::login_mock_user;
 use chrono::DateTime;
-use lib::{db::get_pool, model::invitations::InvitationState};
+use lib::{
+    db::get_pool,
+    model::{auth::VerificationTarget, invitations::InvitationState},
+};
 use std::time::SystemTime;
 
+/// Stores the public key of a seeded user by logging in with the device it owns.
 pub fn insert_mock_public_key(username: &str, public_key: &str) {
     let pool = get_pool();
     let mut client = pool.get().unwrap();
-    client
-        .query(
-            "INSERT INTO users_identity (username, public_key, update_timestamp)
-                VALUES ($1, $2, now())
-                ON CONFLICT (username)
-                    DO UPDATE
-                    SET public_key = $2, update_timestamp = now()
-                ",
-            &[&username.to_string(), &public_key.to_string()],
+    let user = client
+        .query_one(
+            "SELECT users.email, users_devices.device_id FROM users
+             INNER JOIN users_devices
+                ON users_devices.username = users.username AND users_devices.owner = true
+             WHERE users.username = $1
+             LIMIT 1",
+            &[&username],
         )
         .unwrap();
+    let device_id: String = user.get("device_id");
+    login_mock_user(
+        &mut client,
+        &VerificationTarget::Email(user.get("email")),
+        public_key,
+        &device_id,
+    );
 }
 
 pub fn insert_mock_invitation_link(
@@ -1,6 +1,8 @@
 #[allow(dead_code)]
 pub mod auth;
 #[allow(dead_code)]
+pub mod client;
+#[allow(dead_code)]
 pub mod db;
 #[allow(dead_code)]
 pub mod dbmate;
@@ -1,3 +1,4 @@
+use lib::constants::TELEMETRY_UPLOAD_PREFIX;
 use lib::controllers::telemetry::redis_hash_map_name;
 use redis::Commands;
 use std::env;
@@ -15,3 +16,23 @@ pub fn flush_redis() {
         .del::<String, ()>(redis_hash_map_name("billburr"))
         .unwrap();
 }
+
+pub fn flush_rate_limits() {
+    let mut redis =
+        redis::Client::open(env::var("REDIS_URL").expect("REDIS_URL must be set")).unwrap();
+    let keys: Vec<String> = redis.keys("rate_limit.*").unwrap();
+    if !keys.is_empty() {
+        redis.del::<Vec<String>, ()>(keys).unwrap();
+    }
+}
+
+pub fn flush_telemetry_uploads() {
+    let mut redis =
+        redis::Client::open(env::var("REDIS_URL").expect("REDIS_URL must be set")).unwrap();
+    let keys: Vec<String> = redis
+        .keys(format!("{}.*", TELEMETRY_UPLOAD_PREFIX))
+        .unwrap();
+    if !keys.is_empty() {
+        redis.del::<Vec<String>, ()>(keys).unwrap();
+    }
+}
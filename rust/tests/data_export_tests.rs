@@ -0,0 +1,139 @@
+#[macro_use]
+extern crate pretty_assertions;
+
+use chrono::Utc;
+use lib::constants::{DATA_EXPORT_MAX_PER_DAY, DATA_EXPORT_TELEMETRY_PAGE_SIZE};
+use lib::db::get_pool;
+use rocket::http::Status;
+use rocket::local::Client;
+use serde_json::{json, Value};
+
+mod common;
+
+use common::{
+    client::{auth_header, gateway_client},
+    db::insert_mock_telemetry,
+};
+
+fn export(client: &Client) -> Value {
+    let mut request = client.get("/v1/account/export");
+    request.add_header(auth_header("dario", "dario_iphone"));
+    let mut response = request.dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    serde_json::from_str(&response.body_string().unwrap()).unwrap()
+}
+
+fn exports_count() -> i64 {
+    let mut conn = get_pool().get().unwrap();
+    conn.query_one(
+        "SELECT count(*) FROM users_data_exports WHERE username = 'dario'",
+        &[],
+    )
+    .unwrap()
+    .get(0)
+}
+
+#[test]
+fn test_export_user_data() {
+    let client = gateway_client(&[("dario", "coche")]);
+    let now = Utc::now().naive_utc();
+    insert_mock_telemetry("dario", "dario_iphone", "coche", now);
+    insert_mock_telemetry("dario", "dario_iphone", "louisck", now);
+
+    let response = export(&client);
+    assert_eq!(response["success"], true);
+    let export = &response["result"];
+    assert_eq!(export["user"]["username"], "dario");
+    assert_eq!(export["user"]["last_name"], "Lencina-Talarico");
+    assert_eq!(export["followers"].as_array().unwrap().len(), 2);
+    assert!(export["devices"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .any(|device| device["device_id"] == "dario_iphone"));
+    let telemetry = export["telemetry"].as_array().unwrap();
+    assert_eq!(telemetry.len(), 1);
+    assert_eq!(telemetry[0]["recipients"], json!(["coche", "louisck"]));
+    assert_eq!(telemetry[0].get("encrypted_location"), None);
+    assert_eq!(export["telemetryNextCursor"], Value::Null);
+    assert_eq!(exports_count(), 1);
+}
+
+#[test]
+fn test_export_skips_devices_of_other_users() {
+    let client = gateway_client(&[("dario", "coche")]);
+    let mut conn = get_pool().get().unwrap();
+    conn.execute(
+        "INSERT INTO users_devices (username, device_id, owner, access_enabled)
+         VALUES ('dario', 'b526979c-cade-4198-8fa4-fb077ef7544f', false, true)",
+        &[],
+    )
+    .unwrap();
+    conn.execute(
+        "INSERT INTO device_settings (device_id, role, name)
+         VALUES ('b526979c-cade-4198-8fa4-fb077ef7544f', 'garage_door', 'Left garage door')",
+        &[],
+    )
+    .unwrap();
+
+    let response = export(&client);
+    let export = &response["result"];
+    let louisck_device = |row: &Value| row["device_id"] == "b526979c-cade-4198-8fa4-fb077ef7544f";
+    assert!(!export["devices"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .any(louisck_device));
+    assert!(!export["deviceSettings"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .any(louisck_device));
+}
+
+#[test]
+fn test_export_telemetry_pages() {
+    let client = gateway_client(&[("dario", "coche")]);
+    let mut conn = get_pool().get().unwrap();
+    conn.execute(
+        "INSERT INTO device_telemetry
+            (username, device_id, recipient_username, encrypted_location, creation_timestamp)
+         SELECT 'dario', 'dario_iphone', 'coche', 'encryptedData',
+            now() - (n * interval '1 second')
+         FROM generate_series(1, $1::bigint) n",
+        &[&(DATA_EXPORT_TELEMETRY_PAGE_SIZE + 1)],
+    )
+    .unwrap();
+
+    let response = export(&client);
+    let export = &response["result"];
+    assert_eq!(
+        export["telemetry"].as_array().unwrap().len() as i64,
+        DATA_EXPORT_TELEMETRY_PAGE_SIZE
+    );
+    let cursor = export["telemetryNextCursor"].as_str().unwrap();
+
+    let mut request = client.get(format!("/v1/account/export/telemetry?cursor={}", cursor));
+    request.add_header(auth_header("dario", "dario_iphone"));
+    let response: Value = serde_json::from_str(&request.dispatch().body_string().unwrap()).unwrap();
+    assert_eq!(response["result"]["telemetry"].as_array().unwrap().len(), 1);
+    assert_eq!(response["result"]["nextCursor"], Value::Null);
+    assert_eq!(exports_count(), 1);
+}
+
+#[test]
+fn test_daily_export_cap() {
+    let client = gateway_client(&[("dario", "coche")]);
+    let mut conn = get_pool().get().unwrap();
+    for _ in 0..DATA_EXPORT_MAX_PER_DAY {
+        conn.execute(
+            "INSERT INTO users_data_exports (username, device_id) VALUES ('dario', 'dario_iphone')",
+            &[],
+        )
+        .unwrap();
+    }
+
+    let response = export(&client);
+    assert_eq!(response["success"], false);
+    assert_eq!(exports_count(), DATA_EXPORT_MAX_PER_DAY);
+}
@@ -0,0 +1,203 @@
+#[macro_use]
+extern crate pretty_assertions;
+
+use amiquip::Connection as RabbitConnection;
+use chrono::{Duration, NaiveDateTime, Utc};
+use lib::controllers::emergency::update_emergency_settings;
+use lib::db::get_pool;
+use lib::messaging::get_rabbitmq_uri;
+use lib::model::{
+    devices::{BatteryState, BatteryTrend, ChargingState, DeviceHealth, TrackingIssue},
+    emergency::EmergencySettings,
+    responses::APIResponse,
+    PostgresConnection,
+};
+use redis::Commands;
+use rocket::http::{ContentType, Status};
+use rocket::local::Client;
+use serde_json::Value;
+use std::env;
+
+mod common;
+
+use common::{
+    auth::MOCK_PUBLIC_KEY,
+    client::{auth_header, gateway_client},
+    db::insert_mock_public_key,
+    rabbit::{bind_notifications_queue, consume_message},
+};
+
+fn setup() -> (Client, PostgresConnection) {
+    let client = gateway_client(&[("dario", "coche")]);
+    insert_mock_public_key("coche", MOCK_PUBLIC_KEY);
+    let mut conn = get_pool().get().unwrap();
+    conn.execute(
+        "UPDATE devices SET location_permission_state = 'USING', is_power_save_mode_on = true
+         WHERE device_id = 'coche_iphone'",
+        &[],
+    )
+    .unwrap();
+    (client, conn)
+}
+
+fn insert_battery_telemetry(
+    conn: &mut PostgresConnection,
+    battery_level: f64,
+    timestamp: NaiveDateTime,
+) {
+    conn.execute(
+        "INSERT INTO device_telemetry
+            (username, device_id, recipient_username, encrypted_location, creation_timestamp,
+             charging_state, battery_level, is_charging)
+         VALUES ('coche', 'coche_iphone', 'dario', 'encryptedData', $1, 'NotCharging', $2, false)",
+        &[&timestamp, &battery_level],
+    )
+    .unwrap();
+}
+
+fn get_health(client: &Client, username: &str) -> String {
+    let mut request = client.get("/v1/telemetry/coche/health");
+    request.add_header(auth_header(username, &format!("{}_iphone", username)));
+    let mut response = request.dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    response.body_string().unwrap()
+}
+
+fn post_battery(client: &Client, battery_state: BatteryState) {
+    let mut request = client.post("/v1/telemetry");
+    request.add_header(auth_header("coche", "coche_iphone"));
+    request.add_header(ContentType::JSON);
+    request.set_body(
+        serde_json::json!({
+            "returnFriendLocations": false,
+            "telemetry": [{ "recipientUsername": "dario", "data": "encryptedData" }],
+            "batteryState": battery_state,
+        })
+        .to_string(),
+    );
+    assert_eq!(request.dispatch().status(), Status::Ok);
+}
+
+fn low_battery_alert(redis: &mut redis::Connection) -> Option<String> {
+    redis.get("low_battery_alert.coche.coche_iphone").unwrap()
+}
+
+fn battery(level: f64, is_charging: bool) -> BatteryState {
+    BatteryState {
+        batteryLevel: Some(level),
+        chargingState: Some(if is_charging {
+            ChargingState::ChargingAc
+        } else {
+            ChargingState::NotCharging
+        }),
+        isCharging: Some(is_charging),
+    }
+}
+
+#[test]
+fn test_device_health() {
+    let (client, mut conn) = setup();
+    let now = Utc::now().naive_utc();
+    insert_battery_telemetry(&mut conn, 80.0, now - Duration::minutes(130));
+    insert_battery_telemetry(&mut conn, 60.0, now - Duration::minutes(10));
+
+    let response: APIResponse<Vec<DeviceHealth>> =
+        serde_json::from_str(&get_health(&client, "dario")).unwrap();
+
+    assert!(response.success);
+    assert_eq!(response.result.len(), 1);
+    let health = &response.result[0];
+    assert_eq!(health.deviceId, "coche_iphone");
+    assert_eq!(health.batteryLevel, Some(60.0));
+    assert_eq!(health.isCharging, Some(false));
+    assert_eq!(health.batteryTrend, BatteryTrend::Draining);
+    assert_eq!(health.batteryDrainPerHour.map(f64::round), Some(10.0));
+    assert!((595..=605).contains(&health.secondsSinceLastPing.unwrap()));
+    assert_eq!(
+        health.trackingIssues,
+        vec![
+            TrackingIssue::LocationPermission,
+            TrackingIssue::PowerSaveMode
+        ]
+    );
+}
+
+#[test]
+fn test_device_health_without_pings() {
+    let (client, _) = setup();
+
+    let response: APIResponse<Vec<DeviceHealth>> =
+        serde_json::from_str(&get_health(&client, "dario")).unwrap();
+
+    let health = &response.result[0];
+    assert_eq!(health.lastPing, None);
+    assert_eq!(health.batteryLevel, None);
+    assert_eq!(health.batteryTrend, BatteryTrend::Unknown);
+    assert!(health.trackingIssues.contains(&TrackingIssue::NoRecentPing));
+}
+
+#[test]
+fn test_device_health_is_only_available_to_friends() {
+    let (client, _) = setup();
+    insert_mock_public_key("louisck", MOCK_PUBLIC_KEY);
+
+    let response: Value = serde_json::from_str(&get_health(&client, "louisck")).unwrap();
+    assert_eq!(response["success"], false);
+    assert_eq!(
+        response["result"]["message"],
+        "You are not friends with this user"
+    );
+}
+
+#[test]
+fn test_low_battery_alert() {
+    let (client, mut conn) = setup();
+    let mut redis = redis::Client::open(env::var("REDIS_URL").expect("REDIS_URL must be set"))
+        .unwrap()
+        .get_connection()
+        .unwrap();
+    let mut rabbitmq = RabbitConnection::insecure_open(&get_rabbitmq_uri()).unwrap();
+    let channel = rabbitmq.open_channel(None).unwrap();
+    let queue = bind_notifications_queue(&channel);
+
+    // Disabled by default, the setting is not checked again for a while.
+    post_battery(&client, battery(10.0, false));
+    assert_eq!(low_battery_alert(&mut redis), Some("disabled".to_string()));
+    post_battery(&client, battery(90.0, false));
+    assert_eq!(low_battery_alert(&mut redis), None);
+
+    update_emergency_settings(
+        &mut conn,
+        &"coche".to_string(),
+        &EmergencySettings {
+            historicalAccessHours: 168,
+            historicalGraceHours: 24,
+            lowBatteryAlerts: true,
+            trackingAlerts: false,
+        },
+    )
+    .unwrap();
+    post_battery(&client, battery(10.4, false));
+    assert_eq!(low_battery_alert(&mut redis), Some("10.4".to_string()));
+    let message: Value = serde_json::from_slice(&consume_message(&queue)).unwrap();
+    assert_eq!(
+        message,
+        serde_json::json!([{
+            "deviceId": "dario_iphone",
+            "data": {
+                "title": "Low battery",
+                "body": "Coche Rodríguez's phone battery is at 10%, it may stop sending its location soon.",
+                "icon": "ic_stat_logo"
+            }
+        }])
+    );
+
+    // Sent once until the device is charged.
+    post_battery(&client, battery(9.0, false));
+    assert_eq!(low_battery_alert(&mut redis), Some("10.4".to_string()));
+    post_battery(&client, battery(9.0, true));
+    assert_eq!(low_battery_alert(&mut redis), None);
+    post_battery(&client, battery(8.5, false));
+    assert_eq!(low_battery_alert(&mut redis), Some("8.5".to_string()));
+    consume_message(&queue);
+}
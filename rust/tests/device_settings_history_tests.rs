@@ -0,0 +1,141 @@
+#[macro_use]
+extern crate pretty_assertions;
+
+use amiquip::{Connection as RabbitConnection, Queue};
+use lib::controllers::emergency::update_emergency_settings;
+use lib::db::get_pool;
+use lib::messaging::get_rabbitmq_uri;
+use lib::model::{
+    devices::{DeviceSettingsSnapshot, LocationPermissionState, TrackingIssue},
+    emergency::EmergencySettings,
+    responses::APIResponse,
+};
+use rocket::http::{Header, Status};
+use rocket::local::Client;
+use serde_json::Value;
+
+mod common;
+
+use common::{
+    client::{auth_header, gateway_client},
+    rabbit::{bind_notifications_queue, consume_message},
+};
+
+const TRACKING_ON: &str = r#"{
+    "locationPermissionState": "ALWAYS",
+    "isPowerSaveModeOn": false,
+    "isNotificationsEnabled": true,
+    "isBackgroundRefreshOn": true,
+    "isLocationServicesOn": true,
+    "osVersion": "14.0",
+    "appVersion": "2.5 build 8"
+}"#;
+
+const TRACKING_OFF: &str = r#"{
+    "locationPermissionState": "NEVER",
+    "isPowerSaveModeOn": false,
+    "isNotificationsEnabled": true,
+    "isBackgroundRefreshOn": false,
+    "isLocationServicesOn": true,
+    "osVersion": "14.0",
+    "appVersion": "2.5 build 8"
+}"#;
+
+fn update_device(client: &Client, settings: &str) {
+    let mut request = client.post("/v1/device/settings");
+    request.add_header(auth_header("dario", "dario_iphone"));
+    request.add_header(Header::new("Content-type", "application/json"));
+    request.set_body(settings.to_string());
+    assert_eq!(request.dispatch().status(), Status::Ok);
+}
+
+fn get_history(client: &Client, query: &str) -> String {
+    let mut request = client.get(format!("/v1/device/settings/history{}", query));
+    request.add_header(auth_header("dario", "dario_iphone"));
+    let mut response = request.dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    response.body_string().unwrap()
+}
+
+/// Device ids of the push notifications of the next message.
+fn notified_devices(queue: &Queue) -> Vec<String> {
+    let message: Value = serde_json::from_slice(&consume_message(queue)).unwrap();
+    message
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|notification| notification["deviceId"].as_str().unwrap().to_string())
+        .collect()
+}
+
+#[test]
+fn test_device_settings_history() {
+    let client = gateway_client(&[]);
+    update_device(&client, TRACKING_ON);
+    update_device(&client, TRACKING_OFF);
+
+    let response: APIResponse<Vec<DeviceSettingsSnapshot>> =
+        serde_json::from_str(&get_history(&client, "?limit=2")).unwrap();
+
+    assert_eq!(response.result.len(), 2);
+    let (latest, previous) = (&response.result[0], &response.result[1]);
+    assert_eq!(
+        latest.locationPermissionState,
+        LocationPermissionState::NEVER
+    );
+    assert_eq!(
+        latest.regressions,
+        vec![
+            TrackingIssue::LocationPermission,
+            TrackingIssue::BackgroundRefreshOff
+        ]
+    );
+    assert_eq!(latest.trackingIssues, latest.regressions);
+    assert_eq!(
+        previous.locationPermissionState,
+        LocationPermissionState::ALWAYS
+    );
+    assert_eq!(previous.trackingIssues, vec![]);
+    assert_eq!(previous.regressions, vec![]);
+}
+
+#[test]
+fn test_device_settings_history_of_another_user_device() {
+    let client = gateway_client(&[]);
+
+    let response: Value =
+        serde_json::from_str(&get_history(&client, "?device_id=coche_iphone")).unwrap();
+    assert_eq!(response["success"], false);
+    assert_eq!(response["result"]["message"], "Device not found");
+}
+
+#[test]
+fn test_tracking_regression_alerts() {
+    let client = gateway_client(&[("dario", "coche")]);
+    let mut rabbitmq = RabbitConnection::insecure_open(&get_rabbitmq_uri()).unwrap();
+    let channel = rabbitmq.open_channel(None).unwrap();
+    let queue = bind_notifications_queue(&channel);
+
+    // Only the owner is alerted by default.
+    update_device(&client, TRACKING_ON);
+    update_device(&client, TRACKING_OFF);
+    assert_eq!(notified_devices(&queue), vec!["dario_iphone".to_string()]);
+
+    let mut conn = get_pool().get().unwrap();
+    update_emergency_settings(
+        &mut conn,
+        &"dario".to_string(),
+        &EmergencySettings {
+            historicalAccessHours: 168,
+            historicalGraceHours: 24,
+            lowBatteryAlerts: false,
+            trackingAlerts: true,
+        },
+    )
+    .unwrap();
+    update_device(&client, TRACKING_ON);
+    update_device(&client, TRACKING_OFF);
+    let devices = notified_devices(&queue);
+    assert_eq!(devices[0], "dario_iphone");
+    assert!(devices.contains(&"coche_iphone".to_string()));
+}
@@ -0,0 +1,278 @@
+#[macro_use]
+extern crate pretty_assertions;
+
+use amiquip::Connection;
+use lib::db::get_pool;
+use lib::messaging::get_rabbitmq_uri;
+use lib::model::auth::VerificationTarget;
+use lib::server::invitations::rocket;
+use rocket::http::Header;
+use rocket::local::Client;
+use serde_json::{json, Value};
+
+mod common;
+
+use common::{
+    auth::{login_mock_user, register_mock_user, MOCK_PUBLIC_KEY},
+    client::{auth_header, test_client},
+    db::insert_mock_public_key,
+    rabbit::{bind_notifications_queue, consume_message},
+};
+
+fn post(client: &Client, path: &str, username: &str, body: Option<Value>) -> Value {
+    let mut request = client.post(path.to_string());
+    request.add_header(Header::new("Content-Type", "application/json"));
+    request.add_header(auth_header(username, &format!("{}_iphone", username)));
+    if let Some(body) = body {
+        request.set_body(body.to_string());
+    }
+    serde_json::from_str(&request.dispatch().body_string().unwrap()).unwrap()
+}
+
+fn invite(client: &Client, username: &str, body: Value) -> Value {
+    post(client, "/v1/invitations/direct", username, Some(body))
+}
+
+fn received(client: &Client, username: &str) -> Value {
+    let mut request = client.get("/v1/invitations/direct/received");
+    request.add_header(auth_header(username, &format!("{}_iphone", username)));
+    serde_json::from_str(&request.dispatch().body_string().unwrap()).unwrap()
+}
+
+#[test]
+fn test_accept_direct_invitation() {
+    let client = test_client(rocket(), &[]);
+    insert_mock_public_key("coche", MOCK_PUBLIC_KEY);
+
+    let response = invite(
+        &client,
+        "dario",
+        json!({ "email": " LuisCoche9@gmail.com " }),
+    );
+    assert_eq!(response["success"], true);
+    let id = response["result"]["id"].as_str().unwrap().to_string();
+
+    let response = received(&client, "coche");
+    let invitation = &response["result"][0];
+    assert_eq!(invitation["id"], id.as_str());
+    assert_eq!(invitation["creator"]["username"], "dario");
+    assert_eq!(invitation["creator"]["firstName"], "Dario");
+    assert_eq!(invitation["creator"]["lastName"], "Lencina-Talarico");
+    assert_eq!(
+        invitation["terms"],
+        json!({
+            "accessType": "Permanent",
+            "isEmergencyContact": true,
+            "followBack": true
+        })
+    );
+
+    let response = post(
+        &client,
+        &format!("/v1/invitations/direct/{}/accept", id),
+        "coche",
+        None,
+    );
+    assert_eq!(response["success"], true);
+    assert_eq!(response["result"]["username"], "dario");
+
+    let mut conn = get_pool().get().unwrap();
+    let followers: i64 = conn
+        .query_one(
+            "SELECT count(*) FROM users_followers
+             WHERE username IN ('dario', 'coche') AND username_follower IN ('dario', 'coche')",
+            &[],
+        )
+        .unwrap()
+        .get(0);
+    assert_eq!(followers, 2);
+    assert_eq!(received(&client, "coche")["result"], json!([]));
+
+    // Answered invitations can't be answered again.
+    let response = post(
+        &client,
+        &format!("/v1/invitations/direct/{}/reject", id),
+        "coche",
+        None,
+    );
+    assert_eq!(
+        response["result"]["message"],
+        "The invitation is no longer valid"
+    );
+}
+
+#[test]
+fn test_direct_invitation_is_matched_on_verification() {
+    let client = test_client(rocket(), &[]);
+    let mut rabbit = Connection::insecure_open(&get_rabbitmq_uri()).unwrap();
+    let channel = rabbit.open_channel(None).unwrap();
+    let queue = bind_notifications_queue(&channel);
+
+    let response = invite(
+        &client,
+        "dario",
+        json!({
+            "email": "joe@rogan.com",
+            "terms": {
+                "accessType": "EmergencyOnly",
+                "isEmergencyContact": true,
+                "followBack": false
+            }
+        }),
+    );
+    assert_eq!(response["success"], true);
+
+    // Sent to the address, there is no user to take it from.
+    let message: Value = serde_json::from_slice(&consume_message(&queue)).unwrap();
+    assert_eq!(message[0]["email"], "joe@rogan.com");
+    assert_eq!(message[0].get("username"), None);
+    assert_eq!(
+        message[0]["dynamicTemplateData"]["body"],
+        "Dario Lencina-Talarico invited you to Armore to share your locations. \
+        Download the app and sign up with this email to accept the invitation."
+    );
+
+    register_mock_user("joerogan", "joe@rogan.com", "joerogan_iphone");
+
+    let response = received(&client, "joerogan");
+    let invitations = response["result"].as_array().unwrap();
+    assert_eq!(invitations.len(), 1);
+    assert_eq!(invitations[0]["creator"]["username"], "dario");
+    assert_eq!(invitations[0]["terms"]["accessType"], "EmergencyOnly");
+    assert_eq!(invitations[0]["terms"]["followBack"], false);
+}
+
+#[test]
+fn test_reject_and_cancel_direct_invitations() {
+    let client = test_client(rocket(), &[]);
+    let mut conn = get_pool().get().unwrap();
+    conn.execute(
+        "UPDATE users SET phone_number = '+15551234567' WHERE username = 'coche'",
+        &[],
+    )
+    .unwrap();
+    login_mock_user(
+        &mut conn,
+        &VerificationTarget::Phone("+15551234567".to_string()),
+        MOCK_PUBLIC_KEY,
+        "coche_iphone",
+    );
+
+    let response = invite(
+        &client,
+        "dario",
+        json!({ "phoneNumber": "+1 (555) 123-4567" }),
+    );
+    let first = response["result"]["id"].as_str().unwrap().to_string();
+
+    // A single pending invitation for each person.
+    let response = invite(&client, "dario", json!({ "phoneNumber": "+15551234567" }));
+    assert_eq!(
+        response["result"]["message"],
+        "You already invited this person, wait for them to answer"
+    );
+
+    let response = post(
+        &client,
+        &format!("/v1/invitations/direct/{}/cancel", first),
+        "dario",
+        None,
+    );
+    assert_eq!(response["success"], true);
+
+    let response = post(
+        &client,
+        &format!("/v1/invitations/direct/{}/accept", first),
+        "coche",
+        None,
+    );
+    assert_eq!(
+        response["result"]["message"],
+        "The invitation was cancelled by the person who sent it"
+    );
+
+    // Answered invitations don't prevent new ones.
+    let response = invite(&client, "dario", json!({ "phoneNumber": "+15551234567" }));
+    let second = response["result"]["id"].as_str().unwrap().to_string();
+    let response = post(
+        &client,
+        &format!("/v1/invitations/direct/{}/reject", second),
+        "coche",
+        None,
+    );
+    assert_eq!(response["success"], true);
+    assert_eq!(received(&client, "coche")["result"], json!([]));
+}
+
+#[test]
+fn test_invalid_direct_invitations() {
+    let client = test_client(rocket(), &[]);
+
+    let response = invite(
+        &client,
+        "dario",
+        json!({ "email": "darioalessandrolencina@gmail.com" }),
+    );
+    assert_eq!(response["success"], false);
+    assert!(response["result"]["message"]
+        .as_str()
+        .unwrap()
+        .starts_with("You are using an invitation that you've created."));
+
+    let response = invite(
+        &client,
+        "dario",
+        json!({ "email": "joe@rogan.com", "phoneNumber": "+15551234567" }),
+    );
+    assert_eq!(response["success"], false);
+    assert_eq!(
+        response["result"]["engineeringError"],
+        "Exactly one of email or phoneNumber is required"
+    );
+
+    let response = invite(&client, "dario", json!({ "email": "joe" }));
+    assert_eq!(response["success"], false);
+}
+
+#[test]
+fn test_unverified_users_are_not_matched() {
+    let client = test_client(rocket(), &[]);
+
+    // coche never proved that the email is theirs.
+    let response = invite(&client, "dario", json!({ "email": "luiscoche9@gmail.com" }));
+    assert_eq!(response["success"], true);
+    let id = response["result"]["id"].as_str().unwrap().to_string();
+    assert_eq!(received(&client, "coche")["result"], json!([]));
+
+    let response = post(
+        &client,
+        &format!("/v1/invitations/direct/{}/accept", id),
+        "coche",
+        None,
+    );
+    assert_eq!(
+        response["result"]["message"],
+        "There is no invitation with that id"
+    );
+
+    insert_mock_public_key("coche", MOCK_PUBLIC_KEY);
+    let response = received(&client, "coche");
+    assert_eq!(response["result"][0]["id"], id.as_str());
+}
+
+#[test]
+fn test_invalid_direct_invitation_id() {
+    let client = test_client(rocket(), &[]);
+
+    let response = post(
+        &client,
+        "/v1/invitations/direct/not-a-uuid/reject",
+        "coche",
+        None,
+    );
+    assert_eq!(response["success"], false);
+    assert_eq!(
+        response["result"]["message"],
+        "There is no invitation with that id"
+    );
+}
@@ -0,0 +1,170 @@
+#[macro_use]
+extern crate pretty_assertions;
+
+use chrono::{Duration, NaiveDateTime, Utc};
+use jsonwebtoken::{crypto, Algorithm, DecodingKey};
+use lib::constants::DATE_FORMAT;
+use lib::controllers::emergency::update_state;
+use lib::db::get_pool;
+use lib::model::{
+    emergency::{HistoricalLocationExport, SignedExport, UserState},
+    responses::APIResponse,
+    Message,
+};
+use lib::server::emergency::rocket;
+use rocket::http::{Header, Status};
+use rocket::local::Client;
+use std::env;
+
+mod common;
+
+use common::{
+    auth::{MOCK_PRIVATE_KEY, MOCK_PUBLIC_KEY},
+    client::{auth_header, test_client},
+    db::{insert_mock_public_key, insert_mock_telemetry},
+};
+
+fn verification_key() -> String {
+    format!(
+        "-----BEGIN PUBLIC KEY-----\n{}-----END PUBLIC KEY-----",
+        MOCK_PUBLIC_KEY
+    )
+}
+
+fn setup() -> (Client, Vec<NaiveDateTime>) {
+    env::set_var("EXPORT_SIGNING_KEY", MOCK_PRIVATE_KEY);
+    env::set_var("EXPORT_VERIFICATION_KEY", verification_key());
+    let client = test_client(rocket(), &[("dario", "coche")]);
+
+    let pool = get_pool();
+    let mut conn = pool.get().unwrap();
+    conn.execute(
+        "UPDATE devices SET name = 'Coche iPhone', os = 'iOS', model = 'iPhone 12' WHERE device_id = 'coche_iphone'",
+        &[],
+    )
+    .unwrap();
+    update_state(&mut conn, &String::from("coche"), &UserState::Emergency).unwrap();
+
+    let now = Utc::now().naive_utc();
+    let timestamps: Vec<NaiveDateTime> = (1..=3)
+        .rev()
+        .map(|hours| now - Duration::hours(hours))
+        .collect();
+    for timestamp in &timestamps {
+        insert_mock_telemetry("coche", "coche_iphone", "dario", *timestamp);
+    }
+
+    (client, timestamps)
+}
+
+fn get(client: &Client, auth: Header<'static>, endpoint: String) -> String {
+    let mut request = client.get(endpoint);
+    request.add_header(auth);
+    let mut response = request.dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    response.body_string().unwrap()
+}
+
+fn dario() -> Header<'static> {
+    auth_header("dario", "dario_iphone")
+}
+
+fn export_endpoint(query: &str) -> String {
+    let end_time = Utc::now() + Duration::minutes(1);
+    let start_time = end_time - Duration::days(3);
+    format!(
+        "/v1/emergency/coche/telemetry/export?start_time={}&end_time={}{}",
+        start_time.format(DATE_FORMAT),
+        end_time.format(DATE_FORMAT),
+        query
+    )
+}
+
+fn verified_export(body: &str) -> HistoricalLocationExport {
+    let response: APIResponse<SignedExport> = serde_json::from_str(body).unwrap();
+    let signed = response.result;
+    assert_eq!(signed.algorithm, "RS512");
+    let key = DecodingKey::from_rsa_pem(verification_key().as_bytes()).unwrap();
+    assert!(crypto::verify(&signed.signature, &signed.payload, &key, Algorithm::RS512).unwrap());
+    serde_json::from_str(&signed.payload).unwrap()
+}
+
+#[test]
+fn test_export_is_paginated_and_signed() {
+    let (client, timestamps) = setup();
+
+    let first = verified_export(&get(&client, dario(), export_endpoint("&limit=2")));
+    assert_eq!(first.username, "coche");
+    assert_eq!(first.requestedBy, "dario");
+    assert_eq!(
+        first
+            .locations
+            .iter()
+            .map(|location| location.timestamp.clone())
+            .collect::<Vec<String>>(),
+        timestamps[..2]
+            .iter()
+            .map(|timestamp| timestamp.format(DATE_FORMAT).to_string())
+            .collect::<Vec<String>>()
+    );
+    assert_eq!(first.devices.len(), 1);
+    assert_eq!(first.devices[0].deviceId, "coche_iphone");
+    assert_eq!(first.devices[0].model, Some("iPhone 12".to_string()));
+    assert_eq!(first.locations[0].batteryLevel, 0.0);
+    let states: Vec<UserState> = first
+        .stateTransitions
+        .iter()
+        .map(|transition| transition.state)
+        .collect();
+    assert_eq!(states.last(), Some(&UserState::Emergency));
+
+    let cursor = first.nextCursor.expect("there is a second page");
+    let second = verified_export(&get(
+        &client,
+        dario(),
+        export_endpoint(&format!("&limit=2&cursor={}", cursor)),
+    ));
+    assert_eq!(second.locations.len(), 1);
+    assert_eq!(
+        second.locations[0].timestamp,
+        timestamps[2].format(DATE_FORMAT).to_string()
+    );
+    assert_eq!(second.nextCursor, None);
+}
+
+#[test]
+fn test_export_rejects_invalid_cursor() {
+    let (client, _) = setup();
+
+    assert_eq!(
+        get(&client, dario(), export_endpoint("&cursor=yesterday")),
+        r#"{"result":{"engineeringError":null,"message":"Invalid cursor"},"success":false}"#
+    );
+}
+
+#[test]
+fn test_export_is_only_available_to_friends() {
+    let (client, _) = setup();
+    insert_mock_public_key("louisck", MOCK_PUBLIC_KEY);
+
+    let body = get(
+        &client,
+        auth_header("louisck", "louis_iphone"),
+        export_endpoint(""),
+    );
+    assert!(body.starts_with(r#"{"result":{"engineeringError":"#));
+    assert!(body.ends_with(r#""success":false}"#));
+}
+
+#[test]
+fn test_get_export_key() {
+    let (client, _) = setup();
+
+    let response: APIResponse<Message<String>> = serde_json::from_str(&get(
+        &client,
+        dario(),
+        "/v1/emergency/export/key".to_string(),
+    ))
+    .unwrap();
+    assert_eq!(response.result.message, verification_key());
+}
@@ -23,6 +23,7 @@ use common::{
     db::{insert_mock_friends, insert_mock_public_key, insert_mock_telemetry},
     dbmate::dbmate_rebuild,
     rabbit::{bind_notifications_queue, consume_message},
+    redis::flush_rate_limits,
 };
 
 #[test]
@@ -224,6 +225,7 @@ fn test_update_to_normal_on_normal_state() {
 #[test]
 fn test_cant_report_emergency_for_a_friend_in_emergency() {
     dbmate_rebuild();
+    flush_rate_limits();
     insert_mock_public_key("dario", MOCK_PUBLIC_KEY);
     insert_mock_friends("dario", "coche");
 
@@ -247,6 +249,7 @@ fn test_cant_report_emergency_for_a_friend_in_emergency() {
 #[test]
 fn test_can_report_emergency_for_a_friend_not_in_emergency() {
     dbmate_rebuild();
+    flush_rate_limits();
     insert_mock_public_key("dario", MOCK_PUBLIC_KEY);
     insert_mock_friends("dario", "coche");
 
@@ -278,6 +281,7 @@ fn test_can_report_emergency_for_a_friend_not_in_emergency() {
 #[test]
 fn test_cant_report_emergency_for_a_non_friend() {
     dbmate_rebuild();
+    flush_rate_limits();
     insert_mock_public_key("dario", MOCK_PUBLIC_KEY);
 
     let rocket = rocket();
@@ -358,9 +362,13 @@ fn test_cant_get_historical_location_from_invalid_datetime_range() {
 }
 
 #[test]
-fn test_cant_get_historical_location_from_more_than_one_week_ago() {
+fn test_cant_get_historical_location_from_more_than_one_week_before_the_emergency() {
     dbmate_rebuild();
     insert_mock_public_key("dario", MOCK_PUBLIC_KEY);
+    insert_mock_friends("dario", "coche");
+    let pool = get_pool();
+    let mut conn = pool.get().unwrap();
+    update_state(&mut conn, &"coche".to_string(), &UserState::Emergency).unwrap();
 
     let rocket = rocket();
     let client = Client::new(rocket).expect("valid rocket instance");
@@ -371,7 +379,7 @@ fn test_cant_get_historical_location_from_more_than_one_week_ago() {
 
     let endpoint = format!(
         "/v1/emergency/{}/telemetry?start_time={}&end_time={}",
-        "non_friend",
+        "coche",
         start_time.format(DATE_FORMAT),
         end_time.format(DATE_FORMAT)
     );
@@ -384,7 +392,7 @@ fn test_cant_get_historical_location_from_more_than_one_week_ago() {
     assert_eq!(response.status(), Status::Ok);
     assert_eq!(
         &response.body_string().unwrap(),
-        r#"{"result":{"engineeringError":null,"message":"It is not possible to obtain the location from more than a week ago."},"success":false}"#
+        r#"{"result":{"engineeringError":null,"message":"It is not possible to obtain the location from that long before the emergency began."},"success":false}"#
     );
 }
 
@@ -0,0 +1,99 @@
+#[macro_use]
+extern crate pretty_assertions;
+
+use lib::constants::{API_VERSION_HEADER, ASIMOV_LIVES};
+use lib::server::invitations::rocket;
+use rocket::http::{Header, Status};
+use rocket::local::Client;
+
+mod common;
+
+use common::{
+    auth::{create_token, MOCK_PUBLIC_KEY},
+    db::insert_mock_public_key,
+    dbmate::dbmate_rebuild,
+};
+
+#[test]
+fn test_errors_keep_status_ok_for_old_clients() {
+    dbmate_rebuild();
+    insert_mock_public_key("dario", MOCK_PUBLIC_KEY);
+
+    let client = Client::new(rocket()).expect("valid rocket instance");
+    let mut request = client.get("/v1/invitations/unknown/creator");
+    request.add_header(Header::new(
+        ASIMOV_LIVES,
+        create_token("dario", "dario_iphone").unwrap(),
+    ));
+    let mut response = request.dispatch();
+
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(
+        &response.body_string().unwrap(),
+        r#"{"result":{"engineeringError":null,"message":"There is no invitation with that id"},"success":false}"#
+    );
+}
+
+#[test]
+fn test_errors_include_code_and_status_when_opted_in() {
+    dbmate_rebuild();
+    insert_mock_public_key("dario", MOCK_PUBLIC_KEY);
+
+    let client = Client::new(rocket()).expect("valid rocket instance");
+    let mut request = client.get("/v1/invitations/unknown/creator");
+    request.add_header(Header::new(
+        ASIMOV_LIVES,
+        create_token("dario", "dario_iphone").unwrap(),
+    ));
+    request.add_header(Header::new(API_VERSION_HEADER, "2"));
+    let mut response = request.dispatch();
+
+    assert_eq!(response.status(), Status::NotFound);
+    assert_eq!(
+        &response.body_string().unwrap(),
+        r#"{"result":{"code":"INVITATION_NOT_FOUND","engineeringError":null,"message":"There is no invitation with that id"},"success":false}"#
+    );
+}
+
+#[test]
+fn test_catchers_include_code_when_opted_in() {
+    let client = Client::new(rocket()).expect("valid rocket instance");
+
+    let mut request = client.get("/v1/invitations/does/not/exist");
+    request.add_header(Header::new(API_VERSION_HEADER, "2"));
+    let mut response = request.dispatch();
+    assert_eq!(response.status(), Status::NotFound);
+    assert_eq!(
+        &response.body_string().unwrap(),
+        r#"{"success":false,"result":{"message":"Unable to find endpoint","engineeringError":"Unable to find endpoint","code":"ENDPOINT_NOT_FOUND"}}"#
+    );
+
+    let mut response = client.get("/v1/invitations/does/not/exist").dispatch();
+    assert_eq!(
+        &response.body_string().unwrap(),
+        r#"{"success":false,"result":{"message":"Unable to find endpoint","engineeringError":"Unable to find endpoint"}}"#
+    );
+}
+
+#[test]
+fn test_validation_errors_are_translated() {
+    dbmate_rebuild();
+    insert_mock_public_key("dario", MOCK_PUBLIC_KEY);
+
+    let client = Client::new(rocket()).expect("valid rocket instance");
+    let mut request = client.post("/v1/invitations");
+    request.add_header(Header::new("Content-Type", "application/json"));
+    request.add_header(Header::new(
+        ASIMOV_LIVES,
+        create_token("dario", "dario_iphone").unwrap(),
+    ));
+    request.add_header(Header::new(API_VERSION_HEADER, "2"));
+    request.set_body(r#"{"expirationDate":"2100-01-01T00:00:00+00:00","maxUses":0}"#);
+    let mut response = request.dispatch();
+
+    assert_eq!(response.status(), Status::BadRequest);
+    assert_eq!(
+        &response.body_string().unwrap(),
+        r#"{"result":{"code":"INVALID_INVITATION_MAX_USES","engineeringError":"maxUses must be between 1 and 50","message":"The invitation can't be used by that many people"},"success":false}"#
+    );
+}
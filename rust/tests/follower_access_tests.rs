@@ -0,0 +1,83 @@
+#[macro_use]
+extern crate pretty_assertions;
+
+use amiquip::Connection as RabbitConnection;
+use lib::controllers::telemetry::{get_followers, redis_hash_map_name};
+use lib::db::get_pool;
+use lib::messaging::get_rabbitmq_uri;
+use lib::model::emergency::AccessType;
+use redis::Commands;
+use rocket::http::{Header, Status};
+use rocket::local::Client;
+use serde_json::Value;
+use std::env;
+
+mod common;
+
+use common::{
+    client::{auth_header, gateway_client},
+    rabbit::{bind_notifications_queue, consume_message},
+};
+
+fn update_access(client: &Client, follower: &str, access: &str) -> Value {
+    let mut request = client.put(format!("/v1/followers/{}/access", follower));
+    request.add_header(auth_header("dario", "dario_iphone"));
+    request.add_header(Header::new("Content-type", "application/json"));
+    request.set_body(access.to_string());
+    let mut response = request.dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    serde_json::from_str(&response.body_string().unwrap()).unwrap()
+}
+
+#[test]
+fn test_demote_and_promote_follower() {
+    let client = gateway_client(&[("dario", "coche")]);
+    let mut redis =
+        redis::Client::open(env::var("REDIS_URL").expect("REDIS_URL must be set")).unwrap();
+    redis
+        .hset::<_, _, _, ()>(redis_hash_map_name("coche"), "dario", "{}")
+        .unwrap();
+    let mut rabbitmq = RabbitConnection::insecure_open(&get_rabbitmq_uri()).unwrap();
+    let channel = rabbitmq.open_channel(None).unwrap();
+    let queue = bind_notifications_queue(&channel);
+
+    let response = update_access(
+        &client,
+        "coche",
+        r#"{"accessType": "EmergencyOnly", "isEmergencyContact": false}"#,
+    );
+    assert_eq!(response["success"], true);
+    let followers = get_followers("dario", &mut get_pool().get().unwrap()).unwrap();
+    assert_eq!(
+        followers["coche"].accessType,
+        Some(AccessType::EmergencyOnly)
+    );
+    let cached: Option<String> = redis.hget(redis_hash_map_name("coche"), "dario").unwrap();
+    assert_eq!(cached, None);
+    let message: Value = serde_json::from_slice(&consume_message(&queue)).unwrap();
+    assert!(message
+        .as_array()
+        .unwrap()
+        .iter()
+        .all(|notification| notification["deviceId"] == "coche_iphone"));
+
+    update_access(
+        &client,
+        "coche",
+        r#"{"accessType": "Permanent", "isEmergencyContact": true}"#,
+    );
+    let followers = get_followers("dario", &mut get_pool().get().unwrap()).unwrap();
+    assert_eq!(followers["coche"].accessType, Some(AccessType::Permanent));
+}
+
+#[test]
+fn test_update_access_of_a_stranger() {
+    let client = gateway_client(&[("dario", "coche")]);
+
+    let response = update_access(
+        &client,
+        "louisck",
+        r#"{"accessType": "EmergencyOnly", "isEmergencyContact": false}"#,
+    );
+    assert_eq!(response["success"], false);
+}
@@ -0,0 +1,119 @@
+#[macro_use]
+extern crate pretty_assertions;
+
+use lib::constants::TELEMETRY_LAST_SEEN_SET;
+use lib::db::get_pool;
+use lib::model::{emergency::AccessType, friends::FriendsPage, responses::APIResponse};
+use redis::Commands;
+use rocket::http::Status;
+use rocket::local::Client;
+use serde_json::Value;
+use std::env;
+
+mod common;
+
+use common::{
+    auth::MOCK_PUBLIC_KEY,
+    client::{auth_header, gateway_client},
+    db::insert_mock_public_key,
+};
+
+fn setup() -> Client {
+    // dario follows coche, coche does not follow dario.
+    let client = gateway_client(&[("dario", "coche")]);
+    insert_mock_public_key("billburr", MOCK_PUBLIC_KEY);
+    get_pool()
+        .get()
+        .unwrap()
+        .execute(
+            "DELETE FROM users_followers WHERE username = 'dario' AND username_follower = 'coche'",
+            &[],
+        )
+        .unwrap();
+    client
+}
+
+fn get(client: &Client, uri: &str) -> String {
+    let mut request = client.get(uri.to_string());
+    request.add_header(auth_header("dario", "dario_iphone"));
+    let mut response = request.dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    response.body_string().unwrap()
+}
+
+fn get_page(client: &Client, uri: &str) -> FriendsPage {
+    let response: APIResponse<FriendsPage> = serde_json::from_str(&get(client, uri)).unwrap();
+    response.result
+}
+
+fn usernames(page: &FriendsPage) -> Vec<&str> {
+    page.friends
+        .iter()
+        .map(|friend| friend.userDetails.username.as_str())
+        .collect()
+}
+
+#[test]
+fn test_friends_pagination() {
+    let client = setup();
+
+    let first = get_page(&client, "/v1/friends?limit=2");
+    assert_eq!(usernames(&first), vec!["billburr", "coche"]);
+    assert_eq!(first.nextCursor, Some("coche".to_string()));
+
+    let second = get_page(&client, "/v1/friends?limit=2&cursor=coche");
+    assert_eq!(usernames(&second), vec!["louisck"]);
+    assert_eq!(second.nextCursor, None);
+}
+
+#[test]
+fn test_followers_and_following() {
+    let client = setup();
+
+    let followers = get_page(&client, "/v1/followers");
+    assert_eq!(usernames(&followers), vec!["billburr", "louisck"]);
+
+    let following = get_page(&client, "/v1/following");
+    assert_eq!(usernames(&following), vec!["billburr", "coche", "louisck"]);
+    let coche = &following.friends[1];
+    assert!(coche.follower.is_none());
+    assert_eq!(
+        coche.following.as_ref().unwrap().accessType,
+        AccessType::Permanent
+    );
+    let billburr = &following.friends[0];
+    assert!(billburr.publicKey.is_some());
+    assert!(billburr.follower.is_some());
+}
+
+#[test]
+fn test_friends_search_and_last_seen() {
+    let client = setup();
+    let mut redis =
+        redis::Client::open(env::var("REDIS_URL").expect("REDIS_URL must be set")).unwrap();
+    redis
+        .zadd::<_, _, _, ()>(TELEMETRY_LAST_SEEN_SET, "louisck", 1577839798)
+        .unwrap();
+
+    let page = get_page(&client, "/v1/friends?search=louis");
+    assert_eq!(usernames(&page), vec!["louisck"]);
+    assert_eq!(
+        page.friends[0].lastSeen,
+        Some("2020-01-01T00:49:58.000Z".to_string())
+    );
+
+    let page = get_page(&client, "/v1/friends?search=%25");
+    assert_eq!(page.friends.len(), 0);
+}
+
+#[test]
+fn test_friends_limit() {
+    let client = setup();
+
+    let response: Value = serde_json::from_str(&get(&client, "/v1/friends?limit=0")).unwrap();
+    assert_eq!(response["success"], false);
+    assert_eq!(
+        response["result"]["engineeringError"],
+        "limit must be between 1 and 200"
+    );
+}
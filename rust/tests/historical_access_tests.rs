@@ -0,0 +1,183 @@
+#[macro_use]
+extern crate pretty_assertions;
+
+use chrono::{Duration, NaiveDateTime, Utc};
+use lib::constants::DATE_FORMAT;
+use lib::controllers::emergency::update_state;
+use lib::db::get_pool;
+use lib::model::{emergency::UserState, PostgresConnection};
+use lib::server::emergency::rocket;
+use rocket::http::{ContentType, Status};
+use rocket::local::Client;
+use serde_json::Value;
+
+mod common;
+
+use common::{
+    auth::MOCK_PUBLIC_KEY,
+    client::{auth_header, test_client},
+    db::{insert_mock_public_key, insert_mock_telemetry},
+};
+
+fn setup() -> (Client, PostgresConnection) {
+    let client = test_client(rocket(), &[("dario", "coche")]);
+    insert_mock_public_key("coche", MOCK_PUBLIC_KEY);
+    (client, get_pool().get().unwrap())
+}
+
+/// Emergency of coche between `started_at` and `ended_at`.
+fn insert_emergency(
+    conn: &mut PostgresConnection,
+    started_at: NaiveDateTime,
+    ended_at: NaiveDateTime,
+) {
+    conn.execute(
+        "INSERT INTO users_state_history (username, self_perception, creation_timestamp)
+         VALUES ('coche', 'Emergency', $1), ('coche', 'Normal', $2)",
+        &[&started_at, &ended_at],
+    )
+    .unwrap();
+}
+
+fn get(client: &Client, username: &str, endpoint: &str) -> Value {
+    let mut request = client.get(endpoint.to_string());
+    request.add_header(auth_header(username, &format!("{}_iphone", username)));
+    let mut response = request.dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    serde_json::from_str(&response.body_string().unwrap()).unwrap()
+}
+
+fn put_settings(client: &Client, settings: Value) -> Value {
+    let mut request = client
+        .put("/v1/emergency/settings")
+        .header(ContentType::JSON)
+        .body(settings.to_string());
+    request.add_header(auth_header("coche", "coche_iphone"));
+    let mut response = request.dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    serde_json::from_str(&response.body_string().unwrap()).unwrap()
+}
+
+fn history(client: &Client, start_time: NaiveDateTime, end_time: NaiveDateTime) -> Value {
+    get(
+        client,
+        "dario",
+        &format!(
+            "/v1/emergency/coche/telemetry?start_time={}&end_time={}",
+            start_time.format(DATE_FORMAT),
+            end_time.format(DATE_FORMAT)
+        ),
+    )
+}
+
+#[test]
+fn test_emergency_settings() {
+    let (client, _) = setup();
+
+    let defaults = get(&client, "coche", "/v1/emergency/settings");
+    assert_eq!(
+        defaults["result"],
+        serde_json::json!({
+            "historicalAccessHours": 168,
+            "historicalGraceHours": 24,
+            "lowBatteryAlerts": false,
+            "trackingAlerts": false
+        })
+    );
+
+    let settings = serde_json::json!({
+        "historicalAccessHours": 12,
+        "historicalGraceHours": 0,
+        "lowBatteryAlerts": true,
+        "trackingAlerts": true
+    });
+    assert_eq!(put_settings(&client, settings.clone())["result"], settings);
+    assert_eq!(
+        get(&client, "coche", "/v1/emergency/settings")["result"],
+        settings
+    );
+}
+
+#[test]
+fn test_invalid_emergency_settings() {
+    let (client, _) = setup();
+
+    let response = put_settings(
+        &client,
+        serde_json::json!({"historicalAccessHours": 169, "historicalGraceHours": 24}),
+    );
+    assert_eq!(response["success"], false);
+    assert_eq!(
+        response["result"]["engineeringError"],
+        "historicalAccessHours must be between 1 and 168"
+    );
+}
+
+#[test]
+fn test_history_starts_at_the_access_window_before_the_emergency() {
+    let (client, mut conn) = setup();
+    update_state(&mut conn, &"coche".to_string(), &UserState::Emergency).unwrap();
+    put_settings(
+        &client,
+        serde_json::json!({"historicalAccessHours": 2, "historicalGraceHours": 24}),
+    );
+    let now = Utc::now().naive_utc();
+    insert_mock_telemetry("coche", "coche_iphone", "dario", now - Duration::hours(1));
+
+    let within = history(&client, now - Duration::hours(2), now);
+    assert_eq!(within["result"].as_array().unwrap().len(), 1);
+
+    let before = history(&client, now - Duration::hours(3), now);
+    assert_eq!(
+        before["result"]["message"],
+        "It is not possible to obtain the location from that long before the emergency began."
+    );
+}
+
+#[test]
+fn test_history_is_readable_during_the_grace_period() {
+    let (client, mut conn) = setup();
+    let now = Utc::now().naive_utc();
+    insert_emergency(
+        &mut conn,
+        now - Duration::hours(5),
+        now - Duration::hours(3),
+    );
+    let during = now - Duration::hours(4);
+    insert_mock_telemetry("coche", "coche_iphone", "dario", during);
+    insert_mock_telemetry("coche", "coche_iphone", "dario", now - Duration::hours(1));
+
+    let response = history(&client, now - Duration::hours(6), now);
+    let timestamps: Vec<&str> = response["result"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|location| location["timestamp"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        timestamps,
+        vec![during.format(DATE_FORMAT).to_string().as_str()]
+    );
+}
+
+#[test]
+fn test_history_is_not_readable_after_the_grace_period() {
+    let (client, mut conn) = setup();
+    let now = Utc::now().naive_utc();
+    insert_emergency(
+        &mut conn,
+        now - Duration::hours(5),
+        now - Duration::hours(3),
+    );
+    put_settings(
+        &client,
+        serde_json::json!({"historicalAccessHours": 168, "historicalGraceHours": 2}),
+    );
+
+    let response = history(&client, now - Duration::hours(6), now);
+    assert_eq!(response["success"], false);
+    assert_eq!(
+        response["result"]["message"],
+        "This user is not in an emergency"
+    );
+}
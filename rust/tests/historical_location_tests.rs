@@ -0,0 +1,190 @@
+#[macro_use]
+extern crate pretty_assertions;
+
+use chrono::{Duration, NaiveDateTime, Timelike, Utc};
+use lib::constants::DATE_FORMAT;
+use lib::controllers::emergency::update_state;
+use lib::db::get_pool;
+use lib::model::emergency::UserState;
+use lib::server::emergency::rocket;
+use rocket::http::{ContentType, Status};
+use rocket::local::Client;
+use serde_json::Value;
+
+mod common;
+
+use common::{
+    client::{auth_header, test_client},
+    db::insert_mock_telemetry,
+};
+
+/// Yesterday at the start of a minute, so the samples fall in known intervals.
+fn base_time() -> NaiveDateTime {
+    (Utc::now().naive_utc() - Duration::days(1))
+        .with_second(0)
+        .unwrap()
+        .with_nanosecond(0)
+        .unwrap()
+}
+
+fn setup(telemetry: &[(&str, NaiveDateTime)]) -> Client {
+    let client = test_client(rocket(), &[("dario", "coche")]);
+    let pool = get_pool();
+    let mut conn = pool.get().unwrap();
+    update_state(&mut conn, &String::from("coche"), &UserState::Emergency).unwrap();
+    for (device_id, timestamp) in telemetry {
+        insert_mock_telemetry("coche", device_id, "dario", *timestamp);
+    }
+    client
+}
+
+fn endpoint(path: &str, query: &str) -> String {
+    let end_time = Utc::now();
+    let start_time = end_time - Duration::days(3);
+    format!(
+        "{}?start_time={}&end_time={}{}",
+        path,
+        start_time.format(DATE_FORMAT),
+        end_time.format(DATE_FORMAT),
+        query
+    )
+}
+
+fn get(client: &Client, endpoint: String) -> Value {
+    let mut request = client.get(endpoint);
+    request.add_header(auth_header("dario", "dario_iphone"));
+    let mut response = request.dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    serde_json::from_str(&response.body_string().unwrap()).unwrap()
+}
+
+/// (device_id, timestamp) of every location.
+fn locations(locations: &Value) -> Vec<(String, String)> {
+    locations
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|location| {
+            (
+                location["device_id"].as_str().unwrap().to_string(),
+                location["timestamp"].as_str().unwrap().to_string(),
+            )
+        })
+        .collect()
+}
+
+fn expected(telemetry: &[(&str, NaiveDateTime)]) -> Vec<(String, String)> {
+    telemetry
+        .iter()
+        .map(|(device_id, timestamp)| {
+            (
+                device_id.to_string(),
+                timestamp.format(DATE_FORMAT).to_string(),
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn test_v2_historical_location_is_paginated() {
+    let base = base_time();
+    let telemetry = [
+        ("coche_iphone", base),
+        ("coche_ipad", base + Duration::minutes(1)),
+        ("coche_iphone", base + Duration::minutes(1)),
+    ];
+    let client = setup(&telemetry);
+
+    let first = get(
+        &client,
+        endpoint("/v2/emergency/coche/telemetry", "&limit=2"),
+    );
+    assert_eq!(
+        locations(&first["result"]["locations"]),
+        expected(&telemetry[..2])
+    );
+    let cursor = first["result"]["nextCursor"].as_str().unwrap();
+
+    let second = get(
+        &client,
+        endpoint(
+            "/v2/emergency/coche/telemetry",
+            &format!("&limit=2&cursor={}", cursor),
+        ),
+    );
+    assert_eq!(
+        locations(&second["result"]["locations"]),
+        expected(&telemetry[2..])
+    );
+    assert_eq!(second["result"]["nextCursor"], Value::Null);
+}
+
+#[test]
+fn test_historical_location_by_device_and_interval() {
+    let base = base_time();
+    let telemetry = [
+        ("coche_iphone", base + Duration::seconds(5)),
+        ("coche_ipad", base + Duration::seconds(10)),
+        ("coche_iphone", base + Duration::seconds(20)),
+        ("coche_iphone", base + Duration::seconds(65)),
+    ];
+    let client = setup(&telemetry);
+
+    let by_device = get(
+        &client,
+        endpoint("/v1/emergency/coche/telemetry", "&device_id=coche_ipad"),
+    );
+    assert_eq!(locations(&by_device["result"]), expected(&telemetry[1..2]));
+
+    let sampled = get(
+        &client,
+        endpoint("/v1/emergency/coche/telemetry", "&interval=60"),
+    );
+    assert_eq!(
+        locations(&sampled["result"]),
+        expected(&[telemetry[0], telemetry[1], telemetry[3]])
+    );
+}
+
+#[test]
+fn test_invalid_interval() {
+    let client = setup(&[]);
+
+    let response = get(
+        &client,
+        endpoint("/v1/emergency/coche/telemetry", "&interval=0"),
+    );
+    assert_eq!(response["success"], false);
+    assert_eq!(
+        response["result"]["engineeringError"],
+        "interval must be between 1 and 86400 seconds"
+    );
+}
+
+#[test]
+fn test_historical_location_stream() {
+    let base = base_time();
+    let telemetry = [
+        ("coche_iphone", base),
+        ("coche_ipad", base + Duration::minutes(1)),
+        ("coche_iphone", base + Duration::minutes(2)),
+    ];
+    let client = setup(&telemetry);
+
+    let mut request = client.get(endpoint("/v1/emergency/coche/telemetry/stream", ""));
+    request.add_header(auth_header("dario", "dario_iphone"));
+    let mut response = request.dispatch();
+
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(
+        response.content_type(),
+        Some(ContentType::new("application", "x-ndjson"))
+    );
+    let lines: Vec<Value> = response
+        .body_string()
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(locations(&Value::Array(lines)), expected(&telemetry));
+}
@@ -141,6 +141,7 @@ fn test_post_telemetry_with_data() {
                             language: None,
                         },
                         accessType: Some(AccessType::Permanent),
+                        sharingExpiresAt: None,
                         telemetry: Some(Telemetry {
                             batteryState: None,
                             data:"bla bla".to_string(),
@@ -160,6 +161,7 @@ fn test_post_telemetry_with_data() {
                             language: None,
                         },
                         accessType: Some(AccessType::Permanent),
+                        sharingExpiresAt: None,
                         telemetry: None,
                         state: Some(UserState::Normal),
                         publicKey: None,
@@ -177,6 +179,7 @@ fn test_post_telemetry_with_data() {
                             language: None,
                         },
                         accessType: Some(AccessType::Permanent),
+                        sharingExpiresAt: None,
                         telemetry: None,
                         state: None,
                         publicKey: Some("MIICIjANBgkqhkiG9w0BAQEFAAOCAg8AMIICCgKCAgEA6lORI0goLg5HUlkcnnAO\nplNP9RF6QfHQ3EyS8aBEkxYVtQhvrG+cIN0X5ws48wqsCm3/fCQtwPghuDuCXRG8\nrJTxWr5eUOy49HATRMHIdWSSG8sdz2SH//5lDu9u6u6QtUflYPEmNXCwZAzhhaWs\nDhqYkBIbNKcCnspzI/itw7znaKdfSNQvXYWuT7LvDQAjorP+JJfy8JCQzHweT52F\nBU/By9KOl6XyeOqwPc4gcKBj72KWSczwqhM0fxAFaKc/xSRxMYbKCPPGXq1TqS1l\nxHLNHqMBvewxoM6eYHFvO5jekbLbdObh+irwwx1HlG24lYwGTc/7bDBkqMWTrvg+\nVE4oCweIRi93pW21MLxUIZeH7G4gmPutwgY6gaZEYoKY9gvlupGU5TDZvF5Ny69F\nrs3OJF4m9Lp7IQKdOCvnXnug6XB67vSc3a13kDygkTTfBVT8gdkb0yGkyhGwG2VA\n9TGyxGgYFSVHHFW6vPl65b0ksLiED5twulJ4kzb4trEaayrqvYMgoNnq967RuOcp\nnNQ885Uit5HTfNaU8/aRWnkDy/ItZCwzkABkP0GNLAKLKZ6hrtu5gHeVqi1xTvXx\npai+Emj+NmxkhpPsWFqCQznnLQ/BNBhQn/EtMU03W3Q6nA0QO1o37w8b/689dWwV\ncMTE2BCIg/sAjsqQ8I9zEskCAwEAAQ==".to_string()),
@@ -192,6 +195,7 @@ fn test_post_telemetry_with_data() {
                             language: None,
                         },
                         accessType: Some(AccessType::Permanent),
+                        sharingExpiresAt: None,
                         telemetry: None,
                         state: None,
                         publicKey: None
@@ -274,6 +278,7 @@ fn test_post_telemetry_with_battery_data() {
                             language: None,
                         },
                         accessType: Some(AccessType::Permanent),
+                        sharingExpiresAt: None,
                         telemetry: Some(Telemetry {
                             batteryState: Some(BatteryState {
                                 batteryLevel: Some(34.2),
@@ -297,6 +302,7 @@ fn test_post_telemetry_with_battery_data() {
                             language: None,
                         },
                         accessType: Some(AccessType::Permanent),
+                        sharingExpiresAt: None,
                         telemetry: None,
                         state: Some(UserState::Normal),
                         publicKey: None,
@@ -314,6 +320,7 @@ fn test_post_telemetry_with_battery_data() {
                             language: None,
                         },
                         accessType: Some(AccessType::Permanent),
+                        sharingExpiresAt: None,
                         telemetry: None,
                         state: None,
                         publicKey: Some("MIICIjANBgkqhkiG9w0BAQEFAAOCAg8AMIICCgKCAgEA6lORI0goLg5HUlkcnnAO\nplNP9RF6QfHQ3EyS8aBEkxYVtQhvrG+cIN0X5ws48wqsCm3/fCQtwPghuDuCXRG8\nrJTxWr5eUOy49HATRMHIdWSSG8sdz2SH//5lDu9u6u6QtUflYPEmNXCwZAzhhaWs\nDhqYkBIbNKcCnspzI/itw7znaKdfSNQvXYWuT7LvDQAjorP+JJfy8JCQzHweT52F\nBU/By9KOl6XyeOqwPc4gcKBj72KWSczwqhM0fxAFaKc/xSRxMYbKCPPGXq1TqS1l\nxHLNHqMBvewxoM6eYHFvO5jekbLbdObh+irwwx1HlG24lYwGTc/7bDBkqMWTrvg+\nVE4oCweIRi93pW21MLxUIZeH7G4gmPutwgY6gaZEYoKY9gvlupGU5TDZvF5Ny69F\nrs3OJF4m9Lp7IQKdOCvnXnug6XB67vSc3a13kDygkTTfBVT8gdkb0yGkyhGwG2VA\n9TGyxGgYFSVHHFW6vPl65b0ksLiED5twulJ4kzb4trEaayrqvYMgoNnq967RuOcp\nnNQ885Uit5HTfNaU8/aRWnkDy/ItZCwzkABkP0GNLAKLKZ6hrtu5gHeVqi1xTvXx\npai+Emj+NmxkhpPsWFqCQznnLQ/BNBhQn/EtMU03W3Q6nA0QO1o37w8b/689dWwV\ncMTE2BCIg/sAjsqQ8I9zEskCAwEAAQ==".to_string()),
@@ -329,6 +336,7 @@ fn test_post_telemetry_with_battery_data() {
                             language: None,
                         },
                         accessType: Some(AccessType::Permanent),
+                        sharingExpiresAt: None,
                         telemetry: None,
                         state: None,
                         publicKey: None
@@ -321,7 +321,7 @@ fn test_remove_friend() {
 
     assert_eq!(response.status(), Status::Ok);
     assert_eq!(
-        r#"{"success":true,"result":{"firstName":"Dario","lastName":"Lencina-Talarico","username":"dario"}}"#,
+        r#"{"success":true,"result":{"firstName":"Dario","lastName":"Lencina-Talarico","terms":{"accessType":"Permanent","followBack":true,"isEmergencyContact":true},"username":"dario"}}"#,
         &response.body_string().unwrap()
     );
 }
@@ -369,7 +369,7 @@ fn test_get_inv_creator() {
 
     assert_eq!(response.status(), Status::Ok);
     assert_eq!(
-        r#"{"success":true,"result":{"firstName":"Dario","lastName":"Lencina-Talarico","username":"dario"}}"#,
+        r#"{"success":true,"result":{"firstName":"Dario","lastName":"Lencina-Talarico","terms":{"accessType":"Permanent","followBack":true,"isEmergencyContact":true},"username":"dario"}}"#,
         &response.body_string().unwrap()
     );
 }
@@ -393,3 +393,349 @@ fn test_get_non_existent_inv() {
         &response.body_string().unwrap()
     );
 }
+
+#[test]
+fn test_list_invitations() {
+    dbmate_rebuild();
+    insert_mock_public_key("dario", MOCK_PUBLIC_KEY);
+    let token = create_token("dario", "dario_iphone").unwrap();
+
+    let exp_date = (Local::now() + week()).to_rfc3339();
+    let past_date = (Local::now() - week()).to_rfc3339();
+    insert_mock_invitation_link(
+        "dario",
+        "XjKlQptXcAeQ",
+        &exp_date,
+        InvitationState::CREATED,
+        &None,
+    );
+    insert_mock_invitation_link(
+        "dario",
+        "AodWEfA",
+        &past_date,
+        InvitationState::CREATED,
+        &None,
+    );
+    insert_mock_invitation_link(
+        "dario",
+        "PqLmNoRsT",
+        &exp_date,
+        InvitationState::ACCEPTED,
+        &Some("coche".to_string()),
+    );
+    insert_mock_invitation_link(
+        "coche",
+        "ZzYyXx",
+        &exp_date,
+        InvitationState::CREATED,
+        &None,
+    );
+
+    let rocket = rocket();
+    let client = Client::new(rocket).expect("valid rocket instance");
+    let mut request = client.get("/v1/invitations");
+    request.add_header(Header::new(ASIMOV_LIVES, token));
+    let mut response = request.dispatch();
+
+    assert_eq!(response.status(), Status::Ok);
+    let response: serde_json::Value =
+        serde_json::from_str(&response.body_string().unwrap()).unwrap();
+    let invitations = response["result"].as_array().unwrap();
+    assert_eq!(invitations.len(), 3);
+    let state_of = |id: &str| {
+        invitations
+            .iter()
+            .find(|invitation| invitation["id"] == id)
+            .map(|invitation| invitation["state"].clone())
+            .unwrap()
+    };
+    assert_eq!(state_of("XjKlQptXcAeQ"), "CREATED");
+    assert_eq!(state_of("AodWEfA"), "EXPIRED");
+    assert_eq!(state_of("PqLmNoRsT"), "ACCEPTED");
+    assert!(invitations
+        .iter()
+        .any(|invitation| invitation["recipientUsername"] == "coche"));
+}
+
+#[test]
+fn test_accept_revoked_invitation() {
+    dbmate_rebuild();
+    insert_mock_public_key("dario", MOCK_PUBLIC_KEY);
+    insert_mock_public_key("coche", MOCK_PUBLIC_KEY);
+
+    let exp_date = (Local::now() + week()).to_rfc3339();
+    let inv_id = "XjKlQptXcAeQ";
+    insert_mock_invitation_link("dario", inv_id, &exp_date, InvitationState::CREATED, &None);
+
+    let rocket = rocket();
+    let client = Client::new(rocket).expect("valid rocket instance");
+
+    // Only the creator can revoke it.
+    let mut request = client.post(format!("/v1/invitations/{}/revoke", inv_id));
+    request.add_header(Header::new(
+        ASIMOV_LIVES,
+        create_token("coche", "coche_iphone").unwrap(),
+    ));
+    assert_eq!(
+        r#"{"result":{"engineeringError":null,"message":"There is no invitation with that id"},"success":false}"#,
+        &request.dispatch().body_string().unwrap()
+    );
+
+    let mut request = client.post(format!("/v1/invitations/{}/revoke", inv_id));
+    request.add_header(Header::new(
+        ASIMOV_LIVES,
+        create_token("dario", "dario_iphone").unwrap(),
+    ));
+    assert_eq!(
+        r#"{"success":true,"result":{"message":"Ok"}}"#,
+        &request.dispatch().body_string().unwrap()
+    );
+
+    let mut request = client.post(format!("/v1/invitations/{}/accept", inv_id));
+    request.add_header(Header::new(
+        ASIMOV_LIVES,
+        create_token("coche", "coche_iphone").unwrap(),
+    ));
+    assert_eq!(
+        r#"{"result":{"engineeringError":null,"message":"The invitation was cancelled by the person who sent it"},"success":false}"#,
+        &request.dispatch().body_string().unwrap()
+    );
+}
+
+#[test]
+fn test_resend_expired_invitation() {
+    dbmate_rebuild();
+    insert_mock_public_key("dario", MOCK_PUBLIC_KEY);
+    let token = create_token("dario", "dario_iphone").unwrap();
+
+    let past_date = (Local::now() - week()).to_rfc3339();
+    let inv_id = "XjKlQptXcAeQ";
+    insert_mock_invitation_link("dario", inv_id, &past_date, InvitationState::EXPIRED, &None);
+
+    let rocket = rocket();
+    let client = Client::new(rocket).expect("valid rocket instance");
+    let mut request = client.post(format!("/v1/invitations/{}/resend", inv_id));
+    request.add_header(Header::new("Content-Type", "application/json"));
+    request.add_header(Header::new(ASIMOV_LIVES, token));
+    request.set_body(json!({ "expirationDate": (Local::now() + week()).to_rfc3339() }).to_string());
+    let mut response = request.dispatch();
+
+    assert_eq!(response.status(), Status::Ok);
+    assert!(response
+        .body_string()
+        .unwrap()
+        .ends_with(&format!(r#"/invitations/{}"}}}}"#, inv_id)));
+    let mut db_client = get_pool().get().unwrap();
+    let state: InvitationState = db_client
+        .query_one(
+            "SELECT state FROM link_invitations WHERE id = $1",
+            &[&inv_id],
+        )
+        .unwrap()
+        .get("state");
+    assert_eq!(state, InvitationState::CREATED);
+}
+
+#[test]
+fn test_multi_use_invitation() {
+    dbmate_rebuild();
+    insert_mock_public_key("dario", MOCK_PUBLIC_KEY);
+
+    let rocket = rocket();
+    let client = Client::new(rocket).expect("valid rocket instance");
+    let mut request = client.post("/v1/invitations");
+    request.add_header(Header::new("Content-Type", "application/json"));
+    request.add_header(Header::new(
+        ASIMOV_LIVES,
+        create_token("dario", "dario_iphone").unwrap(),
+    ));
+    request.set_body(
+        json!({ "expirationDate": (Local::now() + week()).to_rfc3339(), "maxUses": 2 }).to_string(),
+    );
+    let response: serde_json::Value =
+        serde_json::from_str(&request.dispatch().body_string().unwrap()).unwrap();
+    let link = response["result"]["link"].as_str().unwrap();
+    let inv_id = link.rsplit('/').next().unwrap();
+
+    let accept = |username: &str| {
+        let mut request = client.post(format!("/v1/invitations/{}/accept", inv_id));
+        request.add_header(Header::new(
+            ASIMOV_LIVES,
+            create_token(username, &format!("{}_iphone", username)).unwrap(),
+        ));
+        let response: serde_json::Value =
+            serde_json::from_str(&request.dispatch().body_string().unwrap()).unwrap();
+        response
+    };
+    assert_eq!(accept("coche")["success"], true);
+    assert_eq!(
+        accept("coche")["result"]["message"],
+        "You already accepted this invitation"
+    );
+    assert_eq!(accept("louisck")["success"], true);
+    assert_eq!(
+        accept("billburr")["result"]["message"],
+        "The invitation is no longer valid"
+    );
+
+    let mut db_client = get_pool().get().unwrap();
+    let row = db_client
+        .query_one(
+            "SELECT state, uses, recipient_username FROM link_invitations WHERE id = $1",
+            &[&inv_id],
+        )
+        .unwrap();
+    assert_eq!(
+        row.get::<_, InvitationState>("state"),
+        InvitationState::ACCEPTED
+    );
+    assert_eq!(row.get::<_, i32>("uses"), 2);
+    assert_eq!(row.get::<_, Option<String>>("recipient_username"), None);
+    let recipients: Vec<String> = db_client
+        .query(
+            "SELECT username FROM link_invitations_uses WHERE invitation_id = $1 ORDER BY username",
+            &[&inv_id],
+        )
+        .unwrap()
+        .iter()
+        .map(|row| row.get("username"))
+        .collect();
+    assert_eq!(recipients, vec!["coche", "louisck"]);
+}
+
+#[test]
+fn test_reject_multi_use_invitation() {
+    dbmate_rebuild();
+    insert_mock_public_key("dario", MOCK_PUBLIC_KEY);
+
+    let rocket = rocket();
+    let client = Client::new(rocket).expect("valid rocket instance");
+    let mut request = client.post("/v1/invitations");
+    request.add_header(Header::new("Content-Type", "application/json"));
+    request.add_header(Header::new(
+        ASIMOV_LIVES,
+        create_token("dario", "dario_iphone").unwrap(),
+    ));
+    request.set_body(
+        json!({ "expirationDate": (Local::now() + week()).to_rfc3339(), "maxUses": 2 }).to_string(),
+    );
+    let response: serde_json::Value =
+        serde_json::from_str(&request.dispatch().body_string().unwrap()).unwrap();
+    let link = response["result"]["link"].as_str().unwrap();
+    let inv_id = link.rsplit('/').next().unwrap();
+
+    let mut request = client.post(format!("/v1/invitations/{}/reject", inv_id));
+    request.add_header(Header::new(
+        ASIMOV_LIVES,
+        create_token("coche", "coche_iphone").unwrap(),
+    ));
+    let response: serde_json::Value =
+        serde_json::from_str(&request.dispatch().body_string().unwrap()).unwrap();
+    assert_eq!(response["success"], false);
+    assert_eq!(
+        response["result"]["message"],
+        "This invitation was shared with several people, ignore it if you don't want to accept it"
+    );
+
+    let state: InvitationState = get_pool()
+        .get()
+        .unwrap()
+        .query_one(
+            "SELECT state FROM link_invitations WHERE id = $1",
+            &[&inv_id],
+        )
+        .unwrap()
+        .get("state");
+    assert_eq!(state, InvitationState::CREATED);
+}
+
+#[test]
+fn test_create_invitation_with_invalid_max_uses() {
+    dbmate_rebuild();
+    insert_mock_public_key("dario", MOCK_PUBLIC_KEY);
+
+    let rocket = rocket();
+    let client = Client::new(rocket).expect("valid rocket instance");
+    let mut request = client.post("/v1/invitations");
+    request.add_header(Header::new("Content-Type", "application/json"));
+    request.add_header(Header::new(
+        ASIMOV_LIVES,
+        create_token("dario", "dario_iphone").unwrap(),
+    ));
+    request.set_body(
+        json!({ "expirationDate": (Local::now() + week()).to_rfc3339(), "maxUses": 0 }).to_string(),
+    );
+    let response: serde_json::Value =
+        serde_json::from_str(&request.dispatch().body_string().unwrap()).unwrap();
+
+    assert_eq!(response["success"], false);
+    assert_eq!(
+        response["result"]["engineeringError"],
+        "maxUses must be between 1 and 50"
+    );
+}
+
+#[test]
+fn test_accept_invitation_with_terms() {
+    dbmate_rebuild();
+    insert_mock_public_key("dario", MOCK_PUBLIC_KEY);
+
+    let rocket = rocket();
+    let client = Client::new(rocket).expect("valid rocket instance");
+    let mut request = client.post("/v1/invitations");
+    request.add_header(Header::new("Content-Type", "application/json"));
+    request.add_header(Header::new(
+        ASIMOV_LIVES,
+        create_token("dario", "dario_iphone").unwrap(),
+    ));
+    request.set_body(
+        json!({
+            "expirationDate": (Local::now() + week()).to_rfc3339(),
+            "terms": {
+                "accessType": "EmergencyOnly",
+                "isEmergencyContact": false,
+                "followBack": false
+            }
+        })
+        .to_string(),
+    );
+    let response: serde_json::Value =
+        serde_json::from_str(&request.dispatch().body_string().unwrap()).unwrap();
+    let link = response["result"]["link"].as_str().unwrap();
+    let inv_id = link.rsplit('/').next().unwrap();
+    let coche_token = create_token("coche", "coche_iphone").unwrap();
+
+    // The terms are shown before accepting.
+    let request = client.get(format!("/v1/invitations/public/{}/creator", inv_id));
+    let response: serde_json::Value =
+        serde_json::from_str(&request.dispatch().body_string().unwrap()).unwrap();
+    assert_eq!(
+        response["result"]["terms"],
+        json!({
+            "accessType": "EmergencyOnly",
+            "isEmergencyContact": false,
+            "followBack": false
+        })
+    );
+
+    let mut request = client.post(format!("/v1/invitations/{}/accept", inv_id));
+    request.add_header(Header::new(ASIMOV_LIVES, coche_token));
+    let response: serde_json::Value =
+        serde_json::from_str(&request.dispatch().body_string().unwrap()).unwrap();
+    assert_eq!(response["success"], true);
+
+    let mut db_client = get_pool().get().unwrap();
+    let rows = db_client
+        .query(
+            "SELECT username, username_follower, access_type::text, is_emergency_contact
+             FROM users_followers
+             WHERE username IN ('dario', 'coche') AND username_follower IN ('dario', 'coche')",
+            &[],
+        )
+        .unwrap();
+    assert_eq!(rows.len(), 1);
+    assert_eq!(rows[0].get::<_, String>("username"), "dario");
+    assert_eq!(rows[0].get::<_, String>("username_follower"), "coche");
+    assert_eq!(rows[0].get::<_, String>("access_type"), "EmergencyOnly");
+    assert_eq!(rows[0].get::<_, bool>("is_emergency_contact"), false);
+}
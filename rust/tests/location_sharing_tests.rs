@@ -0,0 +1,137 @@
+#[macro_use]
+extern crate pretty_assertions;
+
+use amiquip::Connection as RabbitConnection;
+use lib::controllers::followers::expire_location_sharing;
+use lib::controllers::telemetry::{getFollowingLastLocation, get_followers, redis_hash_map_name};
+use lib::db::get_pool;
+use lib::messaging::get_rabbitmq_uri;
+use redis::Commands;
+use rocket::http::Header;
+use rocket::local::Client;
+use serde_json::Value;
+use std::env;
+
+mod common;
+
+use common::{
+    client::{auth_header, gateway_client},
+    rabbit::{bind_notifications_queue, consume_message},
+};
+
+const CACHED_LOCATION: &str =
+    r#"{"data": "bla bla", "timestamp": "2020-01-01T00:49:58.000Z", "batteryState": null}"#;
+
+fn setup() -> Client {
+    let client = gateway_client(&[("dario", "coche")]);
+    get_pool()
+        .get()
+        .unwrap()
+        .execute(
+            "UPDATE users_followers SET access_type = 'EmergencyOnly'
+             WHERE username = 'dario' AND username_follower = 'coche'",
+            &[],
+        )
+        .unwrap();
+    client
+}
+
+fn redis_connection() -> redis::Connection {
+    redis::Client::open(env::var("REDIS_URL").expect("REDIS_URL must be set"))
+        .unwrap()
+        .get_connection()
+        .unwrap()
+}
+
+fn share_location(client: &Client, follower: &str, minutes: i32) -> Value {
+    let mut request = client.put(format!("/v1/followers/{}/sharing", follower));
+    request.add_header(auth_header("dario", "dario_iphone"));
+    request.add_header(Header::new("Content-type", "application/json"));
+    request.set_body(format!(r#"{{"durationMinutes": {}}}"#, minutes));
+    let mut response = request.dispatch();
+    serde_json::from_str(&response.body_string().unwrap()).unwrap()
+}
+
+#[test]
+fn test_temporary_location_sharing() {
+    let client = setup();
+    let mut redis = redis_connection();
+    redis
+        .hset::<_, _, _, ()>(redis_hash_map_name("coche"), "dario", CACHED_LOCATION)
+        .unwrap();
+    let mut conn = get_pool().get().unwrap();
+
+    let following = getFollowingLastLocation("coche", &mut conn, &mut redis).unwrap();
+    assert!(following["dario"].telemetry.is_none());
+
+    let response = share_location(&client, "coche", 120);
+    assert_eq!(response["success"], true);
+    let followers = get_followers("dario", &mut conn).unwrap();
+    assert_eq!(
+        followers["coche"].sharingExpiresAt.as_deref(),
+        response["result"]["expiresAt"].as_str()
+    );
+    let following = getFollowingLastLocation("coche", &mut conn, &mut redis).unwrap();
+    assert_eq!(
+        following["dario"].telemetry.as_ref().unwrap().data,
+        "bla bla"
+    );
+
+    let mut rabbitmq = RabbitConnection::insecure_open(&get_rabbitmq_uri()).unwrap();
+    let channel = rabbitmq.open_channel(None).unwrap();
+    let queue = bind_notifications_queue(&channel);
+    conn.execute(
+        "UPDATE users_followers SET sharing_expires_at = now() - interval '1 minute'
+         WHERE username = 'dario' AND username_follower = 'coche'",
+        &[],
+    )
+    .unwrap();
+    assert_eq!(expire_location_sharing(&mut conn, &mut redis).unwrap(), 1);
+    assert_eq!(expire_location_sharing(&mut conn, &mut redis).unwrap(), 0);
+
+    let followers = get_followers("dario", &mut conn).unwrap();
+    assert_eq!(followers["coche"].sharingExpiresAt, None);
+    let following = getFollowingLastLocation("coche", &mut conn, &mut redis).unwrap();
+    assert!(following["dario"].telemetry.is_none());
+    let cached: Option<String> = redis.hget(redis_hash_map_name("coche"), "dario").unwrap();
+    assert_eq!(cached, None);
+
+    let message: Value = serde_json::from_slice(&consume_message(&queue)).unwrap();
+    let devices: Vec<&str> = message
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|notification| notification["deviceId"].as_str().unwrap())
+        .collect();
+    assert!(devices.contains(&"coche_iphone"));
+    assert!(devices.contains(&"dario_iphone"));
+}
+
+#[test]
+fn test_location_sharing_with_permanent_follower() {
+    let client = setup();
+    get_pool()
+        .get()
+        .unwrap()
+        .execute(
+            "UPDATE users_followers SET access_type = 'Permanent'
+             WHERE username = 'dario' AND username_follower = 'coche'",
+            &[],
+        )
+        .unwrap();
+
+    let response = share_location(&client, "coche", 120);
+    assert_eq!(response["success"], false);
+}
+
+#[test]
+fn test_location_sharing_duration() {
+    let client = setup();
+
+    let response = share_location(&client, "coche", 0);
+    assert_eq!(response["success"], false);
+    assert_eq!(
+        response["result"]["engineeringError"],
+        "durationMinutes must be between 1 and 10080"
+    );
+}
@@ -0,0 +1,152 @@
+use lib::server::openapi::{drift, OpenApiDrift};
+use lib::server::{auth, emergency, http_gateway, invitations};
+use rocket::http::Status;
+use rocket::local::Client;
+use rocket::Rocket;
+use serde_json::Value;
+
+mod common;
+
+use common::dbmate::dbmate_rebuild;
+
+fn served_document(rocket: Rocket) -> Value {
+    let client = Client::new(rocket).expect("valid rocket instance");
+    let mut response = client.get("/openapi.json").dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    serde_json::from_str(&response.body_string().unwrap()).unwrap()
+}
+
+fn operation<'a>(document: &'a Value, operation_id: &str) -> &'a Value {
+    document["paths"]
+        .as_object()
+        .unwrap()
+        .values()
+        .flat_map(|item| item.as_object().unwrap().values())
+        .find(|operation| operation["operationId"] == operation_id)
+        .unwrap_or_else(|| panic!("{} is not documented", operation_id))
+}
+
+fn parameter<'a>(operation: &'a Value, name: &str) -> &'a Value {
+    operation["parameters"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|parameter| parameter["name"] == name)
+        .unwrap_or_else(|| panic!("{} is not a parameter", name))
+}
+
+/// Fails when a route is added, removed or changes its body, parameters or
+/// response without updating the operations of its service.
+#[test]
+fn test_every_route_is_documented() {
+    dbmate_rebuild();
+    assert_eq!(
+        drift(&auth::rocket(), &auth::operations()),
+        OpenApiDrift::default()
+    );
+    assert_eq!(
+        drift(&emergency::rocket(), &emergency::operations()),
+        OpenApiDrift::default()
+    );
+    assert_eq!(
+        drift(&http_gateway::rocket(), &http_gateway::operations()),
+        OpenApiDrift::default()
+    );
+    assert_eq!(
+        drift(&invitations::rocket(), &invitations::operations()),
+        OpenApiDrift::default()
+    );
+}
+
+#[test]
+fn test_invitations_document() {
+    dbmate_rebuild();
+    let document = served_document(invitations::rocket());
+
+    assert_eq!(document["openapi"], "3.0.3");
+    assert_eq!(document["info"]["title"], "Armore Invitations API");
+
+    let accept_v1 = &document["paths"]["/v1/invitations/{id}/accept"]["post"];
+    assert_eq!(accept_v1["operationId"], "accept_v1");
+    assert_eq!(accept_v1["parameters"][0]["name"], "id");
+    assert_eq!(
+        accept_v1["security"][0]["asimovlives"],
+        Value::Array(vec![])
+    );
+
+    let accept_v2 = &document["paths"]["/v2/invitations/{id}/accept"]["post"];
+    assert_eq!(accept_v2["operationId"], "accept_v2_v2");
+
+    let public = &document["paths"]["/v1/invitations/public/{id}/creator"]["get"];
+    assert_eq!(public["security"], Value::Null);
+
+    let create = operation(&document, "create_v1");
+    assert_eq!(
+        create["requestBody"]["content"]["application/json"]["schema"]["$ref"],
+        "#/components/schemas/InvitationRequest"
+    );
+    assert!(
+        document["components"]["schemas"]["AcceptInvitationResponse"]["properties"]["message"]
+            .is_object()
+    );
+}
+
+#[test]
+fn test_http_gateway_document() {
+    dbmate_rebuild();
+    let document = served_document(http_gateway::rocket());
+
+    let post_telemetry = &document["paths"]["/v1/telemetry"]["post"];
+    assert_eq!(
+        post_telemetry["requestBody"]["content"]["application/json"]["schema"]["$ref"],
+        "#/components/schemas/TelemetryRequest"
+    );
+    for model in &[
+        "TelemetryRequest",
+        "DeviceUpdateRequest",
+        "CommandResponse",
+        "TelemetryResponse",
+        "FollowerKey",
+    ] {
+        assert!(
+            document["components"]["schemas"][model].is_object(),
+            "{} is not documented",
+            model
+        );
+    }
+    assert_eq!(
+        document["paths"]["/v2/telemetry/{recipient_username}"]["get"]["operationId"],
+        "force_refresh_telemetry_v2"
+    );
+    let friends = operation(&document, "get_user_friends_v1");
+    assert_eq!(parameter(friends, "limit")["in"], "query");
+    assert_eq!(parameter(friends, "limit")["required"], false);
+    assert_eq!(parameter(friends, "limit")["schema"]["type"], "integer");
+    assert_eq!(parameter(friends, "search")["schema"]["type"], "string");
+    assert_eq!(
+        parameter(friends, "search")["schema"]["nullable"],
+        Value::Null
+    );
+
+    let export = operation(&document, "export_user_account_telemetry_v1");
+    assert_eq!(parameter(export, "cursor")["required"], true);
+}
+
+#[test]
+fn test_emergency_document_parameters() {
+    dbmate_rebuild();
+    let document = served_document(emergency::rocket());
+
+    let page = operation(&document, "get_user_historical_location_page_v1");
+    assert_eq!(parameter(page, "username")["in"], "path");
+    assert_eq!(parameter(page, "username")["required"], true);
+    assert_eq!(parameter(page, "start_time")["required"], true);
+    assert_eq!(parameter(page, "interval")["required"], false);
+    assert_eq!(parameter(page, "interval")["schema"]["type"], "integer");
+
+    let stream = operation(&document, "stream_user_historical_location_v1");
+    assert_eq!(
+        stream["responses"]["200"]["content"]["application/x-ndjson"]["schema"]["$ref"],
+        "#/components/schemas/Location"
+    );
+}
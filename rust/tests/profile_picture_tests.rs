@@ -0,0 +1,115 @@
+#[macro_use]
+extern crate pretty_assertions;
+
+use image::{DynamicImage, ImageOutputFormat};
+use lib::constants::PROFILE_PICTURE_SIZES;
+use lib::controllers::telemetry::get_user_details;
+use lib::db::get_pool;
+use lib::storage::picture_storage_from_env;
+use rocket::http::{ContentType, Status};
+use rocket::local::Client;
+use serde_json::Value;
+
+mod common;
+
+use common::client::{auth_header, gateway_client};
+
+fn upload_picture(client: &Client, content_type: ContentType, body: Vec<u8>) -> Value {
+    let mut request = client.put("/v1/profile/picture");
+    request.add_header(auth_header("dario", "dario_iphone"));
+    request.add_header(content_type);
+    request.set_body(body);
+    let mut response = request.dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    serde_json::from_str(&response.body_string().unwrap()).unwrap()
+}
+
+fn png(width: u32, height: u32) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    DynamicImage::new_rgba8(width, height)
+        .write_to(&mut bytes, ImageOutputFormat::Png)
+        .unwrap();
+    bytes
+}
+
+fn picture() -> Option<String> {
+    let mut conn = get_pool().get().unwrap();
+    get_user_details("dario", &mut conn)
+        .unwrap()
+        .unwrap()
+        .picture
+}
+
+#[test]
+fn test_upload_profile_picture() {
+    let client = gateway_client(&[]);
+
+    let response = upload_picture(&client, ContentType::PNG, png(640, 480));
+    assert_eq!(response["success"], true);
+    let key = response["result"]["picture"].as_str().unwrap().to_string();
+    assert!(key.ends_with(".jpg"));
+    assert!(response["result"]["url"].as_str().unwrap().ends_with(&key));
+    assert_eq!(
+        response["result"]["thumbnails"].as_array().unwrap().len(),
+        PROFILE_PICTURE_SIZES.len() - 1
+    );
+    assert_eq!(picture(), Some(key.clone()));
+    let storage = picture_storage_from_env();
+    assert!(storage.exists(&key));
+    let hash = key.trim_end_matches(".jpg");
+    for size in PROFILE_PICTURE_SIZES.iter().skip(1) {
+        assert!(storage.exists(&format!("{}_{}.jpg", hash, size)));
+    }
+
+    // Uploading the same picture again points to the same content.
+    let response = upload_picture(&client, ContentType::PNG, png(640, 480));
+    assert_eq!(response["result"]["picture"], key.as_str());
+}
+
+#[test]
+fn test_reject_invalid_profile_picture() {
+    let client = gateway_client(&[]);
+    let seeded_picture = picture();
+
+    let response = upload_picture(&client, ContentType::PNG, b"not a picture".to_vec());
+    assert_eq!(response["success"], false);
+    let response = upload_picture(&client, ContentType::JPEG, png(64, 64));
+    assert_eq!(response["success"], false);
+    let response = upload_picture(&client, ContentType::GIF, png(64, 64));
+    assert_eq!(response["success"], false);
+    assert_eq!(picture(), seeded_picture);
+}
+
+#[test]
+fn test_unused_pictures_are_deleted() {
+    let client = gateway_client(&[]);
+    let storage = picture_storage_from_env();
+    let keys = |picture: &str| -> Vec<String> {
+        let hash = picture.trim_end_matches(".jpg");
+        let mut keys = vec![picture.to_string()];
+        for size in PROFILE_PICTURE_SIZES.iter().skip(1) {
+            keys.push(format!("{}_{}.jpg", hash, size));
+        }
+        keys
+    };
+
+    let response = upload_picture(&client, ContentType::PNG, png(320, 200));
+    let shared = response["result"]["picture"].as_str().unwrap().to_string();
+    let mut conn = get_pool().get().unwrap();
+    conn.execute(
+        "UPDATE user_details SET picture = $1 WHERE username = 'coche'",
+        &[&shared],
+    )
+    .unwrap();
+
+    // coche still has the previous picture.
+    let response = upload_picture(&client, ContentType::PNG, png(300, 300));
+    let own = response["result"]["picture"].as_str().unwrap().to_string();
+    assert!(keys(&shared).iter().all(|key| storage.exists(key)));
+
+    let mut request = client.delete("/v1/account");
+    request.add_header(auth_header("dario", "dario_iphone"));
+    assert_eq!(request.dispatch().status(), Status::Ok);
+    assert!(keys(&own).iter().all(|key| !storage.exists(key)));
+    assert!(keys(&shared).iter().all(|key| storage.exists(key)));
+}
@@ -0,0 +1,119 @@
+#[macro_use]
+extern crate pretty_assertions;
+
+use amiquip::Connection as RabbitConnection;
+use lib::constants::TELEMETRY_LAST_SEEN_SET;
+use lib::controllers::telemetry::redis_hash_map_name;
+use lib::db::get_pool;
+use lib::messaging::get_rabbitmq_uri;
+use redis::Commands;
+use rocket::http::{Header, Status};
+use rocket::local::Client;
+use serde_json::Value;
+use std::env;
+
+mod common;
+
+use common::{
+    client::{auth_header, gateway_client},
+    rabbit::{bind_notifications_queue, consume_message},
+};
+
+fn update_profile(client: &Client, profile: &str) -> Value {
+    let mut request = client.put("/v1/profile");
+    request.add_header(auth_header("dario", "dario_iphone"));
+    request.add_header(Header::new("Content-type", "application/json"));
+    request.set_body(profile.to_string());
+    let mut response = request.dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    serde_json::from_str(&response.body_string().unwrap()).unwrap()
+}
+
+#[test]
+fn test_update_profile() {
+    let client = gateway_client(&[]);
+
+    let response = update_profile(&client, r#"{"firstName": " Darío ", "language": "es"}"#);
+    assert_eq!(response["success"], true);
+    assert_eq!(response["result"]["firstName"], "Darío");
+    assert_eq!(response["result"]["lastName"], "Lencina-Talarico");
+
+    // Errors are now sent in spanish.
+    let mut request = client.get("/v1/device/settings/history?device_id=coche_iphone");
+    request.add_header(auth_header("dario", "dario_iphone"));
+    let response: Value = serde_json::from_str(&request.dispatch().body_string().unwrap()).unwrap();
+    assert_eq!(response["result"]["message"], "Dispositivo no encontrado");
+}
+
+#[test]
+fn test_update_profile_with_unsupported_language() {
+    let client = gateway_client(&[]);
+
+    let response = update_profile(&client, r#"{"language": "klingon"}"#);
+    assert_eq!(response["success"], false);
+    assert_eq!(
+        response["result"]["engineeringError"],
+        "language must be one of en, es"
+    );
+}
+
+#[test]
+fn test_delete_account() {
+    let client = gateway_client(&[("dario", "coche")]);
+    let mut redis =
+        redis::Client::open(env::var("REDIS_URL").expect("REDIS_URL must be set")).unwrap();
+    redis
+        .hset::<_, _, _, ()>(redis_hash_map_name("coche"), "dario", "{}")
+        .unwrap();
+    redis
+        .hset::<_, _, _, ()>(redis_hash_map_name("dario"), "coche", "{}")
+        .unwrap();
+    redis
+        .zadd::<_, _, _, ()>(TELEMETRY_LAST_SEEN_SET, "dario", 1577839798)
+        .unwrap();
+    let mut rabbitmq = RabbitConnection::insecure_open(&get_rabbitmq_uri()).unwrap();
+    let channel = rabbitmq.open_channel(None).unwrap();
+    let queue = bind_notifications_queue(&channel);
+
+    let mut request = client.delete("/v1/account");
+    request.add_header(auth_header("dario", "dario_iphone"));
+    let response: Value = serde_json::from_str(&request.dispatch().body_string().unwrap()).unwrap();
+    assert_eq!(response["success"], true);
+
+    let mut conn = get_pool().get().unwrap();
+    for (table, column) in &[
+        ("users", "username"),
+        ("users_followers", "username_follower"),
+        ("device_telemetry", "recipient_username"),
+        ("users_state_history", "username"),
+    ] {
+        let rows = conn
+            .query(
+                format!("SELECT 1 FROM {} WHERE {} = 'dario'", table, column).as_str(),
+                &[],
+            )
+            .unwrap();
+        assert_eq!(rows.len(), 0, "{} still has rows of dario", table);
+    }
+    let devices = conn
+        .query(
+            "SELECT 1 FROM devices WHERE device_id = 'dario_iphone'",
+            &[],
+        )
+        .unwrap();
+    assert_eq!(devices.len(), 0);
+
+    let cached: Option<String> = redis.hget(redis_hash_map_name("coche"), "dario").unwrap();
+    assert_eq!(cached, None);
+    let exists: bool = redis.exists(redis_hash_map_name("dario")).unwrap();
+    assert!(!exists);
+    let last_seen: Option<i64> = redis.zscore(TELEMETRY_LAST_SEEN_SET, "dario").unwrap();
+    assert_eq!(last_seen, None);
+
+    let message: Value = serde_json::from_slice(&consume_message(&queue)).unwrap();
+    assert!(message
+        .as_array()
+        .unwrap()
+        .iter()
+        .any(|notification| notification["deviceId"] == "coche_iphone"));
+}
@@ -0,0 +1,64 @@
+#[macro_use]
+extern crate pretty_assertions;
+
+use lib::controllers::devices::get_device_by_id;
+use lib::db::get_pool;
+use rocket::http::{Header, Status};
+use rocket::local::Client;
+use serde_json::Value;
+
+mod common;
+
+use common::client::{auth_header, gateway_client};
+
+const FCM_TOKEN: &str = "cZ3k9Lh2R0aQ:APA91bF-x7Yk_2Qm8Tn4Wp6Zr1Vs3Ud5Hg";
+
+fn update_push_token(client: &Client, device_id: &str, push_token: &str) -> Value {
+    let mut request = client.post("/v1/device/settings");
+    request.add_header(auth_header("dario", device_id));
+    request.add_header(Header::new("Content-type", "application/json"));
+    request.set_body(format!(
+        r#"{{
+            "locationPermissionState": "ALWAYS",
+            "isPowerSaveModeOn": false,
+            "isNotificationsEnabled": true,
+            "isBackgroundRefreshOn": true,
+            "isLocationServicesOn": true,
+            "osVersion": "10",
+            "appVersion": "2.5 build 8",
+            "pushToken": "{}"
+        }}"#,
+        push_token
+    ));
+    let mut response = request.dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    serde_json::from_str(&response.body_string().unwrap()).unwrap()
+}
+
+fn push_token(device_id: &str) -> Option<String> {
+    let mut conn = get_pool().get().unwrap();
+    get_device_by_id(device_id, &mut conn).unwrap().pushToken
+}
+
+#[test]
+fn test_register_and_clear_push_token() {
+    let client = gateway_client(&[]);
+
+    let response = update_push_token(&client, "dario_iphone", FCM_TOKEN);
+    assert_eq!(response["success"], true);
+    assert_eq!(push_token("dario_iphone"), Some(FCM_TOKEN.to_string()));
+
+    let mut request = client.delete("/v1/device/push_token");
+    request.add_header(auth_header("dario", "dario_iphone"));
+    assert_eq!(request.dispatch().status(), Status::Ok);
+    assert_eq!(push_token("dario_iphone"), None);
+}
+
+#[test]
+fn test_invalid_push_token() {
+    let client = gateway_client(&[]);
+
+    let response = update_push_token(&client, "dario_iphone", "not a token");
+    assert_eq!(response["success"], false);
+    assert_eq!(push_token("dario_iphone"), None);
+}
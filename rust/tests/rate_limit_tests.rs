@@ -0,0 +1,179 @@
+#[macro_use]
+extern crate pretty_assertions;
+
+use lib::constants::ASIMOV_LIVES;
+use lib::db::get_pool;
+use rocket::http::{Header, Status};
+use rocket::local::Client;
+use serde_json::json;
+use std::net::SocketAddr;
+
+mod common;
+
+fn peer(ip: &str) -> SocketAddr {
+    format!("{}:40000", ip).parse().unwrap()
+}
+
+use common::{
+    auth::{create_token, MOCK_PUBLIC_KEY},
+    client::test_client,
+    db::insert_mock_public_key,
+    dbmate::dbmate_rebuild,
+    redis::flush_rate_limits,
+};
+
+#[test]
+fn test_public_invitation_creator_is_limited_by_ip() {
+    dbmate_rebuild();
+    flush_rate_limits();
+
+    let client = Client::new(lib::server::invitations::rocket()).expect("valid rocket instance");
+
+    for _ in 0..20 {
+        let mut request = client.get("/v1/invitations/public/unknown/creator");
+        request.set_remote(peer("10.0.0.1"));
+        assert_eq!(request.dispatch().status(), Status::Ok);
+    }
+
+    let mut request = client.get("/v1/invitations/public/unknown/creator");
+    request.set_remote(peer("10.0.0.1"));
+    request.add_header(Header::new("Accept-Language", "es"));
+    let mut response = request.dispatch();
+    assert_eq!(response.status(), Status::TooManyRequests);
+    assert!(response.headers().get_one("Retry-After").is_some());
+    assert_eq!(
+        &response.body_string().unwrap(),
+        r#"{"result":{"engineeringError":null,"message":"Demasiadas solicitudes, por favor espere un momento e intente de nuevo."},"success":false}"#
+    );
+
+    // The header of clients that don't connect through a trusted proxy is ignored.
+    let mut request = client.get("/v1/invitations/public/unknown/creator");
+    request.set_remote(peer("10.0.0.1"));
+    request.add_header(Header::new("X-Real-IP", "10.0.0.3"));
+    assert_eq!(request.dispatch().status(), Status::TooManyRequests);
+
+    // Other clients have their own bucket.
+    let mut request = client.get("/v1/invitations/public/unknown/creator");
+    request.set_remote(peer("10.0.0.2"));
+    assert_eq!(request.dispatch().status(), Status::Ok);
+}
+
+#[test]
+fn test_real_ip_is_trusted_behind_configured_proxies() {
+    dbmate_rebuild();
+    flush_rate_limits();
+    std::env::set_var("RATE_LIMIT_TRUSTED_PROXIES", "10.0.0.254");
+    std::env::set_var("RATE_LIMIT_PUBLIC_INVITATION_CREATOR_CAPACITY", "1");
+
+    let client = Client::new(lib::server::invitations::rocket()).expect("valid rocket instance");
+    let status = |real_ip: &str| {
+        let mut request = client.get("/v1/invitations/public/unknown/creator");
+        request.set_remote(peer("10.0.0.254"));
+        request.add_header(Header::new("X-Real-IP", real_ip.to_string()));
+        request.dispatch().status()
+    };
+    assert_eq!(status("10.0.0.1"), Status::Ok);
+    assert_eq!(status("10.0.0.1"), Status::TooManyRequests);
+    assert_eq!(status("10.0.0.2"), Status::Ok);
+
+    std::env::remove_var("RATE_LIMIT_PUBLIC_INVITATION_CREATOR_CAPACITY");
+    std::env::remove_var("RATE_LIMIT_TRUSTED_PROXIES");
+}
+
+#[test]
+fn test_friend_report_is_limited_by_username() {
+    dbmate_rebuild();
+    flush_rate_limits();
+    insert_mock_public_key("dario", MOCK_PUBLIC_KEY);
+    insert_mock_public_key("coche", MOCK_PUBLIC_KEY);
+
+    let client = Client::new(lib::server::emergency::rocket()).expect("valid rocket instance");
+    let dario_token = create_token("dario", "dario_iphone").unwrap();
+    let coche_token = create_token("coche", "coche_iphone").unwrap();
+
+    for _ in 0..5 {
+        let mut request = client.post("/v1/emergency/non_friend/report");
+        request.add_header(Header::new(ASIMOV_LIVES, dario_token.clone()));
+        request.set_remote(peer("10.0.0.1"));
+        assert_eq!(request.dispatch().status(), Status::Ok);
+    }
+
+    let mut request = client.post("/v1/emergency/non_friend/report");
+    request.add_header(Header::new(ASIMOV_LIVES, dario_token.clone()));
+    request.set_remote(peer("10.0.0.1"));
+    let mut response = request.dispatch();
+    assert_eq!(response.status(), Status::TooManyRequests);
+    assert_eq!(
+        &response.body_string().unwrap(),
+        r#"{"result":{"engineeringError":null,"message":"Too many requests, please wait a moment and try again."},"success":false}"#
+    );
+
+    // Same IP but a different user.
+    let mut request = client.post("/v1/emergency/non_friend/report");
+    request.add_header(Header::new(ASIMOV_LIVES, coche_token));
+    request.set_remote(peer("10.0.0.1"));
+    assert_eq!(request.dispatch().status(), Status::Ok);
+}
+
+#[test]
+fn test_rate_limit_uses_user_language_and_env_overrides() {
+    dbmate_rebuild();
+    flush_rate_limits();
+    insert_mock_public_key("dario", MOCK_PUBLIC_KEY);
+    std::env::set_var("RATE_LIMIT_FORCE_REFRESH_CAPACITY", "1");
+
+    let pool = get_pool();
+    let mut conn = pool.get().unwrap();
+    conn.execute(
+        "UPDATE user_details SET language = 'es' WHERE username = 'dario'",
+        &[],
+    )
+    .unwrap();
+
+    let client = Client::new(lib::server::http_gateway::rocket()).expect("valid rocket instance");
+    let token = create_token("dario", "dario_iphone").unwrap();
+
+    let mut request = client.get("/v1/telemetry/non_friend");
+    request.add_header(Header::new(ASIMOV_LIVES, token.clone()));
+    assert_eq!(request.dispatch().status(), Status::Ok);
+
+    let mut request = client.get("/v1/telemetry/non_friend");
+    request.add_header(Header::new(ASIMOV_LIVES, token));
+    let mut response = request.dispatch();
+    std::env::remove_var("RATE_LIMIT_FORCE_REFRESH_CAPACITY");
+
+    assert_eq!(response.status(), Status::TooManyRequests);
+    assert_eq!(
+        &response.body_string().unwrap(),
+        r#"{"result":{"engineeringError":null,"message":"Demasiadas solicitudes, por favor espere un momento e intente de nuevo."},"success":false}"#
+    );
+}
+
+#[test]
+fn test_verification_codes_are_limited_by_ip_and_email() {
+    let client = test_client(lib::server::auth::rocket(), &[]);
+    let login = |ip: &str, email: &str| {
+        let mut request = client.post("/v1/auth/login");
+        request.set_remote(peer(ip));
+        request.add_header(Header::new("Content-Type", "application/json"));
+        request.set_body(json!({ "email": email, "publicKey": MOCK_PUBLIC_KEY }).to_string());
+        let mut response = request.dispatch();
+        (response.status(), response.body_string().unwrap())
+    };
+
+    for i in 0..5 {
+        let (status, _) = login(&format!("10.0.1.{}", i), "joe@rogan.com");
+        assert_eq!(status, Status::Ok);
+    }
+    // Every address gets a new bucket, the email does not.
+    let (status, body) = login("10.0.1.5", "joe@rogan.com");
+    assert_eq!(status, Status::Ok);
+    assert!(body.contains("Too many requests"));
+
+    for i in 0..5 {
+        let (status, _) = login("10.0.2.1", &format!("joe{}@rogan.com", i));
+        assert_eq!(status, Status::Ok);
+    }
+    let (status, _) = login("10.0.2.1", "joe5@rogan.com");
+    assert_eq!(status, Status::TooManyRequests);
+}
@@ -0,0 +1,133 @@
+#[macro_use]
+extern crate pretty_assertions;
+
+use chrono::{Duration, SecondsFormat, Utc};
+use lib::controllers::telemetry::redis_hash_map_name;
+use lib::db::get_pool;
+use lib::model::telemetry::Telemetry;
+use redis::Commands;
+use rocket::http::{ContentType, Status};
+use rocket::local::Client;
+use rocket_contrib::json;
+use std::env;
+
+mod common;
+
+use common::{
+    auth::MOCK_PUBLIC_KEY,
+    client::{auth_header, gateway_client},
+    db::insert_mock_public_key,
+};
+
+fn rfc3339(ago: Duration) -> String {
+    (Utc::now() - ago).to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+fn post_telemetry(client: &Client, body: String) -> String {
+    let mut request = client.post("/v1/telemetry");
+    request.add_header(auth_header("billburr", "bill_iphone"));
+    request.add_header(ContentType::JSON);
+    request.set_body(body);
+    let mut response = request.dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    response.body_string().unwrap()
+}
+
+fn cached_location(recipient: &str) -> Telemetry {
+    let mut redis = redis::Client::open(env::var("REDIS_URL").expect("REDIS_URL must be set"))
+        .unwrap()
+        .get_connection()
+        .unwrap();
+    let stored: String = redis
+        .hget(redis_hash_map_name(recipient), "billburr")
+        .unwrap();
+    serde_json::from_str(&stored).unwrap()
+}
+
+fn setup() -> Client {
+    let client = gateway_client(&[]);
+    insert_mock_public_key("billburr", MOCK_PUBLIC_KEY);
+    client
+}
+
+#[test]
+fn test_backfilled_locations_are_stored_at_capture_time() {
+    let client = setup();
+    let body = json!({
+        "returnFriendLocations": false,
+        "telemetry": [
+            {"data": "offline", "recipientUsername": "dario", "timestamp": rfc3339(Duration::hours(3))},
+            {"data": "online", "recipientUsername": "dario"}
+        ]
+    })
+    .to_string();
+    post_telemetry(&client, body);
+
+    let pool = get_pool();
+    let mut conn = pool.get().unwrap();
+    let offline_age: f64 = conn
+        .query_one(
+            "SELECT extract(epoch FROM now() - creation_timestamp)::float8 FROM device_telemetry
+             WHERE username = 'billburr' AND encrypted_location = 'offline'",
+            &[],
+        )
+        .unwrap()
+        .get(0);
+    let online_age: f64 = conn
+        .query_one(
+            "SELECT extract(epoch FROM now() - creation_timestamp)::float8 FROM device_telemetry
+             WHERE username = 'billburr' AND encrypted_location = 'online'",
+            &[],
+        )
+        .unwrap()
+        .get(0);
+    assert!((offline_age - 3.0 * 3600.0).abs() < 60.0);
+    assert!(online_age.abs() < 60.0);
+}
+
+#[test]
+fn test_older_locations_do_not_replace_the_cached_one() {
+    let client = setup();
+    let newer = json!({
+        "returnFriendLocations": false,
+        "telemetry": [{"data": "newer", "recipientUsername": "dario", "timestamp": rfc3339(Duration::minutes(5))}]
+    })
+    .to_string();
+    post_telemetry(&client, newer);
+
+    let older = json!({
+        "returnFriendLocations": false,
+        "telemetry": [{"data": "older", "recipientUsername": "dario", "timestamp": rfc3339(Duration::hours(1))}]
+    })
+    .to_string();
+    post_telemetry(&client, older);
+    assert_eq!(cached_location("dario").data, "newer");
+
+    let latest = json!({
+        "returnFriendLocations": false,
+        "telemetry": [{"data": "latest", "recipientUsername": "dario"}]
+    })
+    .to_string();
+    post_telemetry(&client, latest);
+    assert_eq!(cached_location("dario").data, "latest");
+}
+
+#[test]
+fn test_timestamps_out_of_bounds_are_rejected() {
+    let client = setup();
+    for timestamp in &[
+        rfc3339(Duration::days(8)),
+        rfc3339(Duration::hours(-1)),
+        "yesterday".to_string(),
+    ] {
+        let body = json!({
+            "returnFriendLocations": false,
+            "telemetry": [{"data": "bla bla", "recipientUsername": "dario", "timestamp": timestamp}]
+        })
+        .to_string();
+        assert_eq!(
+            post_telemetry(&client, body),
+            r#"{"result":{"engineeringError":"Invalid telemetry timestamp","message":"The phone sent a bad request, this is an app error and has been logged"},"success":false}"#
+        );
+    }
+}
@@ -0,0 +1,193 @@
+use lib::constants::{DATE_FORMAT, NANNY_RETRY_HASH_MAP, TELEMETRY_LAST_SEEN_SET};
+use lib::controllers::telemetry::{redis_hash_map_name, store_telemetry};
+use lib::db::get_pool;
+use lib::model::{
+    auth::AuthInfo,
+    devices::{AppState, BatteryState, ChargingState},
+    requests::TelemetryRequest,
+    telemetry::{Telemetry, TelemetryUpdate},
+};
+use postgres::NoTls;
+use r2d2::PooledConnection;
+use r2d2_postgres::PostgresConnectionManager;
+use redis::Commands;
+use rocket::http::{ContentType, Status};
+use rocket_contrib::json;
+use rocket_contrib::json::Json;
+use std::env;
+use std::time::{Duration, Instant};
+
+mod common;
+
+use common::{
+    client::{auth_header, gateway_client},
+    dbmate::dbmate_rebuild,
+};
+
+const FOLLOWERS: usize = 15;
+const PINGS: u32 = 50;
+
+fn auth_info() -> AuthInfo {
+    AuthInfo {
+        key: "".to_string(),
+        username: "dario".to_string(),
+        deviceId: "dario_iphone".to_string(),
+        language: "en".to_string(),
+    }
+}
+
+fn telemetry_request() -> Json<TelemetryRequest> {
+    Json(TelemetryRequest {
+        returnFriendLocations: false,
+        telemetry: (0..FOLLOWERS)
+            .map(|i| TelemetryUpdate {
+                data: format!("encrypted location {}", i),
+                recipientUsername: format!("follower_{}", i),
+                timestamp: None,
+            })
+            .collect(),
+        appState: Some(AppState::Foreground),
+        batteryState: Some(BatteryState {
+            batteryLevel: Some(0.5),
+            chargingState: Some(ChargingState::NotCharging),
+            isCharging: Some(false),
+        }),
+        correlationId: None,
+        idempotencyKey: None,
+        clientTimestamp: None,
+    })
+}
+
+fn redis_connection() -> redis::Connection {
+    redis::Client::open(env::var("REDIS_URL").expect("REDIS_URL must be set"))
+        .unwrap()
+        .get_connection()
+        .unwrap()
+}
+
+/// How telemetry was stored before batching: one insert and three redis calls per recipient.
+fn store_telemetry_row_by_row(
+    telemetry_request: &TelemetryRequest,
+    auth_info: &AuthInfo,
+    client: &mut PooledConnection<PostgresConnectionManager<NoTls>>,
+    redis: &mut redis::Connection,
+) {
+    let battery_state = telemetry_request.batteryState.as_ref().unwrap();
+    for telemetry in &telemetry_request.telemetry {
+        let statement = client
+            .prepare(
+                "insert into device_telemetry
+                (username, device_id, recipient_username, encrypted_location, creation_timestamp, app_state,
+                charging_state, battery_level, is_charging)
+                values ($1, $2, $3, $4, now(), $5, $6, $7, $8)",
+            )
+            .unwrap();
+        client
+            .query(
+                &statement,
+                &[
+                    &auth_info.username,
+                    &auth_info.deviceId,
+                    &telemetry.recipientUsername,
+                    &telemetry.data,
+                    &telemetry_request.appState.unwrap(),
+                    &battery_state.chargingState.unwrap(),
+                    &battery_state.batteryLevel.unwrap(),
+                    &battery_state.isCharging.unwrap(),
+                ],
+            )
+            .unwrap();
+        let telemetry_string = json!(Telemetry {
+            data: telemetry.data.clone(),
+            timestamp: chrono::Utc::now().format(DATE_FORMAT).to_string(),
+            batteryState: telemetry_request.batteryState.clone()
+        })
+        .to_string();
+        redis
+            .hset::<_, _, _, ()>(
+                redis_hash_map_name(&telemetry.recipientUsername),
+                &auth_info.username,
+                telemetry_string,
+            )
+            .unwrap();
+        redis
+            .zadd::<_, _, _, ()>(
+                TELEMETRY_LAST_SEEN_SET,
+                &auth_info.username,
+                chrono::Local::now().timestamp(),
+            )
+            .unwrap();
+        redis
+            .hdel::<_, _, ()>(NANNY_RETRY_HASH_MAP, &auth_info.username)
+            .unwrap();
+    }
+}
+
+fn stored_rows(client: &mut PooledConnection<PostgresConnectionManager<NoTls>>) -> i64 {
+    client
+        .query_one(
+            "SELECT count(*) FROM device_telemetry WHERE username = 'dario'",
+            &[],
+        )
+        .unwrap()
+        .get(0)
+}
+
+#[test]
+fn test_store_telemetry_stores_every_recipient() {
+    let client = gateway_client(&[]);
+    let mut conn = get_pool().get().unwrap();
+    let mut redis = redis_connection();
+
+    let mut request = client.post("/v1/telemetry");
+    request.add_header(auth_header("dario", "dario_iphone"));
+    request.add_header(ContentType::JSON);
+    request.set_body(serde_json::to_string(&telemetry_request().into_inner()).unwrap());
+    assert_eq!(request.dispatch().status(), Status::Ok);
+
+    assert_eq!(stored_rows(&mut conn), FOLLOWERS as i64);
+    for i in 0..FOLLOWERS {
+        let stored: String = redis
+            .hget(redis_hash_map_name(&format!("follower_{}", i)), "dario")
+            .unwrap();
+        let telemetry: Telemetry = serde_json::from_str(&stored).unwrap();
+        assert_eq!(telemetry.data, format!("encrypted location {}", i));
+    }
+    let last_seen: Option<i64> = redis.zscore(TELEMETRY_LAST_SEEN_SET, "dario").unwrap();
+    assert!(last_seen.is_some());
+}
+
+/// Run with --nocapture to see the timings.
+#[test]
+fn test_batched_store_telemetry_is_faster() {
+    dbmate_rebuild();
+    let pool = get_pool();
+    let mut client = pool.get().unwrap();
+    let mut redis = redis_connection();
+    let request = telemetry_request();
+    let auth_info = auth_info();
+
+    let time = |f: &mut dyn FnMut()| -> Duration {
+        let start = Instant::now();
+        for _ in 0..PINGS {
+            f();
+        }
+        start.elapsed()
+    };
+
+    let row_by_row =
+        time(&mut || store_telemetry_row_by_row(&request, &auth_info, &mut client, &mut redis));
+    let batched = time(&mut || {
+        store_telemetry(&request, &auth_info, &mut client, &mut redis).unwrap();
+    });
+
+    println!(
+        "{} pings with {} followers: row by row {:?}, batched {:?}",
+        PINGS, FOLLOWERS, row_by_row, batched
+    );
+    assert_eq!(
+        stored_rows(&mut client),
+        (2 * PINGS as usize * FOLLOWERS) as i64
+    );
+    assert!(batched < row_by_row);
+}
@@ -0,0 +1,119 @@
+#[macro_use]
+extern crate pretty_assertions;
+
+use lib::db::get_pool;
+use rocket::http::{ContentType, Status};
+use rocket::local::Client;
+use rocket_contrib::json;
+
+mod common;
+
+use common::{
+    auth::MOCK_PUBLIC_KEY,
+    client::{auth_header, gateway_client},
+    db::insert_mock_public_key,
+};
+
+fn stored_rows() -> i64 {
+    let pool = get_pool();
+    let mut conn = pool.get().unwrap();
+    conn.query_one(
+        "SELECT count(*) FROM device_telemetry WHERE username = 'billburr'",
+        &[],
+    )
+    .unwrap()
+    .get(0)
+}
+
+fn post_telemetry(client: &Client, body: String) -> String {
+    let mut request = client.post("/v1/telemetry");
+    request.add_header(auth_header("billburr", "bill_iphone"));
+    request.add_header(ContentType::JSON);
+    request.set_body(body);
+    let mut response = request.dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    response.body_string().unwrap()
+}
+
+fn setup() -> Client {
+    let client = gateway_client(&[]);
+    insert_mock_public_key("billburr", MOCK_PUBLIC_KEY);
+    client
+}
+
+#[test]
+fn test_retries_with_the_same_key_are_stored_once() {
+    let client = setup();
+    let body = json!({
+        "returnFriendLocations": true,
+        "idempotencyKey": "batch-1",
+        "telemetry": [{"data": "bla bla", "recipientUsername": "dario"}]
+    })
+    .to_string();
+
+    let first = post_telemetry(&client, body.clone());
+    let retry = post_telemetry(&client, body);
+
+    // Compared as values, the friends are serialized from a HashMap.
+    assert_eq!(
+        serde_json::from_str::<serde_json::Value>(&first).unwrap(),
+        serde_json::from_str::<serde_json::Value>(&retry).unwrap()
+    );
+    assert_eq!(stored_rows(), 1);
+
+    let other_batch = json!({
+        "returnFriendLocations": false,
+        "idempotencyKey": "batch-2",
+        "telemetry": [{"data": "bla bla", "recipientUsername": "dario"}]
+    })
+    .to_string();
+    post_telemetry(&client, other_batch);
+    assert_eq!(stored_rows(), 2);
+}
+
+#[test]
+fn test_retries_are_detected_by_client_timestamp() {
+    let client = setup();
+    let body = json!({
+        "returnFriendLocations": false,
+        "clientTimestamp": "2021-02-01T10:00:00.000Z",
+        "telemetry": [{"data": "bla bla", "recipientUsername": "dario"}]
+    })
+    .to_string();
+
+    post_telemetry(&client, body.clone());
+    post_telemetry(&client, body);
+
+    assert_eq!(stored_rows(), 1);
+}
+
+#[test]
+fn test_uploads_without_key_are_always_stored() {
+    let client = setup();
+    let body = json!({
+        "returnFriendLocations": false,
+        "telemetry": [{"data": "bla bla", "recipientUsername": "dario"}]
+    })
+    .to_string();
+
+    post_telemetry(&client, body.clone());
+    post_telemetry(&client, body);
+
+    assert_eq!(stored_rows(), 2);
+}
+
+#[test]
+fn test_invalid_client_timestamp() {
+    let client = setup();
+    let body = json!({
+        "returnFriendLocations": false,
+        "clientTimestamp": "yesterday",
+        "telemetry": []
+    })
+    .to_string();
+
+    assert_eq!(
+        post_telemetry(&client, body),
+        r#"{"result":{"engineeringError":"Invalid client timestamp","message":"The phone sent a bad request, this is an app error and has been logged"},"success":false}"#
+    );
+}
@@ -0,0 +1,176 @@
+#[macro_use]
+extern crate pretty_assertions;
+
+use chrono::{Duration, NaiveDate, NaiveDateTime, Utc};
+use lib::controllers::retention::{apply_retention_policy, migrate_unpartitioned_telemetry};
+use lib::db::get_pool;
+use lib::model::{telemetry::RetentionPolicy, PostgresConnection};
+
+mod common;
+
+use common::{db::insert_mock_telemetry, dbmate::dbmate_rebuild};
+
+fn policy() -> RetentionPolicy {
+    RetentionPolicy {
+        retention_days: 8,
+        emergency_retention_days: 90,
+        emergency_window_hours: 24,
+        partitions_ahead_days: 7,
+    }
+}
+
+fn days_ago(today: NaiveDate, days: i64, hour: u32) -> NaiveDateTime {
+    (today - Duration::days(days)).and_hms(hour, 0, 0)
+}
+
+fn create_partition(conn: &mut PostgresConnection, day: NaiveDate) {
+    conn.execute("SELECT create_device_telemetry_partition($1)", &[&day])
+        .unwrap();
+}
+
+fn insert_state(conn: &mut PostgresConnection, state: &str, timestamp: NaiveDateTime) {
+    conn.execute(
+        "INSERT INTO users_state_history (username, self_perception, creation_timestamp)
+         VALUES ('dario', $1::text::userstate, $2)",
+        &[&state, &timestamp],
+    )
+    .unwrap();
+}
+
+fn stored_timestamps(conn: &mut PostgresConnection) -> Vec<NaiveDateTime> {
+    conn.query(
+        "SELECT creation_timestamp FROM device_telemetry ORDER BY creation_timestamp",
+        &[],
+    )
+    .unwrap()
+    .iter()
+    .map(|row| row.get(0))
+    .collect()
+}
+
+fn partition_exists(conn: &mut PostgresConnection, day: NaiveDate) -> bool {
+    let name = format!("device_telemetry_p{}", day.format("%Y%m%d"));
+    conn.query_one("SELECT to_regclass($1) IS NOT NULL", &[&name])
+        .unwrap()
+        .get(0)
+}
+
+#[test]
+fn test_partitions_are_created_ahead_of_time() {
+    dbmate_rebuild();
+    let pool = get_pool();
+    let mut conn = pool.get().unwrap();
+    let today = Utc::today().naive_utc();
+
+    // The migration created the days that can be backfilled.
+    assert!(partition_exists(&mut conn, today - Duration::days(7)));
+    assert!(partition_exists(&mut conn, today));
+
+    let report = apply_retention_policy(&mut conn, &policy(), today).unwrap();
+
+    assert_eq!(report.created_partitions, 7);
+    assert!(partition_exists(&mut conn, today + Duration::days(7)));
+    assert!(!partition_exists(&mut conn, today + Duration::days(8)));
+
+    let report = apply_retention_policy(&mut conn, &policy(), today).unwrap();
+    assert_eq!(report.created_partitions, 0);
+}
+
+#[test]
+fn test_days_with_rows_in_the_default_partition_are_skipped() {
+    dbmate_rebuild();
+    let pool = get_pool();
+    let mut conn = pool.get().unwrap();
+    let today = Utc::today().naive_utc();
+    let tomorrow = today.succ().and_hms(10, 0, 0);
+    insert_mock_telemetry("dario", "dario_iphone", "coche", tomorrow);
+
+    let report = apply_retention_policy(&mut conn, &policy(), today).unwrap();
+
+    assert_eq!(report.created_partitions, 6);
+    assert!(!partition_exists(&mut conn, today.succ()));
+    let in_default: i64 = conn
+        .query_one("SELECT count(*) FROM device_telemetry_default", &[])
+        .unwrap()
+        .get(0);
+    assert_eq!(in_default, 1);
+}
+
+#[test]
+fn test_unpartitioned_rows_are_migrated_in_batches() {
+    dbmate_rebuild();
+    let pool = get_pool();
+    let mut conn = pool.get().unwrap();
+    let today = Utc::today().naive_utc();
+    let yesterday = days_ago(today, 1, 10);
+    let last_month = days_ago(today, 30, 10);
+    conn.execute(
+        "INSERT INTO device_telemetry_unpartitioned
+            (username, recipient_username, encrypted_location, device_id, creation_timestamp)
+         VALUES ('dario', 'coche', 'encryptedData', 'dario_iphone', $1),
+                ('dario', 'coche', 'encryptedData', 'dario_iphone', $2)",
+        &[&yesterday, &last_month],
+    )
+    .unwrap();
+
+    assert_eq!(migrate_unpartitioned_telemetry(&mut conn, 1).unwrap(), 2);
+
+    assert_eq!(stored_timestamps(&mut conn), vec![last_month, yesterday]);
+    let pending: bool = conn
+        .query_one(
+            "SELECT to_regclass('device_telemetry_unpartitioned') IS NOT NULL",
+            &[],
+        )
+        .unwrap()
+        .get(0);
+    assert!(!pending);
+    let report = apply_retention_policy(&mut conn, &policy(), today).unwrap();
+    assert_eq!(report.migrated_rows, 0);
+}
+
+#[test]
+fn test_expired_telemetry_is_dropped_except_around_emergencies() {
+    dbmate_rebuild();
+    let pool = get_pool();
+    let mut conn = pool.get().unwrap();
+    let today = Utc::today().naive_utc();
+    create_partition(&mut conn, today - Duration::days(30));
+    create_partition(&mut conn, today - Duration::days(100));
+
+    let recent = days_ago(today, 1, 10);
+    let expired = days_ago(today, 30, 1);
+    let before_emergency = days_ago(today, 30, 10);
+    let during_emergency = days_ago(today, 30, 13);
+    let beyond_emergency_retention = days_ago(today, 100, 13);
+    let without_partition = days_ago(today, 200, 13);
+    for timestamp in &[
+        recent,
+        expired,
+        before_emergency,
+        during_emergency,
+        beyond_emergency_retention,
+        without_partition,
+    ] {
+        insert_mock_telemetry("dario", "dario_iphone", "coche", *timestamp);
+    }
+    // Emergency from 12:00 to 14:00, with a window of 1 hour.
+    insert_state(&mut conn, "Emergency", days_ago(today, 30, 12));
+    insert_state(&mut conn, "Normal", days_ago(today, 30, 14));
+    let policy = RetentionPolicy {
+        emergency_window_hours: 1,
+        ..policy()
+    };
+
+    let report = apply_retention_policy(&mut conn, &policy, today).unwrap();
+
+    assert_eq!(
+        report.dropped_partitions,
+        vec![format!(
+            "device_telemetry_p{}",
+            (today - Duration::days(100)).format("%Y%m%d")
+        )]
+    );
+    assert_eq!(report.deleted_rows, 3);
+    assert_eq!(stored_timestamps(&mut conn), vec![during_emergency, recent]);
+    assert!(!partition_exists(&mut conn, today - Duration::days(100)));
+}
@@ -0,0 +1,85 @@
+#[macro_use]
+extern crate pretty_assertions;
+
+use chrono::{Duration, Local};
+use lib::constants::ASIMOV_LIVES;
+use lib::model::invitations::InvitationState;
+use lib::server::invitations::rocket;
+use rocket::http::{Header, Status};
+use rocket::local::Client;
+use serde_json::Value;
+
+mod common;
+
+use common::{
+    auth::{create_token, MOCK_PUBLIC_KEY},
+    db::{insert_mock_invitation_link, insert_mock_public_key},
+    dbmate::dbmate_rebuild,
+};
+
+#[test]
+fn test_v2_accept_invitation_has_no_message() {
+    dbmate_rebuild();
+    insert_mock_public_key("dario", MOCK_PUBLIC_KEY);
+    insert_mock_public_key("coche", MOCK_PUBLIC_KEY);
+    let token = create_token("coche", "coche_iphone").unwrap();
+
+    let exp_date = (Local::now() + Duration::days(7)).to_rfc3339();
+    let inv_id = "XjKlQptXcAeQ";
+    insert_mock_invitation_link("dario", inv_id, &exp_date, InvitationState::CREATED, &None);
+
+    let client = Client::new(rocket()).expect("valid rocket instance");
+    let mut request = client.post(format!("/v2/invitations/{}/accept", inv_id));
+    request.add_header(Header::new(ASIMOV_LIVES, token));
+    let mut response = request.dispatch();
+
+    assert_eq!(response.status(), Status::Ok);
+    let body: Value = serde_json::from_str(&response.body_string().unwrap()).unwrap();
+    assert_eq!(body["success"], Value::Bool(true));
+    assert_eq!(body["result"]["username"], Value::from("dario"));
+    assert_eq!(body["result"]["publicKey"], Value::from(MOCK_PUBLIC_KEY));
+    assert_eq!(body["result"].get("message"), None);
+}
+
+#[test]
+fn test_v2_errors_use_error_codes() {
+    dbmate_rebuild();
+    insert_mock_public_key("dario", MOCK_PUBLIC_KEY);
+
+    let client = Client::new(rocket()).expect("valid rocket instance");
+    let mut request = client.get("/v2/invitations/unknown/creator");
+    request.add_header(Header::new(
+        ASIMOV_LIVES,
+        create_token("dario", "dario_iphone").unwrap(),
+    ));
+    let mut response = request.dispatch();
+
+    assert_eq!(response.status(), Status::NotFound);
+    assert_eq!(
+        &response.body_string().unwrap(),
+        r#"{"result":{"code":"INVITATION_NOT_FOUND","engineeringError":null,"message":"There is no invitation with that id"},"success":false}"#
+    );
+}
+
+#[test]
+fn test_deprecated_version_has_sunset_header() {
+    dbmate_rebuild();
+    std::env::set_var("API_V1_SUNSET", "Sat, 31 Jul 2021 23:59:59 GMT");
+    let client = Client::new(rocket()).expect("valid rocket instance");
+    std::env::remove_var("API_V1_SUNSET");
+
+    let response = client
+        .get("/v1/invitations/public/unknown/creator")
+        .dispatch();
+    assert_eq!(
+        response.headers().get_one("Sunset"),
+        Some("Sat, 31 Jul 2021 23:59:59 GMT")
+    );
+    assert_eq!(response.headers().get_one("Deprecation"), Some("true"));
+
+    let response = client
+        .get("/v2/invitations/public/unknown/creator")
+        .dispatch();
+    assert_eq!(response.headers().get_one("Sunset"), None);
+    assert_eq!(response.headers().get_one("Deprecation"), None);
+}